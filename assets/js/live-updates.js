@@ -0,0 +1,104 @@
+/**
+ * Inserts the cards of new photos into the gallery as they are uploaded,
+ * using the Server-Sent Events of /photos/sync/stream.
+ * The browser resumes the stream with the Last-Event-ID header after reconnecting.
+ */
+(function() {
+    if (window.liveUpdatesSource) return;
+
+    const source = new EventSource('/photos/sync/stream');
+    window.liveUpdatesSource = source;
+
+    function getGrid() {
+        return document.querySelector('#photo-grid-container .photo-grid');
+    }
+
+    function reloadGrid() {
+        if (!document.getElementById('photo-grid-container')) return;
+        htmx.ajax('GET', '/gallery/grid' + window.location.search, '#photo-grid-container');
+    }
+
+    function isNewer(card, createdAt, photoId) {
+        const cardCreatedAt = Number(card.dataset.createdAt);
+        return createdAt > cardCreatedAt
+            || (createdAt === cardCreatedAt && photoId > Number(card.dataset.photoId));
+    }
+
+    /**
+     * Finds the element to insert the card before, or null if the card belongs
+     * after the photos loaded so far and will come with the next page instead
+     */
+    function findPosition(grid, monthKey, card) {
+        const createdAt = Number(card.dataset.createdAt);
+        const photoId = Number(card.dataset.photoId);
+        const sentinel = grid.querySelector('.load-more-sentinel');
+
+        let inMonth = false;
+        for (const child of grid.children) {
+            if (child.classList.contains('month-header')) {
+                if (inMonth) return { before: child, withHeader: false };
+                if (child.dataset.month === monthKey) {
+                    inMonth = true;
+                } else if (child.dataset.month < monthKey) {
+                    return { before: child, withHeader: true };
+                }
+            } else if (inMonth && child.classList.contains('photo-card')) {
+                if (isNewer(child, createdAt, photoId)) return { before: child, withHeader: false };
+            }
+        }
+
+        if (sentinel) return null;
+        return { before: null, withHeader: !inMonth };
+    }
+
+    async function insertPhoto(photo) {
+        const grid = getGrid();
+        if (!grid) {
+            // The empty state is shown instead of the grid
+            reloadGrid();
+            return;
+        }
+
+        const response = await fetch(`/gallery/card/${photo.id}${window.location.search}`);
+        // 204 when the current filters don't show the photo
+        if (response.status !== 200) return;
+        if (document.getElementById(`photo-card-${photo.id}`)) return;
+
+        const template = document.createElement('template');
+        template.innerHTML = await response.text();
+        const header = template.content.querySelector('.month-header');
+        const card = template.content.querySelector('.photo-card');
+        if (!header || !card) return;
+
+        const position = findPosition(grid, header.dataset.month, card);
+        if (!position) return;
+
+        if (position.withHeader) {
+            grid.insertBefore(header, position.before);
+        }
+        grid.insertBefore(card, position.before);
+
+        // Lets the lazy loader pick up the image
+        htmx.trigger(card, 'htmx:load');
+        decodeThumbhashes(card);
+    }
+
+    source.addEventListener('photo', (event) => {
+        const photo = JSON.parse(event.data);
+        const card = document.getElementById(`photo-card-${photo.id}`);
+
+        if (photo.trashed_on) {
+            if (card) card.remove();
+        } else if (!card) {
+            insertPhoto(photo).catch(e => console.warn('Failed to insert photo:', e));
+        }
+    });
+
+    source.addEventListener('delete', (event) => {
+        const card = document.getElementById(`photo-card-${event.data}`);
+        if (card) card.remove();
+    });
+
+    // Too many events were missed to replay them
+    source.addEventListener('resync', reloadGrid);
+})();
@@ -0,0 +1,82 @@
+// Map of the geotagged photos, the clusters are computed by the server for the visible area
+(function () {
+    const element = document.getElementById('photo-map');
+    if (!element || typeof L === 'undefined') return;
+
+    const category = element.dataset.category;
+    const map = L.map(element, { worldCopyJump: true }).setView([20, 0], 2);
+
+    L.tileLayer(element.dataset.tileUrl, {
+        maxZoom: parseInt(element.dataset.maxZoom, 10),
+        attribution: element.dataset.attribution
+    }).addTo(map);
+
+    const markers = L.layerGroup().addTo(map);
+    let pendingRequest = null;
+
+    function bboxParam(bounds) {
+        return [bounds.west, bounds.south, bounds.east, bounds.north].join(',');
+    }
+
+    function clusterIcon(cluster) {
+        const size = cluster.count > 1 ? 56 : 48;
+        return L.divIcon({
+            className: '',
+            iconSize: [size, size],
+            html: `<div class="map-cluster" style="width:${size}px;height:${size}px">
+                       <img src="/photos/preview/${cluster.cover_photo_id}" alt="" loading="lazy"/>
+                       ${cluster.count > 1 ? `<span class="map-cluster-count">${cluster.count}</span>` : ''}
+                   </div>`
+        });
+    }
+
+    function openCluster(cluster) {
+        const params = new URLSearchParams({ bbox: bboxParam(cluster.bounds), category });
+
+        htmx.ajax('GET', `/map/photos?${params}`, { target: '#map-photos', swap: 'innerHTML' })
+            .then(() => {
+                const card = document.querySelector('#map-photos .photo-card[data-photo-id]');
+                if (!card) return;
+
+                if (cluster.count === 1) {
+                    photoViewer.open(parseInt(card.dataset.photoId, 10), card);
+                } else {
+                    document.getElementById('map-photos').scrollIntoView({ behavior: 'smooth' });
+                }
+            });
+    }
+
+    async function loadClusters() {
+        pendingRequest?.abort();
+        pendingRequest = new AbortController();
+
+        const bounds = map.getBounds();
+        const params = new URLSearchParams({
+            bbox: [bounds.getWest(), bounds.getSouth(), bounds.getEast(), bounds.getNorth()].join(','),
+            zoom: map.getZoom(),
+            category
+        });
+
+        try {
+            const response = await fetch(`/api/map/clusters?${params}`, { signal: pendingRequest.signal });
+            if (!response.ok) return;
+            const clusters = await response.json();
+
+            markers.clearLayers();
+            for (const cluster of clusters) {
+                const photos = cluster.count === 1 ? '1 photo' : `${cluster.count} photos`;
+                L.marker([cluster.latitude, cluster.longitude], {
+                    icon: clusterIcon(cluster),
+                    title: `${photos}, latest on ${cluster.latest_date}`
+                })
+                    .on('click', () => openCluster(cluster))
+                    .addTo(markers);
+            }
+        } catch (e) {
+            if (e.name !== 'AbortError') console.error('Failed to load the map clusters', e);
+        }
+    }
+
+    map.on('moveend', loadClusters);
+    loadClusters();
+})();
@@ -0,0 +1,26 @@
+CREATE TABLE shares
+(
+    id               INTEGER  NOT NULL PRIMARY KEY,
+    token            TEXT     NOT NULL UNIQUE,
+    user_id          TEXT     NOT NULL,
+    folder           TEXT,
+    folder_is_public BOOLEAN  NOT NULL DEFAULT FALSE,
+    password_hash    TEXT,
+    allow_downloads  BOOLEAN  NOT NULL DEFAULT FALSE,
+    created_at       DATETIME NOT NULL DEFAULT current_timestamp,
+    expires_at       DATETIME NOT NULL,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_shares_user_id ON shares (user_id);
+
+CREATE TABLE share_photos
+(
+    share_id INTEGER NOT NULL,
+    photo_id INTEGER NOT NULL,
+    PRIMARY KEY (share_id, photo_id),
+
+    FOREIGN KEY (share_id) REFERENCES shares (id) ON DELETE CASCADE,
+    FOREIGN KEY (photo_id) REFERENCES photos (id) ON DELETE CASCADE
+);
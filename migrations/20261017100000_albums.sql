@@ -0,0 +1,35 @@
+CREATE TABLE albums
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    owner_id   TEXT     NOT NULL,
+    folder     TEXT     NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT current_timestamp,
+    UNIQUE (owner_id, folder),
+
+    FOREIGN KEY (owner_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE album_members
+(
+    album_id INTEGER NOT NULL,
+    user_id  TEXT    NOT NULL,
+    PRIMARY KEY (album_id, user_id),
+
+    FOREIGN KEY (album_id) REFERENCES albums (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_album_members_user_id ON album_members (user_id);
+
+-- Needed to know which album an event belongs to
+ALTER TABLE photos_event_log ADD COLUMN folder TEXT;
+
+-- Set on the events meant for a single user, when an album is shared with them or no longer is
+ALTER TABLE photos_event_log ADD COLUMN member_id TEXT;
+
+-- The folders each user can see through the albums they are a member of,
+-- so the visibility rules of the queries join a single place
+CREATE VIEW shared_folders AS
+SELECT m.user_id AS member_id, a.owner_id, a.folder
+FROM album_members m
+         INNER JOIN albums a ON a.id = m.album_id;
@@ -0,0 +1,22 @@
+CREATE TABLE user_totp
+(
+    user_id        TEXT     NOT NULL PRIMARY KEY,
+    secret         TEXT     NOT NULL,
+    enabled        BOOLEAN  NOT NULL DEFAULT FALSE,
+    -- The time step of the last accepted code, so that a code can't be used twice
+    last_used_step INTEGER,
+    created_at     DATETIME NOT NULL DEFAULT current_timestamp,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE TABLE user_recovery_codes
+(
+    id        INTEGER NOT NULL PRIMARY KEY,
+    user_id   TEXT    NOT NULL,
+    code_hash TEXT    NOT NULL,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_user_recovery_codes_user_id ON user_recovery_codes (user_id);
@@ -0,0 +1,10 @@
+-- Failed login counters, keyed either by client IP or by user id
+CREATE TABLE login_attempts
+(
+    kind            TEXT     NOT NULL,
+    key             TEXT     NOT NULL,
+    failures        INTEGER  NOT NULL,
+    last_failure_at DATETIME NOT NULL,
+    locked_until    DATETIME,
+    PRIMARY KEY (kind, key)
+);
@@ -0,0 +1,15 @@
+CREATE TABLE api_tokens
+(
+    id           INTEGER  NOT NULL PRIMARY KEY,
+    user_id      TEXT     NOT NULL,
+    name         TEXT     NOT NULL,
+    -- blake3 hash of the token, the token itself is only shown once
+    token_hash   TEXT     NOT NULL UNIQUE,
+    scope        TEXT     NOT NULL,
+    created_at   DATETIME NOT NULL DEFAULT current_timestamp,
+    last_used_at DATETIME,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_api_tokens_user_id ON api_tokens (user_id);
@@ -0,0 +1,15 @@
+-- The devices a user is logged in on. The session data itself is kept by the session store,
+-- which only knows the session id, so each logged in session gets a row here
+CREATE TABLE user_sessions
+(
+    id           TEXT     NOT NULL PRIMARY KEY,
+    user_id      TEXT     NOT NULL,
+    created_at   DATETIME NOT NULL,
+    last_seen_at DATETIME NOT NULL,
+    ip           TEXT,
+    user_agent   TEXT,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_user_sessions_user_id ON user_sessions (user_id);
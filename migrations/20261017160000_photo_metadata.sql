@@ -0,0 +1,19 @@
+-- A row is stored even when the file has no EXIF data, so it is only read once
+CREATE TABLE photo_metadata
+(
+    photo_id      INTEGER  NOT NULL PRIMARY KEY,
+    camera_make   TEXT,
+    camera_model  TEXT,
+    lens_model    TEXT,
+    focal_length  REAL,
+    aperture      REAL,
+    exposure_time REAL,
+    iso           INTEGER,
+    width         INTEGER,
+    height        INTEGER,
+    orientation   INTEGER,
+    latitude      REAL,
+    longitude     REAL,
+
+    FOREIGN KEY (photo_id) REFERENCES photos (id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+ALTER TABLE photo_metadata ADD COLUMN duration REAL;
+ALTER TABLE photo_metadata ADD COLUMN video_codec TEXT;
+ALTER TABLE photo_metadata ADD COLUMN frame_rate REAL;
+
+-- Videos only got empty rows so far, read them again with ffprobe
+DELETE FROM photo_metadata
+WHERE camera_make IS NULL
+  AND camera_model IS NULL
+  AND width IS NULL
+  AND latitude IS NULL;
@@ -0,0 +1 @@
+CREATE INDEX idx_photo_metadata_location ON photo_metadata (latitude, longitude) WHERE latitude IS NOT NULL;
@@ -0,0 +1,25 @@
+-- Cities imported from a GeoNames dump, used to name where geotagged photos were taken
+CREATE TABLE places
+(
+    id           INTEGER NOT NULL PRIMARY KEY, -- The GeoNames id
+    name         TEXT    NOT NULL,
+    country_code TEXT    NOT NULL,
+    country      TEXT,
+    latitude     REAL    NOT NULL,
+    longitude    REAL    NOT NULL,
+    population   INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX idx_places_location ON places (latitude, longitude);
+
+-- A row is stored even when no place is close enough, so the photo is only looked up once
+CREATE TABLE photo_places
+(
+    photo_id INTEGER NOT NULL PRIMARY KEY,
+    place_id INTEGER,
+
+    FOREIGN KEY (photo_id) REFERENCES photos (id) ON DELETE CASCADE,
+    FOREIGN KEY (place_id) REFERENCES places (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_photo_places_place ON photo_places (place_id);
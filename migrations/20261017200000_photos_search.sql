@@ -0,0 +1,17 @@
+-- Full-text index of the photos, kept in sync by the repository. The rowid is the photo id
+CREATE VIRTUAL TABLE photos_search USING fts5
+(
+    name,
+    folder,
+    place,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO photos_search (rowid, name, folder, place)
+SELECT p.id,
+       p.name,
+       coalesce(p.folder, ''),
+       coalesce(pl.name || ' ' || coalesce(pl.country, pl.country_code), '')
+FROM photos p
+         LEFT JOIN photo_places pp ON pp.photo_id = p.id
+         LEFT JOIN places pl ON pl.id = pp.place_id;
@@ -0,0 +1,12 @@
+CREATE TABLE saved_searches
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    user_id    TEXT     NOT NULL,
+    name       TEXT     NOT NULL,
+    -- The search as typed, filters included
+    query      TEXT     NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT current_timestamp,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    UNIQUE (user_id, name)
+);
@@ -0,0 +1,20 @@
+-- Labels shared by the whole family, a photo can have any number of them
+CREATE TABLE tags
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    name       TEXT     NOT NULL UNIQUE COLLATE NOCASE,
+    created_at DATETIME NOT NULL DEFAULT current_timestamp
+);
+
+CREATE TABLE photo_tags
+(
+    photo_id   INTEGER  NOT NULL,
+    tag_id     INTEGER  NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT current_timestamp,
+
+    PRIMARY KEY (photo_id, tag_id),
+    FOREIGN KEY (photo_id) REFERENCES photos (id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_photo_tags_tag_id ON photo_tags (tag_id);
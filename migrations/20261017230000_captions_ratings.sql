@@ -0,0 +1,25 @@
+ALTER TABLE photos ADD COLUMN caption TEXT;
+-- 0 is unrated, like in XMP
+ALTER TABLE photos ADD COLUMN rating INTEGER NOT NULL DEFAULT 0 CHECK (rating BETWEEN 0 AND 5);
+
+-- The captions are searchable too, and FTS5 tables can't get new columns
+DROP TABLE photos_search;
+
+CREATE VIRTUAL TABLE photos_search USING fts5
+(
+    name,
+    folder,
+    place,
+    caption,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO photos_search (rowid, name, folder, place, caption)
+SELECT p.id,
+       p.name,
+       coalesce(p.folder, ''),
+       coalesce(pl.name || ' ' || coalesce(pl.country, pl.country_code), ''),
+       ''
+FROM photos p
+         LEFT JOIN photo_places pp ON pp.photo_id = p.id
+         LEFT JOIN places pl ON pl.id = pp.place_id;
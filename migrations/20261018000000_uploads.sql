@@ -0,0 +1,18 @@
+CREATE TABLE uploads
+(
+    id           TEXT     NOT NULL PRIMARY KEY,
+    user_id      TEXT     NOT NULL,
+    length       INTEGER  NOT NULL,
+    metadata     TEXT     NOT NULL DEFAULT '',
+    file_name    TEXT     NOT NULL,
+    time_created DATETIME NOT NULL,
+    folder       TEXT,
+    make_public  BOOLEAN  NOT NULL DEFAULT FALSE,
+    photo_id     INTEGER,
+    expires_at   DATETIME NOT NULL,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (photo_id) REFERENCES photos (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_uploads_expires_at ON uploads (expires_at);
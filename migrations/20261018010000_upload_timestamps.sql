@@ -0,0 +1,42 @@
+PRAGMA defer_foreign_keys = ON;
+
+-- The timestamp can be read from the file once the upload is complete
+CREATE TABLE uploads_new
+(
+    id            TEXT     NOT NULL PRIMARY KEY,
+    user_id       TEXT     NOT NULL,
+    length        INTEGER  NOT NULL,
+    metadata      TEXT     NOT NULL DEFAULT '',
+    file_name     TEXT     NOT NULL,
+    time_created  DATETIME,
+    last_modified DATETIME,
+    folder        TEXT,
+    make_public   BOOLEAN  NOT NULL DEFAULT FALSE,
+    photo_id      INTEGER,
+    expires_at    DATETIME NOT NULL,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (photo_id) REFERENCES photos (id) ON DELETE SET NULL
+);
+
+INSERT INTO uploads_new (id, user_id, length, metadata, file_name, time_created, folder, make_public, photo_id,
+                         expires_at)
+SELECT id,
+       user_id,
+       length,
+       metadata,
+       file_name,
+       time_created,
+       folder,
+       make_public,
+       photo_id,
+       expires_at
+FROM uploads;
+
+DROP TABLE uploads;
+
+ALTER TABLE uploads_new RENAME TO uploads;
+
+CREATE INDEX idx_uploads_expires_at ON uploads (expires_at);
+
+PRAGMA defer_foreign_keys = OFF;
@@ -1,9 +1,13 @@
 use crate::http::AppStateRef;
+use crate::model::api_token::{ApiTokenScope, generate_api_token, hash_api_token};
+use crate::model::login_attempt::LoginAttemptKind;
 use crate::model::user::User;
-use crate::repo::PhotosRepo;
+use crate::repo::{ApiTokensRepo, LoginAttemptsRepo, PhotosRepo, TwoFactorRepo, UserSessionsRepo};
 use crate::utils::password_hash::generate_hash_from_password;
 use crate::{previews, tasks};
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use time::OffsetDateTime;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -20,6 +24,15 @@ enum Commands {
     #[command(subcommand)]
     /// Manage Photos
     Photos(PhotosCommand),
+    #[command(subcommand)]
+    /// Manage the API tokens used by scripts and apps
+    Tokens(TokensCommand),
+    #[command(subcommand)]
+    /// Manage the login lockouts caused by failed attempts
+    Lockouts(LockoutsCommand),
+    #[command(subcommand)]
+    /// Manage the login sessions
+    Sessions(SessionsCommand),
 }
 
 #[derive(Subcommand)]
@@ -35,6 +48,9 @@ enum UsersCommand {
         #[arg(short, long)]
         /// User's password
         password: String,
+        #[arg(long)]
+        /// Allow the user to manage other users from the admin page
+        admin: bool,
     },
     /// List all users and their respective photo count
     List,
@@ -43,6 +59,27 @@ enum UsersCommand {
         #[arg(short, long)]
         user_id: String,
     },
+    /// Set a new password for an existing user, logging out all their sessions
+    SetPassword {
+        #[arg(short, long)]
+        user_id: String,
+        #[arg(short, long)]
+        /// The new password
+        password: String,
+    },
+    /// Turn off two-factor authentication for a user who lost their authenticator and recovery codes
+    DisableTwoFactor {
+        #[arg(short, long)]
+        user_id: String,
+    },
+    /// Grant or revoke the admin role of an existing user
+    SetAdmin {
+        #[arg(short, long)]
+        user_id: String,
+        #[arg(long)]
+        /// Remove the admin role instead of granting it
+        revoke: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -51,12 +88,64 @@ enum PhotosCommand {
     ScanPhotos,
     /// Trigger a manual generation of previews
     GeneratePreviews,
+    /// Import the cities used to name the places of geotagged photos, from the GeoNames dumps
+    ImportPlaces {
+        #[arg(long)]
+        /// A cities file, like cities15000.txt
+        cities: PathBuf,
+        #[arg(long)]
+        /// The countryInfo.txt file, to show country names instead of their codes
+        countries: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokensCommand {
+    /// Create a new API token for a user. The token is only printed once
+    Create {
+        #[arg(short, long)]
+        user_id: String,
+        #[arg(short, long)]
+        /// A name to recognize the token by
+        name: String,
+        #[arg(short, long, default_value = "full")]
+        /// What the token allows: read, upload or full
+        scope: ApiTokenScope,
+    },
+    /// List the API tokens of a user, or of everyone
+    List {
+        #[arg(short, long)]
+        user_id: Option<String>,
+    },
+    /// Revoke an API token
+    Revoke {
+        #[arg(long)]
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum LockoutsCommand {
+    /// List the IPs, users and shares that currently can't log in
+    List,
+    /// Clear the failed login attempts of an IP, user or share, or all of them if none is given
+    Clear {
+        #[arg(long)]
+        ip: Option<String>,
+        #[arg(long)]
+        user_id: Option<String>,
+        #[arg(long)]
+        share_id: Option<i64>,
+    },
 }
 
 #[derive(Subcommand)]
 enum SessionsCommand {
-    /// Clear all sessions
-    Clear,
+    /// Log out all the sessions of a user, or of everyone if no user is given
+    Clear {
+        #[arg(short, long = "user")]
+        user_id: Option<String>,
+    },
 }
 
 /// Returns true if the program should exit.
@@ -71,6 +160,9 @@ pub async fn run_cli(state: AppStateRef) -> bool {
     match cmd.unwrap() {
         Commands::Users(command) => user_commands(state, command).await,
         Commands::Photos(command) => photos_commands(state, command).await,
+        Commands::Tokens(command) => tokens_commands(state, command).await,
+        Commands::Lockouts(command) => lockouts_commands(state, command).await,
+        Commands::Sessions(command) => sessions_commands(state, command).await,
     };
 
     true
@@ -82,11 +174,13 @@ async fn user_commands(state: AppStateRef, command: UsersCommand) {
             user_id,
             name,
             password,
+            admin,
         } => {
             let user = User {
                 id: user_id,
                 name,
                 password_hash: generate_hash_from_password(password),
+                is_admin: admin,
             };
 
             let user_result = state.users_repo.insert_user(&user).await;
@@ -141,6 +235,180 @@ async fn user_commands(state: AppStateRef, command: UsersCommand) {
                 }
             }
         }
+        UsersCommand::SetPassword { user_id, password } => {
+            let password_hash = generate_hash_from_password(password);
+
+            match state
+                .users_repo
+                .update_password_hash(&user_id, &password_hash)
+                .await
+            {
+                Ok(true) => println!("Password changed for user {user_id}"),
+                Ok(false) => eprintln!("User {user_id} does not exist"),
+                Err(e) => eprintln!("Failed to change the password of user {user_id}: {e}"),
+            }
+        }
+        UsersCommand::DisableTwoFactor { user_id } => {
+            match disable_two_factor(state, &user_id).await {
+                Ok(true) => println!("Two-factor authentication disabled for user {user_id}"),
+                Ok(false) => eprintln!("User {user_id} doesn't use two-factor authentication"),
+                Err(e) => eprintln!("Failed to disable two-factor authentication: {e}"),
+            }
+        }
+        UsersCommand::SetAdmin { user_id, revoke } => {
+            match state.users_repo.update_is_admin(&user_id, !revoke).await {
+                Ok(true) if revoke => println!("User {user_id} is no longer an admin"),
+                Ok(true) => println!("User {user_id} is now an admin"),
+                Ok(false) => eprintln!("User {user_id} does not exist"),
+                Err(e) => eprintln!("Failed to update user {user_id}: {e}"),
+            }
+        }
+    }
+}
+
+async fn disable_two_factor(state: AppStateRef, user_id: &str) -> sqlx::Result<bool> {
+    let mut tx = state.write_pool.begin().await?;
+    let deleted = tx.delete_totp(user_id).await?;
+    tx.delete_recovery_codes(user_id).await?;
+    tx.commit().await?;
+
+    Ok(deleted)
+}
+
+async fn tokens_commands(state: AppStateRef, command: TokensCommand) {
+    match command {
+        TokensCommand::Create {
+            user_id,
+            name,
+            scope,
+        } => {
+            if state.users_repo.get_user(&user_id).await.is_none() {
+                eprintln!("User {user_id} does not exist");
+                return;
+            }
+
+            let token = generate_api_token();
+            match state
+                .write_pool
+                .insert_api_token(&user_id, &name, &hash_api_token(&token), scope)
+                .await
+            {
+                Ok(api_token) => {
+                    println!("Created {scope} token {} for user {user_id}:", api_token.id);
+                    println!("{token}");
+                }
+                Err(e) => eprintln!("Failed to create the token: {e}"),
+            }
+        }
+        TokensCommand::List { user_id } => {
+            let tokens = state
+                .read_pool
+                .get_api_tokens(user_id.as_deref())
+                .await
+                .expect("Failed to get tokens");
+
+            println!(
+                "| {0: <6} | {1: <12} | {2: <20} | {3: <6} | {4: <10} | {5: <10} |",
+                "Id", "User Id", "Name", "Scope", "Created", "Last used"
+            );
+            println!(
+                "+{}+{}+{}+{}+{}+{}+",
+                "-".repeat(8),
+                "-".repeat(14),
+                "-".repeat(22),
+                "-".repeat(8),
+                "-".repeat(12),
+                "-".repeat(12)
+            );
+
+            for token in tokens {
+                let last_used = token
+                    .last_used_at
+                    .map_or("Never".to_string(), |last_used_at| {
+                        last_used_at.date().to_string()
+                    });
+
+                println!(
+                    "| {0: <6} | {1: <12} | {2: <20} | {3: <6} | {4: <10} | {5: <10} |",
+                    token.id,
+                    token.user_id,
+                    token.name,
+                    token.scope,
+                    token.created_at.date(),
+                    last_used
+                );
+            }
+        }
+        TokensCommand::Revoke { id } => match state.write_pool.delete_api_token(id, None).await {
+            Ok(true) => println!("Revoked token {id}"),
+            Ok(false) => eprintln!("Token {id} does not exist"),
+            Err(e) => eprintln!("Failed to revoke token {id}: {e}"),
+        },
+    }
+}
+
+async fn lockouts_commands(state: AppStateRef, command: LockoutsCommand) {
+    match command {
+        LockoutsCommand::List => {
+            let now = OffsetDateTime::now_utc();
+            let lockouts = state
+                .read_pool
+                .get_locked_login_attempts(now)
+                .await
+                .expect("Failed to get lockouts");
+
+            println!(
+                "| {0: <6} | {1: <39} | {2: <8} | {3: <16} |",
+                "Kind", "IP / User Id / Share Id", "Failures", "Minutes left"
+            );
+            println!(
+                "+{}+{}+{}+{}+",
+                "-".repeat(8),
+                "-".repeat(41),
+                "-".repeat(10),
+                "-".repeat(18)
+            );
+
+            for lockout in lockouts {
+                let minutes_left = lockout
+                    .locked_until
+                    .map_or(0, |locked_until| (locked_until - now).whole_minutes() + 1);
+
+                println!(
+                    "| {0: <6} | {1: <39} | {2: <8} | {3: <16} |",
+                    lockout.kind, lockout.key, lockout.failures, minutes_left
+                );
+            }
+        }
+        LockoutsCommand::Clear {
+            ip,
+            user_id,
+            share_id,
+        } => {
+            let keys = [
+                (LoginAttemptKind::Ip, ip),
+                (LoginAttemptKind::User, user_id),
+                (LoginAttemptKind::Share, share_id.map(|id| id.to_string())),
+            ];
+
+            if keys.iter().all(|(_, key)| key.is_none()) {
+                match state.write_pool.delete_all_login_attempts().await {
+                    Ok(count) => println!("Cleared {count} login attempt counters"),
+                    Err(e) => eprintln!("Failed to clear the login attempts: {e}"),
+                }
+                return;
+            }
+
+            for (kind, key) in keys {
+                let Some(key) = key else { continue };
+
+                match state.write_pool.delete_login_attempt(kind, &key).await {
+                    Ok(true) => println!("Cleared the login attempts of {kind} {key}"),
+                    Ok(false) => eprintln!("There are no login attempts for {kind} {key}"),
+                    Err(e) => eprintln!("Failed to clear the login attempts of {kind} {key}: {e}"),
+                }
+            }
+        }
     }
 }
 
@@ -155,5 +423,44 @@ async fn photos_commands(state: AppStateRef, command: PhotosCommand) {
             Ok(_) => println!("Preview generation finished"),
             Err(e) => eprintln!("Preview generation failed: {e}"),
         },
+        PhotosCommand::ImportPlaces { cities, countries } => {
+            match tasks::import_places(state, &cities, countries.as_deref()).await {
+                Ok(count) => println!("Imported {count} places"),
+                Err(e) => eprintln!("Failed to import the places: {e}"),
+            }
+        }
+    }
+}
+
+async fn sessions_commands(state: AppStateRef, command: SessionsCommand) {
+    match command {
+        SessionsCommand::Clear {
+            user_id: Some(user_id),
+        } => {
+            // The sessions are logged out on their next request once they are no longer tracked
+            match state
+                .write_pool
+                .delete_user_sessions(Some(&user_id), None)
+                .await
+            {
+                Ok(count) => println!("Cleared {count} sessions of user {user_id}"),
+                Err(e) => eprintln!("Failed to clear the sessions of user {user_id}: {e}"),
+            }
+        }
+        SessionsCommand::Clear { user_id: None } => {
+            let result = async {
+                let mut tx = state.write_pool.begin().await?;
+                tx.delete_user_sessions(None, None).await?;
+                let count = tx.delete_all_session_records().await?;
+                tx.commit().await?;
+                Ok::<_, sqlx::Error>(count)
+            }
+            .await;
+
+            match result {
+                Ok(count) => println!("Cleared {count} sessions"),
+                Err(e) => eprintln!("Failed to clear the sessions: {e}"),
+            }
+        }
     }
 }
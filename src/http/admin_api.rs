@@ -0,0 +1,373 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AdminUser;
+use crate::http::error::{HttpError, HttpResult, error_alert};
+use crate::http::photos_api::move_photos_service;
+use crate::http::utils::WantsHtml;
+use crate::model::user::{SimpleUser, User, is_valid_user_id};
+use crate::repo::users_repo::UsersTransactionRepo;
+use crate::repo::{PhotosRepo, PhotosTransactionRepo};
+use crate::utils::password_hash::generate_hash_from_password;
+use axum::extract::{Path, Query, State};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Form, Json, Router};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use tokio::task;
+use tracing::{error, info};
+
+pub fn router(app_state: AppStateRef) -> Router {
+    Router::new()
+        .route("/admin/api/users", get(list_users).post(create_user))
+        .route("/admin/api/users/{user_id}", delete(delete_user))
+        .route("/admin/api/users/{user_id}/name", post(rename_user))
+        .route("/admin/api/users/{user_id}/password", post(reset_password))
+        .with_state(app_state)
+}
+
+#[derive(Deserialize)]
+struct CreateUserForm {
+    user_id: String,
+    name: String,
+    password: String,
+    /// Checkboxes are only sent when checked
+    #[serde(default)]
+    is_admin: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RenameUserForm {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ResetPasswordForm {
+    password: String,
+}
+
+/// What happens to the photos of a deleted user
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum UserFilesAction {
+    /// Leave the files on disk, only the database entries are removed
+    #[default]
+    Keep,
+    /// Move the files to another user
+    Reassign,
+    /// Move the files to the family trash, where they can still be restored for 30 days
+    Trash,
+}
+
+#[derive(Deserialize)]
+struct DeleteUserQuery {
+    #[serde(default)]
+    files: UserFilesAction,
+    target_user_id: Option<String>,
+}
+
+async fn list_users(
+    AdminUser(_): AdminUser,
+    State(state): State<AppStateRef>,
+) -> HttpResult<impl IntoResponse> {
+    let users: Vec<SimpleUser> = state
+        .users_repo
+        .get_users()
+        .await?
+        .into_iter()
+        .map(SimpleUser::from)
+        .collect();
+
+    Ok(Json(users))
+}
+
+async fn create_user(
+    AdminUser(admin): AdminUser,
+    State(state): State<AppStateRef>,
+    WantsHtml(wants_html): WantsHtml,
+    Form(form): Form<CreateUserForm>,
+) -> Response {
+    let result = async {
+        validate_user_id(&form.user_id)?;
+        let name = validate_name(&form.name)?;
+        validate_password(&form.password)?;
+
+        if state.users_repo.get_user(&form.user_id).await.is_some() {
+            return Err(HttpError::BadRequest(format!(
+                "User {} already exists",
+                form.user_id
+            )));
+        }
+
+        let password = form.password;
+        let user = User {
+            id: form.user_id,
+            name: name.to_string(),
+            password_hash: hash_password(password).await?,
+            is_admin: form.is_admin.is_some(),
+        };
+        state.users_repo.insert_user(&user).await?;
+
+        info!("Admin {} created user {}", admin.id, user.id);
+
+        Ok(user)
+    }
+    .await;
+
+    respond(wants_html, result.map(SimpleUser::from))
+}
+
+async fn rename_user(
+    AdminUser(admin): AdminUser,
+    State(state): State<AppStateRef>,
+    Path(user_id): Path<String>,
+    WantsHtml(wants_html): WantsHtml,
+    Form(form): Form<RenameUserForm>,
+) -> Response {
+    let result = async {
+        let name = validate_name(&form.name)?;
+
+        if !state.users_repo.update_name(&user_id, name).await? {
+            return Err(HttpError::NotFound);
+        }
+
+        info!("Admin {} renamed user {user_id} to {name}", admin.id);
+
+        Ok(())
+    }
+    .await;
+
+    respond(wants_html, result)
+}
+
+async fn reset_password(
+    AdminUser(admin): AdminUser,
+    State(state): State<AppStateRef>,
+    Path(user_id): Path<String>,
+    WantsHtml(wants_html): WantsHtml,
+    Form(form): Form<ResetPasswordForm>,
+) -> Response {
+    let result = async {
+        validate_password(&form.password)?;
+
+        let password_hash = hash_password(form.password).await?;
+        if !state
+            .users_repo
+            .update_password_hash(&user_id, &password_hash)
+            .await?
+        {
+            return Err(HttpError::NotFound);
+        }
+
+        info!("Admin {} reset the password of user {user_id}", admin.id);
+
+        Ok(())
+    }
+    .await;
+
+    respond(wants_html, result)
+}
+
+async fn delete_user(
+    AdminUser(admin): AdminUser,
+    State(state): State<AppStateRef>,
+    Path(user_id): Path<String>,
+    WantsHtml(wants_html): WantsHtml,
+    Query(query): Query<DeleteUserQuery>,
+) -> Response {
+    let result = async {
+        if admin.id == user_id {
+            return Err(HttpError::BadRequest(
+                "You can't delete your own account".to_string(),
+            ));
+        }
+
+        let target_user_id = query.target_user_id.filter(|id| !id.is_empty());
+        delete_user_service(state, &user_id, query.files, target_user_id).await?;
+
+        info!(
+            "Admin {} deleted user {user_id}, files action: {:?}",
+            admin.id, query.files
+        );
+
+        Ok(())
+    }
+    .await;
+
+    respond(wants_html, result)
+}
+
+async fn delete_user_service(
+    state: AppStateRef,
+    user_id: &str,
+    files: UserFilesAction,
+    target_user_id: Option<String>,
+) -> HttpResult<()> {
+    if state.users_repo.get_user(user_id).await.is_none() {
+        return Err(HttpError::NotFound);
+    }
+
+    match files {
+        UserFilesAction::Keep => {}
+        UserFilesAction::Reassign => {
+            let Some(target_user_id) = target_user_id else {
+                return Err(HttpError::BadRequest(
+                    "Choose who to give the files to".to_string(),
+                ));
+            };
+            if target_user_id == user_id
+                || state.users_repo.get_user(&target_user_id).await.is_none()
+            {
+                return Err(HttpError::BadRequest(format!(
+                    "Can't reassign the files to {target_user_id}"
+                )));
+            }
+
+            move_user_photos(state, user_id, target_user_id).await?;
+        }
+        UserFilesAction::Trash => return trash_photos_and_delete_user(state, user_id).await,
+    }
+
+    state.users_repo.delete_user(user_id).await?;
+
+    Ok(())
+}
+
+/// Moves the photos of the user to the family trash and deletes the user in a single transaction,
+/// the files that were moved go back if anything fails
+async fn trash_photos_and_delete_user(state: AppStateRef, user_id: &str) -> HttpResult<()> {
+    let now = OffsetDateTime::now_utc();
+    let mut moved_paths = Vec::new();
+
+    let result = async {
+        let mut tx = state.write_pool.begin().await?;
+
+        for mut photo in tx.as_mut().get_photos_by_user(Some(user_id)).await? {
+            let source_path = photo.partial_path();
+            photo.user_id = None;
+            photo.trashed_on = photo.trashed_on.or(Some(now));
+            let destination_path = photo.partial_path();
+
+            tx.update_photo(&photo).await?;
+            state.storage.move_photo(&source_path, &destination_path)?;
+            moved_paths.push((source_path, destination_path));
+        }
+
+        tx.delete_user(user_id).await?;
+        tx.commit().await?;
+        Ok::<_, HttpError>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        for (source_path, destination_path) in moved_paths.iter().rev() {
+            if let Err(e) = state.storage.move_photo(destination_path, source_path) {
+                error!("Failed to move the photo {destination_path} back: {e}");
+            }
+        }
+        return Err(e);
+    }
+
+    state.publish_latest_event().await;
+    info!(
+        "Moved {} photos of user {user_id} to the family trash",
+        moved_paths.len()
+    );
+
+    Ok(())
+}
+
+/// Moves all the photos of a user while keeping their folder structure
+async fn move_user_photos(
+    state: AppStateRef,
+    user_id: &str,
+    target_user_id: String,
+) -> HttpResult<()> {
+    let mut folders: BTreeMap<Option<String>, Vec<i64>> = BTreeMap::new();
+    for photo in state.read_pool.get_photos_by_user(Some(user_id)).await? {
+        folders.entry(photo.folder).or_default().push(photo.id);
+    }
+
+    let mut moved_count = 0;
+    for (folder, photo_ids) in folders {
+        moved_count += move_photos_service(
+            &photo_ids,
+            user_id,
+            Some(target_user_id.clone()),
+            folder,
+            state,
+        )
+        .await?
+        .len();
+    }
+
+    info!("Moved {moved_count} photos of user {user_id} to {target_user_id}");
+
+    Ok(())
+}
+
+fn validate_user_id(user_id: &str) -> HttpResult<()> {
+    if !is_valid_user_id(user_id) {
+        return Err(HttpError::BadRequest(
+            "User ID can only contain letters, digits, '_' and '-'".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_name(name: &str) -> HttpResult<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(HttpError::BadRequest("Name is required".to_string()));
+    }
+
+    Ok(name)
+}
+
+fn validate_password(password: &str) -> HttpResult<()> {
+    if password.is_empty() {
+        return Err(HttpError::BadRequest("Password is required".to_string()));
+    }
+
+    Ok(())
+}
+
+async fn hash_password(password: String) -> HttpResult<String> {
+    task::spawn_blocking(move || generate_hash_from_password(password))
+        .await
+        .map_err(|e| HttpError::AnyError(Box::new(e)))
+}
+
+/// HTML clients get the page refreshed or an error message swapped in,
+/// while API clients get JSON or the error status
+fn respond<T: serde::Serialize>(wants_html: bool, result: HttpResult<T>) -> Response {
+    match result {
+        Ok(_) if wants_html => [("HX-Refresh", "true")].into_response(),
+        Ok(value) => Json(value).into_response(),
+        Err(HttpError::BadRequest(message)) if wants_html => (
+            [("HX-Retarget", "#error"), ("HX-Reswap", "outerHTML")],
+            error_alert(&message),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::user::PUBLIC_USER_FOLDER;
+
+    #[test]
+    fn test_validate_user_id() {
+        assert!(validate_user_id("john").is_ok());
+        assert!(validate_user_id("john_doe-2").is_ok());
+
+        assert!(validate_user_id("").is_err());
+        assert!(validate_user_id(PUBLIC_USER_FOLDER).is_err());
+        assert!(validate_user_id("../etc").is_err());
+        assert!(validate_user_id("john doe").is_err());
+        assert!(validate_user_id(&"a".repeat(65)).is_err());
+    }
+}
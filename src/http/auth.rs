@@ -1,8 +1,11 @@
 use crate::http::error::HttpError;
 use crate::http::utils::AuthSession;
 use crate::model::user::User;
-use axum::extract::FromRequestParts;
+use axum::extract::{FromRequestParts, Request};
 use axum::http::request::Parts;
+use axum::http::{HeaderMap, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
 
 /// An extractor that ensures the user is authenticated.
 /// Returns the authenticated User, or responds with 401 Unauthorized.
@@ -32,3 +35,68 @@ where
             .ok_or(HttpError::Unauthorized)
     }
 }
+
+/// An extractor that ensures the user is authenticated and an administrator.
+/// Responds with 401 Unauthorized or 403 Forbidden otherwise.
+pub struct AdminUser(pub User);
+
+impl std::ops::Deref for AdminUser {
+    type Target = User;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S> FromRequestParts<S> for AdminUser
+where
+    S: Send + Sync,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AuthenticatedUser(user) = AuthenticatedUser::from_request_parts(parts, state).await?;
+
+        if !user.is_admin {
+            return Err(HttpError::Forbidden);
+        }
+
+        Ok(AdminUser(user))
+    }
+}
+
+/// Authenticates requests that carry an `Authorization: Bearer` API token instead of a session cookie.
+/// The user is set on the [AuthSession] of the request, so the extractors above and `login_required!`
+/// work the same for both. The scope of the token is enforced here.
+pub async fn bearer_auth(mut request: Request, next: Next) -> Response {
+    let token = bearer_token(request.headers()).map(str::to_string);
+
+    let (Some(token), Some(mut auth_session)) =
+        (token, request.extensions().get::<AuthSession>().cloned())
+    else {
+        return next.run(request).await;
+    };
+
+    let (user, scope) = match auth_session.backend.authenticate_token(&token).await {
+        Ok(Some(authenticated)) => authenticated,
+        Ok(None) => return HttpError::Unauthorized.into_response(),
+        Err(e) => return HttpError::Database(e).into_response(),
+    };
+
+    if !scope.allows(request.method(), request.uri().path()) {
+        return HttpError::Forbidden.into_response();
+    }
+
+    auth_session.user = Some(user);
+    request.extensions_mut().insert(auth_session);
+
+    next.run(request).await
+}
+
+/// The API token of the `Authorization: Bearer` header
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
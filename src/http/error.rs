@@ -1,4 +1,6 @@
-use axum::http::StatusCode;
+use crate::http::template_into_response::TemplateIntoResponse;
+use askama::Template;
+use axum::http::{StatusCode, header};
 use axum::response::{IntoResponse, Response};
 use std::error::Error;
 use thiserror::Error;
@@ -12,6 +14,11 @@ pub enum HttpError {
     NotFound,
     #[error("Unauthorized Status")]
     Unauthorized,
+    #[error("Forbidden Status")]
+    Forbidden,
+    /// The number of seconds after which the client can try again
+    #[error("TooManyRequests Status, retry after {0}s")]
+    TooManyRequests(u64),
     #[error("Internal Error: `{0}`")]
     Internal(String),
     #[error("Database error: `{0}`")]
@@ -28,7 +35,11 @@ impl IntoResponse for HttpError {
     fn into_response(self) -> Response {
         if !matches!(
             self,
-            HttpError::BadRequest(_) | HttpError::NotFound | HttpError::Unauthorized
+            HttpError::BadRequest(_)
+                | HttpError::NotFound
+                | HttpError::Unauthorized
+                | HttpError::Forbidden
+                | HttpError::TooManyRequests(_)
         ) {
             if let Some(source) = self.source() {
                 error!("Error: {self}, caused by: {source}");
@@ -41,6 +52,13 @@ impl IntoResponse for HttpError {
             HttpError::BadRequest(message) => (StatusCode::BAD_REQUEST, message).into_response(),
             HttpError::NotFound => StatusCode::NOT_FOUND.into_response(),
             HttpError::Unauthorized => StatusCode::UNAUTHORIZED.into_response(),
+            HttpError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            HttpError::TooManyRequests(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                too_many_requests_message(retry_after),
+            )
+                .into_response(),
             HttpError::Internal(message) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, message).into_response()
             }
@@ -60,4 +78,29 @@ impl IntoResponse for HttpError {
     }
 }
 
+pub fn too_many_requests_message(retry_after: u64) -> String {
+    let wait = match retry_after {
+        0..=1 => "1 second".to_string(),
+        2..60 => format!("{retry_after} seconds"),
+        60 => "1 minute".to_string(),
+        _ => format!("{} minutes", retry_after.div_ceil(60)),
+    };
+
+    format!("Too many failed login attempts, please try again in {wait}")
+}
+
+/// The alert of the htmx forms, swapped in place of their `#error` element
+pub fn error_alert(message: &str) -> Response {
+    #[derive(Template)]
+    #[template(path = "user/login_error.html")]
+    struct ErrorTemplate<'a> {
+        error_message: &'a str,
+    }
+
+    ErrorTemplate {
+        error_message: message,
+    }
+    .into_response()
+}
+
 pub type HttpResult<T = Response> = Result<T, HttpError>;
@@ -0,0 +1,152 @@
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::model::login_attempt::LoginAttemptKind;
+use crate::repo::LoginAttemptsRepo;
+use std::net::IpAddr;
+use time::{Duration, OffsetDateTime};
+use tracing::warn;
+
+/// Fewer failures than this are not slowed down at all, to forgive typos
+const FREE_FAILURES: i64 = 3;
+/// A user or a share is locked after this many failures in a row
+const MAX_USER_FAILURES: i64 = 10;
+/// An IP gets more failures before being locked, as the whole family may share it
+const MAX_IP_FAILURES: i64 = 20;
+const LOCKOUT_DURATION: Duration = Duration::minutes(15);
+/// Failures older than this are forgotten
+const FAILURE_WINDOW: Duration = Duration::hours(1);
+
+fn max_failures(kind: LoginAttemptKind) -> i64 {
+    match kind {
+        LoginAttemptKind::Ip => MAX_IP_FAILURES,
+        LoginAttemptKind::User | LoginAttemptKind::Share => MAX_USER_FAILURES,
+    }
+}
+
+/// How long to refuse logins after the given number of consecutive failures:
+/// nothing for the first few, then doubling every time until the lockout is reached
+fn backoff_duration(failures: i64, max_failures: i64) -> Option<Duration> {
+    if failures < FREE_FAILURES {
+        return None;
+    }
+
+    if failures >= max_failures {
+        return Some(LOCKOUT_DURATION);
+    }
+
+    let exponent = (failures - FREE_FAILURES + 1).min(16) as u32;
+    Some(Duration::seconds(1 << exponent).min(LOCKOUT_DURATION))
+}
+
+/// The failures are counted for the client IP and for the user id or share id being logged into
+fn keys<'a>(
+    ip: Option<IpAddr>,
+    target_kind: LoginAttemptKind,
+    target: &'a str,
+) -> impl Iterator<Item = (LoginAttemptKind, String)> + 'a {
+    ip.map(|ip| (LoginAttemptKind::Ip, ip.to_string()))
+        .into_iter()
+        .chain((!target.is_empty()).then(|| (target_kind, target.to_string())))
+}
+
+/// Fails with [HttpError::TooManyRequests] while either the IP or the target is backing off
+pub async fn check_login_allowed(
+    state: AppStateRef,
+    ip: Option<IpAddr>,
+    target_kind: LoginAttemptKind,
+    target: &str,
+) -> HttpResult<()> {
+    let now = OffsetDateTime::now_utc();
+
+    for (kind, key) in keys(ip, target_kind, target) {
+        let Some(attempt) = state.read_pool.get_login_attempt(kind, &key).await? else {
+            continue;
+        };
+
+        if let Some(locked_until) = attempt.locked_until
+            && locked_until > now
+        {
+            let retry_after = (locked_until - now).whole_seconds().max(1) as u64;
+            return Err(HttpError::TooManyRequests(retry_after));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn record_login_failure(
+    state: AppStateRef,
+    ip: Option<IpAddr>,
+    target_kind: LoginAttemptKind,
+    target: &str,
+) -> HttpResult<()> {
+    let now = OffsetDateTime::now_utc();
+
+    for (kind, key) in keys(ip, target_kind, target) {
+        let failures = state
+            .write_pool
+            .record_login_failure(kind, &key, now, now - FAILURE_WINDOW)
+            .await?;
+
+        if let Some(backoff) = backoff_duration(failures, max_failures(kind)) {
+            if backoff == LOCKOUT_DURATION {
+                warn!("Locking logins for {kind} {key} after {failures} failures");
+            }
+
+            state
+                .write_pool
+                .set_locked_until(kind, &key, now + backoff)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A successful login forgives the previous failures of the target. Those of the IP are kept,
+/// or logging into an account of their own would let anyone keep guessing the others
+pub async fn clear_login_failures(
+    state: AppStateRef,
+    target_kind: LoginAttemptKind,
+    target: &str,
+) -> HttpResult<()> {
+    state
+        .write_pool
+        .delete_login_attempt(target_kind, target)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_duration() {
+        assert_eq!(backoff_duration(1, MAX_USER_FAILURES), None);
+        assert_eq!(backoff_duration(2, MAX_USER_FAILURES), None);
+        assert_eq!(
+            backoff_duration(3, MAX_USER_FAILURES),
+            Some(Duration::seconds(2))
+        );
+        assert_eq!(
+            backoff_duration(4, MAX_USER_FAILURES),
+            Some(Duration::seconds(4))
+        );
+        assert_eq!(
+            backoff_duration(9, MAX_USER_FAILURES),
+            Some(Duration::seconds(128))
+        );
+        assert_eq!(
+            backoff_duration(10, MAX_USER_FAILURES),
+            Some(LOCKOUT_DURATION)
+        );
+
+        // The backoff never exceeds the lockout
+        assert_eq!(
+            backoff_duration(19, MAX_IP_FAILURES),
+            Some(LOCKOUT_DURATION)
+        );
+    }
+}
@@ -1,37 +1,58 @@
+use crate::repo::event_log::EventLogRepo;
 use crate::repo::users_repo::UsersRepository;
+use crate::utils::env_reader::{MapConfig, OidcConfig, ProxyConfig, WritebackConfig};
+use crate::utils::oidc::OidcClient;
 use crate::utils::storage_resolver::StorageResolver;
-use axum::Router;
 use axum::extract::DefaultBodyLimit;
-use axum::http::{HeaderValue, header};
+use axum::http::{HeaderValue, Method, header};
+use axum::{Router, middleware};
+use axum_login::tower_sessions::cookie::SameSite;
 use axum_login::tower_sessions::{Expiry, SessionManagerLayer};
 use axum_login::{AuthManagerLayerBuilder, login_required};
 use sqlx::SqlitePool;
+use std::collections::HashSet;
 use time::Duration;
 use tokio::signal;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, watch};
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::services::ServeDir;
 use tower_http::set_header::SetResponseHeaderLayer;
 use tower_http::trace::TraceLayer;
 use tower_http::{cors, trace};
 use tower_sessions_sqlx_store::SqliteStore;
-use tracing::{Level, info, warn};
+use tracing::{Level, error, info, warn};
 
+mod admin_api;
 mod auth;
 mod error;
+mod login_limiter;
+mod oidc_api;
 mod pages;
 mod photos_api;
+mod proxy_auth;
+mod sessions_api;
 mod template_into_response;
+mod tokens_api;
+mod two_factor_api;
 mod users_api;
 mod utils;
 
+/// Sessions are logged out after this long without any request
+pub const SESSION_EXPIRY: Duration = Duration::days(60);
+
 pub fn router(
     app_state: AppStateRef,
     session_store: SqliteStore,
     allowed_origins: Vec<String>,
 ) -> Router {
-    let session_layer = SessionManagerLayer::new(session_store)
-        .with_expiry(Expiry::OnInactivity(Duration::days(60)));
+    let mut session_layer =
+        SessionManagerLayer::new(session_store).with_expiry(Expiry::OnInactivity(SESSION_EXPIRY));
+    if app_state.oidc.is_some() {
+        // The provider redirects back to the callback from another site,
+        // strict cookies would not be sent and the pending login would be lost
+        session_layer = session_layer.with_same_site(SameSite::Lax);
+    }
 
     let auth_layer =
         AuthManagerLayerBuilder::new(app_state.users_repo.clone(), session_layer).build();
@@ -40,21 +61,42 @@ pub fn router(
         .nest("/photos", photos_api::router(app_state))
         .route_layer(login_required!(UsersRepository));
 
+    // The resumable uploads use their own headers and methods, which browsers check first
+    let cors_layer = CorsLayer::new()
+        .allow_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers(
+            [header::AUTHORIZATION, header::CONTENT_TYPE]
+                .into_iter()
+                .chain(photos_api::TUS_REQUEST_HEADERS)
+                .collect::<Vec<_>>(),
+        )
+        .expose_headers(photos_api::TUS_RESPONSE_HEADERS);
     let cors_layer = if allowed_origins.is_empty() {
         info!("CORS: Allowing any origin");
-        CorsLayer::new().allow_origin(cors::Any)
+        cors_layer.allow_origin(cors::Any)
     } else {
         info!("CORS: Allowing origins: {:?}", allowed_origins);
         let origins: Vec<_> = allowed_origins
             .iter()
             .filter_map(|s| s.parse().ok())
             .collect();
-        CorsLayer::new().allow_origin(AllowOrigin::list(origins))
+        cors_layer.allow_origin(AllowOrigin::list(origins))
     };
 
     Router::new()
         .merge(pages::router(app_state))
-        .merge(users_api::router())
+        .merge(users_api::router(app_state))
+        .merge(two_factor_api::router(app_state))
+        .merge(oidc_api::router(app_state))
+        .merge(sessions_api::router(app_state))
+        .merge(tokens_api::router(app_state))
+        .merge(admin_api::router(app_state))
         .merge(authenticated_router)
         .nest_service("/assets", ServeDir::new("assets"))
         .layer(SetResponseHeaderLayer::overriding(
@@ -70,7 +112,20 @@ pub fn router(
                 .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                 .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
         )
+        .layer(middleware::from_fn_with_state(
+            app_state,
+            sessions_api::track_session,
+        ))
+        .layer(middleware::from_fn_with_state(
+            app_state,
+            proxy_auth::proxy_auth,
+        ))
+        .layer(middleware::from_fn(auth::bearer_auth))
         .layer(cors_layer)
+        .layer(middleware::from_fn_with_state(
+            app_state,
+            photos_api::advertise_tus_options,
+        ))
         .layer(auth_layer)
         .layer(DefaultBodyLimit::max(1024 * 1024 * 1024)) // 1GB
 }
@@ -81,20 +136,76 @@ pub struct AppState {
     pub write_pool: SqlitePool,
     pub users_repo: UsersRepository,
     pub preview_generation: Mutex<()>,
+    pub proxy: ProxyConfig,
+    pub oidc: Option<OidcClient>,
+    pub map: MapConfig,
+    pub writeback: WritebackConfig,
+    /// The id of the newest event, only kept up to date while clients listen for changes
+    pub latest_event_id: watch::Sender<i64>,
+    /// Cancelled when the server shuts down, to end the requests that never complete on their own
+    pub shutdown: CancellationToken,
+    /// The resumable uploads receiving data, a second request for the same upload has to wait
+    pub active_uploads: std::sync::Mutex<HashSet<String>>,
+    /// The largest file that can be uploaded with the tus protocol, in bytes
+    pub max_resumable_upload_size: i64,
 }
 
 impl AppState {
     pub const CSS_VERSION: &str = env!("CSS_VERSION");
 
-    pub fn new(read_pool: SqlitePool, write_pool: SqlitePool, storage: StorageResolver) -> Self {
+    pub fn new(
+        read_pool: SqlitePool,
+        write_pool: SqlitePool,
+        storage: StorageResolver,
+        proxy: ProxyConfig,
+        oidc: Option<OidcConfig>,
+        map: MapConfig,
+        writeback: WritebackConfig,
+    ) -> Self {
+        let users_repo = UsersRepository::new(write_pool.clone())
+            .with_user_provisioning(storage.storage_folder.clone());
+
         Self {
             storage,
-            users_repo: UsersRepository::new(write_pool.clone()),
+            users_repo,
             read_pool,
             write_pool,
             preview_generation: Mutex::new(()),
+            proxy,
+            oidc: oidc.map(OidcClient::new),
+            map,
+            writeback,
+            latest_event_id: watch::Sender::new(0),
+            shutdown: CancellationToken::new(),
+            active_uploads: std::sync::Mutex::new(HashSet::new()),
+            max_resumable_upload_size: i64::MAX,
         }
     }
+
+    /// Tells the clients listening for changes about the newest event. Called after committing
+    /// anything written to the event log, so the clients never read events that aren't visible yet
+    pub async fn publish_latest_event(&self) {
+        if self.latest_event_id.receiver_count() == 0 {
+            return;
+        }
+
+        match self.read_pool.get_event_id_range().await {
+            Ok(range) => {
+                let latest_event_id = range.map_or(0, |(_, max_id)| max_id);
+                self.latest_event_id.send_if_modified(|current| {
+                    let modified = *current < latest_event_id;
+                    *current = latest_event_id.max(*current);
+                    modified
+                });
+            }
+            Err(e) => error!("Failed to read the latest event: {e}"),
+        }
+    }
+
+    pub fn with_max_resumable_upload_size(mut self, max_size: i64) -> Self {
+        self.max_resumable_upload_size = max_size;
+        self
+    }
 }
 
 pub type AppStateRef = &'static AppState;
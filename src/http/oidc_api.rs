@@ -0,0 +1,174 @@
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::user::render_login_page;
+use crate::http::utils::AuthSession;
+use crate::model::user::{Credentials, User};
+use crate::utils::oidc::OidcClient;
+use axum::Router;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::get;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::{debug, error, warn};
+
+const PENDING_LOGIN_KEY: &str = "oidc.pending_login";
+/// How long the user has to log in on the provider's site
+const PENDING_LOGIN_TTL_SECONDS: i64 = 10 * 60;
+
+pub fn router(app_state: AppStateRef) -> Router {
+    Router::new()
+        .route("/login/oidc", get(begin_login))
+        .route("/login/oidc/callback", get(callback))
+        .with_state(app_state)
+}
+
+/// A login that was sent to the provider and is waiting for the callback
+#[derive(Serialize, Deserialize)]
+struct PendingOidcLogin {
+    state: String,
+    nonce: String,
+    code_verifier: String,
+    created_at: i64,
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+fn oidc_client(state: AppStateRef) -> HttpResult<&'static OidcClient> {
+    state.oidc.as_ref().ok_or(HttpError::NotFound)
+}
+
+async fn begin_login(State(state): State<AppStateRef>, auth: AuthSession) -> HttpResult<Response> {
+    let oidc = oidc_client(state)?;
+
+    if auth.user.is_some() {
+        return Ok(Redirect::to("/").into_response());
+    }
+
+    let request = match oidc.authorization_request().await {
+        Ok(request) => request,
+        Err(e) => {
+            error!("Failed to start the OpenID Connect login: {e}");
+            return Ok(render_login_page(
+                state,
+                Some("The identity provider is unavailable, please try again later"),
+            ));
+        }
+    };
+
+    let pending = PendingOidcLogin {
+        state: request.state,
+        nonce: request.nonce,
+        code_verifier: request.code_verifier,
+        created_at: OffsetDateTime::now_utc().unix_timestamp(),
+    };
+    auth.session
+        .insert(PENDING_LOGIN_KEY, pending)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    Ok(Redirect::to(&request.url).into_response())
+}
+
+async fn callback(
+    State(state): State<AppStateRef>,
+    mut auth: AuthSession,
+    Query(query): Query<CallbackQuery>,
+) -> HttpResult<Response> {
+    let oidc = oidc_client(state)?;
+
+    // The login can only be completed once, whatever the outcome
+    let pending = auth
+        .session
+        .remove::<PendingOidcLogin>(PENDING_LOGIN_KEY)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    match callback_service(oidc, &mut auth, pending, query).await {
+        Ok(user) => {
+            debug!("User has been logged in with OpenID Connect: {}", user.id);
+            Ok(Redirect::to("/").into_response())
+        }
+        Err(HttpError::BadRequest(message)) => Ok(render_login_page(state, Some(&message))),
+        Err(e) => Err(e),
+    }
+}
+
+async fn callback_service(
+    oidc: &OidcClient,
+    auth: &mut AuthSession,
+    pending: Option<PendingOidcLogin>,
+    query: CallbackQuery,
+) -> HttpResult<User> {
+    let expired_error =
+        || HttpError::BadRequest("The login has expired, please try again".to_string());
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let Some(pending) =
+        pending.filter(|pending| now - pending.created_at < PENDING_LOGIN_TTL_SECONDS)
+    else {
+        return Err(expired_error());
+    };
+
+    if query.state.as_deref() != Some(pending.state.as_str()) {
+        warn!("OpenID Connect callback with a mismatched state");
+        return Err(expired_error());
+    }
+
+    if let Some(error) = query.error {
+        warn!(
+            "The identity provider refused the login: {error} {}",
+            query.error_description.unwrap_or_default()
+        );
+        return Err(HttpError::BadRequest(
+            "The identity provider refused the login".to_string(),
+        ));
+    }
+
+    let code = query.code.ok_or_else(expired_error)?;
+
+    let identity = async {
+        let id_token = oidc.exchange_code(&code, &pending.code_verifier).await?;
+        oidc.verify_id_token(&id_token, &pending.nonce).await
+    }
+    .await
+    .map_err(|e| {
+        error!("Failed to complete the OpenID Connect login: {e}");
+        HttpError::BadRequest("The login with the identity provider failed".to_string())
+    })?;
+
+    let user = auth
+        .authenticate(Credentials::External {
+            user_id: identity.user_id.clone(),
+            name: identity.name,
+            create_if_missing: oidc.config().auto_create_users,
+        })
+        .await
+        .map_err(|e| {
+            error!("Failed to authenticate user {}: {e}", identity.user_id);
+            HttpError::Internal("Failed to login".to_string())
+        })?
+        .ok_or_else(|| {
+            warn!(
+                "The identity provider authenticated unknown user {}",
+                identity.user_id
+            );
+            HttpError::BadRequest(format!(
+                "There is no account for {}, ask an admin to create it",
+                identity.user_id
+            ))
+        })?;
+
+    auth.login(&user).await.map_err(|e| {
+        error!("Failed to login user {} with error: {}", user.id, e);
+        HttpError::Internal("Failed to login".to_string())
+    })?;
+
+    Ok(user)
+}
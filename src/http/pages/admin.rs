@@ -0,0 +1,62 @@
+use crate::http::AppStateRef;
+use crate::http::auth::{AdminUser, AuthenticatedUser};
+use crate::http::error::HttpResult;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::repo::PhotosRepo;
+use askama::Template;
+use axum::extract::State;
+use axum::response::{Html, IntoResponse, Response};
+
+struct AdminUserRow {
+    id: String,
+    name: String,
+    is_admin: bool,
+    photo_count: usize,
+}
+
+#[derive(Template)]
+#[template(path = "admin/admin_page.html")]
+struct AdminPageTemplate {
+    current_user_id: String,
+    users: Vec<AdminUserRow>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/sidebar_link.html")]
+struct AdminSidebarLinkTemplate;
+
+pub async fn admin_page(
+    AdminUser(admin): AdminUser,
+    State(state): State<AppStateRef>,
+) -> HttpResult<Response> {
+    let mut users = Vec::new();
+    for user in state.users_repo.get_users().await? {
+        let photo_count = state
+            .read_pool
+            .get_photos_by_user(Some(user.id.as_str()))
+            .await?
+            .len();
+
+        users.push(AdminUserRow {
+            id: user.id,
+            name: user.name,
+            is_admin: user.is_admin,
+            photo_count,
+        });
+    }
+
+    AdminPageTemplate {
+        current_user_id: admin.id,
+        users,
+    }
+    .try_into_response()
+}
+
+/// The sidebar is shared by all pages, so the admin entry is loaded lazily
+pub async fn admin_sidebar_link(AuthenticatedUser(user): AuthenticatedUser) -> Response {
+    if user.is_admin {
+        AdminSidebarLinkTemplate.into_response()
+    } else {
+        Html("").into_response()
+    }
+}
@@ -0,0 +1,99 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::HttpResult;
+use crate::http::photos_api::{
+    CreateAlbumRequest, create_album_service, delete_album_service, get_albums_info,
+    remove_member_service,
+};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::album::AlbumInfo;
+use crate::model::user::SimpleUser;
+use crate::repo::PhotosRepo;
+use askama::Template;
+use axum::Form;
+use axum::extract::{Path, State};
+use axum::response::{Html, IntoResponse, Response};
+use serde::Deserialize;
+
+#[derive(Template)]
+#[template(path = "albums/albums_page.html")]
+struct AlbumsPageTemplate {
+    owned_albums: Vec<AlbumInfo>,
+    shared_albums: Vec<AlbumInfo>,
+    personal_folders: Vec<String>,
+    other_users: Vec<SimpleUser>,
+}
+
+#[derive(Deserialize)]
+pub struct ShareFolderForm {
+    folder_name: String,
+    member_id: String,
+}
+
+pub async fn albums_page(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+) -> HttpResult<Response> {
+    let (owned_albums, shared_albums) = get_albums_info(state, &user.id)
+        .await?
+        .into_iter()
+        .partition(|info| info.album.owner_id == user.id);
+
+    let personal_folders = state
+        .read_pool
+        .get_distinct_personal_folders(&user.id)
+        .await?;
+
+    let other_users = state
+        .users_repo
+        .get_users()
+        .await?
+        .into_iter()
+        .filter(|other| other.id != user.id)
+        .map(SimpleUser::from)
+        .collect();
+
+    AlbumsPageTemplate {
+        owned_albums,
+        shared_albums,
+        personal_folders,
+        other_users,
+    }
+    .try_into_response()
+}
+
+pub async fn share_folder(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Form(form): Form<ShareFolderForm>,
+) -> HttpResult<Response> {
+    let request = CreateAlbumRequest {
+        folder_name: form.folder_name,
+        members: vec![form.member_id],
+    };
+
+    create_album_service(state, &user.id, request).await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+pub async fn delete_album(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(album_id): Path<i64>,
+) -> HttpResult<Response> {
+    delete_album_service(state, &user.id, album_id).await?;
+
+    // Return empty HTML to remove the card via hx-swap="outerHTML"
+    Ok(Html("").into_response())
+}
+
+pub async fn remove_member(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path((album_id, member_id)): Path<(i64, String)>,
+) -> HttpResult<Response> {
+    remove_member_service(state, &user.id, album_id, &member_id).await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
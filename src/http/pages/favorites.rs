@@ -13,7 +13,6 @@ use axum::extract::{Path, Query, State};
 use axum::http::Method;
 use axum::response::Response;
 use serde::Deserialize;
-use std::collections::HashSet;
 
 #[derive(Template)]
 #[template(path = "favorites/favorites_page.html")]
@@ -33,7 +32,9 @@ pub async fn favorites_page(
         .get_favorite_photos_paginated(&user.id, None, PAGE_SIZE)
         .await?;
 
-    let processed = ProcessedPhotos::from_paginated(paginated, &HashSet::default(), None);
+    let processed =
+        ProcessedPhotos::from_paginated_with_details(paginated, &state.read_pool, None, None)
+            .await?;
 
     FavoritesPageTemplate {
         groups: processed.groups,
@@ -57,7 +58,9 @@ pub async fn load_more_favorites(
         .get_favorite_photos_paginated(&user.id, cursor.as_ref(), PAGE_SIZE)
         .await?;
 
-    let processed = ProcessedPhotos::from_paginated(paginated, &HashSet::default(), skip_month);
+    let processed =
+        ProcessedPhotos::from_paginated_with_details(paginated, &state.read_pool, None, skip_month)
+            .await?;
 
     PhotoBatchTemplate {
         groups: processed.groups,
@@ -97,7 +100,7 @@ pub async fn toggle_favorite(
     method: Method,
 ) -> HttpResult<Response> {
     let mut tx = state.write_pool.begin().await?;
-    tx.get_photo(photo_id, &user.id)
+    tx.get_visible_photo(photo_id, &user.id)
         .await?
         .ok_or(HttpError::NotFound)?;
 
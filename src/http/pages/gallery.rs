@@ -2,24 +2,36 @@ use super::timeline::build_timeline_data;
 use crate::http::AppStateRef;
 use crate::http::auth::AuthenticatedUser;
 use crate::http::error::{HttpError, HttpResult};
+use crate::http::photos_api::{UpdateDetailsRequest, update_details_service};
 use crate::http::template_into_response::TemplateIntoResponse;
 use crate::model::photo::Photo;
 use crate::model::photo_category::PhotoCategory;
-use crate::repo::{FavoritesRepo, PaginatedPhotos, PhotoCursor, PhotosRepo};
+use crate::model::photo_metadata::{PhotoMetadata, format_duration};
+use crate::model::place::Place;
+use crate::model::tag::Tag;
+use crate::repo::{
+    FavoritesRepo, PaginatedPhotos, PhotoCursor, PhotoMetadataRepo, PhotosRepo, PlacesRepo,
+    TagsRepo,
+};
 use askama::Template;
+use axum::Form;
 use axum::extract::{Path, Query, State};
-use axum::response::Response;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use base64::Engine;
 use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
 use serde::Deserialize;
 use sqlx::SqlitePool;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use time::{Month, OffsetDateTime};
 
 #[derive(Debug, Default, Deserialize)]
 pub struct GalleryQuery {
     #[serde(default)]
     pub category: PhotoCategory,
+    /// Only used by the gallery, 0 shows the unrated photos too
+    #[serde(default)]
+    pub min_rating: i64,
 }
 
 #[derive(Template)]
@@ -30,10 +42,23 @@ struct GalleryPageTemplate {
     next_cursor: Option<String>,
     has_more: bool,
     last_month: Option<String>,
+    load_more_url: String,
+    min_rating: i64,
     timeline_json: String,
     total_photos: i64,
 }
 
+impl GalleryPageTemplate {
+    /// Keeps the rating filter when switching the category
+    fn rating_query(&self) -> String {
+        if self.min_rating > 0 {
+            format!("&min_rating={}", self.min_rating)
+        } else {
+            String::new()
+        }
+    }
+}
+
 #[derive(Template)]
 #[template(path = "gallery/photo_grid.html")]
 struct PhotoGridTemplate {
@@ -42,6 +67,7 @@ struct PhotoGridTemplate {
     next_cursor: Option<String>,
     has_more: bool,
     last_month: Option<String>,
+    load_more_url: String,
 }
 
 #[derive(Template)]
@@ -55,6 +81,13 @@ pub struct PhotoBatchTemplate {
     pub category: Option<PhotoCategory>,
 }
 
+/// A single card with its month header, for the photos added while the gallery is open
+#[derive(Template)]
+#[template(path = "gallery/live_card.html")]
+struct LiveCardTemplate {
+    groups: Vec<MonthGroup>,
+}
+
 #[derive(Template)]
 #[template(path = "gallery/folder_page.html")]
 struct FolderPageTemplate {
@@ -84,6 +117,18 @@ struct PhotoModalTemplate {
 struct PhotoInfoPanelTemplate {
     photo: Photo,
     file_size_formatted: String,
+    metadata: Option<PhotoMetadata>,
+    place: Option<Place>,
+    tags: Vec<Tag>,
+    /// Only the owner and the family can change the tags, caption and rating of a photo
+    can_edit_tags: bool,
+}
+
+#[derive(Template)]
+#[template(path = "gallery/photo_details.html")]
+struct PhotoDetailsTemplate {
+    photo: Photo,
+    can_edit: bool,
 }
 
 #[derive(Template)]
@@ -101,22 +146,56 @@ pub struct PhotoView {
     pub is_favorite: bool,
     pub thumb_hash: Option<String>,
     pub created_at: OffsetDateTime,
+    /// Only set for videos
+    pub duration: Option<String>,
 }
 
 impl PhotoView {
-    pub fn from_photo(photo: Photo, favorites: &HashSet<i64>) -> Self {
+    pub fn from_photo(photo: Photo, details: &PhotoCardDetails) -> Self {
         let thumb_hash = photo.thumb_hash.as_ref().map(|h| STANDARD.encode(h));
 
         Self {
             id: photo.id,
             name: photo.name,
-            is_favorite: favorites.contains(&photo.id),
+            is_favorite: details.favorite_ids.contains(&photo.id),
             thumb_hash,
             created_at: photo.created_at,
+            duration: details
+                .durations
+                .get(&photo.id)
+                .copied()
+                .map(format_duration),
         }
     }
 }
 
+/// What the photo cards show besides the photo itself
+#[derive(Default)]
+pub struct PhotoCardDetails {
+    pub favorite_ids: HashSet<i64>,
+    pub durations: HashMap<i64, f64>,
+}
+
+impl PhotoCardDetails {
+    /// Favorites are only looked up when a user is given
+    pub async fn load(
+        pool: &SqlitePool,
+        user_id: Option<&str>,
+        photo_ids: &[i64],
+    ) -> sqlx::Result<Self> {
+        let favorite_ids = match user_id {
+            Some(user_id) => pool.check_favorites_for_ids(user_id, photo_ids).await?,
+            None => HashSet::new(),
+        };
+        let durations = pool.get_video_durations(photo_ids).await?;
+
+        Ok(Self {
+            favorite_ids,
+            durations,
+        })
+    }
+}
+
 pub const PAGE_SIZE: u32 = 500;
 
 /// A group of photos from the same month/year
@@ -134,6 +213,8 @@ pub struct PaginatedQuery {
     pub last_month: Option<String>,
     #[serde(default)]
     pub category: PhotoCategory,
+    #[serde(default)]
+    pub min_rating: i64,
 }
 
 /// Encode a cursor to a URL-safe base64 string
@@ -233,6 +314,15 @@ pub fn group_photos_by_month(
     groups
 }
 
+/// The gallery keeps the rating filter while loading more photos
+fn gallery_load_more_url(min_rating: i64) -> String {
+    if min_rating > 0 {
+        format!("/gallery/more?min_rating={min_rating}")
+    } else {
+        "/gallery/more".to_string()
+    }
+}
+
 /// Get the last month from a list of groups
 pub fn get_last_month(groups: &[MonthGroup]) -> Option<String> {
     groups.last().and_then(|g| {
@@ -254,13 +344,13 @@ impl ProcessedPhotos {
     /// Convert paginated photos into a processed result ready for templates
     pub fn from_paginated(
         paginated: PaginatedPhotos,
-        favorite_ids: &HashSet<i64>,
+        details: &PhotoCardDetails,
         skip_month: Option<(i32, Month)>,
     ) -> Self {
         let photo_views: Vec<PhotoView> = paginated
             .photos
             .into_iter()
-            .map(|p| PhotoView::from_photo(p, favorite_ids))
+            .map(|p| PhotoView::from_photo(p, details))
             .collect();
 
         let groups = group_photos_by_month(photo_views, skip_month);
@@ -275,16 +365,40 @@ impl ProcessedPhotos {
         }
     }
 
-    /// Fetch favorites and convert paginated photos in one step
-    pub async fn from_paginated_with_favorites(
+    /// Fetch the card details and convert paginated photos in one step
+    pub async fn from_paginated_with_details(
         paginated: PaginatedPhotos,
         pool: &SqlitePool,
-        user_id: &str,
+        user_id: Option<&str>,
         skip_month: Option<(i32, Month)>,
     ) -> sqlx::Result<Self> {
         let photo_ids: Vec<i64> = paginated.photos.iter().map(|p| p.id).collect();
-        let favorite_ids = pool.check_favorites_for_ids(user_id, &photo_ids).await?;
-        Ok(Self::from_paginated(paginated, &favorite_ids, skip_month))
+        let details = PhotoCardDetails::load(pool, user_id, &photo_ids).await?;
+        Ok(Self::from_paginated(paginated, &details, skip_month))
+    }
+}
+
+/// Folders of albums shared with the user are looked up by membership instead of ownership
+async fn get_folder_photos_paginated(
+    pool: &SqlitePool,
+    user_id: &str,
+    folder_name: &str,
+    category: PhotoCategory,
+    cursor: Option<&PhotoCursor>,
+) -> sqlx::Result<PaginatedPhotos> {
+    match category {
+        PhotoCategory::Shared => {
+            pool.get_shared_folder_photos_paginated(user_id, folder_name, cursor, PAGE_SIZE)
+                .await
+        }
+        PhotoCategory::Personal | PhotoCategory::All => {
+            pool.get_folder_photos_paginated(user_id, folder_name, true, cursor, PAGE_SIZE)
+                .await
+        }
+        PhotoCategory::Family => {
+            pool.get_folder_photos_paginated(user_id, folder_name, false, cursor, PAGE_SIZE)
+                .await
+        }
     }
 }
 
@@ -294,22 +408,23 @@ pub async fn gallery_page(
     Query(query): Query<GalleryQuery>,
 ) -> HttpResult<Response> {
     let category = query.category;
+    let min_rating = query.min_rating;
     let mut tx = state.read_pool.begin().await?;
 
     let paginated = tx
-        .get_photos_paginated(&user.id, category, None, PAGE_SIZE)
+        .get_photos_paginated(&user.id, category, min_rating, None, PAGE_SIZE)
         .await?;
 
     let photo_ids: Vec<i64> = paginated.photos.iter().map(|p| p.id).collect();
-    let favorite_ids = tx.check_favorites_for_ids(&user.id, &photo_ids).await?;
+    let details = PhotoCardDetails::load(&state.read_pool, Some(&user.id), &photo_ids).await?;
 
     let month_summaries = state
         .read_pool
-        .get_month_summaries(&user.id, category)
+        .get_month_summaries(&user.id, category, min_rating)
         .await?;
 
     let timeline = build_timeline_data(month_summaries);
-    let processed = ProcessedPhotos::from_paginated(paginated, &favorite_ids, None);
+    let processed = ProcessedPhotos::from_paginated(paginated, &details, None);
 
     GalleryPageTemplate {
         groups: processed.groups,
@@ -317,6 +432,8 @@ pub async fn gallery_page(
         next_cursor: processed.next_cursor,
         has_more: processed.has_more,
         last_month: processed.last_month,
+        load_more_url: gallery_load_more_url(min_rating),
+        min_rating,
         timeline_json: timeline.data_json,
         total_photos: timeline.total_photos,
     }
@@ -329,15 +446,20 @@ pub async fn photo_grid(
     Query(query): Query<GalleryQuery>,
 ) -> HttpResult<Response> {
     let category = query.category;
+    let min_rating = query.min_rating;
 
     let paginated = state
         .read_pool
-        .get_photos_paginated(&user.id, category, None, PAGE_SIZE)
+        .get_photos_paginated(&user.id, category, min_rating, None, PAGE_SIZE)
         .await?;
 
-    let processed =
-        ProcessedPhotos::from_paginated_with_favorites(paginated, &state.read_pool, &user.id, None)
-            .await?;
+    let processed = ProcessedPhotos::from_paginated_with_details(
+        paginated,
+        &state.read_pool,
+        Some(&user.id),
+        None,
+    )
+    .await?;
 
     PhotoGridTemplate {
         groups: processed.groups,
@@ -345,6 +467,7 @@ pub async fn photo_grid(
         next_cursor: processed.next_cursor,
         has_more: processed.has_more,
         last_month: processed.last_month,
+        load_more_url: gallery_load_more_url(min_rating),
     }
     .try_into_response()
 }
@@ -356,22 +479,32 @@ pub async fn folder_page(
     Query(query): Query<GalleryQuery>,
 ) -> HttpResult<Response> {
     let category = query.category;
-    let is_personal = matches!(category, PhotoCategory::Personal | PhotoCategory::All);
 
-    let paginated = state
-        .read_pool
-        .get_folder_photos_paginated(&user.id, &folder_name, is_personal, None, PAGE_SIZE)
-        .await?;
+    let paginated =
+        get_folder_photos_paginated(&state.read_pool, &user.id, &folder_name, category, None)
+            .await?;
 
-    let month_summaries = state
-        .read_pool
-        .get_folder_month_summaries(&user.id, &folder_name, is_personal)
-        .await?;
+    let month_summaries = if category == PhotoCategory::Shared {
+        state
+            .read_pool
+            .get_shared_folder_month_summaries(&user.id, &folder_name)
+            .await?
+    } else {
+        let is_personal = category != PhotoCategory::Family;
+        state
+            .read_pool
+            .get_folder_month_summaries(&user.id, &folder_name, is_personal)
+            .await?
+    };
 
     let timeline = build_timeline_data(month_summaries);
-    let processed =
-        ProcessedPhotos::from_paginated_with_favorites(paginated, &state.read_pool, &user.id, None)
-            .await?;
+    let processed = ProcessedPhotos::from_paginated_with_details(
+        paginated,
+        &state.read_pool,
+        Some(&user.id),
+        None,
+    )
+    .await?;
 
     FolderPageTemplate {
         groups: processed.groups,
@@ -398,13 +531,19 @@ pub async fn load_more_gallery(
 
     let paginated = state
         .read_pool
-        .get_photos_paginated(&user.id, category, cursor.as_ref(), PAGE_SIZE)
+        .get_photos_paginated(
+            &user.id,
+            category,
+            query.min_rating,
+            cursor.as_ref(),
+            PAGE_SIZE,
+        )
         .await?;
 
-    let processed = ProcessedPhotos::from_paginated_with_favorites(
+    let processed = ProcessedPhotos::from_paginated_with_details(
         paginated,
         &state.read_pool,
-        &user.id,
+        Some(&user.id),
         skip_month,
     )
     .await?;
@@ -414,7 +553,7 @@ pub async fn load_more_gallery(
         next_cursor: processed.next_cursor,
         has_more: processed.has_more,
         last_month: processed.last_month,
-        load_more_url: "/gallery/more".to_string(),
+        load_more_url: gallery_load_more_url(query.min_rating),
         category: Some(category),
     }
     .try_into_response()
@@ -427,26 +566,23 @@ pub async fn load_more_folder(
     Query(query): Query<PaginatedQuery>,
 ) -> HttpResult<Response> {
     let category = query.category;
-    let is_personal = matches!(category, PhotoCategory::Personal | PhotoCategory::All);
 
     let cursor = parse_optional_cursor(query.cursor.as_deref())?;
     let skip_month = query.last_month.as_ref().and_then(|m| parse_month_key(m));
 
-    let paginated = state
-        .read_pool
-        .get_folder_photos_paginated(
-            &user.id,
-            &folder_name,
-            is_personal,
-            cursor.as_ref(),
-            PAGE_SIZE,
-        )
-        .await?;
+    let paginated = get_folder_photos_paginated(
+        &state.read_pool,
+        &user.id,
+        &folder_name,
+        category,
+        cursor.as_ref(),
+    )
+    .await?;
 
-    let processed = ProcessedPhotos::from_paginated_with_favorites(
+    let processed = ProcessedPhotos::from_paginated_with_details(
         paginated,
         &state.read_pool,
-        &user.id,
+        Some(&user.id),
         skip_month,
     )
     .await?;
@@ -462,6 +598,43 @@ pub async fn load_more_folder(
     .try_into_response()
 }
 
+/// Whether the gallery with these filters shows a photo that the user can see
+fn is_in_gallery(photo: &Photo, user_id: &str, query: &GalleryQuery) -> bool {
+    let in_category = match query.category {
+        PhotoCategory::All => true,
+        PhotoCategory::Personal => photo.user_id.as_deref() == Some(user_id),
+        PhotoCategory::Family => photo.user_id.is_none(),
+        PhotoCategory::Shared => photo.user_id.as_deref().is_some_and(|id| id != user_id),
+    };
+
+    in_category && photo.trashed_on.is_none() && photo.rating >= query.min_rating
+}
+
+/// The card of a new photo, or nothing if the gallery with these filters doesn't show it
+pub async fn live_card(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(photo_id): Path<i64>,
+    Query(query): Query<GalleryQuery>,
+) -> HttpResult<Response> {
+    let photo = state
+        .read_pool
+        .get_visible_photo(photo_id, &user.id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    if !is_in_gallery(&photo, &user.id, &query) {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    let details = PhotoCardDetails::load(&state.read_pool, Some(&user.id), &[photo.id]).await?;
+
+    LiveCardTemplate {
+        groups: group_photos_by_month(vec![PhotoView::from_photo(photo, &details)], None),
+    }
+    .try_into_response()
+}
+
 pub async fn photo_modal(
     AuthenticatedUser(user): AuthenticatedUser,
     State(state): State<AppStateRef>,
@@ -469,7 +642,7 @@ pub async fn photo_modal(
 ) -> HttpResult<Response> {
     let photo = state
         .read_pool
-        .get_photo(photo_id, &user.id)
+        .get_visible_photo(photo_id, &user.id)
         .await?
         .ok_or(HttpError::NotFound)?;
 
@@ -498,15 +671,45 @@ pub async fn photo_info_panel(
 ) -> HttpResult<Response> {
     let photo = state
         .read_pool
-        .get_photo(photo_id, &user.id)
+        .get_visible_photo(photo_id, &user.id)
         .await?
         .ok_or(HttpError::NotFound)?;
 
     let file_size_formatted = format_file_size(photo.file_size);
+    let metadata = state
+        .read_pool
+        .get_photo_metadata(photo.id)
+        .await?
+        .filter(|metadata| !metadata.is_empty());
+    let place = state.read_pool.get_photo_place(photo.id).await?;
+    let tags = state.read_pool.get_photo_tags(photo.id).await?;
+    let can_edit_tags = photo
+        .user_id
+        .as_ref()
+        .is_none_or(|owner_id| *owner_id == user.id);
 
     PhotoInfoPanelTemplate {
         photo,
         file_size_formatted,
+        metadata,
+        place,
+        tags,
+        can_edit_tags,
+    }
+    .try_into_response()
+}
+
+pub async fn update_photo_details(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(photo_id): Path<i64>,
+    Form(form): Form<UpdateDetailsRequest>,
+) -> HttpResult<Response> {
+    let photo = update_details_service(state, &user.id, photo_id, form).await?;
+
+    PhotoDetailsTemplate {
+        photo,
+        can_edit: true,
     }
     .try_into_response()
 }
@@ -518,7 +721,7 @@ pub async fn photo_viewer_media(
 ) -> HttpResult<Response> {
     let photo = state
         .read_pool
-        .get_photo(photo_id, &user.id)
+        .get_visible_photo(photo_id, &user.id)
         .await?
         .ok_or(HttpError::NotFound)?;
 
@@ -583,6 +786,77 @@ mod tests {
         assert!(decode_cursor("aGVsbG8").is_none());
     }
 
+    #[test]
+    fn test_is_in_gallery() {
+        let mut photo = Photo {
+            id: 1,
+            user_id: Some("user1".to_string()),
+            name: "photo.jpg".to_string(),
+            created_at: OffsetDateTime::UNIX_EPOCH,
+            file_size: 1,
+            folder: None,
+            thumb_hash: None,
+            trashed_on: None,
+            caption: None,
+            rating: 3,
+        };
+        let query = |category, min_rating| GalleryQuery {
+            category,
+            min_rating,
+        };
+
+        assert!(is_in_gallery(
+            &photo,
+            "user1",
+            &query(PhotoCategory::All, 0)
+        ));
+        assert!(is_in_gallery(
+            &photo,
+            "user1",
+            &query(PhotoCategory::Personal, 3)
+        ));
+        assert!(!is_in_gallery(
+            &photo,
+            "user1",
+            &query(PhotoCategory::Personal, 4)
+        ));
+        assert!(!is_in_gallery(
+            &photo,
+            "user1",
+            &query(PhotoCategory::Family, 0)
+        ));
+        assert!(!is_in_gallery(
+            &photo,
+            "user1",
+            &query(PhotoCategory::Shared, 0)
+        ));
+        // The photo of an album shared with another user
+        assert!(is_in_gallery(
+            &photo,
+            "user2",
+            &query(PhotoCategory::Shared, 0)
+        ));
+
+        photo.user_id = None;
+        assert!(is_in_gallery(
+            &photo,
+            "user1",
+            &query(PhotoCategory::Family, 0)
+        ));
+        assert!(!is_in_gallery(
+            &photo,
+            "user1",
+            &query(PhotoCategory::Personal, 0)
+        ));
+
+        photo.trashed_on = Some(OffsetDateTime::UNIX_EPOCH);
+        assert!(!is_in_gallery(
+            &photo,
+            "user1",
+            &query(PhotoCategory::All, 0)
+        ));
+    }
+
     #[test]
     fn test_parse_month_key_valid() {
         let result = parse_month_key("2024-06");
@@ -624,6 +898,7 @@ mod tests {
         assert_eq!(format!("{}", PhotoCategory::All), "all");
         assert_eq!(format!("{}", PhotoCategory::Personal), "personal");
         assert_eq!(format!("{}", PhotoCategory::Family), "family");
+        assert_eq!(format!("{}", PhotoCategory::Shared), "shared");
     }
 
     #[test]
@@ -642,6 +917,7 @@ mod tests {
                 is_favorite: false,
                 thumb_hash: None,
                 created_at: datetime!(2024-06-15 10:00:00 UTC),
+                duration: None,
             },
             PhotoView {
                 id: 2,
@@ -649,6 +925,7 @@ mod tests {
                 is_favorite: true,
                 thumb_hash: None,
                 created_at: datetime!(2024-06-20 15:00:00 UTC),
+                duration: None,
             },
         ];
 
@@ -669,6 +946,7 @@ mod tests {
                 is_favorite: false,
                 thumb_hash: None,
                 created_at: datetime!(2024-06-15 10:00:00 UTC),
+                duration: None,
             },
             PhotoView {
                 id: 2,
@@ -676,6 +954,7 @@ mod tests {
                 is_favorite: false,
                 thumb_hash: None,
                 created_at: datetime!(2024-05-10 10:00:00 UTC),
+                duration: None,
             },
             PhotoView {
                 id: 3,
@@ -683,6 +962,7 @@ mod tests {
                 is_favorite: false,
                 thumb_hash: None,
                 created_at: datetime!(2024-05-20 10:00:00 UTC),
+                duration: None,
             },
         ];
 
@@ -703,6 +983,7 @@ mod tests {
                 is_favorite: false,
                 thumb_hash: None,
                 created_at: datetime!(2024-06-15 10:00:00 UTC),
+                duration: None,
             },
             PhotoView {
                 id: 2,
@@ -710,6 +991,7 @@ mod tests {
                 is_favorite: false,
                 thumb_hash: None,
                 created_at: datetime!(2024-05-10 10:00:00 UTC),
+                duration: None,
             },
         ];
 
@@ -745,6 +1027,7 @@ mod tests {
                 is_favorite: false,
                 thumb_hash: None,
                 created_at: datetime!(2024-06-15 10:00:00 UTC),
+                duration: None,
             }],
             show_header: true,
         }];
@@ -0,0 +1,130 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::gallery::{GalleryQuery, PAGE_SIZE, PhotoCardDetails, PhotoView};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::map::{MapBounds, cluster_cell_size};
+use crate::model::photo_category::PhotoCategory;
+use crate::repo::MapRepo;
+use askama::Template;
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+#[derive(Template)]
+#[template(path = "map/map_page.html")]
+struct MapPageTemplate {
+    current_category: PhotoCategory,
+    tile_url: &'static str,
+    attribution: &'static str,
+    max_zoom: u8,
+}
+
+#[derive(Template)]
+#[template(path = "map/map_photos.html")]
+struct MapPhotosTemplate {
+    photos: Vec<PhotoView>,
+    has_more: bool,
+}
+
+#[derive(Deserialize)]
+pub struct MapClustersQuery {
+    bbox: String,
+    zoom: u8,
+    #[serde(default)]
+    category: PhotoCategory,
+}
+
+#[derive(Deserialize)]
+pub struct MapPhotosQuery {
+    bbox: String,
+    #[serde(default)]
+    category: PhotoCategory,
+}
+
+#[derive(Serialize)]
+struct MapClusterView {
+    latitude: f64,
+    longitude: f64,
+    count: i64,
+    cover_photo_id: i64,
+    /// The date of the most recent photo, like "2024-03-15"
+    latest_date: String,
+    /// The grid cell of the cluster, used to list its photos
+    bounds: MapBounds,
+}
+
+fn parse_bbox(bbox: &str) -> HttpResult<MapBounds> {
+    MapBounds::parse_bbox(bbox).ok_or_else(|| {
+        HttpError::BadRequest("bbox must be west,south,east,north in degrees".to_string())
+    })
+}
+
+pub async fn map_page(
+    AuthenticatedUser(_user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Query(query): Query<GalleryQuery>,
+) -> HttpResult<Response> {
+    MapPageTemplate {
+        current_category: query.category,
+        tile_url: &state.map.tile_url,
+        attribution: &state.map.attribution,
+        max_zoom: state.map.max_zoom,
+    }
+    .try_into_response()
+}
+
+pub async fn map_clusters(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Query(query): Query<MapClustersQuery>,
+) -> HttpResult<Response> {
+    let bounds = parse_bbox(&query.bbox)?;
+    let cell_size = cluster_cell_size(query.zoom);
+
+    let clusters = state
+        .read_pool
+        .get_map_clusters(&user.id, query.category, &bounds, cell_size)
+        .await?
+        .into_iter()
+        .map(|cluster| MapClusterView {
+            latitude: cluster.latitude,
+            longitude: cluster.longitude,
+            count: cluster.count,
+            cover_photo_id: cluster.cover_photo_id,
+            latest_date: cluster.max_created_at.chars().take(10).collect(),
+            bounds: MapBounds::from_cell(cluster.cell_row, cluster.cell_col, cell_size),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(clusters).into_response())
+}
+
+pub async fn map_photos(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Query(query): Query<MapPhotosQuery>,
+) -> HttpResult<Response> {
+    let bounds = parse_bbox(&query.bbox)?;
+
+    // Fetch one extra to determine if there are more results
+    let mut photos = state
+        .read_pool
+        .get_map_photos(&user.id, query.category, &bounds, PAGE_SIZE as i64 + 1)
+        .await?;
+    let has_more = photos.len() > PAGE_SIZE as usize;
+    photos.truncate(PAGE_SIZE as usize);
+
+    let photo_ids: Vec<i64> = photos.iter().map(|p| p.id).collect();
+    let details = PhotoCardDetails::load(&state.read_pool, Some(&user.id), &photo_ids).await?;
+
+    MapPhotosTemplate {
+        photos: photos
+            .into_iter()
+            .map(|p| PhotoView::from_photo(p, &details))
+            .collect(),
+        has_more,
+    }
+    .try_into_response()
+}
@@ -1,22 +1,31 @@
 use crate::http::AppStateRef;
 use crate::repo::users_repo::UsersRepository;
 use axum::Router;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post};
 use axum_login::login_required;
 
+mod admin;
+mod albums;
 mod favorites;
 mod folders;
-mod gallery;
+pub mod gallery;
+mod map;
+mod places;
+mod search;
+mod shared;
+mod shares;
+mod tags;
 mod timeline;
 mod trash;
 mod upload;
-mod user;
+pub mod user;
 
 pub fn router(app_state: AppStateRef) -> Router {
     let authenticated_router = Router::new()
         .route("/", get(gallery::gallery_page))
         .route("/gallery/grid", get(gallery::photo_grid))
         .route("/gallery/more", get(gallery::load_more_gallery))
+        .route("/gallery/card/{photo_id}", get(gallery::live_card))
         .route("/folder/{folder_name}", get(gallery::folder_page))
         .route("/folder/{folder_name}/more", get(gallery::load_more_folder))
         .route("/photo/{photo_id}", get(gallery::photo_modal))
@@ -25,8 +34,33 @@ pub fn router(app_state: AppStateRef) -> Router {
             get(gallery::photo_info_panel),
         )
         .route("/photo/{photo_id}/viewer", get(gallery::photo_viewer_media))
+        .route(
+            "/photo/{photo_id}/details",
+            post(gallery::update_photo_details),
+        )
+        .route("/photo/{photo_id}/tags", post(tags::add_photo_tag))
+        .route(
+            "/photo/{photo_id}/tags/{tag_id}",
+            delete(tags::remove_photo_tag),
+        )
         .route("/folders", get(folders::folders_page))
         .route("/api/folders", get(folders::folders_list_json))
+        .route("/search", get(search::search_page))
+        .route("/search/more", get(search::load_more_search))
+        .route("/search/saved", post(search::save_search))
+        .route(
+            "/search/saved/{search_id}",
+            delete(search::delete_saved_search),
+        )
+        .route("/places", get(places::places_page))
+        .route("/place/{place_id}", get(places::place_page))
+        .route("/place/{place_id}/more", get(places::load_more_place))
+        .route("/tags", get(tags::tags_page))
+        .route("/tag/{tag_id}", get(tags::tag_page))
+        .route("/tag/{tag_id}/more", get(tags::load_more_tag))
+        .route("/map", get(map::map_page))
+        .route("/map/photos", get(map::map_photos))
+        .route("/api/map/clusters", get(map::map_clusters))
         .route("/favorites", get(favorites::favorites_page))
         .route("/favorites/more", get(favorites::load_more_favorites))
         .route(
@@ -40,9 +74,40 @@ pub fn router(app_state: AppStateRef) -> Router {
             post(trash::trash_photo).delete(trash::permanent_delete),
         )
         .route("/upload", get(upload::upload_page))
+        .route("/admin", get(admin::admin_page))
+        .route("/admin/sidebar-link", get(admin::admin_sidebar_link))
+        .route(
+            "/shares",
+            get(shares::shares_page).post(shares::create_folder_share),
+        )
+        .route("/shares/{share_id}", delete(shares::revoke_share))
+        .route(
+            "/albums",
+            get(albums::albums_page).post(albums::share_folder),
+        )
+        .route("/albums/{album_id}", delete(albums::delete_album))
+        .route(
+            "/albums/{album_id}/members/{member_id}",
+            delete(albums::remove_member),
+        )
         .route_layer(login_required!(UsersRepository, login_url = "/login"));
 
-    let unauthenticated_router = Router::new().route("/login", get(user::login_page));
+    let unauthenticated_router = Router::new()
+        .route("/login", get(user::login_page))
+        .route(
+            "/s/{token}",
+            get(shared::shared_page).post(shared::unlock_share),
+        )
+        .route("/s/{token}/more", get(shared::load_more_shared))
+        .route(
+            "/s/{token}/photo/{photo_id}",
+            get(shared::shared_photo_page),
+        )
+        .route("/s/{token}/preview/{photo_id}", get(shared::shared_preview))
+        .route(
+            "/s/{token}/download/{photo_id}",
+            get(shared::shared_download),
+        );
 
     Router::new()
         .merge(authenticated_router)
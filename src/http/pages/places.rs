@@ -0,0 +1,186 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::gallery::{
+    GalleryQuery, MonthGroup, PAGE_SIZE, PaginatedQuery, PhotoBatchTemplate, ProcessedPhotos,
+    parse_month_key, parse_optional_cursor,
+};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::place::Place;
+use crate::repo::{PlaceInfo, PlacesRepo};
+use askama::Template;
+use axum::extract::{Path, Query, State};
+use axum::response::Response;
+
+/// The places of a country, in the order they are shown
+struct CountryPlaces {
+    name: String,
+    photo_count: i64,
+    places: Vec<PlaceInfo>,
+}
+
+#[derive(Template)]
+#[template(path = "places/places_page.html")]
+struct PlacesPageTemplate {
+    countries: Vec<CountryPlaces>,
+    category: PhotoCategory,
+}
+
+#[derive(Template)]
+#[template(path = "places/place_page.html")]
+struct PlacePageTemplate {
+    place: Place,
+    groups: Vec<MonthGroup>,
+    next_cursor: Option<String>,
+    has_more: bool,
+    last_month: Option<String>,
+    load_more_url: String,
+    category: Option<PhotoCategory>,
+}
+
+/// Groups the places, which are sorted by country
+fn group_by_country(places: Vec<PlaceInfo>) -> Vec<CountryPlaces> {
+    let mut countries: Vec<CountryPlaces> = Vec::new();
+
+    for place in places {
+        match countries.last_mut() {
+            Some(country) if country.name == place.country => {
+                country.photo_count += place.photo_count;
+                country.places.push(place);
+            }
+            _ => countries.push(CountryPlaces {
+                name: place.country.clone(),
+                photo_count: place.photo_count,
+                places: vec![place],
+            }),
+        }
+    }
+
+    countries
+}
+
+pub async fn places_page(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Query(query): Query<GalleryQuery>,
+) -> HttpResult<Response> {
+    let category = query.category;
+
+    let places = state
+        .read_pool
+        .get_places_with_counts(&user.id, category)
+        .await?;
+
+    PlacesPageTemplate {
+        countries: group_by_country(places),
+        category,
+    }
+    .try_into_response()
+}
+
+pub async fn place_page(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(place_id): Path<i64>,
+    Query(query): Query<GalleryQuery>,
+) -> HttpResult<Response> {
+    let category = query.category;
+
+    let place = state
+        .read_pool
+        .get_place(place_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    let paginated = state
+        .read_pool
+        .get_place_photos_paginated(&user.id, category, place_id, None, PAGE_SIZE)
+        .await?;
+
+    let processed = ProcessedPhotos::from_paginated_with_details(
+        paginated,
+        &state.read_pool,
+        Some(&user.id),
+        None,
+    )
+    .await?;
+
+    PlacePageTemplate {
+        place,
+        groups: processed.groups,
+        next_cursor: processed.next_cursor,
+        has_more: processed.has_more,
+        last_month: processed.last_month,
+        load_more_url: format!("/place/{place_id}/more"),
+        category: Some(category),
+    }
+    .try_into_response()
+}
+
+pub async fn load_more_place(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(place_id): Path<i64>,
+    Query(query): Query<PaginatedQuery>,
+) -> HttpResult<Response> {
+    let category = query.category;
+    let cursor = parse_optional_cursor(query.cursor.as_deref())?;
+    let skip_month = query.last_month.as_ref().and_then(|m| parse_month_key(m));
+
+    let paginated = state
+        .read_pool
+        .get_place_photos_paginated(&user.id, category, place_id, cursor.as_ref(), PAGE_SIZE)
+        .await?;
+
+    let processed = ProcessedPhotos::from_paginated_with_details(
+        paginated,
+        &state.read_pool,
+        Some(&user.id),
+        skip_month,
+    )
+    .await?;
+
+    PhotoBatchTemplate {
+        groups: processed.groups,
+        next_cursor: processed.next_cursor,
+        has_more: processed.has_more,
+        last_month: processed.last_month,
+        load_more_url: format!("/place/{place_id}/more"),
+        category: Some(category),
+    }
+    .try_into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place_info(id: i64, country: &str, photo_count: i64) -> PlaceInfo {
+        PlaceInfo {
+            id,
+            name: format!("Place {id}"),
+            country: country.to_string(),
+            photo_count,
+            cover_photo_id: id,
+        }
+    }
+
+    #[test]
+    fn test_group_by_country() {
+        let countries = group_by_country(vec![
+            place_info(1, "France", 3),
+            place_info(2, "France", 2),
+            place_info(3, "Romania", 1),
+        ]);
+
+        assert_eq!(countries.len(), 2);
+        assert_eq!(countries[0].name, "France");
+        assert_eq!(countries[0].photo_count, 5);
+        assert_eq!(countries[0].places.len(), 2);
+        assert_eq!(countries[1].name, "Romania");
+        assert_eq!(countries[1].places[0].id, 3);
+
+        assert!(group_by_country(Vec::new()).is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::gallery::{
+    MonthGroup, PAGE_SIZE, PhotoBatchTemplate, ProcessedPhotos, parse_month_key,
+    parse_optional_cursor,
+};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::saved_search::SavedSearch;
+use crate::model::search::PhotoFilter;
+use crate::repo::{PaginatedPhotos, SavedSearchesRepo, SearchRepo};
+use askama::Template;
+use axum::Form;
+use axum::extract::{Path, Query, State};
+use axum::response::{Html, IntoResponse, Response};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    category: PhotoCategory,
+    cursor: Option<String>,
+    last_month: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "search/search_page.html")]
+struct SearchPageTemplate {
+    query: String,
+    current_category: PhotoCategory,
+    groups: Vec<MonthGroup>,
+    next_cursor: Option<String>,
+    has_more: bool,
+    last_month: Option<String>,
+    load_more_url: String,
+    category: Option<PhotoCategory>,
+    /// Set when the search can't be parsed
+    error: Option<String>,
+    saved_searches: Vec<SavedSearch>,
+}
+
+#[derive(Template)]
+#[template(path = "search/saved_searches.html")]
+struct SavedSearchesTemplate {
+    saved_searches: Vec<SavedSearch>,
+}
+
+#[derive(Deserialize)]
+pub struct SaveSearchForm {
+    name: String,
+    q: String,
+}
+
+fn load_more_url(query: &str) -> String {
+    let query: String = form_urlencoded::byte_serialize(query.as_bytes()).collect();
+    format!("/search/more?q={query}")
+}
+
+pub async fn search_page(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Query(query): Query<SearchQuery>,
+) -> HttpResult<Response> {
+    let category = query.category;
+
+    let (paginated, error) = match PhotoFilter::parse(&query.q) {
+        Ok(filter) if !filter.is_empty() => {
+            let paginated = state
+                .read_pool
+                .search_photos_paginated(&user.id, category, &filter, None, PAGE_SIZE)
+                .await?;
+            (paginated, None)
+        }
+        Ok(_) => (PaginatedPhotos::default(), None),
+        Err(e) => (PaginatedPhotos::default(), Some(e.to_string())),
+    };
+    let saved_searches = state.read_pool.get_saved_searches(&user.id).await?;
+
+    let processed = ProcessedPhotos::from_paginated_with_details(
+        paginated,
+        &state.read_pool,
+        Some(&user.id),
+        None,
+    )
+    .await?;
+
+    SearchPageTemplate {
+        load_more_url: load_more_url(&query.q),
+        query: query.q,
+        current_category: category,
+        groups: processed.groups,
+        next_cursor: processed.next_cursor,
+        has_more: processed.has_more,
+        last_month: processed.last_month,
+        category: Some(category),
+        error,
+        saved_searches,
+    }
+    .try_into_response()
+}
+
+pub async fn load_more_search(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Query(query): Query<SearchQuery>,
+) -> HttpResult<Response> {
+    let category = query.category;
+    let cursor = parse_optional_cursor(query.cursor.as_deref())?;
+    let skip_month = query.last_month.as_ref().and_then(|m| parse_month_key(m));
+
+    let filter = PhotoFilter::parse(&query.q).map_err(|e| HttpError::BadRequest(e.to_string()))?;
+
+    let paginated = if filter.is_empty() {
+        PaginatedPhotos::default()
+    } else {
+        state
+            .read_pool
+            .search_photos_paginated(&user.id, category, &filter, cursor.as_ref(), PAGE_SIZE)
+            .await?
+    };
+
+    let processed = ProcessedPhotos::from_paginated_with_details(
+        paginated,
+        &state.read_pool,
+        Some(&user.id),
+        skip_month,
+    )
+    .await?;
+
+    PhotoBatchTemplate {
+        groups: processed.groups,
+        next_cursor: processed.next_cursor,
+        has_more: processed.has_more,
+        last_month: processed.last_month,
+        load_more_url: load_more_url(&query.q),
+        category: Some(category),
+    }
+    .try_into_response()
+}
+
+pub async fn save_search(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Form(form): Form<SaveSearchForm>,
+) -> HttpResult<Response> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(HttpError::BadRequest("Search name is required".to_string()));
+    }
+    // Only searches that work can be saved
+    let filter = PhotoFilter::parse(&form.q).map_err(|e| HttpError::BadRequest(e.to_string()))?;
+    if filter.is_empty() {
+        return Err(HttpError::BadRequest("The search is empty".to_string()));
+    }
+
+    state
+        .write_pool
+        .insert_saved_search(&user.id, name, form.q.trim())
+        .await?;
+
+    SavedSearchesTemplate {
+        saved_searches: state.write_pool.get_saved_searches(&user.id).await?,
+    }
+    .try_into_response()
+}
+
+pub async fn delete_saved_search(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(search_id): Path<i64>,
+) -> HttpResult<Response> {
+    if !state
+        .write_pool
+        .delete_saved_search(search_id, &user.id)
+        .await?
+    {
+        return Err(HttpError::NotFound);
+    }
+
+    // Return empty HTML to remove the item via hx-swap="outerHTML"
+    Ok(Html("").into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_more_url() {
+        assert_eq!(
+            load_more_url("São Paulo & co"),
+            "/search/more?q=S%C3%A3o+Paulo+%26+co"
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use super::gallery::{
+    MonthGroup, PAGE_SIZE, PaginatedQuery, ProcessedPhotos, parse_month_key, parse_optional_cursor,
+};
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult, too_many_requests_message};
+use crate::http::login_limiter::{check_login_allowed, clear_login_failures, record_login_failure};
+use crate::http::photos_api::{generate_missing_preview, preview_response};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::http::utils::{AuthSession, ClientIp, file_to_response};
+use crate::model::login_attempt::LoginAttemptKind;
+use crate::model::photo::Photo;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::share::Share;
+use crate::repo::{PaginatedPhotos, PhotoCursor, PhotosRepo, SharesRepo};
+use crate::utils::password_hash::validate_credentials;
+use askama::Template;
+use axum::Form;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_extra::TypedHeader;
+use axum_extra::headers::Range;
+use serde::Deserialize;
+use tokio::task;
+use tracing::{error, warn};
+
+#[derive(Template)]
+#[template(path = "shares/shared_page.html")]
+struct SharedPageTemplate {
+    title: String,
+    token: String,
+    groups: Vec<MonthGroup>,
+    next_cursor: Option<String>,
+    has_more: bool,
+    last_month: Option<String>,
+    load_more_url: String,
+    category: Option<PhotoCategory>,
+}
+
+#[derive(Template)]
+#[template(path = "shares/shared_batch.html")]
+struct SharedBatchTemplate {
+    token: String,
+    groups: Vec<MonthGroup>,
+    next_cursor: Option<String>,
+    has_more: bool,
+    last_month: Option<String>,
+    load_more_url: String,
+    category: Option<PhotoCategory>,
+}
+
+#[derive(Template)]
+#[template(path = "shares/shared_photo_page.html")]
+struct SharedPhotoTemplate {
+    token: String,
+    photo: Photo,
+    is_video: bool,
+    mime_type: String,
+    allow_downloads: bool,
+}
+
+#[derive(Template)]
+#[template(path = "shares/share_password_page.html")]
+struct SharePasswordTemplate {
+    token: String,
+    error_message: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UnlockShareForm {
+    password: String,
+}
+
+fn unlocked_session_key(share: &Share) -> String {
+    format!("share_unlocked.{}", share.id)
+}
+
+/// Look up a share, treating expired shares as if they never existed
+async fn get_valid_share(state: AppStateRef, token: &str) -> HttpResult<Share> {
+    state
+        .read_pool
+        .get_share_by_token(token)
+        .await?
+        .filter(|share| !share.is_expired())
+        .ok_or(HttpError::NotFound)
+}
+
+async fn is_unlocked(auth: &AuthSession, share: &Share) -> bool {
+    if share.password_hash.is_none() {
+        return true;
+    }
+
+    match auth.session.get::<bool>(&unlocked_session_key(share)).await {
+        Ok(unlocked) => unlocked.unwrap_or(false),
+        Err(e) => {
+            error!("Failed to read session: {e}");
+            false
+        }
+    }
+}
+
+/// Same as [get_valid_share], but also requires the password to have been entered
+async fn get_unlocked_share(
+    state: AppStateRef,
+    auth: &AuthSession,
+    token: &str,
+) -> HttpResult<Share> {
+    let share = get_valid_share(state, token).await?;
+
+    if !is_unlocked(auth, &share).await {
+        return Err(HttpError::Unauthorized);
+    }
+
+    Ok(share)
+}
+
+async fn get_shared_photos_paginated(
+    state: AppStateRef,
+    share: &Share,
+    cursor: Option<&PhotoCursor>,
+) -> sqlx::Result<PaginatedPhotos> {
+    match share.folder.as_deref() {
+        Some(folder) => {
+            state
+                .read_pool
+                .get_folder_photos_paginated(
+                    &share.user_id,
+                    folder,
+                    !share.folder_is_public,
+                    cursor,
+                    PAGE_SIZE,
+                )
+                .await
+        }
+        None => {
+            state
+                .read_pool
+                .get_share_photos_paginated(share.id, cursor, PAGE_SIZE)
+                .await
+        }
+    }
+}
+
+pub async fn shared_page(
+    State(state): State<AppStateRef>,
+    Path(token): Path<String>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    let share = get_valid_share(state, &token).await?;
+
+    if !is_unlocked(&auth, &share).await {
+        return SharePasswordTemplate {
+            token,
+            error_message: None,
+        }
+        .try_into_response();
+    }
+
+    let paginated = get_shared_photos_paginated(state, &share, None).await?;
+    let processed =
+        ProcessedPhotos::from_paginated_with_details(paginated, &state.read_pool, None, None)
+            .await?;
+
+    SharedPageTemplate {
+        title: share
+            .folder
+            .clone()
+            .unwrap_or_else(|| "Shared Photos".to_string()),
+        load_more_url: format!("{}/more", share.url_path()),
+        token,
+        groups: processed.groups,
+        next_cursor: processed.next_cursor,
+        has_more: processed.has_more,
+        last_month: processed.last_month,
+        category: None,
+    }
+    .try_into_response()
+}
+
+pub async fn unlock_share(
+    State(state): State<AppStateRef>,
+    Path(token): Path<String>,
+    ClientIp(ip): ClientIp,
+    auth: AuthSession,
+    Form(form): Form<UnlockShareForm>,
+) -> HttpResult<Response> {
+    let share = get_valid_share(state, &token).await?;
+
+    let Some(password_hash) = share.password_hash.clone() else {
+        return Ok(Redirect::to(&share.url_path()).into_response());
+    };
+
+    // Wrong passwords are limited like logins, so the links can't be brute-forced
+    let share_id = share.id.to_string();
+    match check_login_allowed(state, ip, LoginAttemptKind::Share, &share_id).await {
+        Ok(()) => {}
+        Err(HttpError::TooManyRequests(retry_after)) => {
+            return SharePasswordTemplate {
+                token,
+                error_message: Some(too_many_requests_message(retry_after)),
+            }
+            .try_into_response();
+        }
+        Err(e) => return Err(e),
+    }
+
+    let valid = task::spawn_blocking(move || validate_credentials(form.password, password_hash))
+        .await
+        .map_err(|e| HttpError::AnyError(Box::new(e)))?
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    if !valid {
+        warn!("Wrong password for share {} from {:?}", share.id, ip);
+        record_login_failure(state, ip, LoginAttemptKind::Share, &share_id).await?;
+
+        return SharePasswordTemplate {
+            token,
+            error_message: Some("Invalid password".to_string()),
+        }
+        .try_into_response();
+    }
+
+    clear_login_failures(state, LoginAttemptKind::Share, &share_id).await?;
+
+    auth.session
+        .insert(&unlocked_session_key(&share), true)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    Ok(Redirect::to(&share.url_path()).into_response())
+}
+
+pub async fn load_more_shared(
+    State(state): State<AppStateRef>,
+    Path(token): Path<String>,
+    Query(query): Query<PaginatedQuery>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    let share = get_unlocked_share(state, &auth, &token).await?;
+    let cursor = parse_optional_cursor(query.cursor.as_deref())?;
+    let skip_month = query.last_month.as_ref().and_then(|m| parse_month_key(m));
+
+    let paginated = get_shared_photos_paginated(state, &share, cursor.as_ref()).await?;
+    let processed =
+        ProcessedPhotos::from_paginated_with_details(paginated, &state.read_pool, None, skip_month)
+            .await?;
+
+    SharedBatchTemplate {
+        load_more_url: format!("{}/more", share.url_path()),
+        token,
+        groups: processed.groups,
+        next_cursor: processed.next_cursor,
+        has_more: processed.has_more,
+        last_month: processed.last_month,
+        category: None,
+    }
+    .try_into_response()
+}
+
+pub async fn shared_photo_page(
+    State(state): State<AppStateRef>,
+    Path((token, photo_id)): Path<(String, i64)>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    let share = get_unlocked_share(state, &auth, &token).await?;
+    let photo = state
+        .read_pool
+        .get_shared_photo(&share, photo_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    let mime = mime_guess::from_path(&photo.name).first_or_octet_stream();
+    let is_video = mime.type_() == mime_guess::mime::VIDEO;
+    let mime_type = mime.to_string();
+
+    SharedPhotoTemplate {
+        token,
+        photo,
+        is_video,
+        mime_type,
+        allow_downloads: share.allow_downloads,
+    }
+    .try_into_response()
+}
+
+pub async fn shared_preview(
+    State(state): State<AppStateRef>,
+    Path((token, photo_id)): Path<(String, i64)>,
+    range: Option<TypedHeader<Range>>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    let share = get_unlocked_share(state, &auth, &token).await?;
+    let photo = state
+        .read_pool
+        .get_shared_photo(&share, photo_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    if share.allow_downloads {
+        return preview_response(state, &photo, range).await;
+    }
+
+    // The original file must not be served in place of the preview when downloads aren't allowed
+    match generate_missing_preview(state, &photo).await? {
+        Some(preview_path) => file_to_response(&preview_path, range).await,
+        None => Ok(StatusCode::SERVICE_UNAVAILABLE.into_response()),
+    }
+}
+
+pub async fn shared_download(
+    State(state): State<AppStateRef>,
+    Path((token, photo_id)): Path<(String, i64)>,
+    range: Option<TypedHeader<Range>>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    let share = get_unlocked_share(state, &auth, &token).await?;
+    if !share.allow_downloads {
+        return Err(HttpError::Unauthorized);
+    }
+
+    let photo = state
+        .read_pool
+        .get_shared_photo(&share, photo_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    let photo_path = state.storage.resolve_photo(photo.partial_path());
+
+    file_to_response(&photo_path, range).await
+}
@@ -0,0 +1,87 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::photos_api::{CreateShareRequest, create_share_service};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::share::ShareInfo;
+use crate::repo::SharesRepo;
+use askama::Template;
+use axum::Form;
+use axum::extract::{Path, State};
+use axum::response::{Html, IntoResponse, Response};
+use serde::Deserialize;
+use time::{Duration, OffsetDateTime};
+
+#[derive(Template)]
+#[template(path = "shares/shares_page.html")]
+struct SharesPageTemplate {
+    shares: Vec<ShareInfo>,
+}
+
+#[derive(Template)]
+#[template(path = "shares/share_created.html")]
+struct ShareCreatedTemplate {
+    url: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateShareForm {
+    folder_name: String,
+    #[serde(default)]
+    folder_is_public: bool,
+    expires_in_days: i64,
+    #[serde(default)]
+    password: String,
+    /// Checkboxes are only sent when checked
+    allow_downloads: Option<String>,
+}
+
+pub async fn shares_page(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+) -> HttpResult<Response> {
+    let shares = state.read_pool.get_shares_for_user(&user.id).await?;
+
+    SharesPageTemplate { shares }.try_into_response()
+}
+
+pub async fn create_folder_share(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Form(form): Form<CreateShareForm>,
+) -> HttpResult<Response> {
+    if !(1..=365).contains(&form.expires_in_days) {
+        return Err(HttpError::BadRequest(
+            "Shares can expire in 1 to 365 days".to_string(),
+        ));
+    }
+
+    let request = CreateShareRequest {
+        photo_ids: Vec::new(),
+        folder_name: Some(form.folder_name),
+        folder_is_public: form.folder_is_public,
+        expires_at: OffsetDateTime::now_utc() + Duration::days(form.expires_in_days),
+        password: Some(form.password),
+        allow_downloads: form.allow_downloads.is_some(),
+    };
+
+    let share = create_share_service(state, &user.id, request).await?;
+
+    ShareCreatedTemplate {
+        url: share.url_path(),
+    }
+    .try_into_response()
+}
+
+pub async fn revoke_share(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(share_id): Path<i64>,
+) -> HttpResult<Response> {
+    if !state.write_pool.delete_share(share_id, &user.id).await? {
+        return Err(HttpError::NotFound);
+    }
+
+    // Return empty HTML to remove the row via hx-swap="outerHTML"
+    Ok(Html("").into_response())
+}
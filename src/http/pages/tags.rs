@@ -0,0 +1,217 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::gallery::{
+    GalleryQuery, MonthGroup, PAGE_SIZE, PaginatedQuery, PhotoBatchTemplate, ProcessedPhotos,
+    parse_month_key, parse_optional_cursor,
+};
+use crate::http::photos_api::normalize_tag_names;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::search::PhotoFilter;
+use crate::model::tag::Tag;
+use crate::repo::{PhotosRepo, SearchRepo, TagInfo, TagsRepo, TagsTransactionRepo};
+use crate::tasks::write_photos_sidecars;
+use askama::Template;
+use axum::Form;
+use axum::extract::{Path, Query, State};
+use axum::response::Response;
+use serde::Deserialize;
+
+#[derive(Template)]
+#[template(path = "tags/tags_page.html")]
+struct TagsPageTemplate {
+    tags: Vec<TagInfo>,
+    category: PhotoCategory,
+}
+
+#[derive(Template)]
+#[template(path = "tags/tag_page.html")]
+struct TagPageTemplate {
+    tag: Tag,
+    groups: Vec<MonthGroup>,
+    next_cursor: Option<String>,
+    has_more: bool,
+    last_month: Option<String>,
+    load_more_url: String,
+    category: Option<PhotoCategory>,
+}
+
+#[derive(Template)]
+#[template(path = "gallery/photo_tags.html")]
+struct PhotoTagsTemplate {
+    photo_id: i64,
+    tags: Vec<Tag>,
+    can_edit_tags: bool,
+}
+
+#[derive(Deserialize)]
+pub struct AddTagForm {
+    name: String,
+}
+
+fn tag_filter(tag: &Tag) -> PhotoFilter {
+    PhotoFilter {
+        tags: vec![tag.name.clone()],
+        ..Default::default()
+    }
+}
+
+pub async fn tags_page(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Query(query): Query<GalleryQuery>,
+) -> HttpResult<Response> {
+    let category = query.category;
+
+    TagsPageTemplate {
+        tags: state
+            .read_pool
+            .get_tags_with_counts(&user.id, category)
+            .await?,
+        category,
+    }
+    .try_into_response()
+}
+
+pub async fn tag_page(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(tag_id): Path<i64>,
+    Query(query): Query<GalleryQuery>,
+) -> HttpResult<Response> {
+    let category = query.category;
+
+    let tag = state
+        .read_pool
+        .get_tag(tag_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    let paginated = state
+        .read_pool
+        .search_photos_paginated(&user.id, category, &tag_filter(&tag), None, PAGE_SIZE)
+        .await?;
+
+    let processed = ProcessedPhotos::from_paginated_with_details(
+        paginated,
+        &state.read_pool,
+        Some(&user.id),
+        None,
+    )
+    .await?;
+
+    TagPageTemplate {
+        tag,
+        groups: processed.groups,
+        next_cursor: processed.next_cursor,
+        has_more: processed.has_more,
+        last_month: processed.last_month,
+        load_more_url: format!("/tag/{tag_id}/more"),
+        category: Some(category),
+    }
+    .try_into_response()
+}
+
+pub async fn load_more_tag(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(tag_id): Path<i64>,
+    Query(query): Query<PaginatedQuery>,
+) -> HttpResult<Response> {
+    let category = query.category;
+    let cursor = parse_optional_cursor(query.cursor.as_deref())?;
+    let skip_month = query.last_month.as_ref().and_then(|m| parse_month_key(m));
+
+    let tag = state
+        .read_pool
+        .get_tag(tag_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    let paginated = state
+        .read_pool
+        .search_photos_paginated(
+            &user.id,
+            category,
+            &tag_filter(&tag),
+            cursor.as_ref(),
+            PAGE_SIZE,
+        )
+        .await?;
+
+    let processed = ProcessedPhotos::from_paginated_with_details(
+        paginated,
+        &state.read_pool,
+        Some(&user.id),
+        skip_month,
+    )
+    .await?;
+
+    PhotoBatchTemplate {
+        groups: processed.groups,
+        next_cursor: processed.next_cursor,
+        has_more: processed.has_more,
+        last_month: processed.last_month,
+        load_more_url: format!("/tag/{tag_id}/more"),
+        category: Some(category),
+    }
+    .try_into_response()
+}
+
+pub async fn add_photo_tag(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(photo_id): Path<i64>,
+    Form(form): Form<AddTagForm>,
+) -> HttpResult<Response> {
+    let add = normalize_tag_names(&[form.name])?;
+    update_photo_tags(state, &user.id, photo_id, &add, &[]).await
+}
+
+pub async fn remove_photo_tag(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path((photo_id, tag_id)): Path<(i64, i64)>,
+) -> HttpResult<Response> {
+    let tag = state
+        .read_pool
+        .get_tag(tag_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    update_photo_tags(state, &user.id, photo_id, &[], &[tag.name]).await
+}
+
+async fn update_photo_tags(
+    state: AppStateRef,
+    user_id: &str,
+    photo_id: i64,
+    add: &[String],
+    remove: &[String],
+) -> HttpResult<Response> {
+    let mut tx = state.write_pool.begin().await?;
+
+    let photo = tx
+        .get_photo(photo_id, user_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+    let changed = tx
+        .update_photos_tags(std::slice::from_ref(&photo), add, remove)
+        .await?;
+    let tags = tx.get_photo_tags(photo_id).await?;
+
+    tx.commit().await?;
+    state.publish_latest_event().await;
+
+    if !changed.is_empty() {
+        write_photos_sidecars(state, &[photo]).await;
+    }
+
+    PhotoTagsTemplate {
+        photo_id,
+        tags,
+        can_edit_tags: true,
+    }
+    .try_into_response()
+}
@@ -1,13 +1,13 @@
 use crate::http::AppStateRef;
 use crate::http::auth::AuthenticatedUser;
 use crate::http::error::{HttpError, HttpResult};
-use crate::http::pages::gallery::PhotoView;
+use crate::http::pages::gallery::{PhotoCardDetails, PhotoView};
 use crate::http::template_into_response::TemplateIntoResponse;
-use crate::repo::{FavoritesRepo, PhotosRepo, PhotosTransactionRepo};
+use crate::repo::{PhotosRepo, PhotosTransactionRepo};
+use crate::utils::xmp::remove_sidecar;
 use askama::Template;
 use axum::extract::{Path, State};
 use axum::response::{Html, IntoResponse, Response};
-use std::collections::HashSet;
 use std::io::ErrorKind;
 use time::OffsetDateTime;
 use tokio::fs;
@@ -28,14 +28,11 @@ pub async fn trash_page(
     let photo_ids: Vec<i64> = trashed_photos.iter().map(|p| p.id).collect();
 
     // Only check favorites for the photos we're displaying
-    let favorite_ids: HashSet<i64> = state
-        .read_pool
-        .check_favorites_for_ids(&user.id, &photo_ids)
-        .await?;
+    let details = PhotoCardDetails::load(&state.read_pool, Some(&user.id), &photo_ids).await?;
 
     let photos: Vec<PhotoView> = trashed_photos
         .into_iter()
-        .map(|p| PhotoView::from_photo(p, &favorite_ids))
+        .map(|p| PhotoView::from_photo(p, &details))
         .collect();
 
     TrashPageTemplate { photos }.try_into_response()
@@ -56,6 +53,7 @@ pub async fn trash_photo(
     photo.trashed_on = Some(OffsetDateTime::now_utc());
     tx.update_photo(&photo).await?;
     tx.commit().await?;
+    state.publish_latest_event().await;
 
     // Return empty HTML to remove the card via hx-swap="outerHTML"
     Ok(Html("").into_response())
@@ -76,6 +74,7 @@ pub async fn restore_photo(
     photo.trashed_on = None;
     tx.update_photo(&photo).await?;
     tx.commit().await?;
+    state.publish_latest_event().await;
 
     // Return empty response with HX-Trigger to refresh the page
     Ok([("HX-Refresh", "true")].into_response())
@@ -96,6 +95,7 @@ pub async fn permanent_delete(
     // First: DB operations in transaction
     tx.delete_photo(&photo).await?;
     tx.commit().await?;
+    state.publish_latest_event().await;
 
     // After commit succeeds: clean up files
     // Preview file - ignore errors (might not exist)
@@ -113,7 +113,15 @@ pub async fn permanent_delete(
     // Photo file - ignore "not found" (already deleted), log other errors
     let photo_path = state.storage.resolve_photo(photo.partial_path());
     match fs::remove_file(&photo_path).await {
-        Ok(()) => info!("Removed file at {}", photo_path.display()),
+        Ok(()) => {
+            info!("Removed file at {}", photo_path.display());
+            if let Err(e) = remove_sidecar(&photo_path) {
+                warn!(
+                    "Failed to remove the sidecar of {}: {e}",
+                    photo_path.display()
+                );
+            }
+        }
         Err(e) if e.kind() == ErrorKind::NotFound => {
             // Already deleted, this is fine
         }
@@ -1,16 +1,37 @@
+use crate::http::AppStateRef;
 use crate::http::template_into_response::TemplateIntoResponse;
 use crate::http::utils::AuthSession;
 use askama::Template;
-use axum::response::{IntoResponse, Redirect};
+use axum::extract::State;
+use axum::response::{IntoResponse, Redirect, Response};
 
 #[derive(Template)]
 #[template(path = "user/login_page.html")]
-struct LoginTemplate;
+struct LoginTemplate<'a> {
+    /// Set when the OpenID Connect login is configured
+    oidc_provider_name: Option<&'a str>,
+    error_message: Option<&'a str>,
+}
 
-pub async fn login_page(auth_session: AuthSession) -> impl IntoResponse {
+pub async fn login_page(
+    State(state): State<AppStateRef>,
+    auth_session: AuthSession,
+) -> impl IntoResponse {
     if auth_session.user.is_some() {
         return Redirect::to("/").into_response();
     }
 
-    LoginTemplate.into_response()
+    render_login_page(state, None)
+}
+
+/// Shows the login page with an error, for the logins that come back from another site
+pub fn render_login_page(state: AppStateRef, error_message: Option<&str>) -> Response {
+    LoginTemplate {
+        oidc_provider_name: state
+            .oidc
+            .as_ref()
+            .map(|oidc| oidc.config().provider_name.as_str()),
+        error_message,
+    }
+    .into_response()
 }
@@ -0,0 +1,201 @@
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::utils::AuthSession;
+use crate::model::album::{Album, AlbumInfo};
+use crate::repo::{AlbumsRepo, AlbumsTransactionRepo};
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use tracing::info;
+
+pub fn router() -> Router<AppStateRef> {
+    Router::new()
+        .route("/", get(list_albums).post(create_album))
+        .route("/{album_id}", delete(delete_album))
+        .route(
+            "/{album_id}/members/{member_id}",
+            post(add_member).delete(remove_member),
+        )
+}
+
+#[derive(Deserialize)]
+pub struct CreateAlbumRequest {
+    pub folder_name: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+}
+
+async fn list_albums(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    Ok(Json(get_albums_info(state, &user.id).await?))
+}
+
+async fn create_album(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    Json(request): Json<CreateAlbumRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    Ok(Json(create_album_service(state, &user.id, request).await?))
+}
+
+async fn delete_album(
+    State(state): State<AppStateRef>,
+    Path(album_id): Path<i64>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    delete_album_service(state, &user.id, album_id).await
+}
+
+async fn add_member(
+    State(state): State<AppStateRef>,
+    Path((album_id, member_id)): Path<(i64, String)>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+    let album = get_owned_album(state, album_id, &user.id).await?;
+
+    add_members(state, &album, &[member_id]).await?;
+
+    Ok(())
+}
+
+async fn remove_member(
+    State(state): State<AppStateRef>,
+    Path((album_id, member_id)): Path<(i64, String)>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+    remove_member_service(state, &user.id, album_id, &member_id).await
+}
+
+/// Albums owned by the user and the ones shared with them, along with their members
+pub async fn get_albums_info(state: AppStateRef, user_id: &str) -> HttpResult<Vec<AlbumInfo>> {
+    let albums = state.read_pool.get_albums_for_user(user_id).await?;
+
+    let mut infos = Vec::with_capacity(albums.len());
+    for album in albums {
+        let members = state.read_pool.get_album_members(album.id).await?;
+        infos.push(AlbumInfo { album, members });
+    }
+
+    Ok(infos)
+}
+
+/// Shares one of the user's folders with the given members.
+/// If the folder is already shared, the new members are added to the existing album
+pub async fn create_album_service(
+    state: AppStateRef,
+    user_id: &str,
+    request: CreateAlbumRequest,
+) -> HttpResult<AlbumInfo> {
+    let folder_name = request.folder_name.trim();
+    if folder_name.is_empty() {
+        return Err(HttpError::BadRequest("Folder name is required".to_string()));
+    }
+
+    let album = match state
+        .write_pool
+        .get_album_by_folder(user_id, folder_name)
+        .await?
+    {
+        Some(album) => album,
+        None => {
+            info!("User {user_id} shared folder {folder_name} as an album");
+            state.write_pool.insert_album(user_id, folder_name).await?
+        }
+    };
+
+    add_members(state, &album, &request.members).await?;
+
+    let members = state.write_pool.get_album_members(album.id).await?;
+    Ok(AlbumInfo { album, members })
+}
+
+/// Only the owner can delete an album, the photos themselves are left untouched
+pub async fn delete_album_service(
+    state: AppStateRef,
+    user_id: &str,
+    album_id: i64,
+) -> HttpResult<()> {
+    let mut tx = state.write_pool.begin().await?;
+    if !tx.remove_album(album_id, user_id).await? {
+        return Err(HttpError::NotFound);
+    }
+    tx.commit().await?;
+    state.publish_latest_event().await;
+
+    info!("User {user_id} deleted album {album_id}");
+
+    Ok(())
+}
+
+/// The owner can remove anyone, while members can only remove themselves
+pub async fn remove_member_service(
+    state: AppStateRef,
+    user_id: &str,
+    album_id: i64,
+    member_id: &str,
+) -> HttpResult<()> {
+    let album = state
+        .write_pool
+        .get_album(album_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    if album.owner_id != user_id && member_id != user_id {
+        return Err(HttpError::NotFound);
+    }
+
+    let mut tx = state.write_pool.begin().await?;
+    if !tx.remove_album_member(&album, member_id).await? {
+        return Err(HttpError::NotFound);
+    }
+    tx.commit().await?;
+    state.publish_latest_event().await;
+
+    Ok(())
+}
+
+async fn get_owned_album(state: AppStateRef, album_id: i64, user_id: &str) -> HttpResult<Album> {
+    state
+        .read_pool
+        .get_album(album_id)
+        .await?
+        .filter(|album| album.owner_id == user_id)
+        .ok_or(HttpError::NotFound)
+}
+
+async fn add_members(state: AppStateRef, album: &Album, members: &[String]) -> HttpResult<()> {
+    for member_id in members {
+        if *member_id == album.owner_id {
+            return Err(HttpError::BadRequest(
+                "The owner is always part of the album".to_string(),
+            ));
+        }
+
+        if state.users_repo.get_user(member_id).await.is_none() {
+            return Err(HttpError::BadRequest(format!(
+                "User {member_id} does not exist"
+            )));
+        }
+    }
+
+    let mut tx = state.write_pool.begin().await?;
+    for member_id in members {
+        tx.add_album_member(album, member_id).await?;
+    }
+    tx.commit().await?;
+    state.publish_latest_event().await;
+
+    Ok(())
+}
@@ -0,0 +1,339 @@
+use super::{UploadDataQuery, resolve_new_photo_path, resolve_time_created};
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::utils::{AuthSession, WrittenFile, write_field_to_file};
+use crate::model::photo::Photo;
+use crate::model::photo_hash::PhotoHash;
+use crate::repo::{PhotosHashRepo, PhotosTransactionRepo};
+use crate::tasks::TimestampSource;
+use axum::extract::{Multipart, State};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+/// The name of the multipart field with the JSON metadata, which must come before the files
+const METADATA_FIELD: &str = "metadata";
+
+pub fn router() -> Router<AppStateRef> {
+    Router::new().route("/", post(upload_photos))
+}
+
+#[derive(Deserialize)]
+struct BatchMetadata {
+    /// The metadata of each file, in the same order as the file fields.
+    /// The files without an entry use the defaults
+    files: Vec<UploadDataQuery>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchUploadResult {
+    name: String,
+    #[serde(flatten)]
+    outcome: UploadOutcome,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum UploadOutcome {
+    Created {
+        photo: Photo,
+        timestamp_source: TimestampSource,
+    },
+    /// The user already has the same file
+    Duplicate {
+        photo: Photo,
+    },
+    Failed {
+        reason: String,
+    },
+}
+
+/// What happens to a file before the new photos are inserted
+enum PendingOutcome {
+    /// Index into the new photos
+    Created(usize, TimestampSource),
+    Duplicate(Photo),
+    /// The same file appeared earlier in the batch, index into the new photos
+    DuplicateOfNew(usize),
+    Failed(String),
+}
+
+struct ReceivedFile {
+    name: String,
+    metadata: UploadDataQuery,
+    /// Resolved as soon as the file is received, so it's not read while the database is locked
+    time_created: (OffsetDateTime, TimestampSource),
+    written_file: WrittenFile,
+}
+
+/// Uploads many files in a single multipart request, with a JSON `metadata` field describing
+/// each of them. The created photos are inserted together, the other files are reported
+async fn upload_photos(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    mut payload: Multipart,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut metadata: Option<std::vec::IntoIter<UploadDataQuery>> = None;
+    let mut files = Vec::new();
+
+    while let Some(field) = payload
+        .next_field()
+        .await
+        .map_err(|e| HttpError::AnyError(Box::new(e)))?
+    {
+        if field.name() == Some(METADATA_FIELD) && field.file_name().is_none() {
+            let bytes = field
+                .bytes()
+                .await
+                .map_err(|e| HttpError::AnyError(Box::new(e)))?;
+            let batch: BatchMetadata = serde_json::from_slice(&bytes)
+                .map_err(|e| HttpError::BadRequest(format!("Invalid metadata: {e}")))?;
+            metadata = Some(batch.files.into_iter());
+            continue;
+        }
+
+        let files_metadata = metadata.as_mut().ok_or_else(|| {
+            HttpError::BadRequest("The metadata must come before the files".to_string())
+        })?;
+
+        let name = field
+            .file_name()
+            .or(field.name())
+            .ok_or_else(|| HttpError::BadRequest("Multipart has no name".to_string()))?
+            .to_owned();
+
+        let file_metadata = files_metadata.next().unwrap_or_default();
+        let written_file = write_field_to_file(field).await?;
+        let time_created = resolve_time_created(&file_metadata, &name, &written_file).await;
+
+        files.push(ReceivedFile {
+            name,
+            metadata: file_metadata,
+            time_created,
+            written_file,
+        });
+    }
+
+    if files.is_empty() {
+        return Err(HttpError::BadRequest("No files were uploaded".to_string()));
+    }
+
+    Ok(Json(store_uploads(state, user.id, files).await?))
+}
+
+/// The duplicates are found and the files saved before the write transaction starts,
+/// so the database is only locked to insert the new photos
+async fn store_uploads(
+    state: AppStateRef,
+    user_id: String,
+    files: Vec<ReceivedFile>,
+) -> HttpResult<Vec<BatchUploadResult>> {
+    let mut names = Vec::with_capacity(files.len());
+    let mut outcomes = Vec::with_capacity(files.len());
+    let mut new_photos: Vec<Photo> = Vec::new();
+    let mut new_paths: Vec<PathBuf> = Vec::new();
+    let mut new_hashes: Vec<Vec<u8>> = Vec::new();
+    // The index of the new photo of each file, to find duplicates within the batch
+    let mut batch_hashes: HashMap<(Option<String>, Vec<u8>), usize> = HashMap::new();
+
+    for file in files {
+        names.push(file.name.clone());
+
+        let metadata = file.metadata;
+        let (time_created, timestamp_source) = file.time_created;
+        let photo_user_id = (!metadata.make_public).then(|| user_id.clone());
+        let hash_key = (photo_user_id.clone(), file.written_file.hash.clone());
+
+        if let Some(&index) = batch_hashes.get(&hash_key) {
+            outcomes.push(PendingOutcome::DuplicateOfNew(index));
+            continue;
+        }
+
+        let existing = state
+            .read_pool
+            .get_photo_with_hash(&file.written_file.hash, photo_user_id.as_deref())
+            .await
+            .inspect_err(|_| remove_files(&new_paths))?;
+
+        if let Some(photo) = existing {
+            info!(
+                "Photo with same hash already exists with path: {}",
+                photo.partial_path()
+            );
+            outcomes.push(PendingOutcome::Duplicate(photo));
+            continue;
+        }
+
+        let mut photo = Photo {
+            id: 0,
+            user_id: photo_user_id,
+            name: file.name,
+            created_at: time_created,
+            file_size: file.written_file.size as i64,
+            folder: metadata.folder_name,
+            thumb_hash: None,
+            trashed_on: None,
+            caption: None,
+            rating: 0,
+        };
+
+        // Persisting right away makes the next files with the same name get a new one
+        let persisted = match resolve_new_photo_path(&state.storage, &mut photo).await {
+            Ok(photo_path) => file
+                .written_file
+                .persist_to(&photo_path)
+                .await
+                .map(|_| photo_path),
+            Err(e) => Err(e),
+        };
+
+        match persisted {
+            Ok(photo_path) => {
+                info!("Uploading file to {}", photo_path.display());
+                new_hashes.push(hash_key.1.clone());
+                batch_hashes.insert(hash_key, new_photos.len());
+                outcomes.push(PendingOutcome::Created(new_photos.len(), timestamp_source));
+                new_photos.push(photo);
+                new_paths.push(photo_path);
+            }
+            Err(e) => {
+                error!("Failed to save uploaded file {}: {e}", photo.name);
+                outcomes.push(PendingOutcome::Failed(
+                    "The file could not be saved".to_string(),
+                ));
+            }
+        }
+    }
+
+    let inserted = async {
+        let mut tx = state.write_pool.begin().await?;
+        let inserted = match_inserted_photos(&new_photos, tx.insert_photos(&new_photos).await?)?;
+        // Stored right away so the files uploaded next are recognized as duplicates
+        let hashes: Vec<_> = inserted
+            .iter()
+            .zip(new_hashes)
+            .map(|(photo, hash)| PhotoHash { id: photo.id, hash })
+            .collect();
+        tx.insert_hashes(&hashes).await?;
+        tx.commit().await?;
+        Ok::<_, HttpError>(inserted)
+    }
+    .await
+    .inspect_err(|_| remove_files(&new_paths))?;
+    state.publish_latest_event().await;
+
+    Ok(names
+        .into_iter()
+        .zip(outcomes)
+        .map(|(name, outcome)| BatchUploadResult {
+            name,
+            outcome: match outcome {
+                PendingOutcome::Created(index, timestamp_source) => UploadOutcome::Created {
+                    photo: inserted[index].clone(),
+                    timestamp_source,
+                },
+                PendingOutcome::Duplicate(photo) => UploadOutcome::Duplicate { photo },
+                PendingOutcome::DuplicateOfNew(index) => UploadOutcome::Duplicate {
+                    photo: inserted[index].clone(),
+                },
+                PendingOutcome::Failed(reason) => UploadOutcome::Failed { reason },
+            },
+        })
+        .collect())
+}
+
+/// Deletes the files saved for a batch whose transaction failed
+fn remove_files(paths: &[PathBuf]) {
+    for path in paths {
+        if let Err(e) = std::fs::remove_file(path) {
+            error!("Failed to remove uploaded file: {e}");
+        }
+    }
+}
+
+/// Orders the inserted photos like the new ones, which all have a different path
+fn match_inserted_photos(new_photos: &[Photo], inserted: Vec<Photo>) -> HttpResult<Vec<Photo>> {
+    let mut by_path: HashMap<_, _> = inserted
+        .into_iter()
+        .map(|photo| (photo.partial_path(), photo))
+        .collect();
+
+    new_photos
+        .iter()
+        .map(|photo| {
+            by_path.remove(&photo.partial_path()).ok_or_else(|| {
+                HttpError::Internal(format!("{} was not inserted", photo.partial_path()))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::OffsetDateTime;
+
+    fn photo(id: i64, user_id: Option<&str>, name: &str) -> Photo {
+        Photo {
+            id,
+            user_id: user_id.map(String::from),
+            name: name.to_string(),
+            created_at: OffsetDateTime::UNIX_EPOCH,
+            file_size: 1,
+            folder: None,
+            thumb_hash: None,
+            trashed_on: None,
+            caption: None,
+            rating: 0,
+        }
+    }
+
+    #[test]
+    fn test_match_inserted_photos() {
+        let new_photos = vec![
+            photo(0, Some("user1"), "a.jpg"),
+            photo(0, None, "a.jpg"),
+            photo(0, Some("user1"), "b.jpg"),
+        ];
+        let inserted = vec![
+            photo(3, Some("user1"), "b.jpg"),
+            photo(1, Some("user1"), "a.jpg"),
+            photo(2, None, "a.jpg"),
+        ];
+
+        let ids: Vec<_> = match_inserted_photos(&new_photos, inserted.clone())
+            .unwrap()
+            .iter()
+            .map(|photo| photo.id)
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        assert!(match_inserted_photos(&new_photos, inserted[1..].to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_result_serialization() {
+        let result = BatchUploadResult {
+            name: "a.jpg".to_string(),
+            outcome: UploadOutcome::Failed {
+                reason: "The file could not be saved".to_string(),
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            serde_json::json!({
+                "name": "a.jpg",
+                "status": "failed",
+                "reason": "The file could not be saved",
+            })
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::utils::AuthSession;
+use crate::model::photo::{MAX_CAPTION_LENGTH, MAX_RATING, Photo};
+use crate::repo::{PhotosRepo, PhotosTransactionRepo};
+use crate::tasks::write_photos_sidecars;
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::Deserialize;
+
+pub fn router() -> Router<AppStateRef> {
+    Router::new().route("/{photo_id}", post(update_details))
+}
+
+/// The details that aren't given are left unchanged, an empty caption removes it
+#[derive(Default, Deserialize)]
+pub struct UpdateDetailsRequest {
+    pub caption: Option<String>,
+    /// 0 removes the rating
+    pub rating: Option<i64>,
+}
+
+async fn update_details(
+    State(state): State<AppStateRef>,
+    Path(photo_id): Path<i64>,
+    auth: AuthSession,
+    Json(request): Json<UpdateDetailsRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    Ok(Json(
+        update_details_service(state, &user.id, photo_id, request).await?,
+    ))
+}
+
+/// Changes the caption and rating of a photo the user can edit
+pub async fn update_details_service(
+    state: AppStateRef,
+    user_id: &str,
+    photo_id: i64,
+    request: UpdateDetailsRequest,
+) -> HttpResult<Photo> {
+    if let Some(rating) = request.rating
+        && !(0..=MAX_RATING).contains(&rating)
+    {
+        return Err(HttpError::BadRequest(format!(
+            "The rating must be between 0 and {MAX_RATING}"
+        )));
+    }
+
+    let caption = request.caption.map(|caption| caption.trim().to_string());
+    if let Some(caption) = &caption
+        && caption.chars().count() > MAX_CAPTION_LENGTH
+    {
+        return Err(HttpError::BadRequest(format!(
+            "Captions can't be longer than {MAX_CAPTION_LENGTH} characters"
+        )));
+    }
+
+    let mut tx = state.write_pool.begin().await?;
+
+    let mut photo = tx
+        .get_photo(photo_id, user_id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    if let Some(caption) = caption {
+        photo.caption = (!caption.is_empty()).then_some(caption);
+    }
+    if let Some(rating) = request.rating {
+        photo.rating = rating;
+    }
+
+    tx.update_photo(&photo).await?;
+    tx.commit().await?;
+    state.publish_latest_event().await;
+
+    write_photos_sidecars(state, std::slice::from_ref(&photo)).await;
+
+    Ok(photo)
+}
@@ -0,0 +1,109 @@
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::utils::AuthSession;
+use crate::repo::PhotosHashRepo;
+use crate::utils::{BLAKE_3_LEN, crop_blake_3_hash};
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_with::hex::Hex;
+use serde_with::serde_as;
+
+/// Each file binds two parameters, so this stays well under the SQLite limit
+const MAX_FILES: usize = 1000;
+
+pub fn router() -> Router<AppStateRef> {
+    Router::new().route("/", post(check_existing))
+}
+
+#[derive(Deserialize)]
+struct ExistsRequest {
+    files: Vec<FileHash>,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+struct FileHash {
+    /// Hex of the blake3 hash of the file, either whole or cropped like the stored ones
+    #[serde_as(as = "Hex")]
+    hash: Vec<u8>,
+    size: i64,
+}
+
+#[serde_as]
+#[derive(Debug, PartialEq, Serialize)]
+struct ExistingFile {
+    /// The cropped hash
+    #[serde_as(as = "Hex")]
+    hash: Vec<u8>,
+    size: i64,
+    photo_id: i64,
+    /// Whether the photo is in the public folder
+    public: bool,
+}
+
+/// Which of the files were already uploaded by the user or to the public folder,
+/// so clients can skip uploading them again
+async fn check_existing(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    Json(request): Json<ExistsRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    if request.files.len() > MAX_FILES {
+        return Err(HttpError::BadRequest(format!(
+            "At most {MAX_FILES} files can be checked at once"
+        )));
+    }
+
+    let files = request
+        .files
+        .into_iter()
+        .map(|file| Ok((crop_hash(file.hash)?, file.size)))
+        .collect::<HttpResult<Vec<_>>>()?;
+
+    let existing = state
+        .read_pool
+        .get_existing_hashes(&user.id, &files)
+        .await?
+        .into_iter()
+        .map(|existing| ExistingFile {
+            hash: existing.hash,
+            size: existing.file_size,
+            photo_id: existing.photo_id,
+            public: existing.public,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(existing))
+}
+
+fn crop_hash(hash: Vec<u8>) -> HttpResult<Vec<u8>> {
+    match <[u8; BLAKE_3_LEN]>::try_from(hash.as_slice()) {
+        Ok(full_hash) => Ok(crop_blake_3_hash(&full_hash)),
+        Err(_) if hash.len() == BLAKE_3_LEN / 2 => Ok(hash),
+        Err(_) => Err(HttpError::BadRequest(format!(
+            "Hashes must be {} or {BLAKE_3_LEN} bytes long",
+            BLAKE_3_LEN / 2
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crop_hash() {
+        let full_hash = *blake3::hash(b"photo").as_bytes();
+        let cropped = crop_blake_3_hash(&full_hash);
+
+        assert_eq!(crop_hash(full_hash.to_vec()).unwrap(), cropped);
+        assert_eq!(crop_hash(cropped.clone()).unwrap(), cropped);
+        assert!(crop_hash(vec![1, 2, 3]).is_err());
+        assert!(crop_hash(Vec::new()).is_err());
+    }
+}
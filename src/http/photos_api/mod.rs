@@ -1,30 +1,53 @@
+mod albums;
+mod batch_upload;
+mod details;
+mod exists;
 mod favorite;
 mod move_photos;
 mod reencode;
+mod search;
+mod shares;
 mod sync;
+mod tags;
 mod trash;
+mod uploads;
 
 use axum::{
     Json, Router,
     extract::Multipart,
     extract::{Path, Query, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     routing::{delete, get, post},
 };
 use sqlx::__rt::timeout;
+use std::path::PathBuf;
 use std::time::Duration;
 use time::OffsetDateTime;
 use tokio::{fs, task};
 use tracing::{error, info, warn};
 
+pub use albums::{
+    CreateAlbumRequest, create_album_service, delete_album_service, get_albums_info,
+    remove_member_service,
+};
+pub use details::{UpdateDetailsRequest, update_details_service};
+pub use move_photos::move_photos_service;
+pub use shares::{CreateShareRequest, create_share_service};
+pub use tags::normalize_tag_names;
+pub use uploads::{TUS_REQUEST_HEADERS, TUS_RESPONSE_HEADERS, advertise_tus_options};
+
 use crate::http::AppStateRef;
 use crate::http::error::{HttpError, HttpResult};
-use crate::http::utils::{AuthSession, file_to_response, write_field_to_file};
+use crate::http::utils::{AuthSession, WrittenFile, file_to_response, write_field_to_file};
 use crate::model::photo::Photo;
+use crate::model::photo_hash::PhotoHash;
 use crate::previews;
 use crate::repo::{PhotosHashRepo, PhotosRepo, PhotosTransactionRepo};
+use crate::tasks::{TimestampSource, get_timestamp_for_file, persist_timestamps};
 use crate::utils::exif::read_exif;
+use crate::utils::storage_resolver::StorageResolver;
+use crate::utils::xmp::remove_sidecar;
 use axum_extra::TypedHeader;
 use axum_extra::headers::Range;
 use time::serde::timestamp;
@@ -35,12 +58,21 @@ pub fn router(app_state: AppStateRef) -> Router {
         .nest("/move", move_photos::router())
         .nest("/trash", trash::router())
         .nest("/reencode", reencode::router())
+        .nest("/shares", shares::router())
+        .nest("/albums", albums::router())
+        .nest("/search", search::router())
+        .nest("/tags", tags::router())
+        .nest("/details", details::router())
+        .nest("/uploads", uploads::router())
+        .nest("/exists", exists::router())
         .route("/timestamp/{photo_id}", post(update_timestamp))
+        .route("/timestamp/shift", post(shift_timestamps))
         .route("/duplicates", get(get_duplicates))
         .route("/download/{photo_id}", get(download_photo))
         .route("/preview/{photo_id}", get(preview_photo))
         .route("/exif/{photo_id}", get(get_photo_exif))
         .route("/upload", post(upload_photo))
+        .nest("/upload/batch", batch_upload::router())
         .route("/delete/{photo_id}", delete(delete_photo))
         .nest("/favorite", favorite::router())
         .with_state(app_state)
@@ -71,10 +103,70 @@ async fn update_timestamp(
     tx.update_photo(&photo).await?;
 
     tx.commit().await?;
+    state.publish_latest_event().await;
+
+    persist_timestamps(state, std::slice::from_ref(&photo)).await;
 
     Ok(Json(photo))
 }
 
+#[derive(serde::Deserialize)]
+struct ShiftTimestampsRequest {
+    photo_ids: Vec<i64>,
+    /// Negative to move the photos back in time
+    hours: i64,
+}
+
+/// Moves the timestamps of the photos by a number of hours, for cameras whose clock was wrong.
+/// Returns the photos that changed
+async fn shift_timestamps(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    Json(request): Json<ShiftTimestampsRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    // A century is more than any wrong clock
+    const MAX_SHIFT_HOURS: i64 = 100 * 366 * 24;
+    if request.hours.abs() > MAX_SHIFT_HOURS {
+        return Err(HttpError::BadRequest(format!(
+            "The timestamps can't be shifted by more than {MAX_SHIFT_HOURS} hours"
+        )));
+    }
+    let shift = time::Duration::hours(request.hours);
+
+    let mut tx = state.write_pool.begin().await?;
+
+    let mut photos = Vec::with_capacity(request.photo_ids.len());
+    for photo_id in request.photo_ids {
+        // Like the other bulk operations, nothing changes unless the user can edit every photo
+        let mut photo = tx
+            .get_photo(photo_id, &user.id)
+            .await?
+            .ok_or(HttpError::NotFound)?;
+
+        photo.created_at = photo.created_at.checked_add(shift).ok_or_else(|| {
+            HttpError::BadRequest("The timestamps can't be shifted that far".to_string())
+        })?;
+        tx.update_photo(&photo).await?;
+        photos.push(photo);
+    }
+
+    tx.commit().await?;
+    state.publish_latest_event().await;
+
+    info!(
+        "User {} shifted the timestamps of {} photos by {} hours",
+        user.id,
+        photos.len(),
+        request.hours
+    );
+
+    persist_timestamps(state, &photos).await;
+
+    Ok(Json(photos))
+}
+
 async fn get_duplicates(
     State(state): State<AppStateRef>,
     auth: AuthSession,
@@ -96,22 +188,47 @@ async fn preview_photo(
     auth: AuthSession,
 ) -> HttpResult<impl IntoResponse> {
     let user = auth.user.ok_or(HttpError::Unauthorized)?;
-    let storage = &state.storage;
 
     let photo = state
         .read_pool
-        .get_photo(photo_id, &user.id)
+        .get_visible_photo(photo_id, &user.id)
         .await?
         .ok_or(HttpError::NotFound)?;
 
+    preview_response(state, &photo, range).await
+}
+
+/// Serves the preview of a photo, generating it first if it's missing.
+/// Falls back to the original file if the preview can't be generated.
+pub async fn preview_response(
+    state: AppStateRef,
+    photo: &Photo,
+    range: Option<TypedHeader<Range>>,
+) -> HttpResult<Response> {
+    let path = match generate_missing_preview(state, photo).await? {
+        Some(preview_path) => preview_path,
+        None => state.storage.resolve_photo(photo.partial_path()),
+    };
+
+    file_to_response(&path, range).await
+}
+
+/// The path of the preview of a photo, which is generated first if it's missing.
+/// None if another preview is taking too long to generate or the generation failed
+pub async fn generate_missing_preview(
+    state: AppStateRef,
+    photo: &Photo,
+) -> HttpResult<Option<PathBuf>> {
+    let storage = &state.storage;
+
     let photo_path = storage.resolve_photo(photo.partial_path());
     let preview_path = storage.resolve_preview(photo.partial_preview_path());
 
-    let preview_generation_mutex =
-        timeout(Duration::from_secs(3), state.preview_generation.lock()).await;
-    if preview_generation_mutex.is_err() {
-        return file_to_response(&photo_path, range).await;
-    }
+    let Ok(_preview_generation_guard) =
+        timeout(Duration::from_secs(3), state.preview_generation.lock()).await
+    else {
+        return Ok(None);
+    };
 
     let needs_generation = match tokio::fs::metadata(&preview_path).await {
         Ok(m) => m.len() < previews::MIN_PREVIEW_SIZE,
@@ -131,18 +248,16 @@ async fn preview_photo(
         Ok(())
     };
 
-    let path = match preview_generated {
-        Ok(_) => preview_path,
+    match preview_generated {
+        Ok(_) => Ok(Some(preview_path)),
         Err(e) => {
             error!(
                 "Preview generation failed for: {}\nCause: {e}",
                 photo_path.display()
             );
-            photo_path
+            Ok(None)
         }
-    };
-
-    file_to_response(&path, range).await
+    }
 }
 
 async fn download_photo(
@@ -154,7 +269,7 @@ async fn download_photo(
     let user = auth.user.ok_or(HttpError::Unauthorized)?;
     let photo = state
         .read_pool
-        .get_photo(photo_id, &user.id)
+        .get_visible_photo(photo_id, &user.id)
         .await?
         .ok_or(HttpError::NotFound)?;
 
@@ -171,7 +286,7 @@ async fn get_photo_exif(
     let user = auth.user.ok_or(HttpError::Unauthorized)?;
     let photo = state
         .read_pool
-        .get_photo(photo_id, &user.id)
+        .get_visible_photo(photo_id, &user.id)
         .await?
         .ok_or(HttpError::NotFound)?;
 
@@ -186,10 +301,14 @@ async fn get_photo_exif(
     }
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Default, serde::Deserialize)]
 struct UploadDataQuery {
-    #[serde(with = "timestamp")]
-    time_created: OffsetDateTime,
+    /// Read from the file when not given
+    #[serde(default, with = "timestamp::option")]
+    time_created: Option<OffsetDateTime>,
+    /// The modification time of the file, used when its contents have no timestamp
+    #[serde(default, with = "timestamp::option")]
+    last_modified: Option<OffsetDateTime>,
     folder_name: Option<String>,
     #[serde(default)]
     make_public: bool,
@@ -214,10 +333,35 @@ async fn upload_photo(
         .or(field.name())
         .ok_or_else(|| HttpError::BadRequest("Multipart has no name".to_string()))?
         .to_owned();
-    let photo_user_id = (!query.make_public).then_some(user.id);
 
     let written_file = write_field_to_file(field).await?;
 
+    Ok(Json(
+        store_upload(state, user.id, file_name, query, written_file).await?,
+    ))
+}
+
+#[derive(Debug, serde::Serialize)]
+struct UploadedPhoto {
+    #[serde(flatten)]
+    photo: Photo,
+    /// Left out when the photo already existed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp_source: Option<TimestampSource>,
+}
+
+/// Saves an uploaded file as a new photo, unless the user already has the same file
+async fn store_upload(
+    state: AppStateRef,
+    user_id: String,
+    file_name: String,
+    query: UploadDataQuery,
+    written_file: WrittenFile,
+) -> HttpResult<UploadedPhoto> {
+    let (time_created, timestamp_source) =
+        resolve_time_created(&query, &file_name, &written_file).await;
+    let photo_user_id = (!query.make_public).then_some(user_id);
+
     let mut tx = state.write_pool.begin().await?;
     let photo = tx
         .get_photo_with_hash(&written_file.hash, photo_user_id.as_deref())
@@ -228,21 +372,86 @@ async fn upload_photo(
             "Photo with same hash already exists with path: {}",
             photo.partial_path()
         );
-        return Ok(Json(photo));
+        return Ok(UploadedPhoto {
+            photo,
+            timestamp_source: None,
+        });
     }
 
     let mut photo = Photo {
         id: 0,
         user_id: photo_user_id,
         name: file_name,
-        created_at: query.time_created,
+        created_at: time_created,
         file_size: written_file.size as i64,
         folder: query.folder_name,
         thumb_hash: None,
         trashed_on: None,
+        caption: None,
+        rating: 0,
     };
 
-    let mut photo_path = state.storage.resolve_photo(photo.partial_path());
+    let photo_path = resolve_new_photo_path(&state.storage, &mut photo).await?;
+
+    info!("Uploading file to {}", photo_path.display());
+
+    let photo = tx.insert_photo(&photo).await?;
+    tx.insert_hashes(&[PhotoHash {
+        id: photo.id,
+        hash: written_file.hash.clone(),
+    }])
+    .await?;
+
+    written_file.persist_to(&photo_path).await?;
+
+    tx.commit().await.inspect_err(|_| {
+        // Transaction failed, delete the file
+        if let Err(e) = std::fs::remove_file(photo_path) {
+            error!("Failed to remove uploaded file: {e}");
+        }
+    })?;
+    state.publish_latest_event().await;
+
+    Ok(UploadedPhoto {
+        photo,
+        timestamp_source: Some(timestamp_source),
+    })
+}
+
+/// The timestamp given by the client, else the one read from the file,
+/// else the modification time of the file, else now
+async fn resolve_time_created(
+    query: &UploadDataQuery,
+    file_name: &str,
+    written_file: &WrittenFile,
+) -> (OffsetDateTime, TimestampSource) {
+    if let Some(time_created) = query.time_created {
+        return (time_created, TimestampSource::Client);
+    }
+
+    let path = written_file.path().to_path_buf();
+    let file_name = PathBuf::from(file_name);
+    let parsed = task::spawn_blocking(move || get_timestamp_for_file(&path, &file_name))
+        .await
+        .inspect_err(|e| error!("Failed to read the timestamp of an upload: {e}"))
+        .ok()
+        .flatten();
+
+    parsed
+        .or_else(|| {
+            query
+                .last_modified
+                .map(|last_modified| (last_modified, TimestampSource::LastModified))
+        })
+        .unwrap_or_else(|| (OffsetDateTime::now_utc(), TimestampSource::Now))
+}
+
+/// Creates the folder of a new photo, renaming the photo if its file already exists
+async fn resolve_new_photo_path(
+    storage: &StorageResolver,
+    photo: &mut Photo,
+) -> std::io::Result<PathBuf> {
+    let mut photo_path = storage.resolve_photo(photo.partial_path());
     if let Some(parent) = photo_path.parent()
         && !parent.exists()
     {
@@ -259,23 +468,10 @@ async fn upload_photo(
                 .map(|str| str.to_string_lossy().to_string())
                 .unwrap_or_default()
         );
-        photo_path = state.storage.resolve_photo(photo.partial_path());
+        photo_path = storage.resolve_photo(photo.partial_path());
     }
 
-    info!("Uploading file to {}", photo_path.display());
-
-    let photo = tx.insert_photo(&photo).await?;
-
-    written_file.persist_to(&photo_path).await?;
-
-    tx.commit().await.inspect_err(|_| {
-        // Transaction failed, delete the file
-        if let Err(e) = std::fs::remove_file(photo_path) {
-            error!("Failed to remove uploaded file: {e}");
-        }
-    })?;
-
-    Ok(Json(photo))
+    Ok(photo_path)
 }
 
 async fn delete_photo(
@@ -297,6 +493,12 @@ async fn delete_photo(
     if photo_path.exists() {
         fs::remove_file(&photo_path).await?;
         info!("Removed file at {}", photo_path.display());
+        if let Err(e) = remove_sidecar(&photo_path) {
+            warn!(
+                "Failed to remove the sidecar of {}: {e}",
+                photo_path.display()
+            );
+        }
     } else {
         warn!("No such file exists at {}", photo_path.display());
     }
@@ -304,6 +506,7 @@ async fn delete_photo(
     tx.delete_photo(&photo).await?;
 
     tx.commit().await?;
+    state.publish_latest_event().await;
 
     Ok(())
 }
@@ -91,7 +91,8 @@ async fn move_photos(
     Ok(Json(changed_photos))
 }
 
-async fn move_photos_service(
+/// Moves the photos the user has access to, skipping the ones that can't be moved
+pub async fn move_photos_service(
     photo_ids: &[i64],
     user_id: &str,
     target_user_name: Option<String>,
@@ -141,5 +142,7 @@ async fn move_photos_service(
         moved_photos.push(photo);
     }
 
+    state.publish_latest_event().await;
+
     Ok(moved_photos)
 }
@@ -183,6 +183,7 @@ async fn perform_atomic_update(
         rollback_fs().await;
         return Err(HttpError::Database(e));
     }
+    state.publish_latest_event().await;
 
     // 4. Cleanup backup
     let _ = fs::remove_file(&backup_path).await;
@@ -0,0 +1,65 @@
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::gallery::{encode_cursor, parse_optional_cursor};
+use crate::http::utils::AuthSession;
+use crate::model::photo::Photo;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::search::PhotoFilter;
+use crate::repo::SearchRepo;
+use axum::extract::{Query, State};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_LIMIT: u32 = 100;
+const MAX_LIMIT: u32 = 500;
+
+pub fn router() -> Router<AppStateRef> {
+    Router::new().route("/", get(search_photos))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    /// Words and filters, like `beach camera:pixel after:2023-06`
+    q: String,
+    #[serde(default)]
+    category: PhotoCategory,
+    /// The `next_cursor` of the previous page
+    cursor: Option<String>,
+    limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SearchResults {
+    photos: Vec<Photo>,
+    /// Set when there are more results
+    next_cursor: Option<String>,
+}
+
+async fn search_photos(
+    State(state): State<AppStateRef>,
+    Query(query): Query<SearchQuery>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    let filter = PhotoFilter::parse(&query.q).map_err(|e| HttpError::BadRequest(e.to_string()))?;
+    if filter.is_empty() {
+        return Err(HttpError::BadRequest(
+            "q must contain a word or a filter to search for".to_string(),
+        ));
+    }
+    let cursor = parse_optional_cursor(query.cursor.as_deref())?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let paginated = state
+        .read_pool
+        .search_photos_paginated(&user.id, query.category, &filter, cursor.as_ref(), limit)
+        .await?;
+
+    Ok(Json(SearchResults {
+        photos: paginated.photos,
+        next_cursor: paginated.next_cursor.as_ref().map(encode_cursor),
+    }))
+}
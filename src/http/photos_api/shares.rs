@@ -0,0 +1,137 @@
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::utils::AuthSession;
+use crate::model::share::{Share, ShareInfo};
+use crate::repo::{PhotosRepo, SharesRepo};
+use crate::utils::password_hash::generate_hash_from_password;
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use time::serde::timestamp;
+use tokio::task;
+use tracing::info;
+
+pub fn router() -> Router<AppStateRef> {
+    Router::new()
+        .route("/", get(list_shares).post(create_share))
+        .route("/{share_id}", delete(revoke_share))
+}
+
+#[derive(Deserialize)]
+pub struct CreateShareRequest {
+    /// Share an explicit list of photos
+    #[serde(default)]
+    pub photo_ids: Vec<i64>,
+    /// Or share a whole folder
+    pub folder_name: Option<String>,
+    #[serde(default)]
+    pub folder_is_public: bool,
+    #[serde(with = "timestamp")]
+    pub expires_at: OffsetDateTime,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub allow_downloads: bool,
+}
+
+async fn list_shares(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    Ok(Json(state.read_pool.get_shares_for_user(&user.id).await?))
+}
+
+async fn create_share(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    Json(request): Json<CreateShareRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    let photo_count = request.photo_ids.len() as i64;
+    let share = create_share_service(state, &user.id, request).await?;
+
+    Ok(Json(ShareInfo::new(share, photo_count)))
+}
+
+async fn revoke_share(
+    State(state): State<AppStateRef>,
+    Path(share_id): Path<i64>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    if !state.write_pool.delete_share(share_id, &user.id).await? {
+        return Err(HttpError::NotFound);
+    }
+
+    info!("User {} revoked share {share_id}", user.id);
+
+    Ok(())
+}
+
+pub async fn create_share_service(
+    state: AppStateRef,
+    user_id: &str,
+    request: CreateShareRequest,
+) -> HttpResult<Share> {
+    let folder = request.folder_name.filter(|folder| !folder.is_empty());
+
+    let has_photos = !request.photo_ids.is_empty();
+
+    if folder.is_some() == has_photos {
+        return Err(HttpError::BadRequest(
+            "A share must contain either a folder or a list of photos".to_string(),
+        ));
+    }
+
+    if request.expires_at <= OffsetDateTime::now_utc() {
+        return Err(HttpError::BadRequest(
+            "The expiry date must be in the future".to_string(),
+        ));
+    }
+
+    let password_hash = match request.password.filter(|password| !password.is_empty()) {
+        Some(password) => Some(
+            task::spawn_blocking(move || generate_hash_from_password(password))
+                .await
+                .map_err(|e| HttpError::AnyError(Box::new(e)))?,
+        ),
+        None => None,
+    };
+
+    let mut tx = state.write_pool.begin().await?;
+
+    // Users can only share photos they can see themselves
+    for photo_id in &request.photo_ids {
+        tx.get_photo(*photo_id, user_id)
+            .await?
+            .ok_or(HttpError::NotFound)?;
+    }
+
+    let share = tx
+        .insert_share(&Share {
+            id: 0,
+            token: uuid::Uuid::new_v4().simple().to_string(),
+            user_id: user_id.to_string(),
+            folder,
+            folder_is_public: request.folder_is_public,
+            password_hash,
+            allow_downloads: request.allow_downloads,
+            created_at: OffsetDateTime::now_utc(),
+            expires_at: request.expires_at,
+        })
+        .await?;
+
+    tx.insert_share_photos(share.id, &request.photo_ids).await?;
+
+    tx.commit().await?;
+
+    info!("User {user_id} created share {}", share.id);
+
+    Ok(share)
+}
@@ -1,18 +1,33 @@
 use crate::http::AppStateRef;
+use crate::http::auth::bearer_token;
 use crate::http::error::{HttpError, HttpResult};
+use crate::http::sessions_api::get_device_id;
 use crate::http::utils::AuthSession;
-use crate::repo::{PhotosTransactionRepo, UserEventLogError};
+use crate::model::api_token::hash_api_token;
+use crate::model::event_log::EventLogEntry;
+use crate::repo::event_log::EventLogRepo;
+use crate::repo::{ApiTokensRepo, PhotosTransactionRepo, UserEventLogError, UserSessionsRepo};
 use axum::extract::{Query, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::routing::get;
 use axum::{Json, Router};
+use futures_util::StreamExt;
+use futures_util::stream;
 use serde::Deserialize;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{Instant, Interval, interval_at};
+use tracing::{error, info};
 
 pub fn router() -> Router<AppStateRef> {
     Router::new()
         .route("/full", get(full_photos_list))
         .route("/partial", get(partial_photos_list))
+        .route("/stream", get(stream_events))
 }
 
 async fn full_photos_list(
@@ -54,3 +69,201 @@ async fn partial_photos_list(
         Err(UserEventLogError::Database(err)) => Err(HttpError::Database(err)),
     }
 }
+
+#[derive(Deserialize)]
+struct StreamEventsQuery {
+    /// For clients that can't set the `Last-Event-ID` header, which takes precedence
+    last_event_id: Option<i64>,
+}
+
+/// How often an idle stream checks that it's still allowed to receive the events
+const AUTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What the stream was opened with, which can be revoked while it's open
+enum StreamCredential {
+    /// The device session, revoked from the profile or by a password change
+    Session(String),
+    /// The hash of the API token, revoked by deleting the token
+    ApiToken(String),
+}
+
+struct EventStream {
+    user_id: String,
+    credential: Option<StreamCredential>,
+    receiver: watch::Receiver<i64>,
+    auth_check: Interval,
+    last_event_id: i64,
+    pending: VecDeque<Event>,
+}
+
+/// Sends the new events the user can see as they are committed, as a `photo` event with the photo
+/// or a `delete` event with the id of the deleted photo. A `resync` event tells the client to
+/// do a full sync, when it resumes from an event that was already deleted.
+/// The stream ends once the user, or the session or API token it was opened with, is gone
+async fn stream_events(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    headers: HeaderMap,
+    Query(query): Query<StreamEventsQuery>,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.clone().ok_or(HttpError::Unauthorized)?;
+
+    let credential = match bearer_token(&headers) {
+        Some(token) => Some(StreamCredential::ApiToken(hash_api_token(token))),
+        None => get_device_id(&auth).await?.map(StreamCredential::Session),
+    };
+
+    let resume_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(query.last_event_id);
+
+    // Subscribing first so no event committed in the meantime is missed
+    let receiver = state.latest_event_id.subscribe();
+    let range = state.read_pool.get_event_id_range().await?;
+    let max_event_id = range.map_or(0, |(_, max_id)| max_id);
+
+    let mut pending = VecDeque::new();
+    let last_event_id = match resume_event_id {
+        None => max_event_id,
+        Some(event_id) if can_resume_from(event_id, range) => {
+            let events = state
+                .read_pool
+                .get_user_events(event_id, max_event_id, &user.id)
+                .await?;
+            pending.extend(events.into_iter().map(to_sse_event));
+            max_event_id
+        }
+        Some(_) => {
+            pending.push_back(
+                Event::default()
+                    .id(max_event_id.to_string())
+                    .event("resync")
+                    .data(max_event_id.to_string()),
+            );
+            max_event_id
+        }
+    };
+
+    let events = EventStream {
+        user_id: user.id,
+        credential,
+        receiver,
+        auth_check: interval_at(Instant::now() + AUTH_CHECK_INTERVAL, AUTH_CHECK_INTERVAL),
+        last_event_id,
+        pending,
+    };
+
+    let stream = stream::unfold(events, move |mut events| async move {
+        loop {
+            if let Some(event) = events.pending.pop_front() {
+                return Some((Ok::<_, Infallible>(event), events));
+            }
+
+            tokio::select! {
+                // Ends the stream if the sender is gone, then the client reconnects
+                changed = events.receiver.changed() => changed.ok()?,
+                _ = events.auth_check.tick() => {}
+            }
+
+            match is_still_authorized(state, &events.user_id, events.credential.as_ref()).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!("Ending the event stream of user {}", events.user_id);
+                    return None;
+                }
+                Err(e) => {
+                    error!("Failed to check the event stream authorization: {e}");
+                    return None;
+                }
+            }
+
+            let latest_event_id = *events.receiver.borrow_and_update();
+            if latest_event_id <= events.last_event_id {
+                continue;
+            }
+
+            match state
+                .read_pool
+                .get_user_events(events.last_event_id, latest_event_id, &events.user_id)
+                .await
+            {
+                Ok(new_events) => {
+                    events
+                        .pending
+                        .extend(new_events.into_iter().map(to_sse_event));
+                    events.last_event_id = latest_event_id;
+                }
+                Err(e) => {
+                    error!("Failed to read the events to stream: {e}");
+                    return None;
+                }
+            }
+        }
+    })
+    .take_until(state.shutdown.clone().cancelled_owned());
+
+    Ok((
+        // Keeps proxies like Nginx from buffering the events
+        [("x-accel-buffering", "no")],
+        Sse::new(stream).keep_alive(KeepAlive::default()),
+    ))
+}
+
+async fn is_still_authorized(
+    state: AppStateRef,
+    user_id: &str,
+    credential: Option<&StreamCredential>,
+) -> sqlx::Result<bool> {
+    if state.users_repo.get_user(user_id).await.is_none() {
+        return Ok(false);
+    }
+
+    Ok(match credential {
+        Some(StreamCredential::Session(device_id)) => state
+            .read_pool
+            .get_user_session(device_id)
+            .await?
+            .is_some_and(|session| session.user_id == user_id),
+        Some(StreamCredential::ApiToken(token_hash)) => state
+            .read_pool
+            .get_api_token_by_hash(token_hash)
+            .await?
+            .is_some_and(|token| token.user_id == user_id),
+        None => true,
+    })
+}
+
+/// Like the partial sync, the events after the given one must all still be kept
+fn can_resume_from(event_id: i64, range: Option<(i64, i64)>) -> bool {
+    match range {
+        Some((min_event_id, max_event_id)) => (min_event_id..=max_event_id).contains(&event_id),
+        None => event_id == 0,
+    }
+}
+
+fn to_sse_event(entry: EventLogEntry) -> Event {
+    let event = Event::default().id(entry.event_id.to_string());
+
+    match entry.data {
+        Some(data) => event.event("photo").data(String::from_utf8_lossy(&data)),
+        None => event.event("delete").data(entry.photo_id.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_can_resume_from() {
+        assert!(can_resume_from(0, None));
+        assert!(!can_resume_from(5, None));
+
+        assert!(can_resume_from(10, Some((10, 20))));
+        assert!(can_resume_from(20, Some((10, 20))));
+        assert!(!can_resume_from(9, Some((10, 20))));
+        assert!(!can_resume_from(21, Some((10, 20))));
+    }
+}
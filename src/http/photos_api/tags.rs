@@ -0,0 +1,116 @@
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::utils::AuthSession;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::tag::{MAX_TAG_LENGTH, normalize_tag_name};
+use crate::repo::{PhotosRepo, TagsRepo, TagsTransactionRepo};
+use crate::tasks::write_photos_sidecars;
+use axum::extract::{Path, Query, State};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use tracing::info;
+
+pub fn router() -> Router<AppStateRef> {
+    Router::new()
+        .route("/", get(get_tags))
+        .route("/bulk", post(update_tags))
+        .route("/{photo_id}", get(get_photo_tags))
+}
+
+#[derive(Deserialize)]
+struct TagsQuery {
+    #[serde(default)]
+    category: PhotoCategory,
+}
+
+#[derive(Deserialize)]
+struct UpdateTagsRequest {
+    photo_ids: Vec<i64>,
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+async fn get_tags(
+    State(state): State<AppStateRef>,
+    Query(query): Query<TagsQuery>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    Ok(Json(
+        state
+            .read_pool
+            .get_tags_with_counts(&user.id, query.category)
+            .await?,
+    ))
+}
+
+async fn get_photo_tags(
+    State(state): State<AppStateRef>,
+    Path(photo_id): Path<i64>,
+    auth: AuthSession,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    state
+        .read_pool
+        .get_visible_photo(photo_id, &user.id)
+        .await?
+        .ok_or(HttpError::NotFound)?;
+
+    Ok(Json(state.read_pool.get_photo_tags(photo_id).await?))
+}
+
+/// Adds and removes tags of the photos the user can edit, skipping the other ones.
+/// Returns the ids of the photos whose tags changed
+async fn update_tags(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    Json(request): Json<UpdateTagsRequest>,
+) -> HttpResult<impl IntoResponse> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    let add = normalize_tag_names(&request.add)?;
+    let remove = normalize_tag_names(&request.remove)?;
+
+    let mut tx = state.write_pool.begin().await?;
+
+    let mut photos = Vec::with_capacity(request.photo_ids.len());
+    for photo_id in request.photo_ids {
+        if let Some(photo) = tx.get_photo(photo_id, &user.id).await? {
+            photos.push(photo);
+        }
+    }
+
+    let changed = tx.update_photos_tags(&photos, &add, &remove).await?;
+    tx.commit().await?;
+    state.publish_latest_event().await;
+
+    photos.retain(|photo| changed.contains(&photo.id));
+    write_photos_sidecars(state, &photos).await;
+
+    info!(
+        "User {} changed the tags of {} photos",
+        user.id,
+        changed.len()
+    );
+
+    Ok(Json(changed))
+}
+
+pub fn normalize_tag_names(names: &[String]) -> HttpResult<Vec<String>> {
+    names
+        .iter()
+        .map(|name| {
+            normalize_tag_name(name).ok_or_else(|| {
+                HttpError::BadRequest(format!(
+                    "Tags can't be empty or longer than {MAX_TAG_LENGTH} characters"
+                ))
+            })
+        })
+        .collect()
+}
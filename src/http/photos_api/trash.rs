@@ -32,6 +32,7 @@ async fn trash_photo(
     tx.update_photo(&photo).await?;
 
     tx.commit().await?;
+    state.publish_latest_event().await;
 
     Ok(Json(photo))
 }
@@ -54,6 +55,7 @@ async fn restore_photo(
     tx.update_photo(&photo).await?;
 
     tx.commit().await?;
+    state.publish_latest_event().await;
 
     Ok(Json(photo))
 }
@@ -0,0 +1,645 @@
+//! Resumable uploads with the tus 1.0 protocol (https://tus.io/protocols/resumable-upload),
+//! supporting the creation, termination and expiration extensions
+
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::photos_api::{UploadDataQuery, UploadedPhoto, store_upload};
+use crate::http::utils::{AuthSession, WrittenFile};
+use crate::model::upload::Upload;
+use crate::repo::UploadsRepo;
+use axum::Router;
+use axum::body::Body;
+use axum::extract::{Path, Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{head, post};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use futures_util::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::sync::Mutex;
+use time::macros::format_description;
+use time::{Duration, OffsetDateTime, UtcOffset};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tower_http::set_header::SetResponseHeaderLayer;
+use tracing::{error, info};
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,termination,expiration";
+/// Extended whenever data is received
+const UPLOAD_EXPIRY: Duration = Duration::days(1);
+
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const TUS_VERSION_HEADER: HeaderName = HeaderName::from_static("tus-version");
+const TUS_EXTENSION: HeaderName = HeaderName::from_static("tus-extension");
+const TUS_MAX_SIZE: HeaderName = HeaderName::from_static("tus-max-size");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+const UPLOAD_EXPIRES: HeaderName = HeaderName::from_static("upload-expires");
+/// Not part of tus, the id of the photo once the upload is complete
+const PHOTO_ID: HeaderName = HeaderName::from_static("photo-id");
+const TIMESTAMP_SOURCE: HeaderName = HeaderName::from_static("timestamp-source");
+
+/// The headers sent by tus clients, which browsers must be allowed to send from other origins
+pub const TUS_REQUEST_HEADERS: [HeaderName; 4] =
+    [TUS_RESUMABLE, UPLOAD_OFFSET, UPLOAD_LENGTH, UPLOAD_METADATA];
+/// The headers of the responses, which browsers only let other origins read when exposed
+pub const TUS_RESPONSE_HEADERS: [HeaderName; 11] = [
+    header::LOCATION,
+    TUS_RESUMABLE,
+    TUS_VERSION_HEADER,
+    TUS_EXTENSION,
+    TUS_MAX_SIZE,
+    UPLOAD_OFFSET,
+    UPLOAD_LENGTH,
+    UPLOAD_METADATA,
+    UPLOAD_EXPIRES,
+    PHOTO_ID,
+    TIMESTAMP_SOURCE,
+];
+
+pub fn router() -> Router<AppStateRef> {
+    Router::new()
+        .route("/", post(create_upload))
+        .route(
+            "/{upload_id}",
+            head(get_offset).patch(append_data).delete(terminate_upload),
+        )
+        .layer(middleware::from_fn(check_tus_version))
+        .layer(SetResponseHeaderLayer::overriding(
+            TUS_RESUMABLE,
+            HeaderValue::from_static(TUS_VERSION),
+        ))
+}
+
+async fn check_tus_version(request: Request, next: Next) -> Response {
+    let version = request.headers().get(TUS_RESUMABLE);
+
+    if version.is_none_or(|version| version != TUS_VERSION) {
+        return (
+            StatusCode::PRECONDITION_FAILED,
+            [(TUS_VERSION_HEADER, TUS_VERSION)],
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Adds the tus capabilities to the `OPTIONS` responses of the uploads, which are answered by
+/// the CORS layer before reaching the router, so this must be layered outside of it
+pub async fn advertise_tus_options(
+    State(state): State<AppStateRef>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_tus_options =
+        request.method() == Method::OPTIONS && request.uri().path().starts_with("/photos/uploads");
+
+    let mut response = next.run(request).await;
+
+    if is_tus_options {
+        let headers = response.headers_mut();
+        headers.insert(TUS_VERSION_HEADER, HeaderValue::from_static(TUS_VERSION));
+        headers.insert(TUS_EXTENSION, HeaderValue::from_static(TUS_EXTENSIONS));
+        headers.insert(
+            TUS_MAX_SIZE,
+            HeaderValue::from(state.max_resumable_upload_size),
+        );
+    }
+
+    response
+}
+
+/// Creates an upload from its `Upload-Length` and its `Upload-Metadata`, which holds the `filename`
+/// and the same `time_created`, `last_modified`, `folder_name` and `make_public` as the simple upload
+async fn create_upload(
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    headers: HeaderMap,
+) -> HttpResult<Response> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    let length = header_number(&headers, &UPLOAD_LENGTH)
+        .ok_or_else(|| HttpError::BadRequest("Upload-Length is required".to_string()))?;
+    if length > state.max_resumable_upload_size {
+        return Ok((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            [(TUS_MAX_SIZE, state.max_resumable_upload_size)],
+        )
+            .into_response());
+    }
+
+    let raw_metadata = headers
+        .get(UPLOAD_METADATA)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let metadata = parse_metadata(&raw_metadata)?;
+
+    let file_name = metadata
+        .get("filename")
+        .or_else(|| metadata.get("name"))
+        .and_then(|name| std::path::Path::new(name).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| HttpError::BadRequest("The filename metadata is required".to_string()))?;
+    let time_created = metadata_timestamp(&metadata, "time_created")?;
+    let last_modified = metadata_timestamp(&metadata, "last_modified")?;
+
+    let upload = Upload {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        user_id: user.id,
+        length,
+        metadata: raw_metadata,
+        file_name,
+        time_created,
+        last_modified,
+        folder: metadata
+            .get("folder_name")
+            .filter(|folder| !folder.is_empty())
+            .cloned(),
+        make_public: metadata
+            .get("make_public")
+            .is_some_and(|value| value == "true" || value == "1"),
+        photo_id: None,
+        expires_at: OffsetDateTime::now_utc() + UPLOAD_EXPIRY,
+    };
+
+    let path = state.storage.resolve_upload(&upload.id);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::File::create(&path).await?;
+
+    let upload = state.write_pool.insert_upload(&upload).await?;
+    info!(
+        "User {} started uploading {} ({} bytes)",
+        upload.user_id, upload.file_name, upload.length
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        [
+            (header::LOCATION, format!("/photos/uploads/{}", upload.id)),
+            (UPLOAD_EXPIRES, format_expiry(upload.expires_at)),
+        ],
+    )
+        .into_response())
+}
+
+async fn get_offset(
+    State(state): State<AppStateRef>,
+    Path(upload_id): Path<String>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+    let upload = get_active_upload(state, &upload_id, &user.id).await?;
+    let offset = upload_offset(state, &upload).await?;
+
+    let mut response = (
+        [
+            (UPLOAD_OFFSET, offset.to_string()),
+            (UPLOAD_LENGTH, upload.length.to_string()),
+            (UPLOAD_EXPIRES, format_expiry(upload.expires_at)),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        (),
+    )
+        .into_response();
+
+    let headers = response.headers_mut();
+    if !upload.metadata.is_empty()
+        && let Ok(metadata) = HeaderValue::from_str(&upload.metadata)
+    {
+        headers.insert(UPLOAD_METADATA, metadata);
+    }
+    if let Some(photo_id) = upload.photo_id {
+        headers.insert(PHOTO_ID, HeaderValue::from(photo_id));
+    }
+
+    Ok(response)
+}
+
+/// Appends the body to the upload, which becomes a photo once it has all of its data
+async fn append_data(
+    State(state): State<AppStateRef>,
+    Path(upload_id): Path<String>,
+    auth: AuthSession,
+    headers: HeaderMap,
+    body: Body,
+) -> HttpResult<Response> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+
+    if headers
+        .get(header::CONTENT_TYPE)
+        .is_none_or(|content_type| content_type != "application/offset+octet-stream")
+    {
+        return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+    }
+    let offset = header_number(&headers, &UPLOAD_OFFSET)
+        .ok_or_else(|| HttpError::BadRequest("Upload-Offset is required".to_string()))?;
+
+    let upload = get_active_upload(state, &upload_id, &user.id).await?;
+    let Some(_lock) = UploadLock::acquire(&state.active_uploads, &upload.id) else {
+        return Ok(StatusCode::LOCKED.into_response());
+    };
+
+    let current_offset = upload_offset(state, &upload).await?;
+    if offset != current_offset {
+        return Ok((
+            StatusCode::CONFLICT,
+            [(UPLOAD_OFFSET, current_offset.to_string())],
+        )
+            .into_response());
+    }
+
+    let mut new_offset = current_offset;
+    let mut expires_at = upload.expires_at;
+    if upload.photo_id.is_none() {
+        let result = write_body(state, &upload, current_offset, body).await;
+
+        // The data that was received is kept even if the connection dropped
+        new_offset = upload_offset(state, &upload).await?;
+        expires_at = OffsetDateTime::now_utc() + UPLOAD_EXPIRY;
+        state
+            .write_pool
+            .extend_upload(&upload.id, expires_at)
+            .await?;
+        result?;
+    }
+
+    let mut response = (
+        StatusCode::NO_CONTENT,
+        [
+            (UPLOAD_OFFSET, new_offset.to_string()),
+            (UPLOAD_EXPIRES, format_expiry(expires_at)),
+        ],
+    )
+        .into_response();
+
+    if new_offset == upload.length {
+        let headers = response.headers_mut();
+        match upload.photo_id {
+            Some(photo_id) => {
+                headers.insert(PHOTO_ID, HeaderValue::from(photo_id));
+            }
+            None => {
+                let uploaded = complete_upload(state, &upload).await?;
+                headers.insert(PHOTO_ID, HeaderValue::from(uploaded.photo.id));
+                if let Some(source) = uploaded.timestamp_source {
+                    headers.insert(TIMESTAMP_SOURCE, HeaderValue::from_static(source.as_str()));
+                }
+            }
+        }
+    }
+
+    Ok(response)
+}
+
+async fn write_body(
+    state: AppStateRef,
+    upload: &Upload,
+    offset: i64,
+    body: Body,
+) -> HttpResult<()> {
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .open(state.storage.resolve_upload(&upload.id))
+        .await?;
+    let mut stream = body.into_data_stream();
+    let mut offset = offset;
+
+    let result = async {
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| HttpError::AnyError(Box::new(e)))?;
+
+            offset += chunk.len() as i64;
+            if offset > upload.length {
+                return Err(HttpError::BadRequest(
+                    "The data is longer than the Upload-Length".to_string(),
+                ));
+            }
+
+            file.write_all(&chunk).await?;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    file.flush().await?;
+    result
+}
+
+/// Stores the complete file as a photo. The data of the upload is only removed once the photo is
+/// committed, so a failed attempt can be retried
+async fn complete_upload(state: AppStateRef, upload: &Upload) -> HttpResult<UploadedPhoto> {
+    let path = state.storage.resolve_upload(&upload.id);
+    let written_file = tokio::task::spawn_blocking(move || WrittenFile::from_path(path))
+        .await
+        .map_err(|e| HttpError::AnyError(Box::new(e)))??;
+
+    let query = UploadDataQuery {
+        time_created: upload.time_created,
+        last_modified: upload.last_modified,
+        folder_name: upload.folder.clone(),
+        make_public: upload.make_public,
+    };
+    let uploaded = store_upload(
+        state,
+        upload.user_id.clone(),
+        upload.file_name.clone(),
+        query,
+        written_file,
+    )
+    .await?;
+
+    state
+        .write_pool
+        .complete_upload(&upload.id, uploaded.photo.id)
+        .await?;
+    remove_upload_file(state, &upload.id).await;
+    info!("Completed the upload of {}", uploaded.photo.partial_path());
+
+    Ok(uploaded)
+}
+
+async fn terminate_upload(
+    State(state): State<AppStateRef>,
+    Path(upload_id): Path<String>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    let user = auth.user.ok_or(HttpError::Unauthorized)?;
+    let upload = get_active_upload(state, &upload_id, &user.id).await?;
+    let Some(_lock) = UploadLock::acquire(&state.active_uploads, &upload.id) else {
+        return Ok(StatusCode::LOCKED.into_response());
+    };
+
+    state.write_pool.delete_upload(&upload.id).await?;
+    remove_upload_file(state, &upload.id).await;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Removes the data of an upload
+async fn remove_upload_file(state: AppStateRef, upload_id: &str) {
+    let path = state.storage.resolve_upload(upload_id);
+
+    if let Err(e) = fs::remove_file(&path).await
+        && e.kind() != ErrorKind::NotFound
+    {
+        error!("Failed to remove the upload at {}: {e}", path.display());
+    }
+}
+
+async fn get_active_upload(
+    state: AppStateRef,
+    upload_id: &str,
+    user_id: &str,
+) -> HttpResult<Upload> {
+    state
+        .read_pool
+        .get_upload(upload_id, user_id)
+        .await?
+        .filter(|upload| !upload.is_expired())
+        .ok_or(HttpError::NotFound)
+}
+
+/// The size of the data received so far
+async fn upload_offset(state: AppStateRef, upload: &Upload) -> HttpResult<i64> {
+    if upload.photo_id.is_some() {
+        return Ok(upload.length);
+    }
+
+    match fs::metadata(state.storage.resolve_upload(&upload.id)).await {
+        Ok(metadata) => Ok(metadata.len() as i64),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(HttpError::NotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn header_number(headers: &HeaderMap, name: &HeaderName) -> Option<i64> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|number: &i64| *number >= 0)
+}
+
+/// Formats an HTTP date, like "Sun, 06 Nov 1994 08:49:37 GMT"
+fn format_expiry(expires_at: OffsetDateTime) -> String {
+    let format = format_description!(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+    );
+
+    expires_at
+        .to_offset(UtcOffset::UTC)
+        .format(&format)
+        .unwrap_or_default()
+}
+
+/// Parses the comma separated pairs of keys and Base64 encoded values, the value can be left out
+fn parse_metadata(metadata: &str) -> HttpResult<HashMap<String, String>> {
+    let invalid = || HttpError::BadRequest("Invalid Upload-Metadata".to_string());
+
+    metadata
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once(' ').unwrap_or((pair, ""));
+            let value = STANDARD.decode(value.trim()).map_err(|_| invalid())?;
+            let value = String::from_utf8(value).map_err(|_| invalid())?;
+
+            Ok((key.to_string(), value))
+        })
+        .collect()
+}
+
+/// An optional unix timestamp of the metadata, which must be valid when given
+fn metadata_timestamp(
+    metadata: &HashMap<String, String>,
+    key: &str,
+) -> HttpResult<Option<OffsetDateTime>> {
+    metadata
+        .get(key)
+        .map(|time| {
+            time.parse()
+                .ok()
+                .and_then(|time| OffsetDateTime::from_unix_timestamp(time).ok())
+                .ok_or_else(|| {
+                    HttpError::BadRequest(format!("The {key} metadata must be a timestamp"))
+                })
+        })
+        .transpose()
+}
+
+struct UploadLock<'a> {
+    active_uploads: &'a Mutex<HashSet<String>>,
+    upload_id: String,
+}
+
+impl<'a> UploadLock<'a> {
+    fn acquire(active_uploads: &'a Mutex<HashSet<String>>, upload_id: &str) -> Option<Self> {
+        let mut active = active_uploads.lock().unwrap_or_else(|e| e.into_inner());
+        if !active.insert(upload_id.to_string()) {
+            return None;
+        }
+
+        Some(Self {
+            active_uploads,
+            upload_id: upload_id.to_string(),
+        })
+    }
+}
+
+impl Drop for UploadLock<'_> {
+    fn drop(&mut self) {
+        let mut active = self
+            .active_uploads
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        active.remove(&self.upload_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::AppState;
+    use crate::repo::tests::{create_test_user, insert_test_user};
+    use crate::utils::env_reader::{MapConfig, ProxyConfig, TimestampWriteback, WritebackConfig};
+    use crate::utils::storage_resolver::StorageResolver;
+    use sqlx::SqlitePool;
+
+    #[test]
+    fn test_metadata_timestamp() {
+        let metadata = parse_metadata("time_created MTcwMDAwMDAwMA==,last_modified YWJj").unwrap();
+
+        assert_eq!(
+            metadata_timestamp(&metadata, "time_created").unwrap(),
+            OffsetDateTime::from_unix_timestamp(1_700_000_000).ok()
+        );
+        assert!(metadata_timestamp(&metadata, "last_modified").is_err());
+        assert_eq!(metadata_timestamp(&metadata, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let metadata =
+            parse_metadata("filename dmlkZW8ubXA0, time_created MTcwMDAwMDAwMA==,make_public")
+                .unwrap();
+
+        assert_eq!(metadata.get("filename").unwrap(), "video.mp4");
+        assert_eq!(metadata.get("time_created").unwrap(), "1700000000");
+        assert_eq!(metadata.get("make_public").unwrap(), "");
+
+        assert!(parse_metadata("").unwrap().is_empty());
+        assert!(parse_metadata("filename not-base64!").is_err());
+    }
+
+    #[test]
+    fn test_format_expiry() {
+        use time::macros::datetime;
+
+        assert_eq!(
+            format_expiry(datetime!(1994-11-06 10:49:37 +2)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+    }
+
+    #[test]
+    fn test_upload_lock() {
+        let active_uploads = Mutex::new(HashSet::new());
+
+        let lock = UploadLock::acquire(&active_uploads, "upload");
+        assert!(lock.is_some());
+        assert!(UploadLock::acquire(&active_uploads, "upload").is_none());
+        assert!(UploadLock::acquire(&active_uploads, "other").is_some());
+
+        drop(lock);
+        assert!(UploadLock::acquire(&active_uploads, "upload").is_some());
+    }
+
+    #[sqlx::test]
+    async fn test_complete_upload_retry(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+
+        let directory = tempfile::tempdir().unwrap();
+        let storage_folder = directory.path().join("storage");
+        let storage =
+            StorageResolver::new(storage_folder.clone(), directory.path().join("previews"));
+        let state: AppStateRef = Box::leak(Box::new(AppState::new(
+            pool.clone(),
+            pool.clone(),
+            storage,
+            ProxyConfig {
+                trusted_proxies: Vec::new(),
+                auth: None,
+            },
+            None,
+            MapConfig {
+                tile_url: String::new(),
+                attribution: String::new(),
+                max_zoom: 19,
+            },
+            WritebackConfig {
+                xmp_sidecars: false,
+                timestamps: TimestampWriteback::Off,
+            },
+        )));
+
+        let upload = pool
+            .insert_upload(&Upload {
+                id: "upload".to_string(),
+                user_id: "user1".to_string(),
+                length: 4,
+                metadata: String::new(),
+                file_name: "photo.jpg".to_string(),
+                time_created: Some(OffsetDateTime::now_utc()),
+                last_modified: None,
+                folder: Some("Blocked".to_string()),
+                make_public: false,
+                photo_id: None,
+                expires_at: OffsetDateTime::now_utc() + UPLOAD_EXPIRY,
+            })
+            .await?;
+        let upload_path = state.storage.resolve_upload(&upload.id);
+        std::fs::create_dir_all(upload_path.parent().unwrap())?;
+        std::fs::write(&upload_path, b"data")?;
+
+        // A file in place of the folder makes storing the photo fail
+        let blocking_file = storage_folder.join("user1").join("Blocked");
+        std::fs::create_dir_all(blocking_file.parent().unwrap())?;
+        std::fs::write(&blocking_file, b"")?;
+
+        assert!(complete_upload(state, &upload).await.is_err());
+        assert_eq!(std::fs::read(&upload_path)?, b"data");
+        assert_eq!(upload_offset(state, &upload).await.ok(), Some(4));
+
+        // The upload can be completed again once the problem is gone
+        std::fs::remove_file(&blocking_file)?;
+        let uploaded = complete_upload(state, &upload).await.ok().unwrap();
+
+        assert_eq!(
+            std::fs::read(state.storage.resolve_photo(uploaded.photo.partial_path()))?,
+            b"data"
+        );
+        assert!(!upload_path.exists());
+        assert_eq!(
+            pool.get_upload(&upload.id, "user1")
+                .await?
+                .unwrap()
+                .photo_id,
+            Some(uploaded.photo.id)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::http::AppStateRef;
+use crate::http::error::HttpError;
+use crate::http::utils::AuthSession;
+use crate::model::user::Credentials;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::net::SocketAddr;
+use tracing::{debug, error, warn};
+
+/// Logs in the user named by the header of a trusted SSO gateway, so that every route
+/// works the same as with a password login. Does nothing unless `PROXY_AUTH_HEADER` is set.
+pub async fn proxy_auth(
+    State(state): State<AppStateRef>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(config) = &state.proxy.auth else {
+        return next.run(request).await;
+    };
+
+    let Some(user_id) = header_value(request.headers(), &config.user_header) else {
+        return next.run(request).await;
+    };
+
+    // API tokens take precedence over the proxy
+    let has_bearer_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("Bearer "));
+    if has_bearer_token {
+        return next.run(request).await;
+    }
+
+    let peer_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if !peer_ip.is_some_and(|ip| config.trusts(ip)) {
+        debug!(
+            "Ignoring the {} header from untrusted address {peer_ip:?}",
+            config.user_header
+        );
+        return next.run(request).await;
+    }
+
+    let Some(mut auth_session) = request.extensions().get::<AuthSession>().cloned() else {
+        return next.run(request).await;
+    };
+
+    if auth_session
+        .user
+        .as_ref()
+        .is_some_and(|user| user.id == user_id)
+    {
+        return next.run(request).await;
+    }
+
+    let name = config
+        .name_header
+        .as_deref()
+        .and_then(|name_header| header_value(request.headers(), name_header));
+
+    let user = match auth_session
+        .authenticate(Credentials::External {
+            user_id: user_id.clone(),
+            name,
+            create_if_missing: config.auto_create_users,
+        })
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            warn!("The proxy authenticated unknown user {user_id}");
+            return HttpError::Forbidden.into_response();
+        }
+        Err(e) => {
+            error!("Failed to authenticate user {user_id} from the proxy: {e}");
+            return HttpError::Internal("Failed to login".to_string()).into_response();
+        }
+    };
+
+    if let Err(e) = auth_session.login(&user).await {
+        error!("Failed to login user {} with error: {}", user.id, e);
+        return HttpError::Internal("Failed to login".to_string()).into_response();
+    }
+
+    debug!("User has been logged in by the proxy: {}", user.id);
+
+    request.extensions_mut().insert(auth_session);
+    next.run(request).await
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
@@ -0,0 +1,216 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::http::utils::{AuthSession, ClientIp};
+use crate::model::user_session::UserSession;
+use crate::repo::UserSessionsRepo;
+use askama::Template;
+use axum::Router;
+use axum::extract::{Path, Request, State};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use std::net::IpAddr;
+use time::OffsetDateTime;
+use tracing::info;
+
+const DEVICE_ID_KEY: &str = "sessions.device_id";
+/// Longer user agents are cut, they are only used to recognize the device
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+pub fn router(app_state: AppStateRef) -> Router {
+    Router::new()
+        .route("/profile/sessions", get(sessions_section))
+        .route("/profile/sessions/revoke-others", post(revoke_others))
+        .route("/profile/sessions/{session_id}", delete(revoke_session))
+        .with_state(app_state)
+}
+
+#[derive(Template)]
+#[template(path = "user/sessions_section.html")]
+struct SessionsSectionTemplate {
+    sessions: Vec<UserSession>,
+    current_session_id: Option<String>,
+}
+
+/// Keeps track of the devices each user is logged in on, and logs out the sessions
+/// that were revoked from another device. Requests using an API token are left alone.
+pub async fn track_session(
+    State(state): State<AppStateRef>,
+    ClientIp(ip): ClientIp,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if request.headers().contains_key(header::AUTHORIZATION) {
+        return next.run(request).await;
+    }
+
+    let Some(mut auth_session) = request.extensions().get::<AuthSession>().cloned() else {
+        return next.run(request).await;
+    };
+
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|user_agent| {
+            user_agent
+                .chars()
+                .take(MAX_USER_AGENT_LENGTH)
+                .collect::<String>()
+        });
+
+    if let Err(e) = track_session_service(state, &mut auth_session, ip, user_agent.as_deref()).await
+    {
+        return e.into_response();
+    }
+
+    request.extensions_mut().insert(auth_session);
+    next.run(request).await
+}
+
+async fn track_session_service(
+    state: AppStateRef,
+    auth: &mut AuthSession,
+    ip: Option<IpAddr>,
+    user_agent: Option<&str>,
+) -> HttpResult<()> {
+    let Some(user_id) = auth.user.as_ref().map(|user| user.id.clone()) else {
+        return Ok(());
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let ip = ip.map(|ip| ip.to_string());
+
+    if let Some(device_id) = get_device_id(auth).await? {
+        match state.write_pool.get_user_session(&device_id).await? {
+            Some(session) if session.user_id == user_id => {
+                state
+                    .write_pool
+                    .update_user_session_last_seen(&device_id, now, ip.as_deref(), user_agent)
+                    .await?;
+                return Ok(());
+            }
+            // Someone else logged in on this browser without logging out first
+            Some(_) => {
+                state
+                    .write_pool
+                    .delete_user_session(&device_id, None)
+                    .await?;
+            }
+            None => {
+                info!("Logging out revoked session of user {user_id}");
+                auth.logout()
+                    .await
+                    .map_err(|e| HttpError::Internal(e.to_string()))?;
+                return Ok(());
+            }
+        }
+    }
+
+    // A new login, or a session started before the devices were tracked
+    let device_id = uuid::Uuid::new_v4().simple().to_string();
+    state
+        .write_pool
+        .insert_user_session(&device_id, &user_id, now, ip.as_deref(), user_agent)
+        .await?;
+    auth.session
+        .insert(DEVICE_ID_KEY, device_id)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    Ok(())
+}
+
+/// The id of the device session of the request, which is removed when the session is revoked
+pub async fn get_device_id(auth: &AuthSession) -> HttpResult<Option<String>> {
+    auth.session
+        .get::<String>(DEVICE_ID_KEY)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))
+}
+
+/// Called when logging out, so the device disappears from the list right away
+pub async fn forget_current_session(state: AppStateRef, auth: &AuthSession) -> HttpResult<()> {
+    if let Some(device_id) = get_device_id(auth).await? {
+        state
+            .write_pool
+            .delete_user_session(&device_id, None)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Logs out every other device of the user on their next request
+pub async fn revoke_other_sessions(
+    state: AppStateRef,
+    auth: &AuthSession,
+    user_id: &str,
+) -> HttpResult<u64> {
+    let device_id = get_device_id(auth).await?;
+
+    Ok(state
+        .write_pool
+        .delete_user_sessions(Some(user_id), device_id.as_deref())
+        .await?)
+}
+
+async fn render_section(
+    state: AppStateRef,
+    auth: &AuthSession,
+    user_id: &str,
+) -> HttpResult<Response> {
+    SessionsSectionTemplate {
+        sessions: state.write_pool.get_user_sessions(user_id).await?,
+        current_session_id: get_device_id(auth).await?,
+    }
+    .try_into_response()
+}
+
+async fn sessions_section(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    render_section(state, &auth, &user.id).await
+}
+
+async fn revoke_session(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+    Path(session_id): Path<String>,
+) -> HttpResult<Response> {
+    if get_device_id(&auth).await?.as_deref() == Some(session_id.as_str()) {
+        return Err(HttpError::BadRequest(
+            "Log out to end the current session".to_string(),
+        ));
+    }
+
+    if !state
+        .write_pool
+        .delete_user_session(&session_id, Some(&user.id))
+        .await?
+    {
+        return Err(HttpError::NotFound);
+    }
+
+    info!("User {} revoked a session", user.id);
+
+    // Return empty HTML to remove the row via hx-swap="outerHTML"
+    Ok(Html("").into_response())
+}
+
+async fn revoke_others(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    auth: AuthSession,
+) -> HttpResult<Response> {
+    let revoked = revoke_other_sessions(state, &auth, &user.id).await?;
+    info!("User {} revoked {revoked} other sessions", user.id);
+
+    render_section(state, &auth, &user.id).await
+}
@@ -0,0 +1,98 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::api_token::{ApiToken, ApiTokenScope, generate_api_token, hash_api_token};
+use crate::repo::ApiTokensRepo;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{delete, get};
+use axum::{Form, Router};
+use serde::Deserialize;
+use tracing::info;
+
+pub fn router(app_state: AppStateRef) -> Router {
+    Router::new()
+        .route("/profile/tokens", get(tokens_section).post(create_token))
+        .route("/profile/tokens/{token_id}", delete(revoke_token))
+        .with_state(app_state)
+}
+
+#[derive(Template)]
+#[template(path = "user/api_tokens_section.html")]
+struct ApiTokensSectionTemplate {
+    tokens: Vec<ApiToken>,
+    /// Only set right after creating a token, as it can't be recovered afterwards
+    created_token: Option<String>,
+    scopes: [ApiTokenScope; 3],
+}
+
+#[derive(Deserialize)]
+struct CreateTokenForm {
+    name: String,
+    scope: ApiTokenScope,
+}
+
+async fn render_section(
+    state: AppStateRef,
+    user_id: &str,
+    created_token: Option<String>,
+) -> HttpResult<Response> {
+    ApiTokensSectionTemplate {
+        tokens: state.write_pool.get_api_tokens(Some(user_id)).await?,
+        created_token,
+        scopes: ApiTokenScope::ALL,
+    }
+    .try_into_response()
+}
+
+async fn tokens_section(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+) -> HttpResult<Response> {
+    render_section(state, &user.id, None).await
+}
+
+async fn create_token(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Form(form): Form<CreateTokenForm>,
+) -> HttpResult<Response> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(HttpError::BadRequest("Token name is required".to_string()));
+    }
+
+    let token = generate_api_token();
+    let api_token = state
+        .write_pool
+        .insert_api_token(&user.id, name, &hash_api_token(&token), form.scope)
+        .await?;
+
+    info!(
+        "User {} created the {} API token {}",
+        user.id, api_token.scope, api_token.id
+    );
+
+    render_section(state, &user.id, Some(token)).await
+}
+
+async fn revoke_token(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Path(token_id): Path<i64>,
+) -> HttpResult<Response> {
+    if !state
+        .write_pool
+        .delete_api_token(token_id, Some(&user.id))
+        .await?
+    {
+        return Err(HttpError::NotFound);
+    }
+
+    info!("User {} revoked the API token {token_id}", user.id);
+
+    // Return empty HTML to remove the row via hx-swap="outerHTML"
+    Ok(Html("").into_response())
+}
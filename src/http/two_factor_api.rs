@@ -0,0 +1,373 @@
+use crate::http::AppStateRef;
+use crate::http::auth::AuthenticatedUser;
+use crate::http::error::{HttpError, HttpResult, error_alert, too_many_requests_message};
+use crate::http::login_limiter::{check_login_allowed, clear_login_failures, record_login_failure};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::http::utils::{AuthSession, ClientIp, WantsHtml};
+use crate::model::login_attempt::LoginAttemptKind;
+use crate::model::two_factor::UserTotp;
+use crate::model::user::{SimpleUser, User};
+use crate::repo::TwoFactorRepo;
+use crate::utils::totp;
+use askama::Template;
+use axum::extract::State;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
+
+const PENDING_LOGIN_KEY: &str = "two_factor.pending_login";
+/// How long the user has to enter the code after the password was accepted
+const PENDING_LOGIN_TTL_SECONDS: i64 = 5 * 60;
+const MAX_CODE_ATTEMPTS: u32 = 5;
+
+pub fn router(app_state: AppStateRef) -> Router {
+    Router::new()
+        .route("/login/2fa", get(login_page).post(login_with_code))
+        .route("/profile/2fa", get(two_factor_section))
+        .route("/profile/2fa/setup", post(setup))
+        .route("/profile/2fa/enable", post(enable))
+        .route("/profile/2fa/disable", post(disable))
+        .route(
+            "/profile/2fa/recovery-codes",
+            post(regenerate_recovery_codes),
+        )
+        .with_state(app_state)
+}
+
+/// A user whose password was accepted, but who still has to enter a code
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    user_id: String,
+    created_at: i64,
+    attempts: u32,
+}
+
+#[derive(Deserialize)]
+struct CodeForm {
+    code: String,
+}
+
+#[derive(Template)]
+#[template(path = "user/two_factor_login_page.html")]
+struct TwoFactorLoginTemplate;
+
+#[derive(Template)]
+#[template(path = "user/two_factor_section.html")]
+struct TwoFactorSectionTemplate {
+    enabled: bool,
+    recovery_codes_left: i64,
+}
+
+#[derive(Template)]
+#[template(path = "user/two_factor_setup.html")]
+struct TwoFactorSetupTemplate {
+    secret: String,
+    otpauth_url: String,
+    qr_code_svg: String,
+}
+
+#[derive(Template)]
+#[template(path = "user/recovery_codes.html")]
+struct RecoveryCodesTemplate {
+    codes: Vec<String>,
+}
+
+/// Called by the login handler once the password has been verified.
+/// Returns true if the user has two-factor authentication enabled,
+/// in which case the login has to be completed on `/login/2fa`
+pub async fn begin_two_factor_login(
+    state: AppStateRef,
+    auth: &AuthSession,
+    user: &User,
+) -> HttpResult<bool> {
+    if !state.read_pool.check_totp_enabled(&user.id).await? {
+        return Ok(false);
+    }
+
+    let pending = PendingLogin {
+        user_id: user.id.clone(),
+        created_at: OffsetDateTime::now_utc().unix_timestamp(),
+        attempts: 0,
+    };
+    auth.session
+        .insert(PENDING_LOGIN_KEY, pending)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    Ok(true)
+}
+
+async fn get_pending_login(auth: &AuthSession) -> HttpResult<Option<PendingLogin>> {
+    let pending = auth
+        .session
+        .get::<PendingLogin>(PENDING_LOGIN_KEY)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    Ok(pending.filter(|pending| now - pending.created_at < PENDING_LOGIN_TTL_SECONDS))
+}
+
+async fn login_page(auth: AuthSession) -> HttpResult<Response> {
+    if auth.user.is_some() {
+        return Ok(Redirect::to("/").into_response());
+    }
+
+    if get_pending_login(&auth).await?.is_none() {
+        return Ok(Redirect::to("/login").into_response());
+    }
+
+    TwoFactorLoginTemplate.try_into_response()
+}
+
+async fn login_with_code(
+    State(state): State<AppStateRef>,
+    mut auth: AuthSession,
+    WantsHtml(wants_html): WantsHtml,
+    ClientIp(ip): ClientIp,
+    Form(form): Form<CodeForm>,
+) -> Response {
+    match login_with_code_service(state, &mut auth, ip, &form.code).await {
+        Ok(user) if wants_html => {
+            info!(
+                "User has been logged in with two-factor authentication: {}",
+                user.id
+            );
+            [("HX-Refresh", "true"), ("HX-Replace-Url", "/")].into_response()
+        }
+        Ok(user) => Json(SimpleUser::from(user)).into_response(),
+        Err(HttpError::BadRequest(message)) if wants_html => error_alert(&message),
+        Err(HttpError::TooManyRequests(retry_after)) if wants_html => {
+            error_alert(&too_many_requests_message(retry_after))
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn login_with_code_service(
+    state: AppStateRef,
+    auth: &mut AuthSession,
+    ip: Option<IpAddr>,
+    code: &str,
+) -> HttpResult<User> {
+    let Some(mut pending) = get_pending_login(auth).await? else {
+        return Err(HttpError::BadRequest(
+            "The login has expired, please enter your password again".to_string(),
+        ));
+    };
+
+    let user = state
+        .users_repo
+        .get_user(&pending.user_id)
+        .await
+        .ok_or(HttpError::Unauthorized)?;
+
+    check_login_allowed(state, ip, LoginAttemptKind::User, &user.id).await?;
+
+    if !verify_code(state, &user.id, code).await? {
+        warn!("Wrong two-factor code for user: {} from {:?}", user.id, ip);
+        record_login_failure(state, ip, LoginAttemptKind::User, &user.id).await?;
+
+        pending.attempts += 1;
+        let result = if pending.attempts >= MAX_CODE_ATTEMPTS {
+            auth.session.remove::<PendingLogin>(PENDING_LOGIN_KEY).await
+        } else {
+            auth.session
+                .insert(PENDING_LOGIN_KEY, pending)
+                .await
+                .map(|_| None)
+        };
+        result.map_err(|e| HttpError::Internal(e.to_string()))?;
+
+        return Err(HttpError::BadRequest("Invalid code".to_string()));
+    }
+
+    auth.session
+        .remove::<PendingLogin>(PENDING_LOGIN_KEY)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    auth.login(&user).await.map_err(|e| {
+        error!("Failed to login user {} with error: {}", user.id, e);
+        HttpError::Internal("Failed to login".to_string())
+    })?;
+
+    clear_login_failures(state, LoginAttemptKind::User, &user.id).await?;
+
+    Ok(user)
+}
+
+/// Accepts either a code from the authenticator app or one of the recovery codes
+async fn verify_code(state: AppStateRef, user_id: &str, code: &str) -> HttpResult<bool> {
+    let Some(user_totp) = state
+        .write_pool
+        .get_totp(user_id)
+        .await?
+        .filter(|user_totp| user_totp.enabled)
+    else {
+        return Ok(false);
+    };
+
+    if check_totp_code(state, &user_totp, code).await? {
+        return Ok(true);
+    }
+
+    let used_recovery_code = state
+        .write_pool
+        .use_recovery_code(user_id, &totp::hash_recovery_code(code))
+        .await?;
+
+    if used_recovery_code {
+        info!("User {user_id} used a recovery code");
+    }
+
+    Ok(used_recovery_code)
+}
+
+/// Each code is only accepted once, so a code that was seen by someone else can't be replayed
+async fn check_totp_code(state: AppStateRef, user_totp: &UserTotp, code: &str) -> HttpResult<bool> {
+    let Some(step) = totp::check_code(&user_totp.secret, code, user_totp.last_used_step) else {
+        return Ok(false);
+    };
+
+    Ok(state
+        .write_pool
+        .use_totp_step(&user_totp.user_id, step)
+        .await?)
+}
+
+async fn two_factor_section(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+) -> HttpResult<Response> {
+    render_section(state, &user.id).await
+}
+
+async fn render_section(state: AppStateRef, user_id: &str) -> HttpResult<Response> {
+    TwoFactorSectionTemplate {
+        enabled: state.write_pool.check_totp_enabled(user_id).await?,
+        recovery_codes_left: state.write_pool.count_recovery_codes(user_id).await?,
+    }
+    .try_into_response()
+}
+
+async fn setup(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+) -> HttpResult<Response> {
+    let secret = totp::generate_secret();
+
+    if !state
+        .write_pool
+        .insert_pending_totp(&user.id, &secret)
+        .await?
+    {
+        return Ok(section_error(
+            "Two-factor authentication is already enabled",
+        ));
+    }
+
+    let otpauth_url = totp::otpauth_url(&secret, &user.id)
+        .ok_or_else(|| HttpError::Internal("Invalid TOTP secret".to_string()))?;
+    let qr_code_svg = totp::qr_code_svg(&otpauth_url)
+        .ok_or_else(|| HttpError::Internal("Failed to generate the QR code".to_string()))?;
+
+    TwoFactorSetupTemplate {
+        secret,
+        otpauth_url,
+        qr_code_svg,
+    }
+    .try_into_response()
+}
+
+async fn enable(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Form(form): Form<CodeForm>,
+) -> HttpResult<Response> {
+    let Some(user_totp) = state.write_pool.get_totp(&user.id).await? else {
+        return Err(HttpError::NotFound);
+    };
+
+    if user_totp.enabled {
+        return render_section(state, &user.id).await;
+    }
+
+    if !check_totp_code(state, &user_totp, &form.code).await? {
+        return Ok(section_error("Invalid code, check the time of your device"));
+    }
+
+    state.write_pool.enable_totp(&user.id).await?;
+    info!("User {} enabled two-factor authentication", user.id);
+
+    replace_recovery_codes(state, &user.id).await
+}
+
+async fn disable(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Form(form): Form<CodeForm>,
+) -> HttpResult<Response> {
+    if !verify_code(state, &user.id, &form.code).await? {
+        return Ok(section_error("Invalid code"));
+    }
+
+    let mut tx = state.write_pool.begin().await?;
+    tx.delete_totp(&user.id).await?;
+    tx.delete_recovery_codes(&user.id).await?;
+    tx.commit().await?;
+    info!("User {} disabled two-factor authentication", user.id);
+
+    render_section(state, &user.id).await
+}
+
+async fn regenerate_recovery_codes(
+    AuthenticatedUser(user): AuthenticatedUser,
+    State(state): State<AppStateRef>,
+    Form(form): Form<CodeForm>,
+) -> HttpResult<Response> {
+    let user_totp = state
+        .write_pool
+        .get_totp(&user.id)
+        .await?
+        .filter(|user_totp| user_totp.enabled)
+        .ok_or(HttpError::NotFound)?;
+
+    if !check_totp_code(state, &user_totp, &form.code).await? {
+        return Ok(section_error("Invalid code"));
+    }
+
+    replace_recovery_codes(state, &user.id).await
+}
+
+async fn replace_recovery_codes(state: AppStateRef, user_id: &str) -> HttpResult<Response> {
+    let codes = totp::generate_recovery_codes();
+    let hashes: Vec<String> = codes
+        .iter()
+        .map(|code| totp::hash_recovery_code(code))
+        .collect();
+
+    let mut tx = state.write_pool.begin().await?;
+    tx.delete_recovery_codes(user_id).await?;
+    tx.insert_recovery_codes(user_id, &hashes).await?;
+    tx.commit().await?;
+
+    RecoveryCodesTemplate { codes }.try_into_response()
+}
+
+/// The forms of the profile section replace the whole section on success,
+/// so errors have to be redirected to the placeholder under the form
+fn section_error(message: &str) -> Response {
+    (
+        [
+            ("HX-Retarget", "#two-factor-error"),
+            ("HX-Reswap", "innerHTML"),
+        ],
+        error_alert(message),
+    )
+        .into_response()
+}
@@ -1,29 +1,60 @@
-use crate::http::error::HttpError;
+use crate::http::AppStateRef;
+use crate::http::error::{HttpError, HttpResult, error_alert, too_many_requests_message};
+use crate::http::login_limiter::{check_login_allowed, clear_login_failures, record_login_failure};
+use crate::http::sessions_api::{forget_current_session, revoke_other_sessions};
 use crate::http::template_into_response::TemplateIntoResponse;
-use crate::http::utils::{AuthSession, WantsHtml};
-use crate::model::user::{SimpleUser, UserCredentials};
+use crate::http::two_factor_api::begin_two_factor_login;
+use crate::http::utils::{AuthSession, ClientIp, WantsHtml};
+use crate::model::login_attempt::LoginAttemptKind;
+use crate::model::user::{Credentials, SimpleUser, User, UserCredentials};
+use crate::utils::password_hash::{generate_hash_from_password, validate_credentials};
 use askama::Template;
+use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Redirect, Response};
 use axum::routing::{get, post};
 use axum::{Form, Json, Router};
-use tracing::{debug, error, warn};
+use serde::Deserialize;
+use std::net::IpAddr;
+use tokio::task;
+use tracing::{debug, error, info, warn};
 
-pub fn router() -> Router {
+pub fn router(app_state: AppStateRef) -> Router {
     Router::new()
         .route("/login", post(login_handler))
         .route("/logout", post(logout))
         .route("/profile", get(profile))
+        .route("/profile/password", post(change_password))
+        .with_state(app_state)
+}
+
+#[derive(Template)]
+#[template(path = "user/profile_page.html")]
+struct ProfileTemplate {
+    user: User,
+}
+
+#[derive(Template)]
+#[template(path = "user/password_changed.html")]
+struct PasswordChangedTemplate;
+
+#[derive(Deserialize)]
+struct ChangePasswordForm {
+    current_password: String,
+    new_password: String,
+    confirm_password: Option<String>,
 }
 
 async fn login_handler(
+    State(state): State<AppStateRef>,
     mut auth: AuthSession,
     WantsHtml(wants_html): WantsHtml,
+    ClientIp(ip): ClientIp,
     Form(credentials): Form<UserCredentials>,
 ) -> Response {
     let error_response = |message: &str| -> Response {
         if wants_html {
-            login_error(message)
+            error_alert(message)
         } else {
             HttpError::BadRequest(message.to_string()).into_response()
         }
@@ -31,7 +62,7 @@ async fn login_handler(
 
     let internal_error = || -> Response {
         if wants_html {
-            login_error("Server encountered a problem. Please try again later.")
+            error_alert("Server encountered a problem. Please try again later.")
         } else {
             HttpError::Internal("Server encountered a problem".to_string()).into_response()
         }
@@ -45,12 +76,35 @@ async fn login_handler(
         return error_response("Password is required");
     }
 
-    let user = match auth.authenticate(credentials.clone()).await {
+    match check_login_allowed(state, ip, LoginAttemptKind::User, &credentials.user_id).await {
+        Ok(()) => {}
+        Err(HttpError::TooManyRequests(retry_after)) if wants_html => {
+            return error_alert(&too_many_requests_message(retry_after));
+        }
+        Err(e @ HttpError::TooManyRequests(_)) => return e.into_response(),
+        Err(e) => {
+            error!("Failed to check the login attempts: {e}");
+            return internal_error();
+        }
+    }
+
+    let user = match auth
+        .authenticate(Credentials::Password(credentials.clone()))
+        .await
+    {
         Ok(Some(user)) => user,
         Ok(None) => {
-            warn!("Wrong credentials for user: {}", credentials.user_id);
+            warn!(
+                "Wrong credentials for user: {} from {:?}",
+                credentials.user_id, ip
+            );
+            if let Err(e) =
+                record_login_failure(state, ip, LoginAttemptKind::User, &credentials.user_id).await
+            {
+                error!("Failed to record the login failure: {e}");
+            }
             return if wants_html {
-                login_error("Invalid user ID or password")
+                error_alert("Invalid user ID or password")
             } else {
                 HttpError::Unauthorized.into_response()
             };
@@ -64,11 +118,35 @@ async fn login_handler(
         }
     };
 
+    match begin_two_factor_login(state, &auth, &user).await {
+        Ok(false) => {}
+        Ok(true) => {
+            debug!("User {} has to enter a two-factor code", user.id);
+            return if wants_html {
+                [("HX-Redirect", "/login/2fa")].into_response()
+            } else {
+                (
+                    StatusCode::ACCEPTED,
+                    Json(serde_json::json!({"two_factor_required": true})),
+                )
+                    .into_response()
+            };
+        }
+        Err(e) => {
+            error!("Failed to start the two-factor login of {}: {}", user.id, e);
+            return internal_error();
+        }
+    }
+
     if let Err(e) = auth.login(&user).await {
         error!("Failed to login user {} with error: {}", user.id, e);
         return internal_error();
     }
 
+    if let Err(e) = clear_login_failures(state, LoginAttemptKind::User, &user.id).await {
+        error!("Failed to clear the login failures: {e}");
+    }
+
     debug!("User has been logged in: {}", user.id);
 
     if wants_html {
@@ -78,32 +156,112 @@ async fn login_handler(
     }
 }
 
-fn login_error(message: &str) -> Response {
-    #[derive(Template)]
-    #[template(path = "user/login_error.html")]
-    struct ErrorTemplate<'a> {
-        error_message: &'a str,
+async fn profile(auth_session: AuthSession, WantsHtml(wants_html): WantsHtml) -> Response {
+    match auth_session.user {
+        Some(user) if wants_html => ProfileTemplate { user }.into_response(),
+        Some(user) => Json(SimpleUser::from(user)).into_response(),
+        None if wants_html => Redirect::to("/login").into_response(),
+        None => StatusCode::UNAUTHORIZED.into_response(),
     }
+}
 
-    ErrorTemplate {
-        error_message: message,
+async fn change_password(
+    State(state): State<AppStateRef>,
+    mut auth: AuthSession,
+    ClientIp(ip): ClientIp,
+    WantsHtml(wants_html): WantsHtml,
+    Form(form): Form<ChangePasswordForm>,
+) -> Response {
+    match change_password_service(state, &mut auth, ip, form).await {
+        Ok(()) if wants_html => PasswordChangedTemplate.into_response(),
+        Ok(()) => Json(serde_json::json!({"message": "Password changed"})).into_response(),
+        Err(HttpError::BadRequest(message)) if wants_html => error_alert(&message),
+        Err(HttpError::TooManyRequests(retry_after)) if wants_html => {
+            error_alert(&too_many_requests_message(retry_after))
+        }
+        Err(e) => e.into_response(),
     }
-    .into_response()
 }
 
-async fn profile(auth_session: AuthSession) -> impl IntoResponse {
-    auth_session
-        .user
-        .map_or(StatusCode::UNAUTHORIZED.into_response(), |user| {
-            Json(SimpleUser::from(user)).into_response()
-        })
+/// Changing the password also changes the session auth hash,
+/// which logs out every other session of the user on their next request.
+/// Wrong current passwords count as failed logins, so a stolen session can't guess it
+async fn change_password_service(
+    state: AppStateRef,
+    auth: &mut AuthSession,
+    ip: Option<IpAddr>,
+    form: ChangePasswordForm,
+) -> HttpResult<()> {
+    let user = auth.user.clone().ok_or(HttpError::Unauthorized)?;
+
+    if form.new_password.is_empty() {
+        return Err(HttpError::BadRequest(
+            "New password is required".to_string(),
+        ));
+    }
+
+    if form
+        .confirm_password
+        .is_some_and(|confirm| confirm != form.new_password)
+    {
+        return Err(HttpError::BadRequest("Passwords don't match".to_string()));
+    }
+
+    check_login_allowed(state, ip, LoginAttemptKind::User, &user.id).await?;
+
+    let current_hash = user.password_hash.clone();
+    let new_password = form.new_password;
+    // Only hash the new password once the current one has been verified
+    let new_hash = task::spawn_blocking(move || {
+        validate_credentials(form.current_password, current_hash)
+            .map(|valid| valid.then(|| generate_hash_from_password(new_password)))
+    })
+    .await
+    .map_err(|e| HttpError::AnyError(Box::new(e)))?
+    .map_err(|e| HttpError::Internal(e.to_string()))?;
+
+    let Some(new_hash) = new_hash else {
+        warn!("Wrong current password for user: {} from {:?}", user.id, ip);
+        record_login_failure(state, ip, LoginAttemptKind::User, &user.id).await?;
+        return Err(HttpError::BadRequest(
+            "Invalid current password".to_string(),
+        ));
+    };
+
+    clear_login_failures(state, LoginAttemptKind::User, &user.id).await?;
+
+    auth.backend
+        .update_password_hash(&user.id, &new_hash)
+        .await?;
+
+    // Store the new hash in the current session so that it stays logged in
+    let updated_user = User {
+        password_hash: new_hash,
+        ..user
+    };
+    auth.login(&updated_user)
+        .await
+        .map_err(|e| HttpError::Internal(e.to_string()))?;
+    revoke_other_sessions(state, auth, &updated_user.id).await?;
+
+    info!("User {} changed their password", updated_user.id);
+
+    Ok(())
 }
 
-pub async fn logout(mut auth: AuthSession, WantsHtml(wants_html): WantsHtml) -> Response {
+pub async fn logout(
+    State(state): State<AppStateRef>,
+    mut auth: AuthSession,
+    WantsHtml(wants_html): WantsHtml,
+) -> Response {
     if let Some(user) = &auth.user {
         debug!("Logging out user: {}", user.id);
     }
 
+    if let Err(e) = forget_current_session(state, &auth).await {
+        return e.into_response();
+    }
+
     if auth.user.is_some()
         && let Err(e) = auth.logout().await
     {
@@ -1,15 +1,18 @@
+use crate::http::AppStateRef;
 use crate::http::error::{HttpError, HttpResult};
 use crate::repo::users_repo::UsersRepository;
 use crate::utils::crop_blake_3_hash;
+use crate::utils::env_reader::ProxyConfig;
 use axum::body::Body;
-use axum::extract::FromRequestParts;
 use axum::extract::multipart;
+use axum::extract::{ConnectInfo, FromRequestParts};
 use axum::http::request::Parts;
 use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::{IntoResponse, Response};
 use axum_extra::TypedHeader;
 use axum_extra::headers::Range;
 use std::io::{BufWriter, SeekFrom, Write};
+use std::net::{IpAddr, SocketAddr};
 use std::ops::Bound;
 use tempfile::NamedTempFile;
 use tokio::fs;
@@ -35,6 +38,51 @@ where
     }
 }
 
+/// Extractor for the IP address of the client.
+/// The `X-Real-IP` and `X-Forwarded-For` headers are only trusted when the request comes
+/// from one of the `TRUSTED_PROXIES`, like the documented Nginx setup
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl FromRequestParts<AppStateRef> for ClientIp {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppStateRef,
+    ) -> Result<Self, Self::Rejection> {
+        let peer_ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(ClientIp(client_ip(peer_ip, &parts.headers, &state.proxy)))
+    }
+}
+
+fn client_ip(peer_ip: Option<IpAddr>, headers: &HeaderMap, proxy: &ProxyConfig) -> Option<IpAddr> {
+    // Without the peer address, there is no telling who set the headers
+    let peer_ip = peer_ip?;
+    if !proxy.trusts(peer_ip) {
+        return Some(peer_ip);
+    }
+
+    let header_ip = |name: &str, take_last: bool| {
+        let value = headers.get(name)?.to_str().ok()?;
+        let mut ips = value.split(',').map(str::trim);
+        // The proxy appends the address it received the request from
+        let ip = if take_last {
+            ips.next_back()
+        } else {
+            ips.next()
+        };
+        ip?.parse::<IpAddr>().ok()
+    };
+
+    header_ip("x-real-ip", false)
+        .or_else(|| header_ip("x-forwarded-for", true))
+        .or(Some(peer_ip))
+}
+
 fn wants_html(headers: &HeaderMap) -> bool {
     if headers.contains_key("hx-request") {
         return true;
@@ -143,16 +191,54 @@ pub async fn file_to_response(
 }
 
 pub struct WrittenFile {
-    temp_file: NamedTempFile,
+    file: WrittenPath,
     pub size: usize,
     pub hash: Vec<u8>,
 }
 
+enum WrittenPath {
+    /// Deleted unless it's persisted
+    Temporary(NamedTempFile),
+    /// Left in place when it's persisted, the caller removes it once the photo is committed
+    Kept(std::path::PathBuf),
+}
+
 impl WrittenFile {
-    /// Moves the temporary file to the target path, handling cross-device scenarios
+    /// Takes over a file that was written in parts, which stays where it is until the caller
+    /// removes it, so the data isn't lost if the photo can't be stored
+    pub fn from_path(path: std::path::PathBuf) -> std::io::Result<Self> {
+        let hash = blake3::Hasher::new().update_mmap(&path)?.finalize();
+        let size = std::fs::metadata(&path)?.len() as usize;
+
+        Ok(Self {
+            file: WrittenPath::Kept(path),
+            size,
+            hash: crop_blake_3_hash(hash.as_bytes()),
+        })
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        match &self.file {
+            WrittenPath::Temporary(temp_file) => temp_file.path(),
+            WrittenPath::Kept(path) => path,
+        }
+    }
+
+    /// Moves the temporary file to the target path, handling cross-device scenarios.
+    /// A kept file is linked or copied instead
     pub async fn persist_to(self, target_path: &std::path::Path) -> std::io::Result<()> {
+        let temp_file = match self.file {
+            WrittenPath::Temporary(temp_file) => temp_file,
+            WrittenPath::Kept(path) => {
+                if fs::hard_link(&path, target_path).await.is_err() {
+                    fs::copy(&path, target_path).await?;
+                }
+                return Ok(());
+            }
+        };
+
         // First, try the fast path (rename)
-        match self.temp_file.persist(target_path) {
+        match temp_file.persist(target_path) {
             Ok(_) => Ok(()),
             Err(tempfile::PersistError { error, file }) => {
                 // If persist failed due to a cross-device link, fall back to copy and delete
@@ -213,8 +299,58 @@ fn write_chunks_blocking(
     let hash = digest.finalize();
 
     Ok(WrittenFile {
-        temp_file,
+        file: WrittenPath::Temporary(temp_file),
         size: written_bytes,
         hash: crop_blake_3_hash(hash.as_bytes()),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ip() {
+        let proxy_config = ProxyConfig {
+            trusted_proxies: vec!["127.0.0.0/8".parse().unwrap()],
+            auth: None,
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "203.0.113.7, 198.51.100.2".parse().unwrap(),
+        );
+
+        let proxy: IpAddr = "127.0.0.1".parse().unwrap();
+        let private: IpAddr = "192.168.1.20".parse().unwrap();
+        let public: IpAddr = "198.51.100.1".parse().unwrap();
+
+        // The last hop is the one added by the trusted proxy
+        assert_eq!(
+            client_ip(Some(proxy), &headers, &proxy_config),
+            "198.51.100.2".parse().ok()
+        );
+
+        headers.insert("x-real-ip", "203.0.113.9".parse().unwrap());
+        assert_eq!(
+            client_ip(Some(proxy), &headers, &proxy_config),
+            "203.0.113.9".parse().ok()
+        );
+
+        // Headers sent directly by a client can't be trusted, even from the local network
+        assert_eq!(
+            client_ip(Some(public), &headers, &proxy_config),
+            Some(public)
+        );
+        assert_eq!(
+            client_ip(Some(private), &headers, &proxy_config),
+            Some(private)
+        );
+        assert_eq!(client_ip(None, &headers, &proxy_config), None);
+        assert_eq!(
+            client_ip(Some(proxy), &HeaderMap::new(), &proxy_config),
+            Some(proxy)
+        );
+    }
+}
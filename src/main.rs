@@ -52,7 +52,16 @@ async fn main() {
         .await
         .expect("Failed to run schema migration for authentication");
 
-    let app_state = AppState::new(read_pool, write_pool, storage_resolver);
+    let app_state = AppState::new(
+        read_pool,
+        write_pool,
+        storage_resolver,
+        vars.proxy,
+        vars.oidc,
+        vars.map,
+        vars.writeback,
+    )
+    .with_max_resumable_upload_size(vars.max_resumable_upload_size);
     let app_state = Box::leak(Box::new(app_state));
 
     session_store
@@ -70,8 +79,8 @@ async fn main() {
         vars.background_threads_count,
     );
 
-    let http_service =
-        http::router(app_state, session_store, vars.allowed_origins).into_make_service();
+    let http_service = http::router(app_state, session_store, vars.allowed_origins)
+        .into_make_service_with_connect_info::<SocketAddr>();
     let addr = SocketAddr::from(([0, 0, 0, 0], vars.server_port));
     let listener = TcpListener::bind(addr)
         .await
@@ -80,7 +89,10 @@ async fn main() {
     info!("Server listening on port {}", vars.server_port);
 
     axum::serve(listener, http_service)
-        .with_graceful_shutdown(http::shutdown_signal())
+        .with_graceful_shutdown(async {
+            http::shutdown_signal().await;
+            app_state.shutdown.cancel();
+        })
         .await
         .expect("Failed to start server")
 }
@@ -0,0 +1,20 @@
+use serde::Serialize;
+use time::OffsetDateTime;
+use time::serde::timestamp;
+
+/// One of the owner's folders, made visible to a list of other users
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct Album {
+    pub id: i64,
+    pub owner_id: String,
+    pub folder: String,
+    #[serde(with = "timestamp")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlbumInfo {
+    #[serde(flatten)]
+    pub album: Album,
+    pub members: Vec<String>,
+}
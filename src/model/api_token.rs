@@ -0,0 +1,101 @@
+use axum::http::Method;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use time::OffsetDateTime;
+
+pub const API_TOKEN_PREFIX: &str = "fp_";
+
+/// What a request authenticated with an API token is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ApiTokenScope {
+    /// Only GET requests, for backups
+    Read,
+    /// Only uploading new photos
+    Upload,
+    /// Everything except managing the account itself
+    Full,
+}
+
+impl ApiTokenScope {
+    pub const ALL: [ApiTokenScope; 3] = [Self::Read, Self::Upload, Self::Full];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenScope::Read => "read",
+            ApiTokenScope::Upload => "upload",
+            ApiTokenScope::Full => "full",
+        }
+    }
+
+    pub fn allows(&self, method: &Method, path: &str) -> bool {
+        // Credentials can only be managed from a real login session
+        if path.starts_with("/profile/") || path.starts_with("/admin") {
+            return false;
+        }
+
+        let is_read = matches!(*method, Method::GET | Method::HEAD);
+
+        match self {
+            ApiTokenScope::Read => is_read,
+            ApiTokenScope::Upload => {
+                (is_read && path == "/profile") || is_upload_path(method, path)
+            }
+            ApiTokenScope::Full => true,
+        }
+    }
+}
+
+fn is_upload_path(method: &Method, path: &str) -> bool {
+    // The resumable uploads use all the methods of the tus protocol
+    (*method == Method::POST
+        && (matches!(
+            path,
+            "/photos/upload" | "/photos/upload/batch" | "/photos/exists"
+        )))
+        || path == "/photos/uploads"
+        || path.starts_with("/photos/uploads/")
+}
+
+impl Display for ApiTokenScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ApiTokenScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("Unknown token scope: {s}"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub scope: ApiTokenScope,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+/// A new random token, shown to the user once
+pub fn generate_api_token() -> String {
+    format!(
+        "{API_TOKEN_PREFIX}{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// Tokens are long and random, so a fast hash is enough to protect them at rest
+pub fn hash_api_token(token: &str) -> String {
+    blake3::hash(token.as_bytes()).to_hex().to_string()
+}
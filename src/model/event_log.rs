@@ -11,3 +11,12 @@ pub struct EventLogs {
     pub event_log_id: i64,
     pub events: Vec<EventLog>,
 }
+
+/// An event along with its id, which clients resume from
+#[derive(Debug, PartialEq, Eq)]
+pub struct EventLogEntry {
+    pub event_id: i64,
+    pub photo_id: i64,
+    /// The photo as JSON, none when it was deleted
+    pub data: Option<Vec<u8>>,
+}
@@ -0,0 +1,38 @@
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use time::OffsetDateTime;
+
+/// Failed logins are counted separately for each client IP and for each user id,
+/// and the wrong passwords of share links for each share id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoginAttemptKind {
+    Ip,
+    User,
+    Share,
+}
+
+impl LoginAttemptKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginAttemptKind::Ip => "ip",
+            LoginAttemptKind::User => "user",
+            LoginAttemptKind::Share => "share",
+        }
+    }
+}
+
+impl Display for LoginAttemptKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct LoginAttempt {
+    pub kind: String,
+    pub key: String,
+    pub failures: i64,
+    pub last_failure_at: OffsetDateTime,
+    pub locked_until: Option<OffsetDateTime>,
+}
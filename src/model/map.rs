@@ -0,0 +1,107 @@
+use serde::Serialize;
+
+/// How many clusters can fit in the width of a map tile
+const CLUSTERS_PER_TILE: f64 = 4.0;
+
+/// A rectangle of coordinates, in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MapBounds {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl MapBounds {
+    /// Parses a `west,south,east,north` bounding box, the order used by OpenStreetMap.
+    /// Longitudes past the antimeridian, which Leaflet sends when the world wraps, widen the
+    /// box to the whole world
+    pub fn parse_bbox(bbox: &str) -> Option<Self> {
+        let values = bbox
+            .split(',')
+            .map(|value| value.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+            .collect::<Option<Vec<_>>>()?;
+        let [west, south, east, north] = values[..] else {
+            return None;
+        };
+
+        if south > north || west > east {
+            return None;
+        }
+
+        let (west, east) = if west < -180.0 || east > 180.0 {
+            (-180.0, 180.0)
+        } else {
+            (west, east)
+        };
+
+        Some(Self {
+            south: south.max(-90.0),
+            west,
+            north: north.min(90.0),
+            east,
+        })
+    }
+
+    /// The bounds of a cell of the clustering grid
+    pub fn from_cell(row: i64, col: i64, cell_size: f64) -> Self {
+        Self {
+            south: row as f64 * cell_size - 90.0,
+            west: col as f64 * cell_size - 180.0,
+            north: (row + 1) as f64 * cell_size - 90.0,
+            east: (col + 1) as f64 * cell_size - 180.0,
+        }
+    }
+}
+
+/// The size in degrees of the cells of the clustering grid, which is anchored to the
+/// south-west corner of the world so clusters don't move while panning
+pub fn cluster_cell_size(zoom: u8) -> f64 {
+    360.0 / (2f64.powi(zoom.min(22) as i32) * CLUSTERS_PER_TILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bbox() {
+        assert_eq!(
+            MapBounds::parse_bbox("2.25,48.8,2.42,48.9"),
+            Some(MapBounds {
+                south: 48.8,
+                west: 2.25,
+                north: 48.9,
+                east: 2.42,
+            })
+        );
+
+        // The world wraps
+        assert_eq!(
+            MapBounds::parse_bbox("-200,-95,150,95"),
+            Some(MapBounds {
+                south: -90.0,
+                west: -180.0,
+                north: 90.0,
+                east: 180.0,
+            })
+        );
+
+        assert_eq!(MapBounds::parse_bbox("2.25,48.8,2.42"), None);
+        assert_eq!(MapBounds::parse_bbox("2.25,48.9,2.42,48.8"), None);
+        assert_eq!(MapBounds::parse_bbox("a,b,c,d"), None);
+        assert_eq!(MapBounds::parse_bbox("NaN,0,1,1"), None);
+    }
+
+    #[test]
+    fn test_cluster_cells() {
+        assert_eq!(cluster_cell_size(0), 90.0);
+        assert_eq!(cluster_cell_size(2), 22.5);
+
+        let cell = MapBounds::from_cell(6, 8, 22.5);
+        assert_eq!(cell.south, 45.0);
+        assert_eq!(cell.west, 0.0);
+        assert_eq!(cell.north, 67.5);
+        assert_eq!(cell.east, 22.5);
+    }
+}
@@ -1,5 +1,18 @@
+pub mod album;
+pub mod api_token;
 pub mod event_log;
+pub mod login_attempt;
+pub mod map;
 pub mod photo;
 pub mod photo_category;
 pub mod photo_hash;
+pub mod photo_metadata;
+pub mod place;
+pub mod saved_search;
+pub mod search;
+pub mod share;
+pub mod tag;
+pub mod two_factor;
+pub mod upload;
 pub mod user;
+pub mod user_session;
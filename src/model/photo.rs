@@ -2,9 +2,14 @@ use serde::Serialize;
 use serde_with::serde_as;
 use time::OffsetDateTime;
 
+use crate::model::photo_metadata::PhotoMetadata;
 use crate::model::user::PUBLIC_USER_FOLDER;
+use std::ops::Deref;
 use time::serde::timestamp;
 
+pub const MAX_CAPTION_LENGTH: usize = 2000;
+pub const MAX_RATING: i64 = 5;
+
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
 pub struct Photo {
@@ -19,6 +24,9 @@ pub struct Photo {
     pub thumb_hash: Option<Vec<u8>>,
     #[serde(with = "timestamp::option")]
     pub trashed_on: Option<OffsetDateTime>,
+    pub caption: Option<String>,
+    /// From 1 to [`MAX_RATING`] stars, 0 when unrated
+    pub rating: i64,
 }
 
 impl Photo {
@@ -57,8 +65,28 @@ impl Photo {
     }
 }
 
+/// A photo as sent to the clients, with the details read from the file once they are known
+/// and its tags
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhotoWithMetadata {
+    #[serde(flatten)]
+    pub photo: Photo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<PhotoMetadata>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Deref for PhotoWithMetadata {
+    type Target = Photo;
+
+    fn deref(&self) -> &Self::Target {
+        &self.photo
+    }
+}
+
 #[derive(Serialize)]
 pub struct FullPhotosList {
     pub event_log_id: i64,
-    pub photos: Vec<Photo>,
+    pub photos: Vec<PhotoWithMetadata>,
 }
@@ -8,6 +8,8 @@ pub enum PhotoCategory {
     All,
     Personal,
     Family,
+    /// Photos from the albums of other users that the user is a member of
+    Shared,
 }
 
 impl fmt::Display for PhotoCategory {
@@ -16,6 +18,7 @@ impl fmt::Display for PhotoCategory {
             PhotoCategory::All => write!(f, "all"),
             PhotoCategory::Personal => write!(f, "personal"),
             PhotoCategory::Family => write!(f, "family"),
+            PhotoCategory::Shared => write!(f, "shared"),
         }
     }
 }
@@ -2,3 +2,12 @@ pub struct PhotoHash {
     pub id: i64,
     pub hash: Vec<u8>,
 }
+
+/// A photo visible to a user whose file has a known hash and size
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct ExistingHash {
+    pub photo_id: i64,
+    pub hash: Vec<u8>,
+    pub file_size: i64,
+    pub public: bool,
+}
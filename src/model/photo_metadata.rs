@@ -0,0 +1,141 @@
+use serde::Serialize;
+
+/// Details read from the EXIF data of a photo, every field is optional as cameras and
+/// editors only write some of them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, sqlx::FromRow)]
+pub struct PhotoMetadata {
+    #[serde(skip)]
+    pub photo_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_make: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lens_model: Option<String>,
+    /// In millimeters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focal_length: Option<f64>,
+    /// The f-number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aperture: Option<f64>,
+    /// Shutter speed, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exposure_time: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iso: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i64>,
+    /// The EXIF orientation, from 1 to 8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// Length of videos, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_codec: Option<String>,
+    /// In frames per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_rate: Option<f64>,
+}
+
+impl PhotoMetadata {
+    /// Whether no detail could be read from the file
+    pub fn is_empty(&self) -> bool {
+        *self
+            == Self {
+                photo_id: self.photo_id,
+                ..Default::default()
+            }
+    }
+
+    pub fn camera(&self) -> Option<String> {
+        match (&self.camera_make, &self.camera_model) {
+            // Most cameras repeat the make in the model name
+            (Some(make), Some(model)) if model.starts_with(make.as_str()) => Some(model.clone()),
+            (Some(make), Some(model)) => Some(format!("{make} {model}")),
+            (Some(make), None) => Some(make.clone()),
+            (None, Some(model)) => Some(model.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Formats the shutter speed like cameras do: 1/250 s or 2 s
+    pub fn exposure_time_formatted(&self) -> Option<String> {
+        let seconds = self.exposure_time.filter(|seconds| *seconds > 0.0)?;
+
+        if seconds < 1.0 {
+            Some(format!("1/{} s", (1.0 / seconds).round()))
+        } else {
+            Some(format!("{} s", (seconds * 10.0).round() / 10.0))
+        }
+    }
+
+    /// Formats the duration of videos like players do: 0:07 or 1:02:03
+    pub fn duration_formatted(&self) -> Option<String> {
+        self.duration.map(format_duration)
+    }
+
+    pub fn dimensions(&self) -> Option<String> {
+        Some(format!("{} × {}", self.width?, self.height?))
+    }
+
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        Some((self.latitude?, self.longitude?))
+    }
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting() {
+        let metadata = PhotoMetadata {
+            camera_make: Some("Canon".to_string()),
+            camera_model: Some("Canon EOS R6".to_string()),
+            exposure_time: Some(0.004),
+            width: Some(6000),
+            height: Some(4000),
+            ..Default::default()
+        };
+
+        assert_eq!(metadata.camera().as_deref(), Some("Canon EOS R6"));
+        assert_eq!(
+            metadata.exposure_time_formatted().as_deref(),
+            Some("1/250 s")
+        );
+        assert_eq!(metadata.dimensions().as_deref(), Some("6000 × 4000"));
+
+        let metadata = PhotoMetadata {
+            camera_make: Some("SONY".to_string()),
+            camera_model: Some("ILCE-7M3".to_string()),
+            exposure_time: Some(2.5),
+            ..Default::default()
+        };
+
+        assert_eq!(metadata.camera().as_deref(), Some("SONY ILCE-7M3"));
+        assert_eq!(metadata.exposure_time_formatted().as_deref(), Some("2.5 s"));
+        assert_eq!(metadata.dimensions(), None);
+
+        assert_eq!(format_duration(7.4), "0:07");
+        assert_eq!(format_duration(125.0), "2:05");
+        assert_eq!(format_duration(3723.0), "1:02:03");
+    }
+}
@@ -0,0 +1,143 @@
+use crate::model::map::MapBounds;
+use serde::Serialize;
+
+/// Photos further than this from every known place are left without one
+pub const MAX_PLACE_DISTANCE_KM: f64 = 50.0;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+const KM_PER_DEGREE: f64 = 111.2;
+
+/// A city from the GeoNames dataset
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct Place {
+    pub id: i64,
+    pub name: String,
+    /// The ISO-3166 alpha-2 code, like "FR"
+    pub country_code: String,
+    /// Only known if the country names were imported as well
+    pub country: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub population: i64,
+}
+
+impl Place {
+    pub fn country_name(&self) -> &str {
+        self.country.as_deref().unwrap_or(&self.country_code)
+    }
+
+    /// Like "Paris, France"
+    pub fn display_name(&self) -> String {
+        format!("{}, {}", self.name, self.country_name())
+    }
+}
+
+/// The place a photo was resolved to, [`None`] if no place is close enough
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhotoPlace {
+    pub photo_id: i64,
+    pub place_id: Option<i64>,
+}
+
+/// The great-circle distance between two coordinates
+pub fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// The box holding every place within [`MAX_PLACE_DISTANCE_KM`] of a coordinate.
+/// It doesn't cross the antimeridian, which only misses places on remote islands
+pub fn place_search_bounds(latitude: f64, longitude: f64) -> MapBounds {
+    let latitude_delta = MAX_PLACE_DISTANCE_KM / KM_PER_DEGREE;
+    // Degrees of longitude get shorter towards the poles
+    let narrowest = (latitude.abs() + latitude_delta)
+        .min(89.0)
+        .to_radians()
+        .cos();
+    let longitude_delta = (latitude_delta / narrowest).min(180.0);
+
+    MapBounds {
+        south: (latitude - latitude_delta).max(-90.0),
+        west: (longitude - longitude_delta).max(-180.0),
+        north: (latitude + latitude_delta).min(90.0),
+        east: (longitude + longitude_delta).min(180.0),
+    }
+}
+
+pub fn nearest_place(latitude: f64, longitude: f64, places: &[Place]) -> Option<&Place> {
+    places
+        .iter()
+        .map(|place| {
+            let distance = distance_km((latitude, longitude), (place.latitude, place.longitude));
+            (place, distance)
+        })
+        .filter(|(_, distance)| *distance <= MAX_PLACE_DISTANCE_KM)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(place, _)| place)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(id: i64, name: &str, latitude: f64, longitude: f64) -> Place {
+        Place {
+            id,
+            name: name.to_string(),
+            country_code: "FR".to_string(),
+            country: None,
+            latitude,
+            longitude,
+            population: 0,
+        }
+    }
+
+    #[test]
+    fn test_distance_km() {
+        let paris = (48.8566, 2.3522);
+        let london = (51.5074, -0.1278);
+
+        assert!((distance_km(paris, london) - 343.5).abs() < 1.0);
+        assert_eq!(distance_km(paris, paris), 0.0);
+    }
+
+    #[test]
+    fn test_nearest_place() {
+        let places = vec![
+            place(1, "Paris", 48.8566, 2.3522),
+            place(2, "Versailles", 48.8049, 2.1204),
+            place(3, "Lyon", 45.7640, 4.8357),
+        ];
+
+        // Next to the Eiffel Tower
+        let nearest = nearest_place(48.8582, 2.2945, &places).unwrap();
+        assert_eq!(nearest.name, "Paris");
+
+        let nearest = nearest_place(48.80, 2.13, &places).unwrap();
+        assert_eq!(nearest.name, "Versailles");
+
+        // In the middle of the Atlantic
+        assert_eq!(nearest_place(40.0, -30.0, &places), None);
+
+        assert_eq!(places[2].display_name(), "Lyon, FR");
+    }
+
+    #[test]
+    fn test_place_search_bounds() {
+        let bounds = place_search_bounds(48.8566, 2.3522);
+        let versailles = (48.8049, 2.1204);
+        assert!((bounds.south..=bounds.north).contains(&versailles.0));
+        assert!((bounds.west..=bounds.east).contains(&versailles.1));
+
+        // The box gets wider towards the poles, but never past the edges of the world
+        let arctic = place_search_bounds(89.9, 179.9);
+        assert_eq!(arctic.north, 90.0);
+        assert_eq!(arctic.east, 180.0);
+        assert!(arctic.east - arctic.west > bounds.east - bounds.west);
+    }
+}
@@ -0,0 +1,10 @@
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub user_id: String,
+    pub name: String,
+    pub query: String,
+    pub created_at: OffsetDateTime,
+}
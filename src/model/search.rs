@@ -0,0 +1,373 @@
+use crate::model::photo::MAX_RATING;
+use thiserror::Error;
+use time::{Date, Month, OffsetDateTime};
+
+/// The parts of a search like `beach camera:pixel after:2023-06 type:video`.
+/// The words that aren't filters are searched in the names, folders, places and captions
+#[derive(Debug, Default, PartialEq)]
+pub struct PhotoFilter {
+    /// An FTS5 query, see [`fts_query`]
+    pub text: Option<String>,
+    /// Inclusive
+    pub after: Option<OffsetDateTime>,
+    /// Exclusive
+    pub before: Option<OffsetDateTime>,
+    pub folder: Option<String>,
+    /// The photos must have all of them
+    pub tags: Vec<String>,
+    pub favorites_only: bool,
+    pub media_type: Option<MediaType>,
+    /// Lowercase, without the dot
+    pub extensions: Vec<String>,
+    /// Part of the camera make or model
+    pub camera: Option<String>,
+    /// In bytes
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    pub has_gps: Option<bool>,
+    /// At least this many stars
+    pub min_rating: Option<i64>,
+    /// Search the trash instead of the other photos
+    pub trashed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaType {
+    Image,
+    Video,
+}
+
+impl MediaType {
+    /// The extensions of the type, as there is no MIME type stored with the photos
+    pub fn extensions(&self) -> &'static [&'static str] {
+        let toplevel = match self {
+            MediaType::Image => "image",
+            MediaType::Video => "video",
+        };
+
+        mime_guess::get_extensions(toplevel, "*").unwrap_or_default()
+    }
+}
+
+#[derive(Debug, PartialEq, Error)]
+pub enum FilterError {
+    #[error("Unknown filter \"{0}:\"")]
+    UnknownFilter(String),
+    #[error("Invalid value for {0}: \"{1}\"")]
+    InvalidValue(&'static str, String),
+}
+
+impl PhotoFilter {
+    /// Parses a search, the values with spaces can be quoted like `folder:"Summer 2023"`
+    pub fn parse(input: &str) -> Result<Self, FilterError> {
+        let mut filter = PhotoFilter::default();
+        let mut words = Vec::new();
+
+        for token in tokenize(input) {
+            let (negated, token) = match token.strip_prefix('-') {
+                Some(rest) if rest.contains(':') => (true, rest.to_string()),
+                _ => (false, token),
+            };
+
+            let Some((key, value)) = token
+                .split_once(':')
+                .filter(|(key, _)| !key.is_empty() && key.chars().all(char::is_alphabetic))
+            else {
+                words.push(token);
+                continue;
+            };
+
+            let key = key.to_lowercase();
+            let value = value.trim();
+            let invalid = |name| FilterError::InvalidValue(name, value.to_string());
+
+            match (key.as_str(), negated) {
+                ("after", false) => {
+                    filter.after = Some(parse_period(value).ok_or(invalid("after"))?.0)
+                }
+                ("before", false) => {
+                    filter.before = Some(parse_period(value).ok_or(invalid("before"))?.0)
+                }
+                ("date", false) => {
+                    let (start, end) = parse_period(value).ok_or(invalid("date"))?;
+                    filter.after = Some(start);
+                    filter.before = Some(end);
+                }
+                ("folder", false) if !value.is_empty() => filter.folder = Some(value.to_string()),
+                ("camera", false) if !value.is_empty() => filter.camera = Some(value.to_string()),
+                ("tag", false) if !value.is_empty() => filter.tags.push(value.to_string()),
+                ("folder", false) => return Err(invalid("folder")),
+                ("tag", false) => return Err(invalid("tag")),
+                ("camera", false) => return Err(invalid("camera")),
+                ("type", false) => {
+                    filter.media_type = Some(match value.to_lowercase().as_str() {
+                        "image" | "images" | "photo" | "photos" => MediaType::Image,
+                        "video" | "videos" => MediaType::Video,
+                        _ => return Err(invalid("type")),
+                    })
+                }
+                ("ext", false) => {
+                    let extensions = value
+                        .split(',')
+                        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                        .filter(|ext| !ext.is_empty());
+                    filter.extensions.extend(extensions);
+                }
+                ("larger", false) => {
+                    filter.min_size = Some(parse_size(value).ok_or(invalid("larger"))?)
+                }
+                ("smaller", false) => {
+                    filter.max_size = Some(parse_size(value).ok_or(invalid("smaller"))?)
+                }
+                ("rating", false) => {
+                    let rating = value
+                        .trim_end_matches('+')
+                        .parse()
+                        .ok()
+                        .filter(|rating| (1..=MAX_RATING).contains(rating));
+                    filter.min_rating = Some(rating.ok_or(invalid("rating"))?);
+                }
+                ("is", false) => match value.to_lowercase().as_str() {
+                    "favorite" | "favorites" | "fav" => filter.favorites_only = true,
+                    "trashed" | "trash" => filter.trashed = true,
+                    _ => return Err(invalid("is")),
+                },
+                ("has", negated) => match value.to_lowercase().as_str() {
+                    "gps" | "location" => filter.has_gps = Some(!negated),
+                    _ => return Err(invalid("has")),
+                },
+                _ => {
+                    let key = if negated { format!("-{key}") } else { key };
+                    return Err(FilterError::UnknownFilter(key));
+                }
+            }
+        }
+
+        filter.text = fts_query(&words.join(" "));
+
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == PhotoFilter::default()
+    }
+}
+
+/// Splits on whitespace, except inside double quotes, which are removed
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+/// Parses a year, month or day like "2023", "2023-06" or "2023-06-15" into its start and end
+fn parse_period(value: &str) -> Option<(OffsetDateTime, OffsetDateTime)> {
+    let parts = value
+        .split('-')
+        .map(|part| part.parse::<u16>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let (start, end) = match parts[..] {
+        [year] => {
+            let start = Date::from_calendar_date(year as i32, Month::January, 1).ok()?;
+            (start, start.replace_year(year as i32 + 1).ok()?)
+        }
+        [year, month] => {
+            let month = Month::try_from(u8::try_from(month).ok()?).ok()?;
+            let start = Date::from_calendar_date(year as i32, month, 1).ok()?;
+            let end_year = if month == Month::December {
+                year + 1
+            } else {
+                year
+            };
+            (
+                start,
+                Date::from_calendar_date(end_year as i32, month.next(), 1).ok()?,
+            )
+        }
+        [year, month, day] => {
+            let month = Month::try_from(u8::try_from(month).ok()?).ok()?;
+            let start =
+                Date::from_calendar_date(year as i32, month, u8::try_from(day).ok()?).ok()?;
+            (start, start.next_day()?)
+        }
+        _ => return None,
+    };
+
+    Some((start.midnight().assume_utc(), end.midnight().assume_utc()))
+}
+
+/// Parses a size like "500kb", "1.5 GB" or "2048" (bytes)
+fn parse_size(value: &str) -> Option<i64> {
+    let value = value.trim().to_lowercase();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier = match unit.trim() {
+        "" | "b" => 1,
+        "k" | "kb" => 1024,
+        "m" | "mb" => 1024 * 1024,
+        "g" | "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+
+    let number: f64 = number.parse().ok()?;
+    (number.is_finite() && number >= 0.0).then_some((number * multiplier as f64) as i64)
+}
+
+/// Turns the words typed by a user into an FTS5 query matching the photos containing all of
+/// them, each as a prefix. The words are quoted, so the FTS5 syntax can't be used by mistake.
+/// Returns [`None`] if there is nothing to search for
+pub fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{word}\"*"))
+        .collect();
+
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("paris"), Some("\"paris\"*".to_string()));
+        assert_eq!(
+            fts_query("  Summer   IMG_2024 "),
+            Some("\"Summer\"* \"IMG_2024\"*".to_string())
+        );
+        assert_eq!(
+            fts_query("\"beach\" OR NEAR("),
+            Some("\"beach\"* \"OR\"* \"NEAR(\"*".to_string())
+        );
+
+        assert_eq!(fts_query(""), None);
+        assert_eq!(fts_query(" - \"\" "), None);
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let filter = PhotoFilter::parse(
+            "beach camera:pixel after:2023-06 type:video folder:\"Summer 2023\" tag:Grandma tag:\"Vacation 2023\"",
+        )
+        .unwrap();
+        assert_eq!(
+            filter,
+            PhotoFilter {
+                text: Some("\"beach\"*".to_string()),
+                after: Some(datetime!(2023-06-01 00:00 UTC)),
+                folder: Some("Summer 2023".to_string()),
+                tags: vec!["Grandma".to_string(), "Vacation 2023".to_string()],
+                media_type: Some(MediaType::Video),
+                camera: Some("pixel".to_string()),
+                ..Default::default()
+            }
+        );
+
+        let filter = PhotoFilter::parse(
+            "date:2024-12 is:favorite ext:.JPG,heic larger:1.5mb smaller:1g -has:gps is:trashed rating:4+",
+        )
+        .unwrap();
+        assert_eq!(
+            filter,
+            PhotoFilter {
+                after: Some(datetime!(2024-12-01 00:00 UTC)),
+                before: Some(datetime!(2025-01-01 00:00 UTC)),
+                favorites_only: true,
+                extensions: vec!["jpg".to_string(), "heic".to_string()],
+                min_size: Some(1572864),
+                max_size: Some(1073741824),
+                has_gps: Some(false),
+                min_rating: Some(4),
+                trashed: true,
+                ..Default::default()
+            }
+        );
+
+        // Colons in words that aren't filters are searched as they are
+        let filter = PhotoFilter::parse("12:30").unwrap();
+        assert_eq!(filter.text, Some("\"12:30\"*".to_string()));
+
+        assert!(PhotoFilter::parse("  ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_filter_errors() {
+        assert_eq!(
+            PhotoFilter::parse("color:red"),
+            Err(FilterError::UnknownFilter("color".to_string()))
+        );
+        assert_eq!(
+            PhotoFilter::parse("-type:video"),
+            Err(FilterError::UnknownFilter("-type".to_string()))
+        );
+        assert_eq!(
+            PhotoFilter::parse("after:2023-13"),
+            Err(FilterError::InvalidValue("after", "2023-13".to_string()))
+        );
+        assert_eq!(
+            PhotoFilter::parse("type:audio"),
+            Err(FilterError::InvalidValue("type", "audio".to_string()))
+        );
+        assert_eq!(
+            PhotoFilter::parse("larger:big"),
+            Err(FilterError::InvalidValue("larger", "big".to_string()))
+        );
+        assert_eq!(
+            PhotoFilter::parse("rating:6"),
+            Err(FilterError::InvalidValue("rating", "6".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(
+            parse_period("2023"),
+            Some((
+                datetime!(2023-01-01 00:00 UTC),
+                datetime!(2024-01-01 00:00 UTC)
+            ))
+        );
+        assert_eq!(
+            parse_period("2024-02-29"),
+            Some((
+                datetime!(2024-02-29 00:00 UTC),
+                datetime!(2024-03-01 00:00 UTC)
+            ))
+        );
+        assert_eq!(parse_period("2023-02-30"), None);
+        assert_eq!(parse_period("june"), None);
+    }
+
+    #[test]
+    fn test_media_type_extensions() {
+        assert!(MediaType::Video.extensions().contains(&"mp4"));
+        assert!(MediaType::Image.extensions().contains(&"jpg"));
+        assert!(!MediaType::Image.extensions().contains(&"mov"));
+    }
+}
@@ -0,0 +1,64 @@
+use serde::Serialize;
+use time::OffsetDateTime;
+use time::serde::timestamp;
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Share {
+    pub id: i64,
+    pub token: String,
+    pub user_id: String,
+    pub folder: Option<String>,
+    pub folder_is_public: bool,
+    pub password_hash: Option<String>,
+    pub allow_downloads: bool,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+}
+
+impl Share {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= OffsetDateTime::now_utc()
+    }
+
+    pub fn url_path(&self) -> String {
+        format!("/s/{}", self.token)
+    }
+}
+
+/// A share as presented to its owner, without the password hash
+#[derive(Debug, Serialize)]
+pub struct ShareInfo {
+    pub id: i64,
+    pub token: String,
+    pub url: String,
+    pub folder: Option<String>,
+    pub folder_is_public: bool,
+    pub photo_count: i64,
+    pub has_password: bool,
+    pub allow_downloads: bool,
+    #[serde(with = "timestamp")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "timestamp")]
+    pub expires_at: OffsetDateTime,
+}
+
+impl ShareInfo {
+    pub fn new(share: Share, photo_count: i64) -> Self {
+        Self {
+            url: share.url_path(),
+            id: share.id,
+            token: share.token,
+            folder: share.folder,
+            folder_is_public: share.folder_is_public,
+            photo_count,
+            has_password: share.password_hash.is_some(),
+            allow_downloads: share.allow_downloads,
+            created_at: share.created_at,
+            expires_at: share.expires_at,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= OffsetDateTime::now_utc()
+    }
+}
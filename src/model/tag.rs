@@ -0,0 +1,42 @@
+use serde::Serialize;
+use time::OffsetDateTime;
+
+pub const MAX_TAG_LENGTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    #[serde(skip)]
+    pub created_at: OffsetDateTime,
+}
+
+/// The name of a tag of a photo, to load the tags of many photos at once
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct PhotoTag {
+    pub photo_id: i64,
+    pub name: String,
+}
+
+/// Trims and collapses the whitespace of a tag name, returns [`None`] if it can't be used
+pub fn normalize_tag_name(name: &str) -> Option<String> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    (!name.is_empty() && name.chars().count() <= MAX_TAG_LENGTH).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tag_name() {
+        assert_eq!(
+            normalize_tag_name("  Vacation \t 2023 "),
+            Some("Vacation 2023".to_string())
+        );
+        assert_eq!(normalize_tag_name("Grandma"), Some("Grandma".to_string()));
+        assert_eq!(normalize_tag_name("   "), None);
+        assert_eq!(normalize_tag_name(&"a".repeat(MAX_TAG_LENGTH + 1)), None);
+    }
+}
@@ -0,0 +1,13 @@
+use time::OffsetDateTime;
+
+/// The TOTP secret of a user. It is only checked at login once the user confirmed it with a code
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct UserTotp {
+    pub user_id: String,
+    /// Base32 encoded, as shown to authenticator apps
+    pub secret: String,
+    pub enabled: bool,
+    pub created_at: OffsetDateTime,
+    /// The time step of the last accepted code
+    pub last_used_step: Option<i64>,
+}
@@ -0,0 +1,27 @@
+use time::OffsetDateTime;
+
+/// A resumable upload, whose data is appended to a file of the uploads folder until it's complete
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Upload {
+    pub id: String,
+    pub user_id: String,
+    /// The size of the whole file, in bytes
+    pub length: i64,
+    /// The `Upload-Metadata` header it was created with, given back to the clients
+    pub metadata: String,
+    pub file_name: String,
+    /// Read from the file once it's complete if not given
+    pub time_created: Option<OffsetDateTime>,
+    pub last_modified: Option<OffsetDateTime>,
+    pub folder: Option<String>,
+    pub make_public: bool,
+    /// The photo created once the upload is complete
+    pub photo_id: Option<i64>,
+    pub expires_at: OffsetDateTime,
+}
+
+impl Upload {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= OffsetDateTime::now_utc()
+    }
+}
@@ -6,12 +6,14 @@ pub struct User {
     pub id: String,
     pub name: String,
     pub password_hash: String,
+    pub is_admin: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct SimpleUser {
     pub user_id: String,
     pub display_name: String,
+    pub is_admin: bool,
 }
 
 impl From<User> for SimpleUser {
@@ -19,6 +21,7 @@ impl From<User> for SimpleUser {
         Self {
             user_id: value.id,
             display_name: value.name,
+            is_admin: value.is_admin,
         }
     }
 }
@@ -29,6 +32,19 @@ pub struct UserCredentials {
     pub password: String,
 }
 
+/// The ways a user can be authenticated by the [UsersRepository](crate::repo::users_repo::UsersRepository)
+#[derive(Clone)]
+pub enum Credentials {
+    Password(UserCredentials),
+    /// The user was already authenticated by a trusted reverse proxy or identity provider
+    External {
+        user_id: String,
+        /// Only used when the user has to be created
+        name: Option<String>,
+        create_if_missing: bool,
+    },
+}
+
 impl AuthUser for User {
     type Id = String;
 
@@ -42,3 +58,13 @@ impl AuthUser for User {
 }
 
 pub const PUBLIC_USER_FOLDER: &str = "public";
+
+/// The user id is also the name of the user's folder on disk
+pub fn is_valid_user_id(user_id: &str) -> bool {
+    !user_id.is_empty()
+        && user_id.len() <= 64
+        && user_id != PUBLIC_USER_FOLDER
+        && user_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
@@ -1,5 +1,5 @@
 use std::fs;
-use std::io::{self, Read as _};
+use std::io;
 use std::path::Path;
 use std::process::{Command, Stdio};
 use std::time::Duration;
@@ -8,23 +8,13 @@ use mime_guess::MimeGuess;
 use tracing::warn;
 use wait_timeout::ChildExt;
 
+use crate::utils::read_stderr;
+
 pub const THUMB_HASH_IMAGE_SIZE: usize = 64;
 
 const GENERATION_TIMEOUT: Duration = Duration::from_secs(15);
 pub const MIN_PREVIEW_SIZE: u64 = 100;
 
-fn read_stderr(child: &mut std::process::Child) -> String {
-    child
-        .stderr
-        .take()
-        .map(|mut s| {
-            let mut buf = String::new();
-            s.read_to_string(&mut buf).ok();
-            buf.trim().to_owned()
-        })
-        .unwrap_or_default()
-}
-
 const PREVIEW_SIZE: u32 = 320;
 const VIDEO_SCALE_FILTER: &str = "scale='if(gt(iw,ih),-1,320)':'if(gt(iw,ih),320,-1)'";
 
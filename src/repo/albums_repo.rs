@@ -0,0 +1,415 @@
+use crate::model::album::Album;
+use crate::model::photo::Photo;
+use crate::repo::event_log::EventLogRepo;
+use crate::repo::{PhotoMetadataRepo, TagsRepo};
+use sqlx::{SqliteExecutor, SqliteTransaction, query, query_as, query_scalar};
+
+/// Keeps the bound parameters of the event queries under the SQLite limit
+const EVENTS_CHUNK_SIZE: usize = 1000;
+
+pub trait AlbumsRepo<'c>: SqliteExecutor<'c> {
+    async fn get_album(self, album_id: i64) -> sqlx::Result<Option<Album>> {
+        query_as!(Album, "select * from albums where id = $1", album_id)
+            .fetch_optional(self)
+            .await
+    }
+
+    async fn get_album_by_folder(
+        self,
+        owner_id: &str,
+        folder: &str,
+    ) -> sqlx::Result<Option<Album>> {
+        query_as!(
+            Album,
+            "select * from albums where owner_id = $1 and folder = $2",
+            owner_id,
+            folder
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    /// Albums owned by the user and albums shared with them
+    async fn get_albums_for_user(self, user_id: &str) -> sqlx::Result<Vec<Album>> {
+        query_as!(
+            Album,
+            "select * from albums
+             where owner_id = $1
+                or id in (select album_id from album_members where user_id = $1)
+             order by owner_id = $1 desc, folder",
+            user_id
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn get_album_members(self, album_id: i64) -> sqlx::Result<Vec<String>> {
+        query_scalar!(
+            "select user_id from album_members where album_id = $1 order by user_id",
+            album_id
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn insert_album(self, owner_id: &str, folder: &str) -> sqlx::Result<Album> {
+        query_as!(
+            Album,
+            "insert into albums (owner_id, folder) values ($1, $2) returning *",
+            owner_id,
+            folder
+        )
+        .fetch_one(self)
+        .await
+    }
+
+    /// All the photos of the album, including the trashed ones like the full sync
+    async fn get_album_photos(self, album: &Album) -> sqlx::Result<Vec<Photo>> {
+        query_as!(
+            Photo,
+            "select * from photos where user_id = $1 and folder = $2",
+            album.owner_id,
+            album.folder
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn get_album_photo_ids(self, album: &Album) -> sqlx::Result<Vec<i64>> {
+        query_scalar!(
+            "select id from photos where user_id = $1 and folder = $2",
+            album.owner_id,
+            album.folder
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    /// Returns false if the user was already a member
+    async fn insert_album_member(self, album_id: i64, user_id: &str) -> sqlx::Result<bool> {
+        query!(
+            "insert or ignore into album_members (album_id, user_id) values ($1, $2)",
+            album_id,
+            user_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    async fn delete_album_member(self, album_id: i64, user_id: &str) -> sqlx::Result<bool> {
+        query!(
+            "delete from album_members where album_id = $1 and user_id = $2",
+            album_id,
+            user_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    /// Only the owner can delete an album, the photos themselves are left untouched
+    async fn delete_album(self, album_id: i64, owner_id: &str) -> sqlx::Result<bool> {
+        query!(
+            "delete from albums where id = $1 and owner_id = $2",
+            album_id,
+            owner_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+}
+
+impl<'c, E> AlbumsRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+/// Changing who can see an album also lets the synced clients of the members know
+pub trait AlbumsTransactionRepo<'c> {
+    /// Returns false if the user was already a member.
+    /// Otherwise they receive creation events for the photos of the album
+    async fn add_album_member(&mut self, album: &Album, user_id: &str) -> sqlx::Result<bool>;
+    /// The member receives deletion events for the photos of the album
+    async fn remove_album_member(&mut self, album: &Album, user_id: &str) -> sqlx::Result<bool>;
+    /// Only the owner can delete an album, the photos themselves are left untouched.
+    /// The members receive deletion events for its photos
+    async fn remove_album(&mut self, album_id: i64, owner_id: &str) -> sqlx::Result<bool>;
+}
+
+impl<'c> AlbumsTransactionRepo<'c> for SqliteTransaction<'c> {
+    async fn add_album_member(&mut self, album: &Album, user_id: &str) -> sqlx::Result<bool> {
+        if !self.as_mut().insert_album_member(album.id, user_id).await? {
+            return Ok(false);
+        }
+
+        let photos = self.as_mut().get_album_photos(album).await?;
+        for photos in photos.chunks(EVENTS_CHUNK_SIZE) {
+            let ids: Vec<i64> = photos.iter().map(|photo| photo.id).collect();
+            let metadata = self.as_mut().get_photos_metadata(&ids).await?;
+            let tags = self.as_mut().get_photos_tags(&ids).await?;
+
+            self.as_mut()
+                .insert_member_creation_event_logs(user_id, photos, &metadata, &tags)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    async fn remove_album_member(&mut self, album: &Album, user_id: &str) -> sqlx::Result<bool> {
+        if !self.as_mut().delete_album_member(album.id, user_id).await? {
+            return Ok(false);
+        }
+
+        let photo_ids = self.as_mut().get_album_photo_ids(album).await?;
+        for photo_ids in photo_ids.chunks(EVENTS_CHUNK_SIZE) {
+            self.as_mut()
+                .insert_member_deletion_event_logs(user_id, photo_ids)
+                .await?;
+        }
+
+        Ok(true)
+    }
+
+    async fn remove_album(&mut self, album_id: i64, owner_id: &str) -> sqlx::Result<bool> {
+        let Some(album) = self
+            .as_mut()
+            .get_album(album_id)
+            .await?
+            .filter(|album| album.owner_id == owner_id)
+        else {
+            return Ok(false);
+        };
+
+        let members = self.as_mut().get_album_members(album.id).await?;
+        let photo_ids = self.as_mut().get_album_photo_ids(&album).await?;
+
+        if !self.as_mut().delete_album(album.id, owner_id).await? {
+            return Ok(false);
+        }
+
+        for member_id in &members {
+            for photo_ids in photo_ids.chunks(EVENTS_CHUNK_SIZE) {
+                self.as_mut()
+                    .insert_member_deletion_event_logs(member_id, photo_ids)
+                    .await?;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::PhotosTransactionRepo;
+    use crate::repo::tests::{create_test_photo, create_test_user, insert_test_user};
+    use sqlx::SqlitePool;
+
+    async fn get_event_photo_ids(
+        pool: &SqlitePool,
+        after_event_id: i64,
+        user_id: &str,
+    ) -> sqlx::Result<Vec<(i64, bool)>> {
+        let (_, max_event_id) = pool.get_event_id_range().await?.unwrap();
+        let events = pool
+            .get_user_events(after_event_id, max_event_id, user_id)
+            .await?;
+
+        Ok(events
+            .into_iter()
+            .map(|event| (event.photo_id, event.data.is_some()))
+            .collect())
+    }
+
+    #[sqlx::test]
+    async fn test_album_membership(pool: SqlitePool) -> sqlx::Result<()> {
+        for id in ["owner", "member", "other"] {
+            insert_test_user(&pool, &create_test_user(id, id)).await?;
+        }
+
+        let album = pool.insert_album("owner", "Parents").await?;
+        assert_eq!(
+            pool.get_album_by_folder("owner", "Parents").await?,
+            Some(album.clone())
+        );
+
+        assert!(pool.insert_album_member(album.id, "member").await?);
+        // Adding the same member twice is a no-op
+        assert!(!pool.insert_album_member(album.id, "member").await?);
+        assert_eq!(pool.get_album_members(album.id).await?, vec!["member"]);
+
+        assert_eq!(pool.get_albums_for_user("owner").await?.len(), 1);
+        assert_eq!(pool.get_albums_for_user("member").await?.len(), 1);
+        assert!(pool.get_albums_for_user("other").await?.is_empty());
+
+        assert!(pool.delete_album_member(album.id, "member").await?);
+        assert!(!pool.delete_album_member(album.id, "member").await?);
+        assert!(pool.get_albums_for_user("member").await?.is_empty());
+
+        // Only the owner can delete it
+        assert!(!pool.delete_album(album.id, "member").await?);
+        assert!(pool.delete_album(album.id, "owner").await?);
+        assert!(pool.get_album(album.id).await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_album_member_events(pool: SqlitePool) -> sqlx::Result<()> {
+        for id in ["owner", "member", "other"] {
+            insert_test_user(&pool, &create_test_user(id, id)).await?;
+        }
+
+        let mut tx = pool.begin().await?;
+        let photo = tx
+            .insert_photo(&create_test_photo(
+                0,
+                Some("owner"),
+                Some("Parents"),
+                "a.jpg",
+            ))
+            .await?;
+        tx.insert_photo(&create_test_photo(0, Some("owner"), None, "b.jpg"))
+            .await?;
+        tx.commit().await?;
+
+        let album = pool.insert_album("owner", "Parents").await?;
+        let (_, last_event_id) = pool.get_event_id_range().await?.unwrap();
+
+        // The new member receives the photos of the album that already exist
+        let mut tx = pool.begin().await?;
+        assert!(tx.add_album_member(&album, "member").await?);
+        assert!(!tx.add_album_member(&album, "member").await?);
+        tx.commit().await?;
+
+        assert_eq!(
+            get_event_photo_ids(&pool, last_event_id, "member").await?,
+            vec![(photo.id, true)]
+        );
+        assert!(
+            get_event_photo_ids(&pool, last_event_id, "other")
+                .await?
+                .is_empty()
+        );
+        assert!(
+            get_event_photo_ids(&pool, last_event_id, "owner")
+                .await?
+                .is_empty()
+        );
+
+        let (_, last_event_id) = pool.get_event_id_range().await?.unwrap();
+        let mut tx = pool.begin().await?;
+        assert!(tx.remove_album_member(&album, "member").await?);
+        assert!(!tx.remove_album_member(&album, "member").await?);
+        tx.commit().await?;
+
+        assert_eq!(
+            get_event_photo_ids(&pool, last_event_id, "member").await?,
+            vec![(photo.id, false)]
+        );
+        assert!(
+            get_event_photo_ids(&pool, last_event_id, "other")
+                .await?
+                .is_empty()
+        );
+
+        // Deleting the album removes its photos from all the members
+        let mut tx = pool.begin().await?;
+        tx.add_album_member(&album, "member").await?;
+        tx.commit().await?;
+        let (_, last_event_id) = pool.get_event_id_range().await?.unwrap();
+
+        let mut tx = pool.begin().await?;
+        assert!(!tx.remove_album(album.id, "member").await?);
+        assert!(tx.remove_album(album.id, "owner").await?);
+        tx.commit().await?;
+
+        assert_eq!(
+            get_event_photo_ids(&pool, last_event_id, "member").await?,
+            vec![(photo.id, false)]
+        );
+        assert!(pool.get_album(album.id).await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_album_deletion_events(pool: SqlitePool) -> sqlx::Result<()> {
+        for id in ["owner", "member"] {
+            insert_test_user(&pool, &create_test_user(id, id)).await?;
+        }
+
+        let mut tx = pool.begin().await?;
+        let shared = tx
+            .insert_photo(&create_test_photo(
+                0,
+                Some("owner"),
+                Some("Parents"),
+                "a.jpg",
+            ))
+            .await?;
+        let private = tx
+            .insert_photo(&create_test_photo(0, Some("owner"), None, "b.jpg"))
+            .await?;
+        tx.commit().await?;
+
+        let album = pool.insert_album("owner", "Parents").await?;
+        let mut tx = pool.begin().await?;
+        tx.add_album_member(&album, "member").await?;
+        tx.commit().await?;
+        let (_, last_event_id) = pool.get_event_id_range().await?.unwrap();
+
+        // The deletion of a photo outside the album is not sent to the member
+        let mut tx = pool.begin().await?;
+        tx.delete_photo(&private).await?;
+        tx.commit().await?;
+
+        assert!(
+            get_event_photo_ids(&pool, last_event_id, "member")
+                .await?
+                .is_empty()
+        );
+        assert_eq!(
+            get_event_photo_ids(&pool, last_event_id, "owner").await?,
+            vec![(private.id, false)]
+        );
+
+        // Moving a photo out of the album removes it from the member
+        let (_, last_event_id) = pool.get_event_id_range().await?.unwrap();
+        let mut tx = pool.begin().await?;
+        tx.update_photo(&Photo {
+            folder: Some("Private".to_string()),
+            ..shared.clone()
+        })
+        .await?;
+        tx.commit().await?;
+
+        assert_eq!(
+            get_event_photo_ids(&pool, last_event_id, "member").await?,
+            vec![(shared.id, false)]
+        );
+        assert_eq!(
+            get_event_photo_ids(&pool, last_event_id, "owner").await?,
+            vec![(shared.id, false), (shared.id, true)]
+        );
+
+        // Updating a photo in place doesn't log a deletion
+        let (_, last_event_id) = pool.get_event_id_range().await?.unwrap();
+        let mut tx = pool.begin().await?;
+        tx.update_photo(&Photo {
+            folder: Some("Private".to_string()),
+            caption: Some("Caption".to_string()),
+            ..shared.clone()
+        })
+        .await?;
+        tx.commit().await?;
+
+        assert_eq!(
+            get_event_photo_ids(&pool, last_event_id, "owner").await?,
+            vec![(shared.id, true)]
+        );
+
+        Ok(())
+    }
+}
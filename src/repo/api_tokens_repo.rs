@@ -0,0 +1,140 @@
+use crate::model::api_token::{ApiToken, ApiTokenScope};
+use sqlx::{SqliteExecutor, query, query_as};
+use time::{Duration, OffsetDateTime};
+
+/// `last_used_at` is only updated once per interval to avoid a write on every request
+const LAST_USED_PRECISION: Duration = Duration::minutes(1);
+
+pub trait ApiTokensRepo<'c>: SqliteExecutor<'c> {
+    async fn get_api_token_by_hash(self, token_hash: &str) -> sqlx::Result<Option<ApiToken>> {
+        query_as!(
+            ApiToken,
+            r#"select id, user_id, name, token_hash, scope as "scope: ApiTokenScope",
+                      created_at, last_used_at
+               from api_tokens where token_hash = $1"#,
+            token_hash
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    /// The tokens of a user, or of everyone if no user is given
+    async fn get_api_tokens(self, user_id: Option<&str>) -> sqlx::Result<Vec<ApiToken>> {
+        query_as!(
+            ApiToken,
+            r#"select id, user_id, name, token_hash, scope as "scope: ApiTokenScope",
+                      created_at, last_used_at
+               from api_tokens
+               where $1 is null or user_id = $1
+               order by user_id, created_at desc, id desc"#,
+            user_id
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn insert_api_token(
+        self,
+        user_id: &str,
+        name: &str,
+        token_hash: &str,
+        scope: ApiTokenScope,
+    ) -> sqlx::Result<ApiToken> {
+        query_as!(
+            ApiToken,
+            r#"insert into api_tokens (user_id, name, token_hash, scope) values ($1, $2, $3, $4)
+               returning id, user_id, name, token_hash, scope as "scope: ApiTokenScope",
+                         created_at, last_used_at"#,
+            user_id,
+            name,
+            token_hash,
+            scope
+        )
+        .fetch_one(self)
+        .await
+    }
+
+    async fn update_api_token_last_used(
+        self,
+        token_id: i64,
+        now: OffsetDateTime,
+    ) -> sqlx::Result<()> {
+        let updated_before = now - LAST_USED_PRECISION;
+
+        query!(
+            "update api_tokens set last_used_at = $2
+             where id = $1 and (last_used_at is null or last_used_at < $3)",
+            token_id,
+            now,
+            updated_before
+        )
+        .execute(self)
+        .await
+        .map(|_| ())
+    }
+
+    /// Returns true if the token existed and belonged to the user, if one is given
+    async fn delete_api_token(self, token_id: i64, user_id: Option<&str>) -> sqlx::Result<bool> {
+        query!(
+            "delete from api_tokens where id = $1 and ($2 is null or user_id = $2)",
+            token_id,
+            user_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+}
+
+impl<'c, E> ApiTokensRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::{create_test_user, insert_test_user};
+    use sqlx::SqlitePool;
+
+    #[sqlx::test]
+    async fn test_api_token_lifecycle(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "Test User")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "Other User")).await?;
+
+        let token = pool
+            .insert_api_token("user1", "Backups", "hash1", ApiTokenScope::Read)
+            .await?;
+        assert_eq!(token.scope, ApiTokenScope::Read);
+        assert!(token.last_used_at.is_none());
+
+        let fetched = pool.get_api_token_by_hash("hash1").await?;
+        assert_eq!(fetched, Some(token.clone()));
+        assert!(pool.get_api_token_by_hash("hash2").await?.is_none());
+
+        let now = OffsetDateTime::now_utc();
+        pool.update_api_token_last_used(token.id, now).await?;
+        let last_used_at = pool
+            .get_api_token_by_hash("hash1")
+            .await?
+            .and_then(|token| token.last_used_at);
+        assert_eq!(last_used_at, Some(now));
+
+        // Requests close to each other don't update the timestamp
+        pool.update_api_token_last_used(token.id, now + Duration::seconds(5))
+            .await?;
+        let last_used_at = pool
+            .get_api_token_by_hash("hash1")
+            .await?
+            .and_then(|token| token.last_used_at);
+        assert_eq!(last_used_at, Some(now));
+
+        assert_eq!(pool.get_api_tokens(Some("user1")).await?.len(), 1);
+        assert!(pool.get_api_tokens(Some("user2")).await?.is_empty());
+        assert_eq!(pool.get_api_tokens(None).await?.len(), 1);
+
+        // Users can only revoke their own tokens
+        assert!(!pool.delete_api_token(token.id, Some("user2")).await?);
+        assert!(pool.delete_api_token(token.id, Some("user1")).await?);
+        assert!(pool.get_api_token_by_hash("hash1").await?.is_none());
+
+        Ok(())
+    }
+}
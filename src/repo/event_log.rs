@@ -1,5 +1,9 @@
+use crate::model::event_log::EventLogEntry;
 use crate::model::photo::Photo;
-use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, query};
+use crate::model::photo_metadata::PhotoMetadata;
+use crate::model::tag::PhotoTag;
+use serde::Serialize;
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, query, query_as};
 
 pub trait EventLogRepo<'c>: SqliteExecutor<'c> {
     async fn insert_event_log(
@@ -7,16 +11,20 @@ pub trait EventLogRepo<'c>: SqliteExecutor<'c> {
         photo_id: i64,
         user_id: Option<&str>,
         photo: Option<&Photo>,
+        metadata: Option<&PhotoMetadata>,
+        tags: &[PhotoTag],
     ) -> sqlx::Result<()> {
         let serialized_data = match photo {
-            Some(photo) => Some(photo_to_json_bytes(photo)?),
+            Some(photo) => Some(photo_to_json_bytes(photo, metadata, tags)?),
             None => None,
         };
+        let folder = photo.and_then(|photo| photo.folder.as_deref());
 
         query!(
-            "insert into photos_event_log (photo_id, user_id, data) values ($1, $2, $3)",
+            "insert into photos_event_log (photo_id, user_id, folder, data) values ($1, $2, $3, $4)",
             photo_id,
             user_id,
+            folder,
             serialized_data
         )
         .execute(self)
@@ -24,33 +32,61 @@ pub trait EventLogRepo<'c>: SqliteExecutor<'c> {
         .map(|_| ())
     }
 
-    async fn insert_creation_event_logs(self, photos: &[Photo]) -> sqlx::Result<()> {
-        if photos.is_empty() {
+    /// `metadata` and `tags` hold the known metadata and tags of any of the photos
+    async fn insert_creation_event_logs(
+        self,
+        photos: &[Photo],
+        metadata: &[PhotoMetadata],
+        tags: &[PhotoTag],
+    ) -> sqlx::Result<()> {
+        insert_creation_events(self, None, photos, metadata, tags).await
+    }
+
+    /// Creation events that only the member receives, when an album is shared with them
+    async fn insert_member_creation_event_logs(
+        self,
+        member_id: &str,
+        photos: &[Photo],
+        metadata: &[PhotoMetadata],
+        tags: &[PhotoTag],
+    ) -> sqlx::Result<()> {
+        insert_creation_events(self, Some(member_id), photos, metadata, tags).await
+    }
+
+    /// Must be called before the photos are deleted or moved, as the events keep their current
+    /// owner and folder, so only the users who could see the photos receive them
+    async fn insert_deletion_event_logs(self, photo_ids: &[i64]) -> sqlx::Result<()> {
+        if photo_ids.is_empty() {
             // An empty vector would cause a SQL syntax error
             return Ok(());
         }
 
-        let photos = photos
-            .iter()
-            .map(|photo| photo_to_json_bytes(photo).map(|data| (photo, data)))
-            .collect::<sqlx::Result<Vec<_>>>()?;
+        let mut query_builder = QueryBuilder::<Sqlite>::new(
+            "insert into photos_event_log (photo_id, user_id, folder) select id, user_id, folder from photos where id in (",
+        );
+        let mut separated = query_builder.separated(", ");
+        for photo_id in photo_ids {
+            separated.push_bind(photo_id);
+        }
+        separated.push_unseparated(")");
 
-        QueryBuilder::<Sqlite>::new("insert into photos_event_log (photo_id, user_id, data) ")
-            .push_values(photos, |mut b, (photo, serialized_data)| {
-                b.push_bind(photo.id)
-                    .push_bind(&photo.user_id)
-                    .push_bind(serialized_data);
-            })
-            .build()
-            .execute(self)
-            .await
-            .map(|_| ())
+        query_builder.build().execute(self).await.map(|_| ())
     }
 
-    async fn insert_deletion_event_logs(self, photo_ids: &[i64]) -> sqlx::Result<()> {
-        QueryBuilder::<Sqlite>::new("insert into photos_event_log (photo_id) ")
+    /// Deletion events that only the member receives, when an album is no longer shared with them
+    async fn insert_member_deletion_event_logs(
+        self,
+        member_id: &str,
+        photo_ids: &[i64],
+    ) -> sqlx::Result<()> {
+        if photo_ids.is_empty() {
+            // An empty vector would cause a SQL syntax error
+            return Ok(());
+        }
+
+        QueryBuilder::<Sqlite>::new("insert into photos_event_log (photo_id, member_id) ")
             .push_values(photo_ids, |mut b, photo_id| {
-                b.push_bind(photo_id);
+                b.push_bind(photo_id).push_bind(member_id);
             })
             .build()
             .execute(self)
@@ -58,6 +94,40 @@ pub trait EventLogRepo<'c>: SqliteExecutor<'c> {
             .map(|_| ())
     }
 
+    /// The ids of the oldest and the newest events that are kept, none if there are no events
+    async fn get_event_id_range(self) -> sqlx::Result<Option<(i64, i64)>> {
+        query!(
+            "select min(event_id) as 'min_id: i64', max(event_id) as 'max_id: i64' from photos_event_log"
+        )
+        .map(|record| record.min_id.zip(record.max_id))
+        .fetch_one(self)
+        .await
+    }
+
+    /// The events after `after_event_id` up to `until_event_id` that the user can see,
+    /// following the same rules as the partial sync
+    async fn get_user_events(
+        self,
+        after_event_id: i64,
+        until_event_id: i64,
+        user_id: &str,
+    ) -> sqlx::Result<Vec<EventLogEntry>> {
+        query_as!(
+            EventLogEntry,
+            "select e.event_id, e.photo_id, e.data from photos_event_log e
+             where e.event_id > $1 and e.event_id <= $2
+               and (e.member_id = $3 or (e.member_id is null and (e.user_id = $3 or e.user_id is null or exists (
+                   select 1 from shared_folders sf
+                   where sf.member_id = $3 and sf.owner_id = e.user_id and sf.folder = e.folder))))
+             order by e.event_id",
+            after_event_id,
+            until_event_id,
+            user_id,
+        )
+        .fetch_all(self)
+        .await
+    }
+
     async fn delete_old_events(self, last_rows_to_keep: u32) -> Result<(), sqlx::Error> {
         query!("delete from photos_event_log where event_id <= (select max(event_id) from photos_event_log) - $1", last_rows_to_keep)
             .execute(self)
@@ -68,8 +138,71 @@ pub trait EventLogRepo<'c>: SqliteExecutor<'c> {
 
 impl<'c, E> EventLogRepo<'c> for E where E: SqliteExecutor<'c> {}
 
-fn photo_to_json_bytes(photo: &Photo) -> sqlx::Result<Vec<u8>> {
-    serde_json::to_vec(&photo).map_err(|e| sqlx::Error::Encode(e.into()))
+async fn insert_creation_events<'c>(
+    executor: impl SqliteExecutor<'c>,
+    member_id: Option<&str>,
+    photos: &[Photo],
+    metadata: &[PhotoMetadata],
+    tags: &[PhotoTag],
+) -> sqlx::Result<()> {
+    if photos.is_empty() {
+        // An empty vector would cause a SQL syntax error
+        return Ok(());
+    }
+
+    let photos = photos
+        .iter()
+        .map(|photo| {
+            let metadata = metadata.iter().find(|m| m.photo_id == photo.id);
+            photo_to_json_bytes(photo, metadata, tags).map(|data| (photo, data))
+        })
+        .collect::<sqlx::Result<Vec<_>>>()?;
+
+    QueryBuilder::<Sqlite>::new(
+        "insert into photos_event_log (photo_id, user_id, folder, data, member_id) ",
+    )
+    .push_values(photos, |mut b, (photo, serialized_data)| {
+        b.push_bind(photo.id)
+            .push_bind(&photo.user_id)
+            .push_bind(&photo.folder)
+            .push_bind(serialized_data)
+            .push_bind(member_id);
+    })
+    .build()
+    .execute(executor)
+    .await
+    .map(|_| ())
+}
+
+/// Same shape as [`crate::model::photo::PhotoWithMetadata`], without cloning the photo
+#[derive(Serialize)]
+struct PhotoPayload<'a> {
+    #[serde(flatten)]
+    photo: &'a Photo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a PhotoMetadata>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<&'a str>,
+}
+
+/// `tags` can also contain the tags of other photos
+fn photo_to_json_bytes(
+    photo: &Photo,
+    metadata: Option<&PhotoMetadata>,
+    tags: &[PhotoTag],
+) -> sqlx::Result<Vec<u8>> {
+    let tags = tags
+        .iter()
+        .filter(|tag| tag.photo_id == photo.id)
+        .map(|tag| tag.name.as_str())
+        .collect();
+
+    serde_json::to_vec(&PhotoPayload {
+        photo,
+        metadata,
+        tags,
+    })
+    .map_err(|e| sqlx::Error::Encode(e.into()))
 }
 
 #[cfg(test)]
@@ -102,7 +235,7 @@ mod tests {
         assert!(event.data.is_some()); // data contains JSON
 
         // Insert deletion event (no photo data)
-        pool.insert_event_log(inserted.id, Some("user1"), None)
+        pool.insert_event_log(inserted.id, Some("user1"), None, None, &[])
             .await?;
 
         let events = sqlx::query!(
@@ -134,13 +267,50 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test]
+    async fn test_get_user_events(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "User 2")).await?;
+
+        assert_eq!(pool.get_event_id_range().await?, None);
+
+        let mut tx = pool.begin().await?;
+        let photos = tx
+            .insert_photos(&[
+                create_test_photo(0, Some("user1"), None, "mine.jpg"),
+                create_test_photo(0, Some("user2"), None, "theirs.jpg"),
+                create_test_photo(0, None, None, "public.jpg"),
+            ])
+            .await?;
+        tx.commit().await?;
+        pool.insert_deletion_event_logs(&[photos[0].id]).await?;
+
+        let (min_id, max_id) = pool.get_event_id_range().await?.unwrap();
+        assert_eq!(max_id - min_id, 3);
+
+        // The photo of the other user is left out
+        let events = pool.get_user_events(min_id - 1, max_id, "user1").await?;
+        let photo_ids: Vec<_> = events.iter().map(|e| e.photo_id).collect();
+        assert_eq!(photo_ids, vec![photos[0].id, photos[2].id, photos[0].id]);
+        assert!(events[0].data.is_some());
+        assert!(events[2].data.is_none());
+        assert_eq!(events[2].event_id, max_id);
+
+        // Only the events in the range are returned
+        let events = pool.get_user_events(min_id, max_id - 1, "user1").await?;
+        let photo_ids: Vec<_> = events.iter().map(|e| e.photo_id).collect();
+        assert_eq!(photo_ids, vec![photos[2].id]);
+
+        Ok(())
+    }
+
     #[sqlx::test]
     async fn test_batch_event_logs(pool: SqlitePool) -> sqlx::Result<()> {
         let user = create_test_user("user1", "Test User");
         insert_test_user(&pool, &user).await?;
 
         // insert_creation_event_logs empty → Ok
-        pool.insert_creation_event_logs(&[]).await?;
+        pool.insert_creation_event_logs(&[], &[], &[]).await?;
 
         // Count events
         let count: i32 =
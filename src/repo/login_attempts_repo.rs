@@ -0,0 +1,203 @@
+use crate::model::login_attempt::{LoginAttempt, LoginAttemptKind};
+use sqlx::{SqliteExecutor, query, query_as, query_scalar};
+use time::OffsetDateTime;
+
+pub trait LoginAttemptsRepo<'c>: SqliteExecutor<'c> {
+    async fn get_login_attempt(
+        self,
+        kind: LoginAttemptKind,
+        key: &str,
+    ) -> sqlx::Result<Option<LoginAttempt>> {
+        let kind = kind.as_str();
+
+        query_as!(
+            LoginAttempt,
+            "select * from login_attempts where kind = $1 and key = $2",
+            kind,
+            key
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    /// Increments the failure counter and returns its new value.
+    /// The counter starts again from 1 if the previous failure happened before `reset_before`
+    async fn record_login_failure(
+        self,
+        kind: LoginAttemptKind,
+        key: &str,
+        now: OffsetDateTime,
+        reset_before: OffsetDateTime,
+    ) -> sqlx::Result<i64> {
+        let kind = kind.as_str();
+
+        query_scalar!(
+            r#"insert into login_attempts (kind, key, failures, last_failure_at) values ($1, $2, 1, $3)
+               on conflict (kind, key) do update
+                   set failures = case when login_attempts.last_failure_at < $4 then 1
+                                       else login_attempts.failures + 1 end,
+                       last_failure_at = excluded.last_failure_at
+               returning failures as "failures!: i64""#,
+            kind,
+            key,
+            now,
+            reset_before
+        )
+        .fetch_one(self)
+        .await
+    }
+
+    async fn set_locked_until(
+        self,
+        kind: LoginAttemptKind,
+        key: &str,
+        locked_until: OffsetDateTime,
+    ) -> sqlx::Result<()> {
+        let kind = kind.as_str();
+
+        query!(
+            "update login_attempts set locked_until = $3 where kind = $1 and key = $2",
+            kind,
+            key,
+            locked_until
+        )
+        .execute(self)
+        .await
+        .map(|_| ())
+    }
+
+    async fn delete_login_attempt(self, kind: LoginAttemptKind, key: &str) -> sqlx::Result<bool> {
+        let kind = kind.as_str();
+
+        query!(
+            "delete from login_attempts where kind = $1 and key = $2",
+            kind,
+            key
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    async fn get_locked_login_attempts(
+        self,
+        now: OffsetDateTime,
+    ) -> sqlx::Result<Vec<LoginAttempt>> {
+        query_as!(
+            LoginAttempt,
+            r#"select kind, key, failures, last_failure_at, locked_until
+               from login_attempts
+               where locked_until > $1
+               order by locked_until desc"#,
+            now
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn delete_all_login_attempts(self) -> sqlx::Result<u64> {
+        query!("delete from login_attempts")
+            .execute(self)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// Forget the failures that are too old to count and are no longer locked
+    async fn delete_stale_login_attempts(self, before: OffsetDateTime) -> sqlx::Result<u64> {
+        query!(
+            "delete from login_attempts
+             where last_failure_at < $1 and (locked_until is null or locked_until < $1)",
+            before
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected())
+    }
+}
+
+impl<'c, E> LoginAttemptsRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+    use time::Duration;
+
+    #[sqlx::test]
+    async fn test_record_login_failure(pool: SqlitePool) -> sqlx::Result<()> {
+        let now = OffsetDateTime::now_utc();
+        let reset_before = now - Duration::hours(1);
+
+        for expected in 1..=3 {
+            let failures = pool
+                .record_login_failure(LoginAttemptKind::User, "user1", now, reset_before)
+                .await?;
+            assert_eq!(failures, expected);
+        }
+
+        // Counters are independent for each kind and key
+        let failures = pool
+            .record_login_failure(LoginAttemptKind::Ip, "user1", now, reset_before)
+            .await?;
+        assert_eq!(failures, 1);
+
+        // Old failures are forgotten
+        let later = now + Duration::hours(2);
+        let failures = pool
+            .record_login_failure(
+                LoginAttemptKind::User,
+                "user1",
+                later,
+                later - Duration::hours(1),
+            )
+            .await?;
+        assert_eq!(failures, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_locked_login_attempts(pool: SqlitePool) -> sqlx::Result<()> {
+        let now = OffsetDateTime::now_utc();
+        let reset_before = now - Duration::hours(1);
+
+        pool.record_login_failure(LoginAttemptKind::Ip, "10.0.0.1", now, reset_before)
+            .await?;
+        pool.record_login_failure(LoginAttemptKind::User, "user1", now, reset_before)
+            .await?;
+        pool.set_locked_until(
+            LoginAttemptKind::Ip,
+            "10.0.0.1",
+            now + Duration::minutes(15),
+        )
+        .await?;
+
+        let attempt = pool
+            .get_login_attempt(LoginAttemptKind::Ip, "10.0.0.1")
+            .await?
+            .unwrap();
+        assert!(attempt.locked_until.is_some_and(|until| until > now));
+
+        let locked = pool.get_locked_login_attempts(now).await?;
+        assert_eq!(locked.len(), 1);
+        assert_eq!(locked[0].key, "10.0.0.1");
+
+        // Only old and unlocked attempts are stale
+        let deleted = pool
+            .delete_stale_login_attempts(now + Duration::minutes(10))
+            .await?;
+        assert_eq!(deleted, 1);
+
+        assert!(
+            pool.delete_login_attempt(LoginAttemptKind::Ip, "10.0.0.1")
+                .await?
+        );
+        assert!(
+            pool.get_login_attempt(LoginAttemptKind::Ip, "10.0.0.1")
+                .await?
+                .is_none()
+        );
+
+        Ok(())
+    }
+}
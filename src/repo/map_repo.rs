@@ -0,0 +1,335 @@
+use crate::model::map::MapBounds;
+use crate::model::photo::Photo;
+use crate::model::photo_category::PhotoCategory;
+use sqlx::{SqliteExecutor, query_as};
+
+/// Geotagged photos grouped by a cell of the clustering grid
+pub struct MapCluster {
+    pub cell_row: i64,
+    pub cell_col: i64,
+    pub count: i64,
+    /// The average position of the photos
+    pub latitude: f64,
+    pub longitude: f64,
+    pub max_created_at: String,
+    /// The most recent photo of the cluster
+    pub cover_photo_id: i64,
+}
+
+pub trait MapRepo<'c>: SqliteExecutor<'c> {
+    async fn get_map_clusters(
+        self,
+        user_id: &str,
+        category: PhotoCategory,
+        bounds: &MapBounds,
+        cell_size: f64,
+    ) -> sqlx::Result<Vec<MapCluster>> {
+        match category {
+            PhotoCategory::Personal => {
+                query_as!(
+                    MapCluster,
+                    r#"select
+                        cast((m.latitude + 90.0) / $2 as integer) as "cell_row!: i64",
+                        cast((m.longitude + 180.0) / $2 as integer) as "cell_col!: i64",
+                        count(*) as "count!: i64",
+                        avg(m.latitude) as "latitude!: f64",
+                        avg(m.longitude) as "longitude!: f64",
+                        max(p.created_at) as "max_created_at!: String",
+                        p.id as "cover_photo_id!: i64"
+                    from photos p
+                    inner join photo_metadata m on m.photo_id = p.id
+                    where p.user_id = $1 and p.trashed_on is null
+                      and m.latitude between $3 and $4
+                      and m.longitude between $5 and $6
+                    group by 1, 2"#,
+                    user_id,
+                    cell_size,
+                    bounds.south,
+                    bounds.north,
+                    bounds.west,
+                    bounds.east,
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Family => {
+                query_as!(
+                    MapCluster,
+                    r#"select
+                        cast((m.latitude + 90.0) / $1 as integer) as "cell_row!: i64",
+                        cast((m.longitude + 180.0) / $1 as integer) as "cell_col!: i64",
+                        count(*) as "count!: i64",
+                        avg(m.latitude) as "latitude!: f64",
+                        avg(m.longitude) as "longitude!: f64",
+                        max(p.created_at) as "max_created_at!: String",
+                        p.id as "cover_photo_id!: i64"
+                    from photos p
+                    inner join photo_metadata m on m.photo_id = p.id
+                    where p.user_id is null and p.trashed_on is null
+                      and m.latitude between $2 and $3
+                      and m.longitude between $4 and $5
+                    group by 1, 2"#,
+                    cell_size,
+                    bounds.south,
+                    bounds.north,
+                    bounds.west,
+                    bounds.east,
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Shared => {
+                query_as!(
+                    MapCluster,
+                    r#"select
+                        cast((m.latitude + 90.0) / $2 as integer) as "cell_row!: i64",
+                        cast((m.longitude + 180.0) / $2 as integer) as "cell_col!: i64",
+                        count(*) as "count!: i64",
+                        avg(m.latitude) as "latitude!: f64",
+                        avg(m.longitude) as "longitude!: f64",
+                        max(p.created_at) as "max_created_at!: String",
+                        p.id as "cover_photo_id!: i64"
+                    from photos p
+                    inner join photo_metadata m on m.photo_id = p.id
+                    inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+                    where p.trashed_on is null
+                      and m.latitude between $3 and $4
+                      and m.longitude between $5 and $6
+                    group by 1, 2"#,
+                    user_id,
+                    cell_size,
+                    bounds.south,
+                    bounds.north,
+                    bounds.west,
+                    bounds.east,
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::All => {
+                query_as!(
+                    MapCluster,
+                    r#"select
+                        cast((m.latitude + 90.0) / $2 as integer) as "cell_row!: i64",
+                        cast((m.longitude + 180.0) / $2 as integer) as "cell_col!: i64",
+                        count(*) as "count!: i64",
+                        avg(m.latitude) as "latitude!: f64",
+                        avg(m.longitude) as "longitude!: f64",
+                        max(p.created_at) as "max_created_at!: String",
+                        p.id as "cover_photo_id!: i64"
+                    from photos p
+                    inner join photo_metadata m on m.photo_id = p.id
+                    where (p.user_id is null or p.user_id = $1 or exists (
+                        select 1 from shared_folders sf
+                        where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder))
+                      and p.trashed_on is null
+                      and m.latitude between $3 and $4
+                      and m.longitude between $5 and $6
+                    group by 1, 2"#,
+                    user_id,
+                    cell_size,
+                    bounds.south,
+                    bounds.north,
+                    bounds.west,
+                    bounds.east,
+                )
+                .fetch_all(self)
+                .await
+            }
+        }
+    }
+
+    /// The most recent photos taken within the bounds
+    async fn get_map_photos(
+        self,
+        user_id: &str,
+        category: PhotoCategory,
+        bounds: &MapBounds,
+        limit: i64,
+    ) -> sqlx::Result<Vec<Photo>> {
+        match category {
+            PhotoCategory::Personal => {
+                query_as!(
+                    Photo,
+                    r#"select p.*
+                    from photos p
+                    inner join photo_metadata m on m.photo_id = p.id
+                    where p.user_id = $1 and p.trashed_on is null
+                      and m.latitude between $2 and $3
+                      and m.longitude between $4 and $5
+                    order by p.created_at desc
+                    limit $6"#,
+                    user_id,
+                    bounds.south,
+                    bounds.north,
+                    bounds.west,
+                    bounds.east,
+                    limit,
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Family => {
+                query_as!(
+                    Photo,
+                    r#"select p.*
+                    from photos p
+                    inner join photo_metadata m on m.photo_id = p.id
+                    where p.user_id is null and p.trashed_on is null
+                      and m.latitude between $1 and $2
+                      and m.longitude between $3 and $4
+                    order by p.created_at desc
+                    limit $5"#,
+                    bounds.south,
+                    bounds.north,
+                    bounds.west,
+                    bounds.east,
+                    limit,
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Shared => {
+                query_as!(
+                    Photo,
+                    r#"select p.*
+                    from photos p
+                    inner join photo_metadata m on m.photo_id = p.id
+                    inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+                    where p.trashed_on is null
+                      and m.latitude between $2 and $3
+                      and m.longitude between $4 and $5
+                    order by p.created_at desc
+                    limit $6"#,
+                    user_id,
+                    bounds.south,
+                    bounds.north,
+                    bounds.west,
+                    bounds.east,
+                    limit,
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::All => {
+                query_as!(
+                    Photo,
+                    r#"select p.*
+                    from photos p
+                    inner join photo_metadata m on m.photo_id = p.id
+                    where (p.user_id is null or p.user_id = $1 or exists (
+                        select 1 from shared_folders sf
+                        where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder))
+                      and p.trashed_on is null
+                      and m.latitude between $2 and $3
+                      and m.longitude between $4 and $5
+                    order by p.created_at desc
+                    limit $6"#,
+                    user_id,
+                    bounds.south,
+                    bounds.north,
+                    bounds.west,
+                    bounds.east,
+                    limit,
+                )
+                .fetch_all(self)
+                .await
+            }
+        }
+    }
+}
+
+impl<'c, E> MapRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::map::cluster_cell_size;
+    use crate::model::photo_metadata::PhotoMetadata;
+    use crate::repo::tests::{create_test_photo_with_time, create_test_user, insert_test_user};
+    use crate::repo::{PhotoMetadataRepo, PhotosTransactionRepo};
+    use sqlx::SqlitePool;
+    use time::macros::datetime;
+
+    #[sqlx::test]
+    async fn test_map_clusters(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "User 2")).await?;
+
+        let located = [
+            (
+                Some("user1"),
+                datetime!(2024-01-01 10:00 UTC),
+                48.8584,
+                2.2945,
+            ),
+            (
+                Some("user1"),
+                datetime!(2024-06-01 10:00 UTC),
+                48.8606,
+                2.3376,
+            ),
+            (
+                Some("user2"),
+                datetime!(2024-03-01 10:00 UTC),
+                48.8530,
+                2.3499,
+            ),
+            (None, datetime!(2023-07-04 10:00 UTC), 40.6892, -74.0445),
+        ];
+
+        let mut tx = pool.begin().await?;
+        let mut photos = Vec::new();
+        let mut metadata = Vec::new();
+        for (user_id, created_at, latitude, longitude) in located {
+            let name = format!("{created_at}.jpg");
+            let photo = create_test_photo_with_time(0, user_id, None, &name, created_at);
+            let photo = tx.insert_photo(&photo).await?;
+            metadata.push(PhotoMetadata {
+                photo_id: photo.id,
+                latitude: Some(latitude),
+                longitude: Some(longitude),
+                ..Default::default()
+            });
+            photos.push(photo);
+        }
+        tx.insert_photos_metadata(&metadata).await?;
+        tx.commit().await?;
+
+        let world = MapBounds::parse_bbox("-180,-90,180,90").unwrap();
+        let cell_size = cluster_cell_size(5);
+
+        // The photos of other users aren't visible
+        let mut clusters = pool
+            .get_map_clusters("user1", PhotoCategory::All, &world, cell_size)
+            .await?;
+        clusters.sort_by_key(|c| c.count);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].count, 1);
+        assert_eq!(clusters[0].cover_photo_id, photos[3].id);
+        assert_eq!(clusters[1].count, 2);
+        assert_eq!(clusters[1].cover_photo_id, photos[1].id);
+        assert!((clusters[1].latitude - 48.8595).abs() < 1e-6);
+
+        let cell = MapBounds::from_cell(clusters[1].cell_row, clusters[1].cell_col, cell_size);
+        let cluster_photos = pool
+            .get_map_photos("user1", PhotoCategory::All, &cell, 10)
+            .await?;
+        assert_eq!(cluster_photos, vec![photos[1].clone(), photos[0].clone()]);
+
+        let family = pool
+            .get_map_clusters("user1", PhotoCategory::Family, &world, cell_size)
+            .await?;
+        assert_eq!(family.len(), 1);
+        assert_eq!(family[0].cover_photo_id, photos[3].id);
+
+        // Only the photos within the bounds
+        let paris = MapBounds::parse_bbox("2.2,48.8,2.4,48.9").unwrap();
+        let personal = pool
+            .get_map_photos("user1", PhotoCategory::Personal, &paris, 1)
+            .await?;
+        assert_eq!(personal, vec![photos[1].clone()]);
+
+        Ok(())
+    }
+}
@@ -1,12 +1,38 @@
+mod albums_repo;
+mod api_tokens_repo;
 pub mod event_log;
 mod favorites_repo;
+mod login_attempts_repo;
+mod map_repo;
+mod photo_metadata_repo;
 mod photos_hash_repo;
 mod photos_repo;
+mod places_repo;
+mod saved_searches_repo;
+mod search_repo;
+mod shares_repo;
+mod tags_repo;
+mod two_factor_repo;
+mod uploads_repo;
+mod user_sessions_repo;
 pub mod users_repo;
 
+pub use albums_repo::*;
+pub use api_tokens_repo::*;
 pub use favorites_repo::*;
+pub use login_attempts_repo::*;
+pub use map_repo::*;
+pub use photo_metadata_repo::*;
 pub use photos_hash_repo::*;
 pub use photos_repo::*;
+pub use places_repo::*;
+pub use saved_searches_repo::*;
+pub use search_repo::*;
+pub use shares_repo::*;
+pub use tags_repo::*;
+pub use two_factor_repo::*;
+pub use uploads_repo::*;
+pub use user_sessions_repo::*;
 
 #[cfg(test)]
 pub mod tests;
@@ -0,0 +1,181 @@
+use crate::model::photo::Photo;
+use crate::model::photo_metadata::PhotoMetadata;
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, query_as};
+use std::collections::HashMap;
+
+pub trait PhotoMetadataRepo<'c>: SqliteExecutor<'c> {
+    async fn get_photos_without_metadata(self) -> sqlx::Result<Vec<Photo>> {
+        query_as!(
+            Photo,
+            "select p.* from photos p left join photo_metadata m on p.id = m.photo_id
+             where m.photo_id is null and p.trashed_on is null"
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn get_photo_metadata(self, photo_id: i64) -> sqlx::Result<Option<PhotoMetadata>> {
+        query_as!(
+            PhotoMetadata,
+            "select * from photo_metadata where photo_id = $1",
+            photo_id
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    async fn get_photos_metadata(self, photo_ids: &[i64]) -> sqlx::Result<Vec<PhotoMetadata>> {
+        if photo_ids.is_empty() {
+            // An empty vector would cause a SQL syntax error
+            return Ok(Vec::new());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("select * from photo_metadata where photo_id in (");
+        let mut sep = qb.separated(", ");
+        for id in photo_ids {
+            sep.push_bind(*id);
+        }
+        sep.push_unseparated(")");
+
+        qb.build_query_as().fetch_all(self).await
+    }
+
+    /// Durations of the videos among the given photos
+    async fn get_video_durations(self, photo_ids: &[i64]) -> sqlx::Result<HashMap<i64, f64>> {
+        if photo_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "select photo_id, duration from photo_metadata where duration is not null and photo_id in (",
+        );
+        let mut sep = qb.separated(", ");
+        for id in photo_ids {
+            sep.push_bind(*id);
+        }
+        sep.push_unseparated(")");
+
+        qb.build_query_as::<(i64, f64)>()
+            .fetch_all(self)
+            .await
+            .map(|rows| rows.into_iter().collect())
+    }
+
+    async fn insert_photos_metadata(self, metadata: &[PhotoMetadata]) -> sqlx::Result<()> {
+        if metadata.is_empty() {
+            return Ok(());
+        }
+
+        QueryBuilder::<Sqlite>::new(
+            "insert or replace into photo_metadata (photo_id, camera_make, camera_model, lens_model, focal_length, aperture, exposure_time, iso, width, height, orientation, latitude, longitude, duration, video_codec, frame_rate) ",
+        )
+        .push_values(metadata, |mut b, m| {
+            b.push_bind(m.photo_id)
+                .push_bind(&m.camera_make)
+                .push_bind(&m.camera_model)
+                .push_bind(&m.lens_model)
+                .push_bind(m.focal_length)
+                .push_bind(m.aperture)
+                .push_bind(m.exposure_time)
+                .push_bind(m.iso)
+                .push_bind(m.width)
+                .push_bind(m.height)
+                .push_bind(m.orientation)
+                .push_bind(m.latitude)
+                .push_bind(m.longitude)
+                .push_bind(m.duration)
+                .push_bind(&m.video_codec)
+                .push_bind(m.frame_rate);
+        })
+        .build()
+        .execute(self)
+        .await
+        .map(|_| {})
+    }
+}
+
+impl<'c, E> PhotoMetadataRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::PhotosTransactionRepo;
+    use crate::repo::tests::{create_test_photo, create_test_user, insert_test_user};
+    use sqlx::SqlitePool;
+
+    #[sqlx::test]
+    async fn test_photos_metadata(pool: SqlitePool) -> sqlx::Result<()> {
+        let user = create_test_user("user1", "Test User");
+        insert_test_user(&pool, &user).await?;
+
+        let mut tx = pool.begin().await?;
+        let p1 = tx
+            .insert_photo(&create_test_photo(0, Some("user1"), None, "p1.jpg"))
+            .await?;
+        let p2 = tx
+            .insert_photo(&create_test_photo(0, Some("user1"), None, "p2.png"))
+            .await?;
+        tx.commit().await?;
+
+        assert_eq!(pool.get_photos_without_metadata().await?.len(), 2);
+
+        let metadata = PhotoMetadata {
+            photo_id: p1.id,
+            camera_make: Some("Canon".to_string()),
+            focal_length: Some(50.0),
+            iso: Some(400),
+            latitude: Some(45.5),
+            longitude: Some(-73.25),
+            ..Default::default()
+        };
+        // Files without EXIF still get an empty row
+        let empty = PhotoMetadata {
+            photo_id: p2.id,
+            ..Default::default()
+        };
+        pool.insert_photos_metadata(&[metadata.clone(), empty.clone()])
+            .await?;
+
+        assert!(pool.get_photos_without_metadata().await?.is_empty());
+        assert_eq!(
+            pool.get_photo_metadata(p1.id).await?,
+            Some(metadata.clone())
+        );
+
+        assert!(pool.get_video_durations(&[p1.id, p2.id]).await?.is_empty());
+
+        let all = pool.get_photos_metadata(&[p1.id, p2.id]).await?;
+        assert_eq!(all.len(), 2);
+        assert!(all.contains(&empty));
+
+        // The metadata is sent along with the photos
+        let mut tx = pool.begin().await?;
+        let full_list = tx.get_photos_by_user_and_public("user1").await?;
+        let synced = full_list.photos.iter().find(|p| p.id == p1.id).unwrap();
+        assert_eq!(synced.metadata.as_ref(), Some(&metadata));
+
+        tx.update_photo(&p1).await?;
+        tx.commit().await?;
+
+        let data = sqlx::query_scalar!(
+            "select data from photos_event_log where photo_id = $1 order by event_id desc limit 1",
+            p1.id
+        )
+        .fetch_one(&pool)
+        .await?
+        .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+        assert_eq!(json["name"], "p1.jpg");
+        assert_eq!(json["metadata"]["camera_make"], "Canon");
+        assert_eq!(json["metadata"]["iso"], 400);
+
+        // Deleting the photo deletes its metadata
+        let mut tx = pool.begin().await?;
+        tx.delete_photo(&p1).await?;
+        tx.commit().await?;
+        assert_eq!(pool.get_photo_metadata(p1.id).await?, None);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use crate::model::photo::Photo;
-use crate::model::photo_hash::PhotoHash;
+use crate::model::photo_hash::{ExistingHash, PhotoHash};
 use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, query, query_as};
 use std::num::ParseIntError;
 
@@ -14,6 +14,8 @@ pub trait PhotosHashRepo<'c>: SqliteExecutor<'c> {
         .await
     }
 
+    /// The photo of the same owner with this hash, so an upload of the same file is not stored twice.
+    /// `user_id` is `None` for the public folder
     async fn get_photo_with_hash(
         self,
         hash: &[u8],
@@ -22,7 +24,7 @@ pub trait PhotosHashRepo<'c>: SqliteExecutor<'c> {
         query_as!(
             Photo,
             "select p.* from photos p left join photos_hash e on p.id = e.photo_id
-             where e.hash = $1 and (user_id = $2 or ($2 is null and user_id is null))",
+             where e.hash = $1 and p.user_id is $2",
             hash,
             user_id
         )
@@ -30,6 +32,39 @@ pub trait PhotosHashRepo<'c>: SqliteExecutor<'c> {
         .await
     }
 
+    /// The photos of the user or the public folder that match any of the (hash, file size) pairs,
+    /// leaving out the trashed ones
+    async fn get_existing_hashes(
+        self,
+        user_id: &str,
+        files: &[(Vec<u8>, i64)],
+    ) -> sqlx::Result<Vec<ExistingHash>> {
+        if files.is_empty() {
+            // An empty vector would cause a SQL syntax error
+            return Ok(Vec::new());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "select h.photo_id, h.hash, p.file_size, p.user_id is null as public
+             from photos_hash h join photos p on p.id = h.photo_id
+             where (p.user_id = ",
+        );
+        qb.push_bind(user_id).push(
+            " or p.user_id is null) and p.trashed_on is null and (h.hash, p.file_size) in (values ",
+        );
+        let mut sep = qb.separated(", ");
+        for (hash, file_size) in files {
+            sep.push("(")
+                .push_bind_unseparated(hash)
+                .push_unseparated(", ")
+                .push_bind_unseparated(*file_size)
+                .push_unseparated(")");
+        }
+        sep.push_unseparated(")");
+
+        qb.build_query_as().fetch_all(self).await
+    }
+
     async fn get_duplicates_for_user(self, user_id: &str) -> sqlx::Result<Vec<Vec<i64>>> {
         // Note: Parentheses are important here for correct precedence
         // We want: (user's photos OR public photos) AND not trashed
@@ -280,6 +315,81 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test]
+    async fn test_get_existing_hashes(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "Test User")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "Other User")).await?;
+
+        let mut trashed = create_test_photo(0, Some("user1"), None, "trashed.jpg");
+        trashed.trashed_on = Some(OffsetDateTime::now_utc());
+
+        let mut tx = pool.begin().await?;
+        let photos = tx
+            .insert_photos(&[
+                create_test_photo(0, Some("user1"), None, "mine.jpg"),
+                create_test_photo(0, None, None, "public.jpg"),
+                create_test_photo(0, Some("user2"), None, "theirs.jpg"),
+                trashed,
+            ])
+            .await?;
+        tx.commit().await?;
+
+        let hashes: Vec<_> = photos
+            .iter()
+            .enumerate()
+            .map(|(i, photo)| PhotoHash {
+                id: photo.id,
+                hash: vec![i as u8; 4],
+            })
+            .collect();
+        pool.insert_hashes(&hashes).await?;
+
+        // Empty input → empty output
+        assert!(pool.get_existing_hashes("user1", &[]).await?.is_empty());
+
+        let mut existing = pool
+            .get_existing_hashes(
+                "user1",
+                &[
+                    (vec![0; 4], 1024),
+                    (vec![1; 4], 1024),
+                    // Another user's photo
+                    (vec![2; 4], 1024),
+                    // A trashed photo
+                    (vec![3; 4], 1024),
+                    // Same hash but a different size
+                    (vec![0; 4], 2048),
+                    (vec![9; 4], 1024),
+                ],
+            )
+            .await?;
+        existing.sort_by_key(|e| e.photo_id);
+
+        assert_eq!(
+            existing,
+            vec![
+                ExistingHash {
+                    photo_id: photos[0].id,
+                    hash: vec![0; 4],
+                    file_size: 1024,
+                    public: false,
+                },
+                ExistingHash {
+                    photo_id: photos[1].id,
+                    hash: vec![1; 4],
+                    file_size: 1024,
+                    public: true,
+                },
+            ]
+        );
+
+        // An upload of a trashed photo is still a duplicate
+        let duplicate = pool.get_photo_with_hash(&[3; 4], Some("user1")).await?;
+        assert_eq!(duplicate.map(|photo| photo.id), Some(photos[3].id));
+
+        Ok(())
+    }
+
     #[sqlx::test]
     async fn test_insert_hashes(pool: SqlitePool) -> sqlx::Result<()> {
         let user = create_test_user("user1", "Test User");
@@ -1,11 +1,15 @@
 use crate::model::event_log::{EventLog, EventLogs};
-use crate::model::photo::{FullPhotosList, Photo};
+use crate::model::photo::{FullPhotosList, Photo, PhotoWithMetadata};
 use crate::model::photo_category::PhotoCategory;
+use crate::model::photo_metadata::PhotoMetadata;
+use crate::model::tag::PhotoTag;
 use crate::repo::event_log::EventLogRepo;
+use crate::repo::{PhotoMetadataRepo, SearchRepo, TagsRepo};
 use serde::{Deserialize, Serialize};
 use sqlx::{
     FromRow, QueryBuilder, Sqlite, SqliteExecutor, SqliteTransaction, query, query_as, query_scalar,
 };
+use std::collections::HashMap;
 use thiserror::Error;
 use time::OffsetDateTime;
 
@@ -26,6 +30,7 @@ pub struct FolderInfo {
 }
 
 /// Result of a paginated photo query
+#[derive(Default)]
 pub struct PaginatedPhotos {
     pub photos: Vec<Photo>,
     pub next_cursor: Option<PhotoCursor>,
@@ -51,6 +56,23 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
         .await
     }
 
+    /// Like [Self::get_photo], but also includes photos from albums shared with the user.
+    /// Only use this for read-only access
+    async fn get_visible_photo(self, id: i64, user_id: &str) -> sqlx::Result<Option<Photo>> {
+        query_as!(
+            Photo,
+            "select * from photos p
+             where p.id = $1
+               and (p.user_id is null or p.user_id = $2 or exists (
+                   select 1 from shared_folders sf
+                   where sf.member_id = $2 and sf.owner_id = p.user_id and sf.folder = p.folder))",
+            id,
+            user_id
+        )
+        .fetch_optional(self)
+        .await
+    }
+
     async fn get_photo_without_check(self, id: i64) -> sqlx::Result<Option<Photo>> {
         query_as!(Photo, "select * from photos where id = $1", id)
             .fetch_optional(self)
@@ -149,10 +171,12 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
         .await
     }
 
+    /// Only the photos rated with at least `min_rating` stars, so 0 includes all of them
     async fn get_photos_paginated(
         self,
         user_id: &str,
         category: PhotoCategory,
+        min_rating: i64,
         cursor: Option<&PhotoCursor>,
         limit: u32,
     ) -> sqlx::Result<PaginatedPhotos> {
@@ -172,13 +196,15 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                     r#"select * from photos
                     where user_id = $1
                       and trashed_on is null
+                      and rating >= $5
                       and ($2 is null or created_at < $2 or (created_at = $2 and id < $3))
                     order by created_at desc
                     limit $4"#,
                     user_id,
                     cursor_created_at,
                     cursor_id,
-                    fetch_limit
+                    fetch_limit,
+                    min_rating
                 )
                 .fetch_all(self)
                 .await?
@@ -189,12 +215,33 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                     r#"select * from photos
                     where user_id is null
                       and trashed_on is null
+                      and rating >= $4
                       and ($1 is null or created_at < $1 or (created_at = $1 and id < $2))
                     order by created_at desc
                     limit $3"#,
                     cursor_created_at,
                     cursor_id,
-                    fetch_limit
+                    fetch_limit,
+                    min_rating
+                )
+                .fetch_all(self)
+                .await?
+            }
+            PhotoCategory::Shared => {
+                query_as!(
+                    Photo,
+                    r#"select p.* from photos p
+                    inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+                    where p.trashed_on is null
+                      and p.rating >= $5
+                      and ($2 is null or p.created_at < $2 or (p.created_at = $2 and p.id < $3))
+                    order by p.created_at desc
+                    limit $4"#,
+                    user_id,
+                    cursor_created_at,
+                    cursor_id,
+                    fetch_limit,
+                    min_rating
                 )
                 .fetch_all(self)
                 .await?
@@ -207,19 +254,28 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                         select * from photos
                         where user_id is null
                           and trashed_on is null
+                          and rating >= $5
                           and ($1 is null or created_at < $1 or (created_at = $1 and id < $2))
                         union all
                         select * from photos
                         where user_id = $3
                           and trashed_on is null
+                          and rating >= $5
                           and ($1 is null or created_at < $1 or (created_at = $1 and id < $2))
+                        union all
+                        select p.* from photos p
+                        inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $3
+                        where p.trashed_on is null
+                          and p.rating >= $5
+                          and ($1 is null or p.created_at < $1 or (p.created_at = $1 and p.id < $2))
                     )
                     order by created_at desc
                     limit $4"#,
                     cursor_created_at,
                     cursor_id,
                     user_id,
-                    fetch_limit
+                    fetch_limit,
+                    min_rating
                 )
                 .fetch_all(self)
                 .await?
@@ -282,6 +338,40 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
         build_paginated_result(photos, limit)
     }
 
+    /// Photos of a folder from all the albums with that name shared with the user
+    async fn get_shared_folder_photos_paginated(
+        self,
+        user_id: &str,
+        folder_name: &str,
+        cursor: Option<&PhotoCursor>,
+        limit: u32,
+    ) -> sqlx::Result<PaginatedPhotos> {
+        let fetch_limit = limit as i64 + 1;
+
+        let cursor_created_at = cursor.map(|c| c.created_at);
+        let cursor_id = cursor.map(|c| c.id);
+
+        let photos = query_as!(
+            Photo,
+            r#"select p.* from photos p
+            inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+            where p.trashed_on is null
+              and p.folder = $2
+              and ($3 is null or p.created_at < $3 or (p.created_at = $3 and p.id < $4))
+            order by p.created_at desc, p.id desc
+            limit $5"#,
+            user_id,
+            folder_name,
+            cursor_created_at,
+            cursor_id,
+            fetch_limit
+        )
+        .fetch_all(self)
+        .await?;
+
+        build_paginated_result(photos, limit)
+    }
+
     async fn get_favorite_photos_paginated(
         self,
         user_id: &str,
@@ -295,7 +385,9 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                 Photo,
                 r#"select p.* from photos p
                 inner join favorite_photos f on p.id = f.photo_id and f.user_id = $1
-                where (p.user_id is null or p.user_id = $1)
+                where (p.user_id is null or p.user_id = $1 or exists (
+                    select 1 from shared_folders sf
+                    where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder))
                   and p.trashed_on is null
                   and (p.created_at < $2 or (p.created_at = $2 and p.id < $3))
                 order by p.created_at desc, p.id desc
@@ -312,7 +404,9 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                 Photo,
                 r#"select p.* from photos p
                 inner join favorite_photos f on p.id = f.photo_id and f.user_id = $1
-                where (p.user_id is null or p.user_id = $1)
+                where (p.user_id is null or p.user_id = $1 or exists (
+                    select 1 from shared_folders sf
+                    where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder))
                   and p.trashed_on is null
                 order by p.created_at desc, p.id desc
                 limit $2"#,
@@ -376,6 +470,27 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                 .fetch_all(self)
                 .await
             }
+            PhotoCategory::Shared => {
+                query_as!(
+                    FolderInfo,
+                    r#"select
+                        folder as "name!",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then id end) as "cover_photo_id!: i64"
+                    from (
+                        select p.folder, p.id,
+                               row_number() over (partition by p.folder order by p.created_at desc) as rn
+                        from photos p
+                        inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+                        where p.trashed_on is null
+                    )
+                    group by folder
+                    order by folder"#,
+                    user_id
+                )
+                .fetch_all(self)
+                .await
+            }
             PhotoCategory::All => {
                 query_as!(
                     FolderInfo,
@@ -401,10 +516,12 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
         }
     }
 
+    /// `min_rating` filters the photos like in [`Self::get_photos_paginated`]
     async fn get_month_summaries(
         self,
         user_id: &str,
         category: PhotoCategory,
+        min_rating: i64,
     ) -> sqlx::Result<Vec<MonthSummary>> {
         match category {
             PhotoCategory::Personal => {
@@ -415,10 +532,11 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                         count(*) as "count!: i64",
                         id as "cover_photo_id!: i64"
                     from photos
-                    where user_id = $1 and trashed_on is null
+                    where user_id = $1 and trashed_on is null and rating >= $2
                     group by strftime('%Y-%m', created_at)
                     order by 1 desc"#,
-                    user_id
+                    user_id,
+                    min_rating
                 )
                 .fetch_all(self)
                 .await
@@ -431,9 +549,28 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                         count(*) as "count!: i64",
                         id as "cover_photo_id!: i64"
                     from photos
-                    where user_id is null and trashed_on is null
+                    where user_id is null and trashed_on is null and rating >= $1
                     group by strftime('%Y-%m', created_at)
-                    order by 1 desc"#
+                    order by 1 desc"#,
+                    min_rating
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Shared => {
+                query_as!(
+                    MonthSummary,
+                    r#"select
+                        max(p.created_at) as "max_created_at!: String",
+                        count(*) as "count!: i64",
+                        p.id as "cover_photo_id!: i64"
+                    from photos p
+                    inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+                    where p.trashed_on is null and p.rating >= $2
+                    group by strftime('%Y-%m', p.created_at)
+                    order by 1 desc"#,
+                    user_id,
+                    min_rating
                 )
                 .fetch_all(self)
                 .await
@@ -445,11 +582,16 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
                         max(created_at) as "max_created_at!: String",
                         count(*) as "count!: i64",
                         id as "cover_photo_id!: i64"
-                    from photos
-                    where (user_id is null or user_id = $1) and trashed_on is null
+                    from photos p
+                    where (user_id is null or user_id = $1 or exists (
+                        select 1 from shared_folders sf
+                        where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder))
+                      and trashed_on is null
+                      and rating >= $2
                     group by strftime('%Y-%m', created_at)
                     order by 1 desc"#,
-                    user_id
+                    user_id,
+                    min_rating
                 )
                 .fetch_all(self)
                 .await
@@ -496,6 +638,29 @@ pub trait PhotosRepo<'c>: SqliteExecutor<'c> {
             .await
         }
     }
+
+    async fn get_shared_folder_month_summaries(
+        self,
+        user_id: &str,
+        folder_name: &str,
+    ) -> sqlx::Result<Vec<MonthSummary>> {
+        query_as!(
+            MonthSummary,
+            r#"select
+                max(p.created_at) as "max_created_at!: String",
+                count(*) as "count!: i64",
+                p.id as "cover_photo_id!: i64"
+            from photos p
+            inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+            where p.trashed_on is null and p.folder = $2
+            group by strftime('%Y-%m', p.created_at)
+            order by 1 desc"#,
+            user_id,
+            folder_name
+        )
+        .fetch_all(self)
+        .await
+    }
 }
 
 impl<'c, E> PhotosRepo<'c> for E where E: SqliteExecutor<'c> {}
@@ -513,7 +678,8 @@ pub trait PhotosTransactionRepo<'c> {
     /// photo.id is ignored
     async fn insert_photo(&mut self, photo: &Photo) -> sqlx::Result<Photo>;
     /// photo.id is ignored
-    async fn insert_photos(&mut self, photos: &[Photo]) -> sqlx::Result<()>;
+    /// Returns the inserted photos, with their ids
+    async fn insert_photos(&mut self, photos: &[Photo]) -> sqlx::Result<Vec<Photo>>;
     async fn update_photo(&mut self, photo: &Photo) -> sqlx::Result<()>;
     async fn update_thumb_hashes(&mut self, photos: &[(i64, Vec<u8>)]) -> sqlx::Result<()>;
     async fn delete_photo(&mut self, photo: &Photo) -> sqlx::Result<u64>;
@@ -533,11 +699,57 @@ impl<'c> PhotosTransactionRepo<'c> for SqliteTransaction<'c> {
 
         let photos = query_as!(
             Photo,
-            "select * from photos where user_id is null or user_id = $1 order by created_at desc",
+            "select * from photos p
+             where p.user_id is null or p.user_id = $1 or exists (
+                 select 1 from shared_folders sf
+                 where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder)
+             order by p.created_at desc",
+            user_id,
+        )
+        .fetch_all(self.as_mut())
+        .await?;
+
+        let mut metadata: HashMap<i64, PhotoMetadata> = query_as!(
+            PhotoMetadata,
+            "select pm.* from photo_metadata pm
+             inner join photos p on p.id = pm.photo_id
+             where p.user_id is null or p.user_id = $1 or exists (
+                 select 1 from shared_folders sf
+                 where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder)",
+            user_id,
+        )
+        .fetch_all(self.as_mut())
+        .await?
+        .into_iter()
+        .map(|metadata| (metadata.photo_id, metadata))
+        .collect();
+
+        let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+        let photo_tags = query_as!(
+            PhotoTag,
+            "select pt.photo_id, t.name from photo_tags pt
+             inner join tags t on t.id = pt.tag_id
+             inner join photos p on p.id = pt.photo_id
+             where p.user_id is null or p.user_id = $1 or exists (
+                 select 1 from shared_folders sf
+                 where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder)
+             order by t.name collate nocase",
             user_id,
         )
         .fetch_all(self.as_mut())
         .await?;
+        for tag in photo_tags {
+            tags.entry(tag.photo_id).or_default().push(tag.name);
+        }
+
+        let photos = photos
+            .into_iter()
+            .map(|photo| PhotoWithMetadata {
+                metadata: metadata.remove(&photo.id),
+                tags: tags.remove(&photo.id).unwrap_or_default(),
+                photo,
+            })
+            .collect();
 
         Ok(FullPhotosList {
             event_log_id: lastest_event_id,
@@ -564,14 +776,21 @@ impl<'c> PhotosTransactionRepo<'c> for SqliteTransaction<'c> {
             return Err(UserEventLogError::InvalidEventId);
         }
 
+        // Deletion events keep the folder the photo was in, so they only reach the members of its album.
+        // The events with a member are only for them, when an album is shared with them or unshared
         let event_logs = query_as!(
             EventLog,
-            "select photo_id, data from photos_event_log where event_id > $1 and (user_id = $2 or user_id is null) order by event_id",
+            "select e.photo_id, e.data from photos_event_log e
+             where e.event_id > $1
+               and (e.member_id = $2 or (e.member_id is null and (e.user_id = $2 or e.user_id is null or exists (
+                   select 1 from shared_folders sf
+                   where sf.member_id = $2 and sf.owner_id = e.user_id and sf.folder = e.folder))))
+             order by e.event_id",
             last_event_id,
             user_id,
         )
-            .fetch_all(self.as_mut())
-            .await?;
+        .fetch_all(self.as_mut())
+        .await?;
 
         Ok(EventLogs {
             event_log_id: max_event_id,
@@ -582,31 +801,34 @@ impl<'c> PhotosTransactionRepo<'c> for SqliteTransaction<'c> {
     async fn insert_photo(&mut self, photo: &Photo) -> sqlx::Result<Photo> {
         let photo = query_as!(
             Photo,
-            "insert into photos (user_id, name, created_at, file_size, folder, trashed_on) values ($1, $2, $3, $4, $5, $6) returning *",
+            "insert into photos (user_id, name, created_at, file_size, folder, trashed_on, caption, rating) values ($1, $2, $3, $4, $5, $6, $7, $8) returning *",
             photo.user_id,
             photo.name,
             photo.created_at,
             photo.file_size,
             photo.folder,
-            photo.trashed_on
+            photo.trashed_on,
+            photo.caption,
+            photo.rating
         )
             .fetch_one(self.as_mut())
             .await?;
 
-        self.insert_event_log(photo.id, photo.user_id.as_deref(), Some(&photo))
+        self.as_mut().index_photos(&[photo.id]).await?;
+        self.insert_event_log(photo.id, photo.user_id.as_deref(), Some(&photo), None, &[])
             .await?;
 
         Ok(photo)
     }
 
     /// photo.id is ignored
-    async fn insert_photos(&mut self, photos: &[Photo]) -> sqlx::Result<()> {
+    async fn insert_photos(&mut self, photos: &[Photo]) -> sqlx::Result<Vec<Photo>> {
         if photos.is_empty() {
-            return Ok(());
+            return Ok(Vec::new());
         }
 
         let photos = QueryBuilder::<Sqlite>::new(
-            "insert into photos (user_id, name, created_at, file_size, folder, trashed_on, thumb_hash) ",
+            "insert into photos (user_id, name, created_at, file_size, folder, trashed_on, thumb_hash, caption, rating) ",
         )
             .push_values(photos, |mut b, photo| {
                 b.push_bind(&photo.user_id)
@@ -615,7 +837,9 @@ impl<'c> PhotosTransactionRepo<'c> for SqliteTransaction<'c> {
                     .push_bind(photo.file_size)
                     .push_bind(&photo.folder)
                     .push_bind(photo.trashed_on)
-                    .push_bind(&photo.thumb_hash);
+                    .push_bind(&photo.thumb_hash)
+                    .push_bind(&photo.caption)
+                    .push_bind(photo.rating);
             })
             .push(" returning *")
             .build()
@@ -623,26 +847,55 @@ impl<'c> PhotosTransactionRepo<'c> for SqliteTransaction<'c> {
             .fetch_all(self.as_mut())
             .await?;
 
-        self.insert_creation_event_logs(&photos).await
+        let ids: Vec<i64> = photos.iter().map(|photo| photo.id).collect();
+        self.as_mut().index_photos(&ids).await?;
+
+        self.insert_creation_event_logs(&photos, &[], &[]).await?;
+
+        Ok(photos)
     }
 
     /// Thumb hash is purposely left out, as [`Self::update_thumb_hashes`] exists
     async fn update_photo(&mut self, photo: &Photo) -> sqlx::Result<()> {
+        let previous = query!("select user_id, folder from photos where id = $1", photo.id)
+            .fetch_optional(self.as_mut())
+            .await?;
+        if let Some(previous) = previous
+            && (previous.user_id != photo.user_id || previous.folder != photo.folder)
+        {
+            // The members of the album it was in must no longer see it
+            self.as_mut()
+                .insert_deletion_event_logs(&[photo.id])
+                .await?;
+        }
+
         query!(
-            "update photos set user_id = $2, name = $3, created_at = $4, file_size = $5, folder = $6, trashed_on = $7 where id = $1",
+            "update photos set user_id = $2, name = $3, created_at = $4, file_size = $5, folder = $6, trashed_on = $7, caption = $8, rating = $9 where id = $1",
             photo.id,
             photo.user_id,
             photo.name,
             photo.created_at,
             photo.file_size,
             photo.folder,
-            photo.trashed_on
+            photo.trashed_on,
+            photo.caption,
+            photo.rating
         )
             .execute(self.as_mut())
             .await?;
 
-        self.insert_event_log(photo.id, photo.user_id.as_deref(), Some(photo))
-            .await
+        self.as_mut().index_photos(&[photo.id]).await?;
+        let metadata = self.as_mut().get_photo_metadata(photo.id).await?;
+        let tags = self.as_mut().get_photos_tags(&[photo.id]).await?;
+
+        self.insert_event_log(
+            photo.id,
+            photo.user_id.as_deref(),
+            Some(photo),
+            metadata.as_ref(),
+            &tags,
+        )
+        .await
     }
 
     async fn update_thumb_hashes(&mut self, photos: &[(i64, Vec<u8>)]) -> sqlx::Result<()> {
@@ -677,17 +930,24 @@ impl<'c> PhotosTransactionRepo<'c> for SqliteTransaction<'c> {
         sep.push_unseparated(")");
         let updated_photos: Vec<Photo> = qb.build_query_as().fetch_all(self.as_mut()).await?;
 
-        self.insert_creation_event_logs(&updated_photos).await
+        let ids: Vec<i64> = photos.iter().map(|(id, _)| *id).collect();
+        let metadata = self.as_mut().get_photos_metadata(&ids).await?;
+        let tags = self.as_mut().get_photos_tags(&ids).await?;
+
+        self.insert_creation_event_logs(&updated_photos, &metadata, &tags)
+            .await
     }
 
     async fn delete_photo(&mut self, photo: &Photo) -> sqlx::Result<u64> {
+        self.as_mut()
+            .insert_deletion_event_logs(&[photo.id])
+            .await?;
         let rows_deleted = query!("delete from photos where id = $1", photo.id)
             .execute(self.as_mut())
             .await
             .map(|result| result.rows_affected())?;
 
-        self.insert_event_log(photo.id, photo.user_id.as_deref(), None)
-            .await?;
+        self.as_mut().unindex_photos(&[photo.id]).await?;
 
         Ok(rows_deleted)
     }
@@ -698,6 +958,8 @@ impl<'c> PhotosTransactionRepo<'c> for SqliteTransaction<'c> {
             return Ok(0);
         }
 
+        self.as_mut().insert_deletion_event_logs(photo_ids).await?;
+
         let mut query_builder: QueryBuilder<Sqlite> =
             QueryBuilder::new("delete from photos where id in (");
 
@@ -713,13 +975,16 @@ impl<'c> PhotosTransactionRepo<'c> for SqliteTransaction<'c> {
             .await?
             .rows_affected();
 
-        self.insert_deletion_event_logs(photo_ids).await?;
+        self.as_mut().unindex_photos(photo_ids).await?;
 
         Ok(rows_deleted)
     }
 }
 
-fn build_paginated_result(mut photos: Vec<Photo>, limit: u32) -> sqlx::Result<PaginatedPhotos> {
+pub(super) fn build_paginated_result(
+    mut photos: Vec<Photo>,
+    limit: u32,
+) -> sqlx::Result<PaginatedPhotos> {
     let has_more = photos.len() > limit as usize;
     if has_more {
         photos.pop(); // Remove the extra photo we fetched
@@ -1374,7 +1639,7 @@ mod tests {
         // No cursor, no filters → first page of all accessible photos
         let mut tx = pool.begin().await?;
         let result = tx
-            .get_photos_paginated("user1", PhotoCategory::All, None, 2)
+            .get_photos_paginated("user1", PhotoCategory::All, 0, None, 2)
             .await?;
         tx.commit().await?;
 
@@ -1388,7 +1653,7 @@ mod tests {
         let cursor = result.next_cursor.as_ref().unwrap();
         let mut tx = pool.begin().await?;
         let result = tx
-            .get_photos_paginated("user1", PhotoCategory::All, Some(cursor), 10)
+            .get_photos_paginated("user1", PhotoCategory::All, 0, Some(cursor), 10)
             .await?;
         tx.commit().await?;
 
@@ -1399,7 +1664,7 @@ mod tests {
         // PhotoCategory::Personal → only user's private photos
         let mut tx = pool.begin().await?;
         let result = tx
-            .get_photos_paginated("user1", PhotoCategory::Personal, None, 10)
+            .get_photos_paginated("user1", PhotoCategory::Personal, 0, None, 10)
             .await?;
         tx.commit().await?;
         assert_eq!(result.photos.len(), 3);
@@ -1408,12 +1673,32 @@ mod tests {
         // PhotoCategory::Family → only public photos
         let mut tx = pool.begin().await?;
         let result = tx
-            .get_photos_paginated("user1", PhotoCategory::Family, None, 10)
+            .get_photos_paginated("user1", PhotoCategory::Family, 0, None, 10)
             .await?;
         tx.commit().await?;
         assert_eq!(result.photos.len(), 1);
         assert!(result.photos[0].user_id.is_none());
 
+        // Minimum rating → only the photos rated high enough
+        let mut tx = pool.begin().await?;
+        let mut rated = tx.get_photos_by_user(Some("user1")).await?;
+        rated.retain(|p| p.name == "p2.jpg" || p.name == "p3.jpg");
+        for (photo, rating) in rated.iter_mut().zip([4, 2]) {
+            photo.rating = rating;
+            tx.update_photo(photo).await?;
+        }
+        let result = tx
+            .get_photos_paginated("user1", PhotoCategory::All, 3, None, 10)
+            .await?;
+        let summaries = tx
+            .get_month_summaries("user1", PhotoCategory::All, 3)
+            .await?;
+        tx.commit().await?;
+        assert_eq!(result.photos.len(), 1);
+        assert_eq!(result.photos[0].rating, 4);
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].count, 1);
+
         Ok(())
     }
 
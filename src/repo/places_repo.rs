@@ -0,0 +1,473 @@
+use crate::model::map::MapBounds;
+use crate::model::photo::Photo;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::place::{PhotoPlace, Place};
+use crate::repo::photos_repo::build_paginated_result;
+use crate::repo::{PaginatedPhotos, PhotoCursor};
+use serde::Serialize;
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, query, query_as, query_scalar};
+
+/// The position of a geotagged photo that wasn't resolved to a place yet
+pub struct PhotoLocation {
+    pub photo_id: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Place with photo count for display
+#[derive(Serialize)]
+pub struct PlaceInfo {
+    pub id: i64,
+    pub name: String,
+    /// The country name, or its code if the names weren't imported
+    pub country: String,
+    #[serde(rename = "count")]
+    pub photo_count: i64,
+    pub cover_photo_id: i64,
+}
+
+pub trait PlacesRepo<'c>: SqliteExecutor<'c> {
+    async fn count_places(self) -> sqlx::Result<i64> {
+        query_scalar!(r#"select count(*) as "count!: i64" from places"#)
+            .fetch_one(self)
+            .await
+    }
+
+    async fn get_place(self, id: i64) -> sqlx::Result<Option<Place>> {
+        query_as!(Place, "select * from places where id = $1", id)
+            .fetch_optional(self)
+            .await
+    }
+
+    async fn get_photo_place(self, photo_id: i64) -> sqlx::Result<Option<Place>> {
+        query_as!(
+            Place,
+            "select pl.* from places pl
+             inner join photo_places pp on pp.place_id = pl.id
+             where pp.photo_id = $1",
+            photo_id
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    async fn get_places_in_bounds(self, bounds: &MapBounds) -> sqlx::Result<Vec<Place>> {
+        query_as!(
+            Place,
+            "select * from places
+             where latitude between $1 and $2
+               and longitude between $3 and $4",
+            bounds.south,
+            bounds.north,
+            bounds.west,
+            bounds.east,
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn get_photo_locations_without_place(self) -> sqlx::Result<Vec<PhotoLocation>> {
+        query_as!(
+            PhotoLocation,
+            r#"select m.photo_id, m.latitude as "latitude!: f64", m.longitude as "longitude!: f64"
+            from photo_metadata m
+            left join photo_places pp on pp.photo_id = m.photo_id
+            where pp.photo_id is null
+              and m.latitude is not null and m.longitude is not null"#
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn get_places_with_counts(
+        self,
+        user_id: &str,
+        category: PhotoCategory,
+    ) -> sqlx::Result<Vec<PlaceInfo>> {
+        match category {
+            PhotoCategory::Personal => {
+                query_as!(
+                    PlaceInfo,
+                    r#"select
+                        pl.id as "id!: i64",
+                        pl.name as "name!",
+                        coalesce(pl.country, pl.country_code) as "country!: String",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then photo_id end) as "cover_photo_id!: i64"
+                    from (
+                        select pp.place_id, p.id as photo_id,
+                               row_number() over (partition by pp.place_id order by p.created_at desc) as rn
+                        from photos p
+                        inner join photo_places pp on pp.photo_id = p.id
+                        where p.user_id = $1
+                          and p.trashed_on is null
+                          and pp.place_id is not null
+                    )
+                    inner join places pl on pl.id = place_id
+                    group by pl.id
+                    order by 3, pl.name"#,
+                    user_id
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Family => {
+                query_as!(
+                    PlaceInfo,
+                    r#"select
+                        pl.id as "id!: i64",
+                        pl.name as "name!",
+                        coalesce(pl.country, pl.country_code) as "country!: String",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then photo_id end) as "cover_photo_id!: i64"
+                    from (
+                        select pp.place_id, p.id as photo_id,
+                               row_number() over (partition by pp.place_id order by p.created_at desc) as rn
+                        from photos p
+                        inner join photo_places pp on pp.photo_id = p.id
+                        where p.user_id is null
+                          and p.trashed_on is null
+                          and pp.place_id is not null
+                    )
+                    inner join places pl on pl.id = place_id
+                    group by pl.id
+                    order by 3, pl.name"#
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Shared => {
+                query_as!(
+                    PlaceInfo,
+                    r#"select
+                        pl.id as "id!: i64",
+                        pl.name as "name!",
+                        coalesce(pl.country, pl.country_code) as "country!: String",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then photo_id end) as "cover_photo_id!: i64"
+                    from (
+                        select pp.place_id, p.id as photo_id,
+                               row_number() over (partition by pp.place_id order by p.created_at desc) as rn
+                        from photos p
+                        inner join photo_places pp on pp.photo_id = p.id
+                        inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+                        where true
+                          and p.trashed_on is null
+                          and pp.place_id is not null
+                    )
+                    inner join places pl on pl.id = place_id
+                    group by pl.id
+                    order by 3, pl.name"#,
+                    user_id
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::All => {
+                query_as!(
+                    PlaceInfo,
+                    r#"select
+                        pl.id as "id!: i64",
+                        pl.name as "name!",
+                        coalesce(pl.country, pl.country_code) as "country!: String",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then photo_id end) as "cover_photo_id!: i64"
+                    from (
+                        select pp.place_id, p.id as photo_id,
+                               row_number() over (partition by pp.place_id order by p.created_at desc) as rn
+                        from photos p
+                        inner join photo_places pp on pp.photo_id = p.id
+                        where (p.user_id is null or p.user_id = $1 or exists (
+                            select 1 from shared_folders sf
+                            where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder))
+                          and p.trashed_on is null
+                          and pp.place_id is not null
+                    )
+                    inner join places pl on pl.id = place_id
+                    group by pl.id
+                    order by 3, pl.name"#,
+                    user_id
+                )
+                .fetch_all(self)
+                .await
+            }
+        }
+    }
+
+    async fn get_place_photos_paginated(
+        self,
+        user_id: &str,
+        category: PhotoCategory,
+        place_id: i64,
+        cursor: Option<&PhotoCursor>,
+        limit: u32,
+    ) -> sqlx::Result<PaginatedPhotos> {
+        let fetch_limit = limit as i64 + 1;
+
+        let cursor_created_at = cursor.map(|c| c.created_at);
+        let cursor_id = cursor.map(|c| c.id);
+
+        let photos = match category {
+            PhotoCategory::Personal => {
+                query_as!(
+                    Photo,
+                    r#"select p.* from photos p
+                    inner join photo_places pp on pp.photo_id = p.id and pp.place_id = $2
+                    where p.user_id = $1
+                      and p.trashed_on is null
+                      and ($3 is null or p.created_at < $3 or (p.created_at = $3 and p.id < $4))
+                    order by p.created_at desc, p.id desc
+                    limit $5"#,
+                    user_id,
+                    place_id,
+                    cursor_created_at,
+                    cursor_id,
+                    fetch_limit
+                )
+                .fetch_all(self)
+                .await?
+            }
+            PhotoCategory::Family => {
+                query_as!(
+                    Photo,
+                    r#"select p.* from photos p
+                    inner join photo_places pp on pp.photo_id = p.id and pp.place_id = $1
+                    where p.user_id is null
+                      and p.trashed_on is null
+                      and ($2 is null or p.created_at < $2 or (p.created_at = $2 and p.id < $3))
+                    order by p.created_at desc, p.id desc
+                    limit $4"#,
+                    place_id,
+                    cursor_created_at,
+                    cursor_id,
+                    fetch_limit
+                )
+                .fetch_all(self)
+                .await?
+            }
+            PhotoCategory::Shared => {
+                query_as!(
+                    Photo,
+                    r#"select p.* from photos p
+                    inner join photo_places pp on pp.photo_id = p.id and pp.place_id = $2
+                    inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+                    where true
+                      and p.trashed_on is null
+                      and ($3 is null or p.created_at < $3 or (p.created_at = $3 and p.id < $4))
+                    order by p.created_at desc, p.id desc
+                    limit $5"#,
+                    user_id,
+                    place_id,
+                    cursor_created_at,
+                    cursor_id,
+                    fetch_limit
+                )
+                .fetch_all(self)
+                .await?
+            }
+            PhotoCategory::All => {
+                query_as!(
+                    Photo,
+                    r#"select p.* from photos p
+                    inner join photo_places pp on pp.photo_id = p.id and pp.place_id = $2
+                    where (p.user_id is null or p.user_id = $1 or exists (
+                        select 1 from shared_folders sf
+                        where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder))
+                      and p.trashed_on is null
+                      and ($3 is null or p.created_at < $3 or (p.created_at = $3 and p.id < $4))
+                    order by p.created_at desc, p.id desc
+                    limit $5"#,
+                    user_id,
+                    place_id,
+                    cursor_created_at,
+                    cursor_id,
+                    fetch_limit
+                )
+                .fetch_all(self)
+                .await?
+            }
+        };
+
+        build_paginated_result(photos, limit)
+    }
+
+    async fn insert_photo_places(self, photo_places: &[PhotoPlace]) -> sqlx::Result<()> {
+        if photo_places.is_empty() {
+            return Ok(());
+        }
+
+        QueryBuilder::<Sqlite>::new("insert or replace into photo_places (photo_id, place_id) ")
+            .push_values(photo_places, |mut b, photo_place| {
+                b.push_bind(photo_place.photo_id)
+                    .push_bind(photo_place.place_id);
+            })
+            .build()
+            .execute(self)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn insert_places(self, places: &[Place]) -> sqlx::Result<()> {
+        if places.is_empty() {
+            return Ok(());
+        }
+
+        QueryBuilder::<Sqlite>::new(
+            "insert or replace into places (id, name, country_code, country, latitude, longitude, population) ",
+        )
+        .push_values(places, |mut b, place| {
+            b.push_bind(place.id)
+                .push_bind(&place.name)
+                .push_bind(&place.country_code)
+                .push_bind(&place.country)
+                .push_bind(place.latitude)
+                .push_bind(place.longitude)
+                .push_bind(place.population);
+        })
+        .build()
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Forgets the place of every photo, including the ones without a place nearby,
+    /// so they are all looked up again
+    async fn delete_all_photo_places(self) -> sqlx::Result<u64> {
+        query!("delete from photo_places")
+            .execute(self)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    async fn delete_all_places(self) -> sqlx::Result<u64> {
+        query!("delete from places")
+            .execute(self)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
+
+impl<'c, E> PlacesRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::photo_metadata::PhotoMetadata;
+    use crate::repo::tests::{create_test_photo_with_time, create_test_user, insert_test_user};
+    use crate::repo::{PhotoMetadataRepo, PhotosTransactionRepo};
+    use sqlx::SqlitePool;
+    use time::macros::datetime;
+
+    fn create_test_place(id: i64, name: &str, country: Option<&str>, lat: f64, lon: f64) -> Place {
+        Place {
+            id,
+            name: name.to_string(),
+            country_code: "FR".to_string(),
+            country: country.map(String::from),
+            latitude: lat,
+            longitude: lon,
+            population: 1000,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_photo_places(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "User 2")).await?;
+
+        let places = vec![
+            create_test_place(1, "Paris", Some("France"), 48.8566, 2.3522),
+            create_test_place(2, "Lyon", None, 45.7640, 4.8357),
+        ];
+        pool.insert_places(&places).await?;
+        assert_eq!(pool.count_places().await?, 2);
+
+        let mut tx = pool.begin().await?;
+        let mut photos = Vec::new();
+        for (user_id, created_at) in [
+            (Some("user1"), datetime!(2024-01-01 10:00 UTC)),
+            (Some("user1"), datetime!(2024-06-01 10:00 UTC)),
+            (Some("user2"), datetime!(2024-03-01 10:00 UTC)),
+            (None, datetime!(2023-07-04 10:00 UTC)),
+        ] {
+            let name = format!("{created_at}.jpg");
+            let photo = create_test_photo_with_time(0, user_id, None, &name, created_at);
+            photos.push(tx.insert_photo(&photo).await?);
+        }
+        let metadata: Vec<_> = photos
+            .iter()
+            .map(|photo| PhotoMetadata {
+                photo_id: photo.id,
+                latitude: Some(48.85),
+                longitude: Some(2.35),
+                ..Default::default()
+            })
+            .collect();
+        tx.insert_photos_metadata(&metadata).await?;
+        tx.commit().await?;
+
+        assert_eq!(pool.get_photo_locations_without_place().await?.len(), 4);
+
+        let photo_places: Vec<_> = photos
+            .iter()
+            .enumerate()
+            .map(|(i, photo)| PhotoPlace {
+                photo_id: photo.id,
+                place_id: if i == 3 { Some(2) } else { Some(1) },
+            })
+            .collect();
+        pool.insert_photo_places(&photo_places).await?;
+        assert!(pool.get_photo_locations_without_place().await?.is_empty());
+
+        let place = pool.get_photo_place(photos[0].id).await?.unwrap();
+        assert_eq!(place.display_name(), "Paris, France");
+
+        // The photos of other users aren't counted
+        let counts = pool
+            .get_places_with_counts("user1", PhotoCategory::All)
+            .await?;
+        assert_eq!(counts.len(), 2);
+        // Sorted by country, which falls back to the code
+        assert_eq!(counts[0].country, "FR");
+        assert_eq!(counts[1].name, "Paris");
+        assert_eq!(counts[1].country, "France");
+        assert_eq!(counts[1].photo_count, 2);
+        assert_eq!(counts[1].cover_photo_id, photos[1].id);
+
+        let family = pool
+            .get_places_with_counts("user1", PhotoCategory::Family)
+            .await?;
+        assert_eq!(family.len(), 1);
+        assert_eq!(family[0].id, 2);
+
+        let first_page = pool
+            .get_place_photos_paginated("user1", PhotoCategory::Personal, 1, None, 1)
+            .await?;
+        assert_eq!(first_page.photos, vec![photos[1].clone()]);
+        assert!(first_page.has_more);
+
+        let second_page = pool
+            .get_place_photos_paginated(
+                "user1",
+                PhotoCategory::Personal,
+                1,
+                first_page.next_cursor.as_ref(),
+                1,
+            )
+            .await?;
+        assert_eq!(second_page.photos, vec![photos[0].clone()]);
+        assert!(!second_page.has_more);
+
+        // A new import looks up every photo again
+        let mut tx = pool.begin().await?;
+        tx.delete_all_photo_places().await?;
+        tx.delete_all_places().await?;
+        tx.commit().await?;
+        assert_eq!(pool.count_places().await?, 0);
+        assert_eq!(pool.get_photo_locations_without_place().await?.len(), 4);
+
+        Ok(())
+    }
+}
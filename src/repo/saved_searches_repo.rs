@@ -0,0 +1,85 @@
+use crate::model::saved_search::SavedSearch;
+use sqlx::{SqliteExecutor, query, query_as};
+
+pub trait SavedSearchesRepo<'c>: SqliteExecutor<'c> {
+    async fn get_saved_searches(self, user_id: &str) -> sqlx::Result<Vec<SavedSearch>> {
+        query_as!(
+            SavedSearch,
+            "select * from saved_searches where user_id = $1 order by name collate nocase",
+            user_id
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    /// Saving a search with the name of an existing one replaces its query
+    async fn insert_saved_search(
+        self,
+        user_id: &str,
+        name: &str,
+        search: &str,
+    ) -> sqlx::Result<SavedSearch> {
+        query_as!(
+            SavedSearch,
+            "insert into saved_searches (user_id, name, query) values ($1, $2, $3)
+             on conflict (user_id, name) do update set query = excluded.query
+             returning *",
+            user_id,
+            name,
+            search
+        )
+        .fetch_one(self)
+        .await
+    }
+
+    /// Returns true if the search existed and belonged to the user
+    async fn delete_saved_search(self, id: i64, user_id: &str) -> sqlx::Result<bool> {
+        query!(
+            "delete from saved_searches where id = $1 and user_id = $2",
+            id,
+            user_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+}
+
+impl<'c, E> SavedSearchesRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::{create_test_user, insert_test_user};
+    use sqlx::SqlitePool;
+
+    #[sqlx::test]
+    async fn test_saved_searches(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "User 2")).await?;
+
+        let videos = pool
+            .insert_saved_search("user1", "Videos", "type:video")
+            .await?;
+        pool.insert_saved_search("user1", "beach", "beach has:gps")
+            .await?;
+
+        // Saving again under the same name updates the query
+        let updated = pool
+            .insert_saved_search("user1", "Videos", "type:video after:2023")
+            .await?;
+        assert_eq!(updated.id, videos.id);
+
+        let searches = pool.get_saved_searches("user1").await?;
+        let names: Vec<_> = searches.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["beach", "Videos"]);
+        assert_eq!(searches[1].query, "type:video after:2023");
+        assert!(pool.get_saved_searches("user2").await?.is_empty());
+
+        assert!(!pool.delete_saved_search(videos.id, "user2").await?);
+        assert!(pool.delete_saved_search(videos.id, "user1").await?);
+        assert_eq!(pool.get_saved_searches("user1").await?.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+use crate::model::photo::Photo;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::search::PhotoFilter;
+use crate::repo::photos_repo::build_paginated_result;
+use crate::repo::{PaginatedPhotos, PhotoCursor};
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor};
+
+pub trait SearchRepo<'c>: SqliteExecutor<'c> {
+    /// Photos matching a filter, sorted like the gallery so they can be paginated the same way
+    async fn search_photos_paginated(
+        self,
+        user_id: &str,
+        category: PhotoCategory,
+        filter: &PhotoFilter,
+        cursor: Option<&PhotoCursor>,
+        limit: u32,
+    ) -> sqlx::Result<PaginatedPhotos> {
+        let fetch_limit = limit as i64 + 1;
+
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new("select p.* from photos p where ");
+        push_visibility(&mut qb, user_id, category);
+        push_filter(&mut qb, user_id, filter);
+
+        if let Some(cursor) = cursor {
+            qb.push(" and (p.created_at < ")
+                .push_bind(cursor.created_at)
+                .push(" or (p.created_at = ")
+                .push_bind(cursor.created_at)
+                .push(" and p.id < ")
+                .push_bind(cursor.id)
+                .push("))");
+        }
+
+        qb.push(" order by p.created_at desc, p.id desc limit ")
+            .push_bind(fetch_limit);
+
+        let photos = qb.build_query_as::<Photo>().fetch_all(self).await?;
+
+        build_paginated_result(photos, limit)
+    }
+
+    /// Adds the photos to the search index, or refreshes them if they are already in it
+    async fn index_photos(self, photo_ids: &[i64]) -> sqlx::Result<()> {
+        if photo_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "insert or replace into photos_search (rowid, name, folder, place, caption)
+             select p.id, p.name, coalesce(p.folder, ''),
+                    coalesce(pl.name || ' ' || coalesce(pl.country, pl.country_code), ''),
+                    coalesce(p.caption, '')
+             from photos p
+             left join photo_places pp on pp.photo_id = p.id
+             left join places pl on pl.id = pp.place_id
+             where p.id in (",
+        );
+        let mut sep = qb.separated(", ");
+        for id in photo_ids {
+            sep.push_bind(*id);
+        }
+        sep.push_unseparated(")");
+
+        qb.build().execute(self).await?;
+
+        Ok(())
+    }
+
+    async fn unindex_photos(self, photo_ids: &[i64]) -> sqlx::Result<()> {
+        if photo_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("delete from photos_search where rowid in (");
+        let mut sep = qb.separated(", ");
+        for id in photo_ids {
+            sep.push_bind(*id);
+        }
+        sep.push_unseparated(")");
+
+        qb.build().execute(self).await?;
+
+        Ok(())
+    }
+}
+
+impl<'c, E> SearchRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+/// The same visibility rules as [`crate::repo::PhotosRepo::get_photos_paginated`]
+fn push_visibility<'a>(
+    qb: &mut QueryBuilder<'a, Sqlite>,
+    user_id: &'a str,
+    category: PhotoCategory,
+) {
+    const SHARED_WITH_USER: &str = "exists (select 1 from shared_folders sf where sf.member_id = ";
+    const SHARED_WITH_USER_END: &str = " and sf.owner_id = p.user_id and sf.folder = p.folder)";
+
+    match category {
+        PhotoCategory::Personal => {
+            qb.push("p.user_id = ").push_bind(user_id);
+        }
+        PhotoCategory::Family => {
+            qb.push("p.user_id is null");
+        }
+        PhotoCategory::Shared => {
+            qb.push(SHARED_WITH_USER)
+                .push_bind(user_id)
+                .push(SHARED_WITH_USER_END);
+        }
+        PhotoCategory::All => {
+            qb.push("(p.user_id is null or p.user_id = ")
+                .push_bind(user_id)
+                .push(" or ")
+                .push(SHARED_WITH_USER)
+                .push_bind(user_id)
+                .push(SHARED_WITH_USER_END)
+                .push(")");
+        }
+    }
+}
+
+fn push_filter<'a>(qb: &mut QueryBuilder<'a, Sqlite>, user_id: &'a str, filter: &'a PhotoFilter) {
+    if filter.trashed {
+        qb.push(" and p.trashed_on is not null");
+    } else {
+        qb.push(" and p.trashed_on is null");
+    }
+
+    if let Some(text) = &filter.text {
+        qb.push(" and p.id in (select rowid from photos_search where photos_search match ")
+            .push_bind(text)
+            .push(")");
+    }
+    if let Some(after) = filter.after {
+        qb.push(" and p.created_at >= ").push_bind(after);
+    }
+    if let Some(before) = filter.before {
+        qb.push(" and p.created_at < ").push_bind(before);
+    }
+    if let Some(folder) = &filter.folder {
+        qb.push(" and p.folder = ")
+            .push_bind(folder)
+            .push(" collate nocase");
+    }
+    for tag in &filter.tags {
+        qb.push(
+            " and exists (select 1 from photo_tags pt inner join tags t on t.id = pt.tag_id
+              where pt.photo_id = p.id and t.name = ",
+        )
+        .push_bind(tag)
+        .push(")");
+    }
+    if filter.favorites_only {
+        qb.push(
+            " and exists (select 1 from favorite_photos f where f.photo_id = p.id and f.user_id = ",
+        )
+        .push_bind(user_id)
+        .push(")");
+    }
+    if let Some(media_type) = filter.media_type {
+        push_extensions(qb, media_type.extensions().iter().copied());
+    }
+    if !filter.extensions.is_empty() {
+        push_extensions(qb, filter.extensions.iter().map(String::as_str));
+    }
+    if let Some(camera) = &filter.camera {
+        qb.push(
+            " and exists (select 1 from photo_metadata m where m.photo_id = p.id
+              and coalesce(m.camera_make, '') || ' ' || coalesce(m.camera_model, '') like ",
+        )
+        .push_bind(format!("%{}%", escape_like(camera)))
+        .push(" escape '\\')");
+    }
+    if let Some(min_size) = filter.min_size {
+        qb.push(" and p.file_size >= ").push_bind(min_size);
+    }
+    if let Some(max_size) = filter.max_size {
+        qb.push(" and p.file_size <= ").push_bind(max_size);
+    }
+    if let Some(min_rating) = filter.min_rating {
+        qb.push(" and p.rating >= ").push_bind(min_rating);
+    }
+    if let Some(has_gps) = filter.has_gps {
+        qb.push(if has_gps {
+            " and exists"
+        } else {
+            " and not exists"
+        })
+        .push(
+            " (select 1 from photo_metadata m where m.photo_id = p.id and m.latitude is not null)",
+        );
+    }
+}
+
+/// Matches the photos with any of the extensions
+fn push_extensions<'a>(
+    qb: &mut QueryBuilder<'a, Sqlite>,
+    extensions: impl Iterator<Item = &'a str>,
+) {
+    qb.push(" and (false");
+    for extension in extensions {
+        qb.push(" or p.name like ")
+            .push_bind(format!("%.{}", escape_like(extension)))
+            .push(" escape '\\'");
+    }
+    qb.push(")");
+}
+
+/// Escapes the wildcards of LIKE, which are then used with `escape '\'`
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::photo_metadata::PhotoMetadata;
+    use crate::repo::tests::{
+        create_test_photo, create_test_photo_with_time, create_test_user, insert_test_user,
+    };
+    use crate::repo::{
+        FavoritesRepo, PhotoMetadataRepo, PhotosTransactionRepo, TagsTransactionRepo,
+    };
+    use sqlx::SqlitePool;
+    use time::macros::datetime;
+
+    async fn search(pool: &SqlitePool, user_id: &str, query: &str) -> sqlx::Result<Vec<String>> {
+        let filter = PhotoFilter::parse(query).unwrap();
+        let paginated = pool
+            .search_photos_paginated(user_id, PhotoCategory::All, &filter, None, 10)
+            .await?;
+
+        Ok(paginated.photos.into_iter().map(|p| p.name).collect())
+    }
+
+    #[sqlx::test]
+    async fn test_search_index_sync(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "User 2")).await?;
+
+        let mut tx = pool.begin().await?;
+        let beach = tx
+            .insert_photo(&create_test_photo(
+                0,
+                Some("user1"),
+                Some("Holidays"),
+                "beach.jpg",
+            ))
+            .await?;
+        tx.insert_photos(&[
+            create_test_photo(0, None, Some("Birthdays"), "IMG_20240101_120000.jpg"),
+            create_test_photo(0, Some("user2"), Some("Holidays"), "mountain.jpg"),
+        ])
+        .await?;
+        tx.commit().await?;
+
+        assert_eq!(search(&pool, "user1", "beach").await?, vec!["beach.jpg"]);
+        assert_eq!(
+            search(&pool, "user1", "img_2024").await?,
+            vec!["IMG_20240101_120000.jpg"]
+        );
+        // The photos of other users aren't visible
+        assert_eq!(search(&pool, "user1", "holi").await?, vec!["beach.jpg"]);
+        assert_eq!(
+            search(&pool, "user2", "holidays").await?,
+            vec!["mountain.jpg"]
+        );
+
+        // Moving and renaming updates the index
+        let mut tx = pool.begin().await?;
+        let mut moved = beach.clone();
+        moved.name = "sunset.jpg".to_string();
+        moved.folder = Some("Seaside".to_string());
+        tx.update_photo(&moved).await?;
+        tx.commit().await?;
+        assert!(search(&pool, "user1", "beach").await?.is_empty());
+        assert_eq!(
+            search(&pool, "user1", "seaside sun").await?,
+            vec!["sunset.jpg"]
+        );
+
+        let mut tx = pool.begin().await?;
+        tx.delete_photo(&moved).await?;
+        tx.commit().await?;
+        assert!(search(&pool, "user1", "sunset").await?.is_empty());
+        let indexed: i64 = sqlx::query_scalar("select count(*) from photos_search")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(indexed, 2);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_search_filters(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+
+        let photo = |folder, name, created_at, file_size| Photo {
+            file_size,
+            ..create_test_photo_with_time(0, Some("user1"), Some(folder), name, created_at)
+        };
+
+        let mut tx = pool.begin().await?;
+        let beach = tx
+            .insert_photo(&photo(
+                "Summer 2023",
+                "beach.jpg",
+                datetime!(2023-06-10 12:00 UTC),
+                3_000_000,
+            ))
+            .await?;
+        let clip = tx
+            .insert_photo(&photo(
+                "Summer 2023",
+                "clip.mp4",
+                datetime!(2023-07-01 00:00 UTC),
+                50_000_000,
+            ))
+            .await?;
+        let scan = tx
+            .insert_photo(&photo(
+                "Scans",
+                "scan_100%.PNG",
+                datetime!(2022-12-31 23:59 UTC),
+                200_000,
+            ))
+            .await?;
+        let mut trashed = photo("Scans", "old.png", datetime!(2022-01-01 00:00 UTC), 1000);
+        trashed.trashed_on = Some(datetime!(2024-01-01 00:00 UTC));
+        tx.insert_photo(&trashed).await?;
+        tx.commit().await?;
+
+        pool.insert_photos_metadata(&[
+            PhotoMetadata {
+                photo_id: beach.id,
+                camera_make: Some("Google".to_string()),
+                camera_model: Some("Pixel 7".to_string()),
+                latitude: Some(43.7),
+                longitude: Some(7.26),
+                ..Default::default()
+            },
+            PhotoMetadata {
+                photo_id: clip.id,
+                camera_make: Some("Apple".to_string()),
+                camera_model: Some("iPhone 13".to_string()),
+                ..Default::default()
+            },
+        ])
+        .await?;
+        pool.insert_favorite(scan.id, "user1").await?;
+        let mut tx = pool.begin().await?;
+        tx.update_photo(&Photo {
+            caption: Some("Grandma's birthday".to_string()),
+            rating: 5,
+            ..beach.clone()
+        })
+        .await?;
+        tx.update_photo(&Photo {
+            rating: 3,
+            ..scan.clone()
+        })
+        .await?;
+        tx.update_photos_tags(&[beach, clip], &["Vacation".to_string()], &[])
+            .await?;
+        tx.update_photos_tags(std::slice::from_ref(&scan), &["Documents".to_string()], &[])
+            .await?;
+        tx.commit().await?;
+
+        assert_eq!(
+            search(&pool, "user1", "camera:pixel").await?,
+            vec!["beach.jpg"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "camera:\"apple iphone\"").await?,
+            vec!["clip.mp4"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "type:video").await?,
+            vec!["clip.mp4"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "type:photo").await?,
+            vec!["beach.jpg", "scan_100%.PNG"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "ext:png").await?,
+            vec!["scan_100%.PNG"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "after:2023 before:2023-07").await?,
+            vec!["beach.jpg"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "date:2022").await?,
+            vec!["scan_100%.PNG"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "folder:\"summer 2023\" larger:10mb").await?,
+            vec!["clip.mp4"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "smaller:1mb").await?,
+            vec!["scan_100%.PNG"]
+        );
+        assert_eq!(search(&pool, "user1", "has:gps").await?, vec!["beach.jpg"]);
+        assert_eq!(
+            search(&pool, "user1", "-has:gps").await?,
+            vec!["clip.mp4", "scan_100%.PNG"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "is:favorite").await?,
+            vec!["scan_100%.PNG"]
+        );
+        assert_eq!(search(&pool, "user1", "is:trashed").await?, vec!["old.png"]);
+        assert_eq!(
+            search(&pool, "user1", "tag:vacation type:photo").await?,
+            vec!["beach.jpg"]
+        );
+        assert!(
+            search(&pool, "user1", "tag:vacation tag:documents")
+                .await?
+                .is_empty()
+        );
+        assert_eq!(
+            search(&pool, "user1", "summer type:video").await?,
+            vec!["clip.mp4"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "rating:3").await?,
+            vec!["beach.jpg", "scan_100%.PNG"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "rating:4+").await?,
+            vec!["beach.jpg"]
+        );
+        assert_eq!(
+            search(&pool, "user1", "grandma birth").await?,
+            vec!["beach.jpg"]
+        );
+        // The LIKE wildcards are matched literally
+        assert!(search(&pool, "user1", "camera:%").await?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,437 @@
+use crate::model::photo::Photo;
+use crate::model::share::{Share, ShareInfo};
+use crate::repo::photos_repo::build_paginated_result;
+use crate::repo::{PaginatedPhotos, PhotoCursor};
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, query, query_as};
+use time::OffsetDateTime;
+
+pub trait SharesRepo<'c>: SqliteExecutor<'c> {
+    async fn get_share_by_token(self, token: &str) -> sqlx::Result<Option<Share>> {
+        query_as!(Share, "select * from shares where token = $1", token)
+            .fetch_optional(self)
+            .await
+    }
+
+    /// All the shares created by a user, newest first, with the number of photos they expose
+    async fn get_shares_for_user(self, user_id: &str) -> sqlx::Result<Vec<ShareInfo>> {
+        query!(
+            r#"select s.id, s.token, s.user_id, s.folder, s.folder_is_public, s.password_hash,
+                      s.allow_downloads, s.created_at, s.expires_at,
+                      case when s.folder is null
+                          then (select count(*) from share_photos sp where sp.share_id = s.id)
+                          else (select count(*) from photos p
+                                where p.folder = s.folder and p.trashed_on is null
+                                  and ((s.folder_is_public and p.user_id is null)
+                                    or (not s.folder_is_public and p.user_id = s.user_id)))
+                      end as "photo_count!: i64"
+               from shares s
+               where s.user_id = $1
+               order by s.created_at desc, s.id desc"#,
+            user_id
+        )
+        .map(|record| {
+            let share = Share {
+                id: record.id,
+                token: record.token,
+                user_id: record.user_id,
+                folder: record.folder,
+                folder_is_public: record.folder_is_public,
+                password_hash: record.password_hash,
+                allow_downloads: record.allow_downloads,
+                created_at: record.created_at,
+                expires_at: record.expires_at,
+            };
+            ShareInfo::new(share, record.photo_count)
+        })
+        .fetch_all(self)
+        .await
+    }
+
+    /// share.id and share.created_at are ignored
+    async fn insert_share(self, share: &Share) -> sqlx::Result<Share> {
+        query_as!(
+            Share,
+            "insert into shares (token, user_id, folder, folder_is_public, password_hash, allow_downloads, expires_at)
+             values ($1, $2, $3, $4, $5, $6, $7) returning *",
+            share.token,
+            share.user_id,
+            share.folder,
+            share.folder_is_public,
+            share.password_hash,
+            share.allow_downloads,
+            share.expires_at
+        )
+        .fetch_one(self)
+        .await
+    }
+
+    async fn insert_share_photos(self, share_id: i64, photo_ids: &[i64]) -> sqlx::Result<()> {
+        if photo_ids.is_empty() {
+            // An empty vector would cause a SQL syntax error
+            return Ok(());
+        }
+
+        QueryBuilder::<Sqlite>::new("insert or ignore into share_photos (share_id, photo_id) ")
+            .push_values(photo_ids, |mut b, photo_id| {
+                b.push_bind(share_id).push_bind(photo_id);
+            })
+            .build()
+            .execute(self)
+            .await
+            .map(|_| ())
+    }
+
+    /// Returns true if the share existed and belonged to the user
+    async fn delete_share(self, share_id: i64, user_id: &str) -> sqlx::Result<bool> {
+        query!(
+            "delete from shares where id = $1 and user_id = $2",
+            share_id,
+            user_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    async fn delete_expired_shares(self) -> sqlx::Result<u64> {
+        let now = OffsetDateTime::now_utc();
+
+        query!("delete from shares where expires_at <= $1", now)
+            .execute(self)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// Paginated photos of a share that was created from an explicit list of photos
+    async fn get_share_photos_paginated(
+        self,
+        share_id: i64,
+        cursor: Option<&PhotoCursor>,
+        limit: u32,
+    ) -> sqlx::Result<PaginatedPhotos> {
+        let fetch_limit = limit as i64 + 1;
+
+        let cursor_created_at = cursor.map(|c| c.created_at);
+        let cursor_id = cursor.map(|c| c.id);
+
+        let photos = query_as!(
+            Photo,
+            r#"select p.* from photos p
+            inner join share_photos s on s.photo_id = p.id and s.share_id = $1
+            where p.trashed_on is null
+              and ($2 is null or p.created_at < $2 or (p.created_at = $2 and p.id < $3))
+            order by p.created_at desc, p.id desc
+            limit $4"#,
+            share_id,
+            cursor_created_at,
+            cursor_id,
+            fetch_limit
+        )
+        .fetch_all(self)
+        .await?;
+
+        build_paginated_result(photos, limit)
+    }
+
+    /// Get a photo only if it is exposed by the given share
+    async fn get_shared_photo(self, share: &Share, photo_id: i64) -> sqlx::Result<Option<Photo>> {
+        match share.folder.as_deref() {
+            Some(folder) => {
+                let owner_id = (!share.folder_is_public).then_some(share.user_id.as_str());
+
+                query_as!(
+                    Photo,
+                    "select * from photos
+                     where id = $1 and folder = $2 and trashed_on is null
+                       and (($3 is null and user_id is null) or user_id = $3)",
+                    photo_id,
+                    folder,
+                    owner_id
+                )
+                .fetch_optional(self)
+                .await
+            }
+            None => {
+                query_as!(
+                    Photo,
+                    "select p.* from photos p
+                     inner join share_photos s on s.photo_id = p.id and s.share_id = $1
+                     where p.id = $2 and p.trashed_on is null",
+                    share.id,
+                    photo_id
+                )
+                .fetch_optional(self)
+                .await
+            }
+        }
+    }
+}
+
+impl<'c, E> SharesRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::PhotosTransactionRepo;
+    use crate::repo::tests::{
+        create_test_photo, create_test_photo_with_time, create_test_user, insert_test_user,
+    };
+    use sqlx::SqlitePool;
+    use time::macros::datetime;
+    use time::{Duration, OffsetDateTime};
+
+    fn create_test_share(token: &str, user_id: &str, folder: Option<&str>) -> Share {
+        Share {
+            id: 0,
+            token: token.to_string(),
+            user_id: user_id.to_string(),
+            folder: folder.map(String::from),
+            folder_is_public: false,
+            password_hash: None,
+            allow_downloads: false,
+            created_at: OffsetDateTime::now_utc(),
+            expires_at: OffsetDateTime::now_utc() + Duration::days(7),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_insert_and_get_share(pool: SqlitePool) -> sqlx::Result<()> {
+        let user = create_test_user("user1", "Test User");
+        insert_test_user(&pool, &user).await?;
+
+        // Unknown token → None
+        assert!(pool.get_share_by_token("missing").await?.is_none());
+
+        let share = create_test_share("token1", "user1", Some("vacation"));
+        let inserted = pool.insert_share(&share).await?;
+        assert!(inserted.id > 0);
+
+        let fetched = pool.get_share_by_token("token1").await?;
+        assert_eq!(fetched, Some(inserted));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_get_shares_for_user(pool: SqlitePool) -> sqlx::Result<()> {
+        let user1 = create_test_user("user1", "User One");
+        let user2 = create_test_user("user2", "User Two");
+        insert_test_user(&pool, &user1).await?;
+        insert_test_user(&pool, &user2).await?;
+
+        let mut tx = pool.begin().await?;
+        let p1 = tx
+            .insert_photo(&create_test_photo(
+                0,
+                Some("user1"),
+                Some("vacation"),
+                "v1.jpg",
+            ))
+            .await?;
+        tx.insert_photo(&create_test_photo(
+            0,
+            Some("user1"),
+            Some("vacation"),
+            "v2.jpg",
+        ))
+        .await?;
+        tx.insert_photo(&create_test_photo(0, None, Some("vacation"), "public.jpg"))
+            .await?;
+        tx.commit().await?;
+
+        let folder_share = pool
+            .insert_share(&create_test_share("folder", "user1", Some("vacation")))
+            .await?;
+        let list_share = pool
+            .insert_share(&create_test_share("list", "user1", None))
+            .await?;
+        pool.insert_share_photos(list_share.id, &[p1.id]).await?;
+        pool.insert_share(&create_test_share("other", "user2", None))
+            .await?;
+
+        let shares = pool.get_shares_for_user("user1").await?;
+        assert_eq!(shares.len(), 2);
+
+        let folder_info = shares.iter().find(|s| s.id == folder_share.id).unwrap();
+        // Only the personal folder photos are counted
+        assert_eq!(folder_info.photo_count, 2);
+        assert_eq!(folder_info.url, "/s/folder");
+        assert!(!folder_info.has_password);
+
+        let list_info = shares.iter().find(|s| s.id == list_share.id).unwrap();
+        assert_eq!(list_info.photo_count, 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_delete_share(pool: SqlitePool) -> sqlx::Result<()> {
+        let user1 = create_test_user("user1", "User One");
+        let user2 = create_test_user("user2", "User Two");
+        insert_test_user(&pool, &user1).await?;
+        insert_test_user(&pool, &user2).await?;
+
+        let share = pool
+            .insert_share(&create_test_share("token", "user1", None))
+            .await?;
+
+        // Other users can't revoke the share
+        assert!(!pool.delete_share(share.id, "user2").await?);
+        assert!(pool.get_share_by_token("token").await?.is_some());
+
+        assert!(pool.delete_share(share.id, "user1").await?);
+        assert!(pool.get_share_by_token("token").await?.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_delete_expired_shares(pool: SqlitePool) -> sqlx::Result<()> {
+        let user = create_test_user("user1", "Test User");
+        insert_test_user(&pool, &user).await?;
+
+        let mut expired = create_test_share("expired", "user1", None);
+        expired.expires_at = OffsetDateTime::now_utc() - Duration::hours(1);
+        let expired = pool.insert_share(&expired).await?;
+        assert!(expired.is_expired());
+
+        pool.insert_share(&create_test_share("valid", "user1", None))
+            .await?;
+
+        assert_eq!(pool.delete_expired_shares().await?, 1);
+        assert!(pool.get_share_by_token("expired").await?.is_none());
+        assert!(pool.get_share_by_token("valid").await?.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_get_share_photos_paginated(pool: SqlitePool) -> sqlx::Result<()> {
+        let user = create_test_user("user1", "Test User");
+        insert_test_user(&pool, &user).await?;
+
+        let mut tx = pool.begin().await?;
+        let p1 = tx
+            .insert_photo(&create_test_photo_with_time(
+                0,
+                Some("user1"),
+                None,
+                "p1.jpg",
+                datetime!(2024-01-15 10:00:00 UTC),
+            ))
+            .await?;
+        let p2 = tx
+            .insert_photo(&create_test_photo_with_time(
+                0,
+                Some("user1"),
+                None,
+                "p2.jpg",
+                datetime!(2024-01-14 10:00:00 UTC),
+            ))
+            .await?;
+        let mut trashed = create_test_photo(0, Some("user1"), None, "trashed.jpg");
+        trashed.trashed_on = Some(OffsetDateTime::now_utc());
+        let trashed = tx.insert_photo(&trashed).await?;
+        tx.insert_photo(&create_test_photo(0, Some("user1"), None, "not_shared.jpg"))
+            .await?;
+        tx.commit().await?;
+
+        let share = pool
+            .insert_share(&create_test_share("token", "user1", None))
+            .await?;
+        pool.insert_share_photos(share.id, &[p1.id, p2.id, trashed.id])
+            .await?;
+
+        let page = pool.get_share_photos_paginated(share.id, None, 1).await?;
+        assert_eq!(page.photos.len(), 1);
+        assert_eq!(page.photos[0].id, p1.id);
+        assert!(page.has_more);
+
+        let page = pool
+            .get_share_photos_paginated(share.id, page.next_cursor.as_ref(), 1)
+            .await?;
+        assert_eq!(page.photos.len(), 1);
+        assert_eq!(page.photos[0].id, p2.id);
+        // Trashed and unshared photos are never exposed
+        assert!(!page.has_more);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_get_shared_photo(pool: SqlitePool) -> sqlx::Result<()> {
+        let user = create_test_user("user1", "Test User");
+        insert_test_user(&pool, &user).await?;
+
+        let mut tx = pool.begin().await?;
+        let in_folder = tx
+            .insert_photo(&create_test_photo(
+                0,
+                Some("user1"),
+                Some("vacation"),
+                "v.jpg",
+            ))
+            .await?;
+        let public_in_folder = tx
+            .insert_photo(&create_test_photo(0, None, Some("vacation"), "pv.jpg"))
+            .await?;
+        let outside = tx
+            .insert_photo(&create_test_photo(0, Some("user1"), None, "o.jpg"))
+            .await?;
+        tx.commit().await?;
+
+        // Personal folder share
+        let folder_share = pool
+            .insert_share(&create_test_share("folder", "user1", Some("vacation")))
+            .await?;
+        assert!(
+            pool.get_shared_photo(&folder_share, in_folder.id)
+                .await?
+                .is_some()
+        );
+        assert!(
+            pool.get_shared_photo(&folder_share, public_in_folder.id)
+                .await?
+                .is_none()
+        );
+        assert!(
+            pool.get_shared_photo(&folder_share, outside.id)
+                .await?
+                .is_none()
+        );
+
+        // Family folder share
+        let mut public_share = create_test_share("public", "user1", Some("vacation"));
+        public_share.folder_is_public = true;
+        let public_share = pool.insert_share(&public_share).await?;
+        assert!(
+            pool.get_shared_photo(&public_share, public_in_folder.id)
+                .await?
+                .is_some()
+        );
+        assert!(
+            pool.get_shared_photo(&public_share, in_folder.id)
+                .await?
+                .is_none()
+        );
+
+        // Photo list share
+        let list_share = pool
+            .insert_share(&create_test_share("list", "user1", None))
+            .await?;
+        pool.insert_share_photos(list_share.id, &[outside.id])
+            .await?;
+        assert!(
+            pool.get_shared_photo(&list_share, outside.id)
+                .await?
+                .is_some()
+        );
+        assert!(
+            pool.get_shared_photo(&list_share, in_folder.id)
+                .await?
+                .is_none()
+        );
+
+        Ok(())
+    }
+}
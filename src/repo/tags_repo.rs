@@ -0,0 +1,406 @@
+use crate::model::photo::Photo;
+use crate::model::photo_category::PhotoCategory;
+use crate::model::tag::{PhotoTag, Tag};
+use crate::repo::PhotoMetadataRepo;
+use crate::repo::event_log::EventLogRepo;
+use serde::Serialize;
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, SqliteTransaction, query, query_as};
+use std::collections::{BTreeSet, HashMap};
+
+/// Tag with photo count for display
+#[derive(Serialize)]
+pub struct TagInfo {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "count")]
+    pub photo_count: i64,
+    pub cover_photo_id: i64,
+}
+
+pub trait TagsRepo<'c>: SqliteExecutor<'c> {
+    async fn get_tag(self, id: i64) -> sqlx::Result<Option<Tag>> {
+        query_as!(Tag, "select * from tags where id = $1", id)
+            .fetch_optional(self)
+            .await
+    }
+
+    async fn get_photo_tags(self, photo_id: i64) -> sqlx::Result<Vec<Tag>> {
+        query_as!(
+            Tag,
+            "select t.* from tags t
+             inner join photo_tags pt on pt.tag_id = t.id
+             where pt.photo_id = $1
+             order by t.name collate nocase",
+            photo_id
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    /// The tag names of the given photos
+    async fn get_photos_tags(self, photo_ids: &[i64]) -> sqlx::Result<Vec<PhotoTag>> {
+        if photo_ids.is_empty() {
+            // An empty vector would cause a SQL syntax error
+            return Ok(Vec::new());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "select pt.photo_id, t.name from photo_tags pt
+             inner join tags t on t.id = pt.tag_id
+             where pt.photo_id in (",
+        );
+        let mut sep = qb.separated(", ");
+        for id in photo_ids {
+            sep.push_bind(*id);
+        }
+        sep.push_unseparated(") order by t.name collate nocase");
+
+        qb.build_query_as().fetch_all(self).await
+    }
+
+    /// The tags of the photos visible to the user, with the most recent photo as cover
+    async fn get_tags_with_counts(
+        self,
+        user_id: &str,
+        category: PhotoCategory,
+    ) -> sqlx::Result<Vec<TagInfo>> {
+        match category {
+            PhotoCategory::Personal => {
+                query_as!(
+                    TagInfo,
+                    r#"select
+                        t.id as "id!: i64",
+                        t.name as "name!",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then photo_id end) as "cover_photo_id!: i64"
+                    from (
+                        select pt.tag_id, p.id as photo_id,
+                               row_number() over (partition by pt.tag_id order by p.created_at desc) as rn
+                        from photos p
+                        inner join photo_tags pt on pt.photo_id = p.id
+                        where p.user_id = $1
+                          and p.trashed_on is null
+                    )
+                    inner join tags t on t.id = tag_id
+                    group by t.id
+                    order by t.name collate nocase"#,
+                    user_id
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Family => {
+                query_as!(
+                    TagInfo,
+                    r#"select
+                        t.id as "id!: i64",
+                        t.name as "name!",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then photo_id end) as "cover_photo_id!: i64"
+                    from (
+                        select pt.tag_id, p.id as photo_id,
+                               row_number() over (partition by pt.tag_id order by p.created_at desc) as rn
+                        from photos p
+                        inner join photo_tags pt on pt.photo_id = p.id
+                        where p.user_id is null
+                          and p.trashed_on is null
+                    )
+                    inner join tags t on t.id = tag_id
+                    group by t.id
+                    order by t.name collate nocase"#
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::Shared => {
+                query_as!(
+                    TagInfo,
+                    r#"select
+                        t.id as "id!: i64",
+                        t.name as "name!",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then photo_id end) as "cover_photo_id!: i64"
+                    from (
+                        select pt.tag_id, p.id as photo_id,
+                               row_number() over (partition by pt.tag_id order by p.created_at desc) as rn
+                        from photos p
+                        inner join photo_tags pt on pt.photo_id = p.id
+                        inner join shared_folders sf on sf.owner_id = p.user_id and sf.folder = p.folder and sf.member_id = $1
+                        where p.trashed_on is null
+                    )
+                    inner join tags t on t.id = tag_id
+                    group by t.id
+                    order by t.name collate nocase"#,
+                    user_id
+                )
+                .fetch_all(self)
+                .await
+            }
+            PhotoCategory::All => {
+                query_as!(
+                    TagInfo,
+                    r#"select
+                        t.id as "id!: i64",
+                        t.name as "name!",
+                        count(*) as "photo_count!: i64",
+                        max(case when rn = 1 then photo_id end) as "cover_photo_id!: i64"
+                    from (
+                        select pt.tag_id, p.id as photo_id,
+                               row_number() over (partition by pt.tag_id order by p.created_at desc) as rn
+                        from photos p
+                        inner join photo_tags pt on pt.photo_id = p.id
+                        where (p.user_id is null or p.user_id = $1 or exists (
+                            select 1 from shared_folders sf
+                            where sf.member_id = $1 and sf.owner_id = p.user_id and sf.folder = p.folder))
+                          and p.trashed_on is null
+                    )
+                    inner join tags t on t.id = tag_id
+                    group by t.id
+                    order by t.name collate nocase"#,
+                    user_id
+                )
+                .fetch_all(self)
+                .await
+            }
+        }
+    }
+
+    /// Creates the missing tags, the names are matched case-insensitively
+    async fn insert_tags(self, names: &[String]) -> sqlx::Result<()> {
+        if names.is_empty() {
+            // An empty vector would cause a SQL syntax error
+            return Ok(());
+        }
+
+        QueryBuilder::<Sqlite>::new("insert into tags (name) ")
+            .push_values(names, |mut b, name| {
+                b.push_bind(name);
+            })
+            .push(" on conflict (name) do nothing")
+            .build()
+            .execute(self)
+            .await
+            .map(|_| ())
+    }
+
+    async fn get_tags_by_name(self, names: &[String]) -> sqlx::Result<Vec<Tag>> {
+        if names.is_empty() {
+            // An empty vector would cause a SQL syntax error
+            return Ok(Vec::new());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new("select * from tags where name in (");
+        let mut sep = qb.separated(", ");
+        for name in names {
+            sep.push_bind(name);
+        }
+        sep.push_unseparated(")");
+
+        qb.build_query_as().fetch_all(self).await
+    }
+
+    async fn delete_unused_tags(self) -> sqlx::Result<u64> {
+        query!("delete from tags where id not in (select tag_id from photo_tags)")
+            .execute(self)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
+
+impl<'c, E> TagsRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+pub trait TagsTransactionRepo {
+    /// Adds and removes tags of the photos and lets the clients know about the photos that
+    /// changed, which are returned. Tags left without photos are deleted
+    async fn update_photos_tags(
+        &mut self,
+        photos: &[Photo],
+        add: &[String],
+        remove: &[String],
+    ) -> sqlx::Result<Vec<i64>>;
+}
+
+impl<'c> TagsTransactionRepo for SqliteTransaction<'c> {
+    async fn update_photos_tags(
+        &mut self,
+        photos: &[Photo],
+        add: &[String],
+        remove: &[String],
+    ) -> sqlx::Result<Vec<i64>> {
+        if photos.is_empty() || (add.is_empty() && remove.is_empty()) {
+            return Ok(Vec::new());
+        }
+
+        let photo_ids: Vec<i64> = photos.iter().map(|photo| photo.id).collect();
+        let tags_before = tag_sets(self.as_mut().get_photos_tags(&photo_ids).await?);
+
+        if !remove.is_empty() {
+            let mut qb: QueryBuilder<Sqlite> =
+                QueryBuilder::new("delete from photo_tags where photo_id in (");
+            let mut sep = qb.separated(", ");
+            for id in &photo_ids {
+                sep.push_bind(*id);
+            }
+            qb.push(") and tag_id in (select id from tags where name in (");
+            let mut sep = qb.separated(", ");
+            for name in remove {
+                sep.push_bind(name);
+            }
+            qb.push("))");
+            qb.build().execute(self.as_mut()).await?;
+        }
+
+        self.as_mut().insert_tags(add).await?;
+        let tags = self.as_mut().get_tags_by_name(add).await?;
+        if !tags.is_empty() {
+            let pairs = photo_ids
+                .iter()
+                .flat_map(|photo_id| tags.iter().map(move |tag| (*photo_id, tag.id)));
+
+            QueryBuilder::<Sqlite>::new("insert or ignore into photo_tags (photo_id, tag_id) ")
+                .push_values(pairs, |mut b, (photo_id, tag_id)| {
+                    b.push_bind(photo_id).push_bind(tag_id);
+                })
+                .build()
+                .execute(self.as_mut())
+                .await?;
+        }
+
+        self.as_mut().delete_unused_tags().await?;
+
+        let tags_after = self.as_mut().get_photos_tags(&photo_ids).await?;
+        let changed_ids: Vec<i64> = {
+            let after = tag_sets(tags_after.clone());
+            photo_ids
+                .iter()
+                .copied()
+                .filter(|id| tags_before.get(id) != after.get(id))
+                .collect()
+        };
+
+        let changed_photos: Vec<Photo> = photos
+            .iter()
+            .filter(|photo| changed_ids.contains(&photo.id))
+            .cloned()
+            .collect();
+        let metadata = self.as_mut().get_photos_metadata(&changed_ids).await?;
+        self.insert_creation_event_logs(&changed_photos, &metadata, &tags_after)
+            .await?;
+
+        Ok(changed_ids)
+    }
+}
+
+/// The lowercase tag names of each photo
+fn tag_sets(tags: Vec<PhotoTag>) -> HashMap<i64, BTreeSet<String>> {
+    let mut sets: HashMap<i64, BTreeSet<String>> = HashMap::new();
+    for tag in tags {
+        sets.entry(tag.photo_id)
+            .or_default()
+            .insert(tag.name.to_lowercase());
+    }
+    sets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::PhotosTransactionRepo;
+    use crate::repo::tests::{create_test_photo, create_test_user, insert_test_user};
+    use sqlx::SqlitePool;
+
+    #[sqlx::test]
+    async fn test_update_photos_tags(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "User 2")).await?;
+
+        let mut tx = pool.begin().await?;
+        let beach = tx
+            .insert_photo(&create_test_photo(0, Some("user1"), None, "beach.jpg"))
+            .await?;
+        let cake = tx
+            .insert_photo(&create_test_photo(0, None, None, "cake.jpg"))
+            .await?;
+        let private = tx
+            .insert_photo(&create_test_photo(0, Some("user2"), None, "private.jpg"))
+            .await?;
+        tx.commit().await?;
+
+        let mut tx = pool.begin().await?;
+        let changed = tx
+            .update_photos_tags(
+                &[beach.clone(), cake.clone()],
+                &["Vacation 2023".to_string(), "Grandma".to_string()],
+                &[],
+            )
+            .await?;
+        tx.update_photos_tags(
+            std::slice::from_ref(&private),
+            &["grandma".to_string()],
+            &[],
+        )
+        .await?;
+        tx.commit().await?;
+        assert_eq!(changed, vec![beach.id, cake.id]);
+
+        // The names are matched case-insensitively, the first spelling is kept
+        let tags = pool.get_photo_tags(private.id).await?;
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].name, "Grandma");
+
+        let counts = pool
+            .get_tags_with_counts("user1", PhotoCategory::All)
+            .await?;
+        let counts: Vec<_> = counts
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.photo_count))
+            .collect();
+        assert_eq!(counts, vec![("Grandma", 2), ("Vacation 2023", 2)]);
+        let family = pool
+            .get_tags_with_counts("user1", PhotoCategory::Family)
+            .await?;
+        assert_eq!(family.len(), 2);
+        assert_eq!(family[0].cover_photo_id, cake.id);
+
+        // Adding a tag again changes nothing, so no event is written
+        let events_before: i64 = sqlx::query_scalar("select count(*) from photos_event_log")
+            .fetch_one(&pool)
+            .await?;
+        let mut tx = pool.begin().await?;
+        let changed = tx
+            .update_photos_tags(std::slice::from_ref(&beach), &["grandma".to_string()], &[])
+            .await?;
+        tx.commit().await?;
+        assert!(changed.is_empty());
+        let events_after: i64 = sqlx::query_scalar("select count(*) from photos_event_log")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(events_before, events_after);
+
+        // The events carry the tags of the photo
+        let mut tx = pool.begin().await?;
+        tx.update_photos_tags(
+            &[beach.clone(), cake.clone()],
+            &[],
+            &["VACATION 2023".to_string()],
+        )
+        .await?;
+        tx.commit().await?;
+        let data: Vec<u8> = sqlx::query_scalar(
+            "select data from photos_event_log where photo_id = $1 order by event_id desc limit 1",
+        )
+        .bind(beach.id)
+        .fetch_one(&pool)
+        .await?;
+        let payload: serde_json::Value = serde_json::from_slice(&data).unwrap();
+        assert_eq!(payload["tags"], serde_json::json!(["Grandma"]));
+
+        // Unused tags are deleted
+        let tags: Vec<String> = sqlx::query_scalar("select name from tags")
+            .fetch_all(&pool)
+            .await?;
+        assert_eq!(tags, vec!["Grandma"]);
+
+        Ok(())
+    }
+}
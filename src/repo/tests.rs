@@ -26,6 +26,8 @@ pub fn create_test_photo(
         folder: folder.map(String::from),
         thumb_hash: None,
         trashed_on: None,
+        caption: None,
+        rating: 0,
     }
 }
 
@@ -46,6 +48,8 @@ pub fn create_test_photo_with_time(
         folder: folder.map(String::from),
         thumb_hash: None,
         trashed_on: None,
+        caption: None,
+        rating: 0,
     }
 }
 
@@ -55,16 +59,18 @@ pub fn create_test_user(id: &str, display_name: &str) -> User {
         id: id.to_string(),
         name: display_name.to_string(),
         password_hash: "$argon2id$v=19$m=19456,t=2,p=1$test$testhash".to_string(),
+        is_admin: false,
     }
 }
 
 /// Insert a test user into the database
 pub async fn insert_test_user(pool: &SqlitePool, user: &User) -> sqlx::Result<()> {
     sqlx::query!(
-        "insert into users (id, name, password_hash) values ($1, $2, $3)",
+        "insert into users (id, name, password_hash, is_admin) values ($1, $2, $3, $4)",
         user.id,
         user.name,
-        user.password_hash
+        user.password_hash,
+        user.is_admin
     )
     .execute(pool)
     .await?;
@@ -75,7 +81,10 @@ pub async fn insert_test_user(pool: &SqlitePool, user: &User) -> sqlx::Result<()
 #[cfg(test)]
 mod integration {
     use super::*;
-    use crate::repo::{FavoritesRepo, PhotosRepo, PhotosTransactionRepo, UserEventLogError};
+    use crate::repo::{
+        AlbumsRepo, FavoritesRepo, PhotosRepo, PhotosTransactionRepo, UserEventLogError,
+        users_repo::UsersRepository,
+    };
     use time::macros::datetime;
 
     #[sqlx::test]
@@ -170,7 +179,7 @@ mod integration {
         // Get paginated photos
         let mut tx = pool.begin().await?;
         let paginated = tx
-            .get_photos_paginated("user1", PhotoCategory::All, None, 2)
+            .get_photos_paginated("user1", PhotoCategory::All, 0, None, 2)
             .await?;
         tx.commit().await?;
 
@@ -353,4 +362,134 @@ mod integration {
 
         Ok(())
     }
+
+    #[sqlx::test]
+    async fn test_album_visibility(pool: SqlitePool) -> sqlx::Result<()> {
+        for id in ["owner", "member", "other"] {
+            insert_test_user(&pool, &create_test_user(id, id)).await?;
+        }
+
+        let mut tx = pool.begin().await?;
+        let shared = tx
+            .insert_photo(&create_test_photo(
+                0,
+                Some("owner"),
+                Some("Parents"),
+                "a.jpg",
+            ))
+            .await?;
+        let private = tx
+            .insert_photo(&create_test_photo(
+                0,
+                Some("owner"),
+                Some("Private"),
+                "b.jpg",
+            ))
+            .await?;
+        tx.commit().await?;
+
+        let mut tx = pool.begin().await?;
+        let last_event_id = tx
+            .get_photos_by_user_and_public("member")
+            .await?
+            .event_log_id;
+        tx.commit().await?;
+
+        let album = pool.insert_album("owner", "Parents").await?;
+        pool.insert_album_member(album.id, "member").await?;
+
+        // Only the shared folder is visible to the member, and only for reading
+        assert!(pool.get_visible_photo(shared.id, "member").await?.is_some());
+        assert!(
+            pool.get_visible_photo(private.id, "member")
+                .await?
+                .is_none()
+        );
+        assert!(pool.get_visible_photo(shared.id, "other").await?.is_none());
+        assert!(pool.get_photo(shared.id, "member").await?.is_none());
+
+        let paginated = pool
+            .get_photos_paginated("member", PhotoCategory::Shared, 0, None, 10)
+            .await?;
+        assert_eq!(paginated.photos, vec![shared.clone()]);
+
+        let paginated = pool
+            .get_photos_paginated("member", PhotoCategory::All, 0, None, 10)
+            .await?;
+        assert_eq!(paginated.photos.len(), 1);
+
+        let folders = pool
+            .get_folders_with_counts("member", PhotoCategory::Shared)
+            .await?;
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].name, "Parents");
+
+        let mut tx = pool.begin().await?;
+        let full_list = tx.get_photos_by_user_and_public("member").await?;
+        assert_eq!(full_list.photos.len(), 1);
+        assert_eq!(full_list.photos[0].photo, shared);
+
+        // Only changes to the shared folder are synced to the member
+        let mut renamed_shared = shared.clone();
+        renamed_shared.name = "c.jpg".to_string();
+        tx.update_photo(&renamed_shared).await?;
+        let mut renamed_private = private.clone();
+        renamed_private.name = "d.jpg".to_string();
+        tx.update_photo(&renamed_private).await?;
+        tx.commit().await?;
+
+        let mut tx = pool.begin().await?;
+        let events = tx
+            .get_events_for_user(last_event_id, "member")
+            .await
+            .expect("Should get events after valid event_id");
+        tx.commit().await?;
+
+        assert_eq!(events.events.len(), 1);
+        assert_eq!(events.events[0].photo_id, shared.id);
+
+        // Removing the member hides the album again
+        pool.delete_album_member(album.id, "member").await?;
+        let paginated = pool
+            .get_photos_paginated("member", PhotoCategory::Shared, 0, None, 10)
+            .await?;
+        assert!(paginated.photos.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_delete_user_with_favorites(pool: SqlitePool) -> sqlx::Result<()> {
+        let users_repo = UsersRepository::new(pool.clone());
+        for id in ["user1", "user2"] {
+            insert_test_user(&pool, &create_test_user(id, id)).await?;
+        }
+
+        let mut tx = pool.begin().await?;
+        let own = tx
+            .insert_photo(&create_test_photo(0, Some("user1"), None, "a.jpg"))
+            .await?;
+        let public = tx
+            .insert_photo(&create_test_photo(0, None, None, "b.jpg"))
+            .await?;
+        tx.commit().await?;
+
+        // Favorites both by and of the deleted user must not block the deletion
+        pool.insert_favorite(own.id, "user1").await?;
+        pool.insert_favorite(own.id, "user2").await?;
+        pool.insert_favorite(public.id, "user1").await?;
+
+        users_repo.delete_user("user1").await?;
+
+        assert!(users_repo.get_user("user1").await.is_none());
+        assert!(pool.get_photo(own.id, "user1").await?.is_none());
+        assert!(
+            pool.check_favorite(public.id, "user1")
+                .await
+                .is_ok_and(|f| !f)
+        );
+        assert!(pool.get_favorite_photos("user2").await?.is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,192 @@
+use crate::model::two_factor::UserTotp;
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, query, query_as, query_scalar};
+
+pub trait TwoFactorRepo<'c>: SqliteExecutor<'c> {
+    async fn get_totp(self, user_id: &str) -> sqlx::Result<Option<UserTotp>> {
+        query_as!(
+            UserTotp,
+            "select * from user_totp where user_id = $1",
+            user_id
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    async fn check_totp_enabled(self, user_id: &str) -> sqlx::Result<bool> {
+        query_scalar!(
+            r#"select exists(select 1 from user_totp where user_id = $1 and enabled) as "enabled!: bool""#,
+            user_id
+        )
+        .fetch_one(self)
+        .await
+    }
+
+    /// Stores a new secret that still has to be confirmed.
+    /// An enabled secret is never replaced, it has to be deleted first
+    async fn insert_pending_totp(self, user_id: &str, secret: &str) -> sqlx::Result<bool> {
+        query!(
+            "insert into user_totp (user_id, secret) values ($1, $2)
+             on conflict (user_id) do update
+                 set secret = excluded.secret, created_at = current_timestamp
+                 where not user_totp.enabled",
+            user_id,
+            secret
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    async fn enable_totp(self, user_id: &str) -> sqlx::Result<bool> {
+        query!(
+            "update user_totp set enabled = true where user_id = $1",
+            user_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    /// Fails if a code of the same or a later time step was already used,
+    /// which also stops two requests from using the same code at once
+    async fn use_totp_step(self, user_id: &str, step: i64) -> sqlx::Result<bool> {
+        query!(
+            "update user_totp set last_used_step = $2
+             where user_id = $1 and (last_used_step is null or last_used_step < $2)",
+            user_id,
+            step
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    async fn delete_totp(self, user_id: &str) -> sqlx::Result<bool> {
+        query!("delete from user_totp where user_id = $1", user_id)
+            .execute(self)
+            .await
+            .map(|result| result.rows_affected() != 0)
+    }
+
+    async fn count_recovery_codes(self, user_id: &str) -> sqlx::Result<i64> {
+        query_scalar!(
+            r#"select count(*) as "count!: i64" from user_recovery_codes where user_id = $1"#,
+            user_id
+        )
+        .fetch_one(self)
+        .await
+    }
+
+    async fn insert_recovery_codes(
+        self,
+        user_id: &str,
+        code_hashes: &[String],
+    ) -> sqlx::Result<()> {
+        if code_hashes.is_empty() {
+            // An empty vector would cause a SQL syntax error
+            return Ok(());
+        }
+
+        QueryBuilder::<Sqlite>::new("insert into user_recovery_codes (user_id, code_hash) ")
+            .push_values(code_hashes, |mut b, code_hash| {
+                b.push_bind(user_id).push_bind(code_hash);
+            })
+            .build()
+            .execute(self)
+            .await
+            .map(|_| ())
+    }
+
+    async fn delete_recovery_codes(self, user_id: &str) -> sqlx::Result<()> {
+        query!(
+            "delete from user_recovery_codes where user_id = $1",
+            user_id
+        )
+        .execute(self)
+        .await
+        .map(|_| ())
+    }
+
+    /// Recovery codes can only be used once. Returns true if the code was valid
+    async fn use_recovery_code(self, user_id: &str, code_hash: &str) -> sqlx::Result<bool> {
+        query!(
+            "delete from user_recovery_codes
+             where id = (select id from user_recovery_codes where user_id = $1 and code_hash = $2 limit 1)",
+            user_id,
+            code_hash
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+}
+
+impl<'c, E> TwoFactorRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::{create_test_user, insert_test_user};
+    use sqlx::SqlitePool;
+
+    #[sqlx::test]
+    async fn test_totp_enrollment(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "Test User")).await?;
+
+        assert!(pool.get_totp("user1").await?.is_none());
+        assert!(pool.insert_pending_totp("user1", "SECRET1").await?);
+        assert!(!pool.check_totp_enabled("user1").await?);
+
+        // A pending secret can be replaced until it is confirmed
+        assert!(pool.insert_pending_totp("user1", "SECRET2").await?);
+        assert!(pool.enable_totp("user1").await?);
+        assert!(pool.check_totp_enabled("user1").await?);
+
+        assert!(!pool.insert_pending_totp("user1", "SECRET3").await?);
+        let totp = pool.get_totp("user1").await?.unwrap();
+        assert_eq!(totp.secret, "SECRET2");
+        assert!(totp.enabled);
+
+        assert!(pool.delete_totp("user1").await?);
+        assert!(!pool.check_totp_enabled("user1").await?);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_totp_steps_are_single_use(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "Test User")).await?;
+        pool.insert_pending_totp("user1", "SECRET1").await?;
+
+        assert!(pool.use_totp_step("user1", 100).await?);
+        assert!(!pool.use_totp_step("user1", 100).await?);
+        assert!(!pool.use_totp_step("user1", 99).await?);
+        assert!(pool.use_totp_step("user1", 101).await?);
+        assert_eq!(
+            pool.get_totp("user1").await?.unwrap().last_used_step,
+            Some(101)
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_recovery_codes_are_single_use(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "Test User")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "Other User")).await?;
+
+        let hashes = vec!["hash1".to_string(), "hash2".to_string()];
+        pool.insert_recovery_codes("user1", &hashes).await?;
+        assert_eq!(pool.count_recovery_codes("user1").await?, 2);
+
+        assert!(!pool.use_recovery_code("user2", "hash1").await?);
+        assert!(pool.use_recovery_code("user1", "hash1").await?);
+        assert!(!pool.use_recovery_code("user1", "hash1").await?);
+        assert_eq!(pool.count_recovery_codes("user1").await?, 1);
+
+        pool.delete_recovery_codes("user1").await?;
+        assert_eq!(pool.count_recovery_codes("user1").await?, 0);
+
+        Ok(())
+    }
+}
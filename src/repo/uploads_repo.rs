@@ -0,0 +1,148 @@
+use crate::model::upload::Upload;
+use sqlx::{SqliteExecutor, query, query_as, query_scalar};
+use time::OffsetDateTime;
+
+pub trait UploadsRepo<'c>: SqliteExecutor<'c> {
+    async fn get_upload(self, id: &str, user_id: &str) -> sqlx::Result<Option<Upload>> {
+        query_as!(
+            Upload,
+            "select * from uploads where id = $1 and user_id = $2",
+            id,
+            user_id
+        )
+        .fetch_optional(self)
+        .await
+    }
+
+    async fn insert_upload(self, upload: &Upload) -> sqlx::Result<Upload> {
+        query_as!(
+            Upload,
+            "insert into uploads (id, user_id, length, metadata, file_name, time_created, last_modified, folder, make_public, photo_id, expires_at)
+             values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) returning *",
+            upload.id,
+            upload.user_id,
+            upload.length,
+            upload.metadata,
+            upload.file_name,
+            upload.time_created,
+            upload.last_modified,
+            upload.folder,
+            upload.make_public,
+            upload.photo_id,
+            upload.expires_at
+        )
+        .fetch_one(self)
+        .await
+    }
+
+    /// Called when data is received, so the uploads that are still in progress don't expire
+    async fn extend_upload(self, id: &str, expires_at: OffsetDateTime) -> sqlx::Result<()> {
+        query!(
+            "update uploads set expires_at = $2 where id = $1",
+            id,
+            expires_at
+        )
+        .execute(self)
+        .await
+        .map(|_| ())
+    }
+
+    async fn complete_upload(self, id: &str, photo_id: i64) -> sqlx::Result<()> {
+        query!(
+            "update uploads set photo_id = $2 where id = $1",
+            id,
+            photo_id
+        )
+        .execute(self)
+        .await
+        .map(|_| ())
+    }
+
+    async fn delete_upload(self, id: &str) -> sqlx::Result<()> {
+        query!("delete from uploads where id = $1", id)
+            .execute(self)
+            .await
+            .map(|_| ())
+    }
+
+    /// Returns the ids of the deleted uploads, whose files have to be removed
+    async fn delete_expired_uploads(self) -> sqlx::Result<Vec<String>> {
+        let now = OffsetDateTime::now_utc();
+
+        query_scalar!(
+            "delete from uploads where expires_at <= $1 returning id",
+            now
+        )
+        .fetch_all(self)
+        .await
+    }
+}
+
+impl<'c, E> UploadsRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::{create_test_user, insert_test_user};
+    use sqlx::SqlitePool;
+    use time::Duration;
+
+    fn create_test_upload(id: &str, user_id: &str) -> Upload {
+        Upload {
+            id: id.to_string(),
+            user_id: user_id.to_string(),
+            length: 1024,
+            metadata: String::new(),
+            file_name: "video.mp4".to_string(),
+            time_created: Some(OffsetDateTime::UNIX_EPOCH),
+            last_modified: None,
+            folder: None,
+            make_public: false,
+            photo_id: None,
+            expires_at: OffsetDateTime::now_utc() + Duration::days(1),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_insert_and_get_upload(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "User 2")).await?;
+
+        let new_upload = create_test_upload("upload1", "user1");
+        let upload = pool.insert_upload(&new_upload).await?;
+        assert_eq!(upload, new_upload);
+
+        assert_eq!(
+            pool.get_upload("upload1", "user1").await?,
+            Some(upload.clone())
+        );
+        // Only the owner can continue an upload
+        assert_eq!(pool.get_upload("upload1", "user2").await?, None);
+
+        let expires_at = upload.expires_at + Duration::hours(1);
+        pool.extend_upload("upload1", expires_at).await?;
+        let upload = pool.get_upload("upload1", "user1").await?.unwrap();
+        assert_eq!(upload.expires_at, expires_at);
+
+        pool.delete_upload("upload1").await?;
+        assert_eq!(pool.get_upload("upload1", "user1").await?, None);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn test_delete_expired_uploads(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "User 1")).await?;
+
+        let mut expired = create_test_upload("expired", "user1");
+        expired.expires_at = OffsetDateTime::now_utc() - Duration::hours(1);
+        assert!(pool.insert_upload(&expired).await?.is_expired());
+        pool.insert_upload(&create_test_upload("valid", "user1"))
+            .await?;
+
+        assert_eq!(pool.delete_expired_uploads().await?, vec!["expired"]);
+        assert!(pool.get_upload("valid", "user1").await?.is_some());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use crate::model::user_session::UserSession;
+use sqlx::{SqliteExecutor, query, query_as};
+use time::{Duration, OffsetDateTime};
+
+/// `last_seen_at` is only updated once per interval to avoid a write on every request
+const LAST_SEEN_PRECISION: Duration = Duration::minutes(1);
+
+pub trait UserSessionsRepo<'c>: SqliteExecutor<'c> {
+    async fn get_user_session(self, id: &str) -> sqlx::Result<Option<UserSession>> {
+        query_as!(UserSession, "select * from user_sessions where id = $1", id)
+            .fetch_optional(self)
+            .await
+    }
+
+    async fn get_user_sessions(self, user_id: &str) -> sqlx::Result<Vec<UserSession>> {
+        query_as!(
+            UserSession,
+            "select * from user_sessions where user_id = $1 order by last_seen_at desc",
+            user_id
+        )
+        .fetch_all(self)
+        .await
+    }
+
+    async fn insert_user_session(
+        self,
+        id: &str,
+        user_id: &str,
+        now: OffsetDateTime,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> sqlx::Result<()> {
+        query!(
+            "insert into user_sessions (id, user_id, created_at, last_seen_at, ip, user_agent)
+             values ($1, $2, $3, $3, $4, $5)",
+            id,
+            user_id,
+            now,
+            ip,
+            user_agent
+        )
+        .execute(self)
+        .await
+        .map(|_| ())
+    }
+
+    async fn update_user_session_last_seen(
+        self,
+        id: &str,
+        now: OffsetDateTime,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> sqlx::Result<()> {
+        let updated_before = now - LAST_SEEN_PRECISION;
+
+        query!(
+            "update user_sessions set last_seen_at = $2, ip = $3, user_agent = $4
+             where id = $1 and last_seen_at < $5",
+            id,
+            now,
+            ip,
+            user_agent,
+            updated_before
+        )
+        .execute(self)
+        .await
+        .map(|_| ())
+    }
+
+    /// Returns true if the session existed and belonged to the user, if one is given
+    async fn delete_user_session(self, id: &str, user_id: Option<&str>) -> sqlx::Result<bool> {
+        query!(
+            "delete from user_sessions where id = $1 and ($2 is null or user_id = $2)",
+            id,
+            user_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    /// Deletes the sessions of a user, or of everyone if no user is given, except for `keep_id`
+    async fn delete_user_sessions(
+        self,
+        user_id: Option<&str>,
+        keep_id: Option<&str>,
+    ) -> sqlx::Result<u64> {
+        query!(
+            "delete from user_sessions
+             where ($1 is null or user_id = $1) and ($2 is null or id != $2)",
+            user_id,
+            keep_id
+        )
+        .execute(self)
+        .await
+        .map(|result| result.rows_affected())
+    }
+
+    /// Sessions that have not been used since `before` have expired in the session store
+    async fn delete_stale_user_sessions(self, before: OffsetDateTime) -> sqlx::Result<u64> {
+        query!("delete from user_sessions where last_seen_at < $1", before)
+            .execute(self)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// Removes every session from the session store, including the ones that were never tracked.
+    /// The table is created by the session store at startup, so it can't be checked at compile time
+    async fn delete_all_session_records(self) -> sqlx::Result<u64> {
+        sqlx::query("delete from tower_sessions")
+            .execute(self)
+            .await
+            .map(|result| result.rows_affected())
+    }
+}
+
+impl<'c, E> UserSessionsRepo<'c> for E where E: SqliteExecutor<'c> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::tests::{create_test_user, insert_test_user};
+    use sqlx::SqlitePool;
+
+    #[sqlx::test]
+    async fn test_user_session_lifecycle(pool: SqlitePool) -> sqlx::Result<()> {
+        insert_test_user(&pool, &create_test_user("user1", "Test User")).await?;
+        insert_test_user(&pool, &create_test_user("user2", "Other User")).await?;
+
+        let now = OffsetDateTime::now_utc();
+        pool.insert_user_session("phone", "user1", now, Some("10.0.0.2"), Some("Phone"))
+            .await?;
+        pool.insert_user_session("laptop", "user1", now, None, None)
+            .await?;
+        pool.insert_user_session("other", "user2", now, None, None)
+            .await?;
+
+        // Requests close to each other don't update the session
+        pool.update_user_session_last_seen("phone", now + Duration::seconds(5), None, None)
+            .await?;
+        let session = pool.get_user_session("phone").await?.unwrap();
+        assert_eq!(session.last_seen_at, now);
+        assert_eq!(session.ip.as_deref(), Some("10.0.0.2"));
+
+        let later = now + Duration::minutes(5);
+        pool.update_user_session_last_seen("phone", later, Some("10.0.0.3"), Some("Phone"))
+            .await?;
+        let session = pool.get_user_session("phone").await?.unwrap();
+        assert_eq!(session.created_at, now);
+        assert_eq!(session.last_seen_at, later);
+        assert_eq!(session.ip.as_deref(), Some("10.0.0.3"));
+
+        // The most recently used session comes first
+        let sessions = pool.get_user_sessions("user1").await?;
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].id, "phone");
+
+        // Users can only revoke their own sessions
+        assert!(!pool.delete_user_session("other", Some("user1")).await?);
+
+        assert_eq!(
+            pool.delete_user_sessions(Some("user1"), Some("phone"))
+                .await?,
+            1
+        );
+        assert!(pool.get_user_session("laptop").await?.is_none());
+        assert!(pool.get_user_session("other").await?.is_some());
+
+        assert_eq!(pool.delete_stale_user_sessions(later).await?, 1);
+        assert!(pool.get_user_session("other").await?.is_none());
+        assert!(pool.get_user_session("phone").await?.is_some());
+
+        Ok(())
+    }
+}
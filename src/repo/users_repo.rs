@@ -1,18 +1,46 @@
-use crate::model::user::{User, UserCredentials};
-use crate::utils::password_hash::validate_credentials;
+use crate::model::api_token::{ApiTokenScope, hash_api_token};
+use crate::model::user::{Credentials, User, UserCredentials, is_valid_user_id};
+use crate::repo::ApiTokensRepo;
+use crate::utils::password_hash::{generate_hash_from_password, validate_credentials};
 use argon2::password_hash;
 use axum_login::{AuthnBackend, UserId};
-use sqlx::{SqlitePool, query, query_as};
+use sqlx::{SqlitePool, SqliteTransaction, query, query_as};
+use std::fs;
+use std::path::PathBuf;
+use time::OffsetDateTime;
 use tokio::task;
+use tracing::{info, warn};
 
 #[derive(Clone)]
 pub struct UsersRepository {
     pool: SqlitePool,
+    /// Where the folders of the users authenticated externally are created
+    provisioning_folder: Option<PathBuf>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error(transparent)]
+    PasswordHash(#[from] password_hash::Error),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error("Failed to create the folder of the user: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 impl UsersRepository {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            provisioning_folder: None,
+        }
+    }
+
+    /// Allows creating the users authenticated by the reverse proxy or the identity provider
+    /// on their first login, along with their folder in `storage_folder`
+    pub fn with_user_provisioning(mut self, storage_folder: PathBuf) -> Self {
+        self.provisioning_folder = Some(storage_folder);
+        self
     }
 
     pub async fn get_user(&self, user_id: &str) -> Option<User> {
@@ -23,40 +51,134 @@ impl UsersRepository {
     }
 
     pub async fn get_users(&self) -> sqlx::Result<Vec<User>> {
-        query_as!(User, "select * from users")
+        query_as!(User, "select * from users order by id")
             .fetch_all(&self.pool)
             .await
     }
 
     pub async fn insert_user(&self, user: &User) -> sqlx::Result<()> {
         query!(
-            "insert into users (id, name, password_hash) values ($1, $2, $3)",
+            "insert into users (id, name, password_hash, is_admin) values ($1, $2, $3, $4)",
             user.id,
             user.name,
-            user.password_hash
+            user.password_hash,
+            user.is_admin
         )
         .execute(&self.pool)
         .await
         .map(|_| ())
     }
 
-    pub async fn delete_user(&self, user_id: &str) -> sqlx::Result<()> {
-        query!("delete from users where id = $1", user_id)
+    pub async fn update_name(&self, user_id: &str, name: &str) -> sqlx::Result<bool> {
+        query!("update users set name = $2 where id = $1", user_id, name)
             .execute(&self.pool)
             .await
-            .map(|_| ())
+            .map(|result| result.rows_affected() != 0)
+    }
+
+    pub async fn update_password_hash(
+        &self,
+        user_id: &str,
+        password_hash: &str,
+    ) -> sqlx::Result<bool> {
+        query!(
+            "update users set password_hash = $2 where id = $1",
+            user_id,
+            password_hash
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    pub async fn update_is_admin(&self, user_id: &str, is_admin: bool) -> sqlx::Result<bool> {
+        query!(
+            "update users set is_admin = $2 where id = $1",
+            user_id,
+            is_admin
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() != 0)
+    }
+
+    /// [`UsersTransactionRepo::delete_user`] in its own transaction
+    pub async fn delete_user(&self, user_id: &str) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        tx.delete_user(user_id).await?;
+        tx.commit().await
     }
 }
 
-impl AuthnBackend for UsersRepository {
-    type User = User;
-    type Credentials = UserCredentials;
-    type Error = password_hash::Error;
+pub trait UsersTransactionRepo {
+    /// Deletes the user along with the database entries of their photos, the files are left untouched
+    async fn delete_user(&mut self, user_id: &str) -> sqlx::Result<()>;
+}
 
-    async fn authenticate(
+impl UsersTransactionRepo for SqliteTransaction<'_> {
+    async fn delete_user(&mut self, user_id: &str) -> sqlx::Result<()> {
+        // Favorites don't cascade and would block the deletion
+        query!(
+            "delete from favorite_photos where user_id = $1 or photo_id in (select id from photos where user_id = $1)",
+            user_id
+        )
+        .execute(self.as_mut())
+        .await?;
+
+        // Let the other clients know the photos are gone
+        query!(
+            "insert into photos_event_log (photo_id, user_id, folder) select id, user_id, folder from photos where user_id = $1",
+            user_id
+        )
+        .execute(self.as_mut())
+        .await?;
+
+        // The search index isn't a real table, so it doesn't cascade
+        query!(
+            "delete from photos_search where rowid in (select id from photos where user_id = $1)",
+            user_id
+        )
+        .execute(self.as_mut())
+        .await?;
+
+        query!("delete from users where id = $1", user_id)
+            .execute(self.as_mut())
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl UsersRepository {
+    /// Looks up the owner of an API token, and records when the token was last used
+    pub async fn authenticate_token(
         &self,
-        creds: Self::Credentials,
-    ) -> Result<Option<Self::User>, Self::Error> {
+        token: &str,
+    ) -> sqlx::Result<Option<(User, ApiTokenScope)>> {
+        let Some(api_token) = self
+            .pool
+            .get_api_token_by_hash(&hash_api_token(token))
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        self.pool
+            .update_api_token_last_used(api_token.id, OffsetDateTime::now_utc())
+            .await?;
+
+        Ok(self
+            .get_user(&api_token.user_id)
+            .await
+            .map(|user| (user, api_token.scope)))
+    }
+}
+
+impl UsersRepository {
+    async fn authenticate_password(
+        &self,
+        creds: UserCredentials,
+    ) -> Result<Option<User>, password_hash::Error> {
         if let Some(user) = self.get_user(&creds.user_id).await {
             return task::spawn_blocking(|| {
                 Ok(
@@ -74,6 +196,75 @@ impl AuthnBackend for UsersRepository {
         Ok(None)
     }
 
+    async fn authenticate_external_user(
+        &self,
+        user_id: String,
+        name: Option<String>,
+        create_if_missing: bool,
+    ) -> Result<Option<User>, AuthError> {
+        if let Some(user) = self.get_user(&user_id).await {
+            return Ok(Some(user));
+        }
+
+        let Some(storage_folder) = self
+            .provisioning_folder
+            .as_ref()
+            .filter(|_| create_if_missing)
+        else {
+            return Ok(None);
+        };
+
+        if !is_valid_user_id(&user_id) {
+            warn!("Not creating user with invalid id {user_id:?}");
+            return Ok(None);
+        }
+
+        // The password can't be used, the user always logs in externally
+        let random_password = uuid::Uuid::new_v4().simple().to_string();
+        let user = User {
+            name: name
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| user_id.clone()),
+            id: user_id,
+            password_hash: task::spawn_blocking(move || {
+                generate_hash_from_password(random_password)
+            })
+            .await
+            .expect("Password hashing failed unexpectedly"),
+            is_admin: false,
+        };
+
+        fs::create_dir_all(storage_folder.join(&user.id))?;
+        self.insert_user(&user).await?;
+        info!("Created externally authenticated user {}", user.id);
+
+        Ok(Some(user))
+    }
+}
+
+impl AuthnBackend for UsersRepository {
+    type User = User;
+    type Credentials = Credentials;
+    type Error = AuthError;
+
+    async fn authenticate(
+        &self,
+        creds: Self::Credentials,
+    ) -> Result<Option<Self::User>, Self::Error> {
+        match creds {
+            Credentials::Password(creds) => Ok(self.authenticate_password(creds).await?),
+            Credentials::External {
+                user_id,
+                name,
+                create_if_missing,
+            } => {
+                self.authenticate_external_user(user_id, name, create_if_missing)
+                    .await
+            }
+        }
+    }
+
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
         Ok(self.get_user(user_id).await)
     }
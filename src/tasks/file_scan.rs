@@ -1,4 +1,5 @@
 use rayon::prelude::*;
+use sqlx::SqliteTransaction;
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::fs;
@@ -8,10 +9,12 @@ use tracing::{error, info, warn};
 use walkdir::{DirEntry, WalkDir};
 
 use crate::http::AppStateRef;
-use crate::model::photo::Photo;
+use crate::model::photo::{MAX_CAPTION_LENGTH, MAX_RATING, Photo};
+use crate::model::tag::normalize_tag_name;
 use crate::model::user::PUBLIC_USER_FOLDER;
-use crate::repo::{PhotosRepo, PhotosTransactionRepo};
+use crate::repo::{PhotosRepo, PhotosTransactionRepo, TagsTransactionRepo};
 use crate::tasks::timestamp_parsing;
+use crate::utils::xmp;
 
 pub async fn scan_new_files(app_state: AppStateRef) -> sqlx::Result<()> {
     let instant = Instant::now();
@@ -48,14 +51,28 @@ pub async fn scan_new_files(app_state: AppStateRef) -> sqlx::Result<()> {
         }
 
         if !new_photos.is_empty() {
+            let (new_photos, keywords): (Vec<Photo>, HashMap<String, Vec<String>>) = new_photos
+                .into_iter()
+                .map(|(photo, keywords)| {
+                    let full_name = photo.full_name();
+                    (photo, (full_name, keywords))
+                })
+                .unzip();
+
             for chunk in new_photos.chunks(1024) {
-                if let Err(e) = tx.insert_photos(chunk).await {
-                    error!("Failed inserting photos: {e}")
+                match tx.insert_photos(chunk).await {
+                    Ok(inserted) => {
+                        if let Err(e) = tag_with_keywords(&mut tx, &inserted, &keywords).await {
+                            error!("Failed tagging photos with their keywords: {e}")
+                        }
+                    }
+                    Err(e) => error!("Failed inserting photos: {e}"),
                 }
             }
         }
 
         tx.commit().await?;
+        app_state.publish_latest_event().await;
     }
 
     info!(
@@ -66,11 +83,29 @@ pub async fn scan_new_files(app_state: AppStateRef) -> sqlx::Result<()> {
     Ok(())
 }
 
+/// Tags the new photos with the keywords of their sidecars
+async fn tag_with_keywords(
+    tx: &mut SqliteTransaction<'_>,
+    photos: &[Photo],
+    keywords: &HashMap<String, Vec<String>>,
+) -> sqlx::Result<()> {
+    for photo in photos {
+        if let Some(keywords) = keywords.get(&photo.full_name())
+            && !keywords.is_empty()
+        {
+            tx.update_photos_tags(std::slice::from_ref(photo), keywords, &[])
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
 fn scan_user_photos(
     user_id: Option<&str>,
     user_folder_path: PathBuf,
     existing_photos: Vec<Photo>,
-) -> (Vec<Photo>, Vec<i64>) {
+) -> (Vec<(Photo, Vec<String>)>, Vec<i64>) {
     if !user_folder_path.exists() {
         if let Err(e) = fs::create_dir(user_folder_path) {
             error!(
@@ -91,7 +126,7 @@ fn scan_user_photos(
         .filter_map(|e| e.ok())
         .filter(|entry| {
             let path = entry.path();
-            !path.is_dir() && path.extension() != json_extension
+            !path.is_dir() && path.extension() != json_extension && !xmp::is_sidecar(path)
         })
         .map(|entry| {
             let filename = entry.file_name().to_string_lossy().to_string();
@@ -116,7 +151,7 @@ fn scan_user_photos(
         .map(|photo| photo.full_name())
         .collect();
 
-    let new_photos: Vec<Photo> = disk_entries_with_name
+    let new_photos: Vec<_> = disk_entries_with_name
         .into_par_iter()
         .filter(|(full_name, _)| !existing_photos_names.contains(full_name))
         .filter_map(|(_, entry)| parse_image(user_id, entry))
@@ -132,14 +167,27 @@ fn scan_user_photos(
     (new_photos, removed_photo_ids)
 }
 
-pub fn parse_image(user_id: Option<&str>, entry: DirEntry) -> Option<Photo> {
+/// Also returns the keywords of the photo's XMP sidecar, whose date, caption and rating are used
+pub fn parse_image(user_id: Option<&str>, entry: DirEntry) -> Option<(Photo, Vec<String>)> {
     let path = entry.path();
+    let sidecar = xmp::read_sidecar(path).unwrap_or_default();
 
-    if let Some(timestamp) = timestamp_parsing::get_timestamp_for_path(path) {
+    if let Some(timestamp) = sidecar
+        .date_taken
+        .or_else(|| timestamp_parsing::get_timestamp_for_path(path))
+    {
         let file_size = entry.metadata().map_or(0i64, |data| data.len() as i64);
         let folder = get_folder_name(&entry);
-
-        Some(Photo {
+        let caption = sidecar
+            .caption
+            .map(|caption| caption.chars().take(MAX_CAPTION_LENGTH).collect());
+        let keywords = sidecar
+            .keywords
+            .iter()
+            .filter_map(|keyword| normalize_tag_name(keyword))
+            .collect();
+
+        let photo = Photo {
             id: 0,
             user_id: user_id.map(ToOwned::to_owned),
             name: entry.file_name().to_string_lossy().to_string(),
@@ -148,7 +196,11 @@ pub fn parse_image(user_id: Option<&str>, entry: DirEntry) -> Option<Photo> {
             folder,
             thumb_hash: None,
             trashed_on: None,
-        })
+            caption,
+            rating: sidecar.rating.clamp(0, MAX_RATING),
+        };
+
+        Some((photo, keywords))
     } else {
         warn!("No timestamp: {}", path.display());
         None
@@ -58,7 +58,7 @@ pub async fn compute_photos_hash(app_state: AppStateRef) -> Result<(), sqlx::Err
     Ok(())
 }
 
-fn compute_hash(path: &Path) -> std::io::Result<Vec<u8>> {
+pub(super) fn compute_hash(path: &Path) -> std::io::Result<Vec<u8>> {
     let hash = blake3::Hasher::new().update_mmap(path)?.finalize();
     let hash = crop_blake_3_hash(hash.as_bytes());
 
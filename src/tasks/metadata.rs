@@ -0,0 +1,97 @@
+use crate::http::AppStateRef;
+use crate::model::photo_metadata::PhotoMetadata;
+use crate::repo::event_log::EventLogRepo;
+use crate::repo::{PhotoMetadataRepo, TagsRepo};
+use crate::utils::exif::read_photo_metadata;
+use crate::utils::ffprobe::probe_video;
+use mime_guess::MimeGuess;
+use rayon::prelude::*;
+use std::path::Path;
+use tokio::task::spawn_blocking;
+use tracing::{error, info};
+
+pub async fn extract_photos_metadata(app_state: AppStateRef) -> Result<(), sqlx::Error> {
+    const CHUNK_SIZE: usize = 256;
+
+    let mut tx = app_state.write_pool.begin().await?;
+    let photos = tx.get_photos_without_metadata().await?;
+
+    if photos.is_empty() {
+        return Ok(());
+    }
+    info!("Reading metadata of {} photos", photos.len());
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    spawn_blocking(move || {
+        photos.par_chunks(CHUNK_SIZE).for_each(|chunk| {
+            let chunk: Vec<_> = chunk
+                .iter()
+                .filter_map(|photo| {
+                    let path = app_state.storage.resolve_photo(photo.partial_path());
+
+                    let metadata = if is_video(&path) {
+                        // Retried on the next run, like failed hashes
+                        probe_video(&path)
+                            .inspect_err(|e| {
+                                error!("Failed to probe video {}: {e}", path.display())
+                            })
+                            .ok()?
+                            .into()
+                    } else {
+                        // Files without EXIF data still get a row, so they aren't read again
+                        read_photo_metadata(&path).unwrap_or_default()
+                    };
+
+                    Some((
+                        photo.clone(),
+                        PhotoMetadata {
+                            photo_id: photo.id,
+                            ..metadata
+                        },
+                    ))
+                })
+                .collect();
+
+            if let Err(e) = sender.send(chunk) {
+                error!("Failed to send metadata over channel: {e}");
+            }
+        });
+
+        drop(sender);
+    });
+
+    let mut metadata_count = 0;
+
+    while let Some(chunk) = receiver.recv().await {
+        let (photos, metadata): (Vec<_>, Vec<_>) = chunk.into_iter().unzip();
+        tx.insert_photos_metadata(&metadata).await?;
+
+        // Let the clients know about the new details
+        let photos: Vec<_> = photos
+            .into_iter()
+            .zip(&metadata)
+            .filter(|(_, metadata)| !metadata.is_empty())
+            .map(|(photo, _)| photo)
+            .collect();
+        let photo_ids: Vec<i64> = photos.iter().map(|photo| photo.id).collect();
+        let tags = tx.get_photos_tags(&photo_ids).await?;
+        tx.insert_creation_event_logs(&photos, &metadata, &tags)
+            .await?;
+
+        metadata_count += photos.len();
+    }
+
+    tx.commit().await?;
+    app_state.publish_latest_event().await;
+
+    info!("Read metadata of {metadata_count} photos");
+
+    Ok(())
+}
+
+fn is_video(path: &Path) -> bool {
+    MimeGuess::from_path(path)
+        .first()
+        .is_some_and(|mime| mime.type_() == mime_guess::mime::VIDEO)
+}
@@ -1,7 +1,11 @@
 mod file_scan;
 mod hash;
+mod metadata;
+mod places;
+mod sidecars;
 mod thumb_hash;
 mod timestamp_parsing;
+mod timestamps;
 mod trash;
 
 pub use file_scan::scan_new_files;
@@ -9,14 +13,22 @@ use std::collections::HashSet;
 use std::fs;
 use std::num::NonZero;
 use std::time::Duration;
+use time::OffsetDateTime;
 use tracing::{debug, error, info};
 
-use crate::http::AppStateRef;
+use crate::http::{AppStateRef, SESSION_EXPIRY};
 use crate::previews::generate_all_previews;
 use crate::repo::event_log::EventLogRepo;
-use crate::repo::{PhotosRepo, PhotosTransactionRepo};
+use crate::repo::{
+    LoginAttemptsRepo, PhotosRepo, PhotosTransactionRepo, SharesRepo, UploadsRepo, UserSessionsRepo,
+};
 pub use crate::tasks::hash::compute_photos_hash;
+pub use crate::tasks::metadata::extract_photos_metadata;
+pub use crate::tasks::places::{import_places, resolve_photo_places};
+pub use crate::tasks::sidecars::write_photos_sidecars;
 use crate::tasks::thumb_hash::generate_thumb_hashes;
+pub use crate::tasks::timestamp_parsing::{TimestampSource, get_timestamp_for_file};
+pub use crate::tasks::timestamps::persist_timestamps;
 use crate::tasks::trash::cleanup_trash;
 
 pub fn start_periodic_tasks(
@@ -57,10 +69,35 @@ pub fn start_periodic_tasks(
                 error!("Failed to compute hashes: {e}");
             }
 
+            if let Err(e) = extract_photos_metadata(app_state).await {
+                error!("Failed to read photos metadata: {e}");
+            }
+
+            // Places are resolved from the coordinates read along with the metadata
+            if let Err(e) = resolve_photo_places(app_state).await {
+                error!("Failed to resolve photo places: {e}");
+            }
+
             if let Err(e) = cleanup_trash(app_state).await {
                 error!("Failed to cleanup trash: {e}");
             }
 
+            if let Err(e) = delete_expired_shares(app_state).await {
+                error!("Failed to delete expired shares: {e}");
+            }
+
+            if let Err(e) = delete_expired_uploads(app_state).await {
+                error!("Failed to delete expired uploads: {e}");
+            }
+
+            if let Err(e) = delete_stale_login_attempts(app_state).await {
+                error!("Failed to delete stale login attempts: {e}");
+            }
+
+            if let Err(e) = delete_stale_user_sessions(app_state).await {
+                error!("Failed to delete stale user sessions: {e}");
+            }
+
             if let Err(e) = delete_invalid_photo_previews(app_state).await {
                 error!("Failed to delete invalid photo previews: {e}");
             }
@@ -97,6 +134,60 @@ async fn resolve_duplicates_db_entry(app_state: AppStateRef) -> Result<(), sqlx:
     }
 
     tx.commit().await?;
+    app_state.publish_latest_event().await;
+
+    Ok(())
+}
+
+async fn delete_expired_shares(app_state: AppStateRef) -> Result<(), sqlx::Error> {
+    let deleted = app_state.write_pool.delete_expired_shares().await?;
+
+    if deleted > 0 {
+        info!("Deleted {deleted} expired shares");
+    }
+
+    Ok(())
+}
+
+async fn delete_expired_uploads(app_state: AppStateRef) -> Result<(), sqlx::Error> {
+    let deleted = app_state.write_pool.delete_expired_uploads().await?;
+
+    for upload_id in &deleted {
+        // Completed uploads have no file left
+        let _ = fs::remove_file(app_state.storage.resolve_upload(upload_id));
+    }
+
+    if !deleted.is_empty() {
+        info!("Deleted {} expired uploads", deleted.len());
+    }
+
+    Ok(())
+}
+
+async fn delete_stale_login_attempts(app_state: AppStateRef) -> Result<(), sqlx::Error> {
+    let before = OffsetDateTime::now_utc() - time::Duration::days(1);
+    let deleted = app_state
+        .write_pool
+        .delete_stale_login_attempts(before)
+        .await?;
+
+    if deleted > 0 {
+        info!("Deleted {deleted} stale login attempts");
+    }
+
+    Ok(())
+}
+
+async fn delete_stale_user_sessions(app_state: AppStateRef) -> Result<(), sqlx::Error> {
+    let before = OffsetDateTime::now_utc() - SESSION_EXPIRY;
+    let deleted = app_state
+        .write_pool
+        .delete_stale_user_sessions(before)
+        .await?;
+
+    if deleted > 0 {
+        info!("Deleted {deleted} expired user sessions");
+    }
 
     Ok(())
 }
@@ -0,0 +1,87 @@
+use crate::http::AppStateRef;
+use crate::model::place::{PhotoPlace, nearest_place, place_search_bounds};
+use crate::repo::{PlacesRepo, SearchRepo};
+use crate::utils::geonames::{parse_cities, parse_country_names};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use tracing::info;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportPlacesError {
+    #[error("Failed to read the GeoNames file: {0}")]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+/// Names the place of the geotagged photos after the nearest imported city
+pub async fn resolve_photo_places(app_state: AppStateRef) -> Result<(), sqlx::Error> {
+    if app_state.read_pool.count_places().await? == 0 {
+        return Ok(());
+    }
+
+    let locations = app_state
+        .read_pool
+        .get_photo_locations_without_place()
+        .await?;
+
+    if locations.is_empty() {
+        return Ok(());
+    }
+    info!("Resolving the places of {} photos", locations.len());
+
+    let mut photo_places = Vec::with_capacity(locations.len());
+    for location in locations {
+        let bounds = place_search_bounds(location.latitude, location.longitude);
+        let places = app_state.read_pool.get_places_in_bounds(&bounds).await?;
+
+        photo_places.push(PhotoPlace {
+            photo_id: location.photo_id,
+            place_id: nearest_place(location.latitude, location.longitude, &places)
+                .map(|place| place.id),
+        });
+    }
+
+    let resolved_count = photo_places.iter().filter(|p| p.place_id.is_some()).count();
+
+    let mut tx = app_state.write_pool.begin().await?;
+    for chunk in photo_places.chunks(1024) {
+        tx.insert_photo_places(chunk).await?;
+
+        // The place names are searchable
+        let ids: Vec<i64> = chunk.iter().map(|p| p.photo_id).collect();
+        tx.index_photos(&ids).await?;
+    }
+    tx.commit().await?;
+
+    info!("Resolved the places of {resolved_count} photos");
+
+    Ok(())
+}
+
+/// Replaces the known places with a GeoNames cities dump, like `cities15000.txt`.
+/// Every photo is looked up again on the next run of [`resolve_photo_places`]
+pub async fn import_places(
+    app_state: AppStateRef,
+    cities_path: &Path,
+    countries_path: Option<&Path>,
+) -> Result<usize, ImportPlacesError> {
+    let countries = match countries_path {
+        Some(path) => parse_country_names(BufReader::new(File::open(path)?))?,
+        None => HashMap::new(),
+    };
+    let places = parse_cities(BufReader::new(File::open(cities_path)?), &countries)?;
+
+    let mut tx = app_state.write_pool.begin().await?;
+    tx.delete_all_photo_places().await?;
+    tx.delete_all_places().await?;
+    // Stay under the SQLite limit of bound variables
+    for chunk in places.chunks(1024) {
+        tx.insert_places(chunk).await?;
+    }
+    tx.commit().await?;
+
+    Ok(places.len())
+}
@@ -0,0 +1,61 @@
+use crate::http::AppStateRef;
+use crate::model::photo::Photo;
+use crate::repo::TagsRepo;
+use crate::utils::xmp::{XmpData, write_sidecar};
+use std::collections::HashMap;
+use tracing::{error, info};
+
+/// Writes the tags, caption, rating and date of the photos to their XMP sidecars, so other
+/// programs see the changes. Does nothing unless enabled, failures are only logged
+pub async fn write_photos_sidecars(app_state: AppStateRef, photos: &[Photo]) {
+    if app_state.writeback.xmp_sidecars {
+        write_sidecars(app_state, photos).await;
+    }
+}
+
+pub(super) async fn write_sidecars(app_state: AppStateRef, photos: &[Photo]) {
+    if photos.is_empty() {
+        return;
+    }
+
+    let photo_ids: Vec<i64> = photos.iter().map(|photo| photo.id).collect();
+    let tags = match app_state.read_pool.get_photos_tags(&photo_ids).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            error!("Failed to get the tags of the sidecars: {e}");
+            return;
+        }
+    };
+
+    let mut keywords: HashMap<i64, Vec<String>> = HashMap::new();
+    for tag in tags {
+        keywords.entry(tag.photo_id).or_default().push(tag.name);
+    }
+
+    let sidecars: Vec<_> = photos
+        .iter()
+        .map(|photo| {
+            let data = XmpData {
+                rating: photo.rating,
+                caption: photo.caption.clone(),
+                keywords: keywords.remove(&photo.id).unwrap_or_default(),
+                date_taken: Some(photo.created_at),
+            };
+            (app_state.storage.resolve_photo(photo.partial_path()), data)
+        })
+        .collect();
+
+    let result = tokio::task::spawn_blocking(move || {
+        for (path, data) in sidecars {
+            match write_sidecar(&path, &data) {
+                Ok(()) => info!("Updated the sidecar of {}", path.display()),
+                Err(e) => error!("Failed to write the sidecar of {}: {e}", path.display()),
+            }
+        }
+    })
+    .await;
+
+    if let Err(e) = result {
+        error!("Failed to write the sidecars: {e}");
+    }
+}
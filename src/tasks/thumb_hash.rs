@@ -64,6 +64,7 @@ pub async fn generate_thumb_hashes(app_state: AppStateRef) -> Result<(), sqlx::E
     }
 
     tx.commit().await?;
+    app_state.publish_latest_event().await;
 
     info!("Updated {count} thumb hashes");
 
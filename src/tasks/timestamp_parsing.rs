@@ -1,7 +1,8 @@
+use crate::utils::ffprobe::probe_video;
 use exif::{Field, In, Tag, Value};
 use mime_guess::MimeGuess;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::BufReader;
 use std::path::Path;
@@ -11,13 +12,62 @@ use time::macros::format_description;
 use time::{OffsetDateTime, PrimitiveDateTime};
 use tracing::error;
 
+/// Where the timestamp of a new photo came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampSource {
+    /// Given by the client when uploading
+    Client,
+    GoogleJson,
+    Exif,
+    Video,
+    FileName,
+    /// The modification time of the uploaded file, given by the client
+    LastModified,
+    Now,
+}
+
+impl TimestampSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimestampSource::Client => "client",
+            TimestampSource::GoogleJson => "google_json",
+            TimestampSource::Exif => "exif",
+            TimestampSource::Video => "video",
+            TimestampSource::FileName => "file_name",
+            TimestampSource::LastModified => "last_modified",
+            TimestampSource::Now => "now",
+        }
+    }
+}
+
 pub fn get_timestamp_for_path<P: AsRef<Path>>(path: P) -> Option<OffsetDateTime> {
-    get_json_timestamp(path.as_ref())
-        .map_or_else(
-            || get_exif_timestamp(path.as_ref()),
-            |json_timestamp| OffsetDateTime::from_unix_timestamp(json_timestamp as i64).ok(),
-        )
-        .or_else(|| get_regex_timestamp(path))
+    get_timestamp_for_file(path.as_ref(), path.as_ref()).map(|(timestamp, _)| timestamp)
+}
+
+/// Like [`get_timestamp_for_path`] for a file whose contents are stored at `path`,
+/// but whose type and name come from `file_name`, like a temporary file of an upload
+pub fn get_timestamp_for_file(
+    path: &Path,
+    file_name: &Path,
+) -> Option<(OffsetDateTime, TimestampSource)> {
+    let timestamp = match get_json_timestamp(path) {
+        Some(json_timestamp) => OffsetDateTime::from_unix_timestamp(json_timestamp as i64)
+            .ok()
+            .map(|timestamp| (timestamp, TimestampSource::GoogleJson)),
+        None => {
+            get_exif_timestamp(path, file_name).map(|timestamp| (timestamp, TimestampSource::Exif))
+        }
+    };
+
+    timestamp
+        .or_else(|| {
+            get_video_timestamp(path, file_name)
+                .map(|timestamp| (timestamp, TimestampSource::Video))
+        })
+        .or_else(|| {
+            get_regex_timestamp(file_name).map(|timestamp| (timestamp, TimestampSource::FileName))
+        })
 }
 
 #[derive(Deserialize)]
@@ -89,8 +139,8 @@ fn parse_date_from_field(field: &Field) -> Option<OffsetDateTime> {
         .ok()
 }
 
-fn get_exif_timestamp(path: &Path) -> Option<OffsetDateTime> {
-    let mime = MimeGuess::from_path(path).first_or_octet_stream();
+fn get_exif_timestamp(path: &Path, file_name: &Path) -> Option<OffsetDateTime> {
+    let mime = MimeGuess::from_path(file_name).first_or_octet_stream();
     if mime.type_() != "image" {
         return None;
     }
@@ -120,6 +170,15 @@ fn get_exif_timestamp(path: &Path) -> Option<OffsetDateTime> {
     None
 }
 
+fn get_video_timestamp(path: &Path, file_name: &Path) -> Option<OffsetDateTime> {
+    let mime = MimeGuess::from_path(file_name).first_or_octet_stream();
+    if mime.type_() != "video" {
+        return None;
+    }
+
+    probe_video(path).ok()?.creation_time
+}
+
 fn get_regex_timestamp<P: AsRef<Path>>(path: P) -> Option<OffsetDateTime> {
     static DATE_HOUR_PATTERN: LazyLock<Regex> = LazyLock::new(|| {
         Regex::new(r"(\d{4})\D*(\d{2})\D*(\d{2})\D*(\d{2})\D*(\d{2})\D*(\d{2})").unwrap()
@@ -214,6 +273,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_timestamp_for_file() {
+        // A temporary file of an upload, which has no meaningful name or extension
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"not an image").unwrap();
+
+        assert_eq!(
+            get_timestamp_for_file(file.path(), Path::new("IMG_20160922_160430.jpg")),
+            Some((
+                datetime!(2016-09-22 16:04:30 UTC),
+                TimestampSource::FileName
+            ))
+        );
+        assert_eq!(
+            get_timestamp_for_file(file.path(), Path::new("photo.jpg")),
+            None
+        );
+    }
+
     #[test]
     fn json_parsing() {
         let json = r#"{
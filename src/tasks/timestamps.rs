@@ -0,0 +1,71 @@
+use crate::http::AppStateRef;
+use crate::model::photo::Photo;
+use crate::model::photo_hash::PhotoHash;
+use crate::repo::PhotosHashRepo;
+use crate::tasks::hash::compute_hash;
+use crate::tasks::sidecars::{write_photos_sidecars, write_sidecars};
+use crate::utils::env_reader::TimestampWriteback;
+use crate::utils::exif::write_date_taken;
+use crate::utils::xmp::find_sidecar;
+use tokio::task::spawn_blocking;
+use tracing::{error, info};
+
+/// Writes the corrected timestamps of the photos to the files, so they are found again when the
+/// photos are rescanned
+pub async fn persist_timestamps(app_state: AppStateRef, photos: &[Photo]) {
+    let sidecar_photos = match app_state.writeback.timestamps {
+        TimestampWriteback::Off => return write_photos_sidecars(app_state, photos).await,
+        TimestampWriteback::Sidecar => photos.to_vec(),
+        TimestampWriteback::File => write_exif_timestamps(app_state, photos.to_vec()).await,
+    };
+
+    write_sidecars(app_state, &sidecar_photos).await;
+}
+
+/// Returns the photos that still need their sidecar written: the ones whose EXIF data can't be
+/// changed and the ones with a sidecar, whose date would be read instead
+async fn write_exif_timestamps(app_state: AppStateRef, photos: Vec<Photo>) -> Vec<Photo> {
+    let result = spawn_blocking(move || {
+        let mut hashes = Vec::new();
+        let mut sidecar_photos = Vec::new();
+
+        for photo in photos {
+            let path = app_state.storage.resolve_photo(photo.partial_path());
+
+            match write_date_taken(&path, photo.created_at) {
+                Ok(true) => {
+                    info!("Updated the EXIF date of {}", path.display());
+                    // The file changed, so did its hash
+                    match compute_hash(&path) {
+                        Ok(hash) => hashes.push(PhotoHash { id: photo.id, hash }),
+                        Err(e) => error!("Failed to compute hash for {}: {e}", path.display()),
+                    }
+
+                    if app_state.writeback.xmp_sidecars || find_sidecar(&path).is_some() {
+                        sidecar_photos.push(photo);
+                    }
+                }
+                Ok(false) => sidecar_photos.push(photo),
+                Err(e) => {
+                    error!("Failed to write the EXIF date of {}: {e}", path.display());
+                    sidecar_photos.push(photo);
+                }
+            }
+        }
+
+        (hashes, sidecar_photos)
+    })
+    .await;
+
+    let Ok((hashes, sidecar_photos)) =
+        result.inspect_err(|e| error!("Failed to write the EXIF dates: {e}"))
+    else {
+        return Vec::new();
+    };
+
+    if let Err(e) = app_state.write_pool.insert_hashes(&hashes).await {
+        error!("Failed to update the hashes of the photos: {e}");
+    }
+
+    sidecar_photos
+}
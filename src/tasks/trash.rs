@@ -1,5 +1,6 @@
 use crate::http::AppStateRef;
 use crate::repo::{PhotosRepo, PhotosTransactionRepo};
+use crate::utils::xmp::remove_sidecar;
 use tokio::fs;
 use tracing::{error, info, warn};
 
@@ -25,6 +26,9 @@ pub async fn cleanup_trash(app_state: AppStateRef) -> Result<(), sqlx::Error> {
                 error!("Failed to remove file at {}: {e}", display_path);
             })?;
             info!("Removed trashed file at {}", display_path);
+            if let Err(e) = remove_sidecar(&photo_path) {
+                warn!("Failed to remove the sidecar of {}: {e}", display_path);
+            }
         } else {
             warn!("No such file exists at {}", display_path);
         }
@@ -32,5 +36,8 @@ pub async fn cleanup_trash(app_state: AppStateRef) -> Result<(), sqlx::Error> {
         tx.delete_photo(photo).await?;
     }
 
-    tx.commit().await
+    tx.commit().await?;
+    app_state.publish_latest_event().await;
+
+    Ok(())
 }
@@ -1,5 +1,7 @@
+use ipnet::IpNet;
 use std::env::VarError;
 use std::fmt::Display;
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
 use tracing::warn;
@@ -23,6 +25,132 @@ where
     })
 }
 
+/// Authentication by a header set by an SSO gateway in front of the server
+pub struct ProxyAuthConfig {
+    /// The header holding the user id, like `Remote-User`
+    pub user_header: String,
+    /// The header holding the display name, used when creating users
+    pub name_header: Option<String>,
+    /// The header is ignored unless the request comes from one of these networks
+    pub trusted_proxies: Vec<IpNet>,
+    /// Create the users that don't exist yet, instead of refusing them
+    pub auto_create_users: bool,
+}
+
+impl ProxyAuthConfig {
+    pub fn trusts(&self, ip: IpAddr) -> bool {
+        networks_contain(&self.trusted_proxies, ip)
+    }
+}
+
+/// The reverse proxies in front of the server
+pub struct ProxyConfig {
+    /// Only these networks can set the client IP with the `X-Real-IP` or `X-Forwarded-For` headers
+    pub trusted_proxies: Vec<IpNet>,
+    pub auth: Option<ProxyAuthConfig>,
+}
+
+impl ProxyConfig {
+    pub fn trusts(&self, ip: IpAddr) -> bool {
+        networks_contain(&self.trusted_proxies, ip)
+    }
+}
+
+fn networks_contain(networks: &[IpNet], ip: IpAddr) -> bool {
+    let ip = ip.to_canonical();
+    networks.iter().any(|net| net.contains(&ip))
+}
+
+/// Login through an OpenID Connect provider, next to the password login
+#[derive(Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    /// Not needed by public clients, which only rely on PKCE
+    pub client_secret: Option<String>,
+    /// The public URL of `/login/oidc/callback`, as registered with the provider
+    pub redirect_url: String,
+    /// Shown on the login button
+    pub provider_name: String,
+    pub scopes: String,
+    /// The ID token claim used as the user id
+    pub user_claim: String,
+    /// The ID token claim used as the display name of new users
+    pub name_claim: String,
+    /// Create the users that don't exist yet, instead of refusing them
+    pub auto_create_users: bool,
+}
+
+fn env_var_or(var_name: &str, default_value: &str) -> String {
+    std::env::var(var_name)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| default_value.to_string())
+}
+
+fn parse_networks(var_name: &str, value: &str) -> Result<Vec<IpNet>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            // Single addresses are accepted as well
+            s.parse::<IpNet>()
+                .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("Invalid network in {var_name}: {s}"))
+        })
+        .collect()
+}
+
+/// The tiles of the map page, which can point to a self-hosted tile server
+pub struct MapConfig {
+    /// A Leaflet URL template, like `https://tile.openstreetmap.org/{z}/{x}/{y}.png`
+    pub tile_url: String,
+    pub attribution: String,
+    pub max_zoom: u8,
+}
+
+/// Where the corrected timestamps of photos are written, so they are found again by rescans
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampWriteback {
+    /// Only in the database
+    Off,
+    /// In the EXIF data of JPEG and HEIC photos, in an XMP sidecar for the other files
+    File,
+    Sidecar,
+}
+
+impl FromStr for TimestampWriteback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "file" => Ok(Self::File),
+            "sidecar" => Ok(Self::Sidecar),
+            _ => Err(format!("Unknown value {s}, expected off, file or sidecar")),
+        }
+    }
+}
+
+impl Display for TimestampWriteback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::File => "file",
+            Self::Sidecar => "sidecar",
+        })
+    }
+}
+
+/// Writing the changes of the photos back to the files, for the programs that read them
+pub struct WritebackConfig {
+    /// Write the tags, captions, ratings and dates of the photos to their XMP sidecars
+    pub xmp_sidecars: bool,
+    pub timestamps: TimestampWriteback,
+}
+
 pub struct EnvVariables {
     pub server_port: u16,
     pub storage_path: PathBuf,
@@ -31,6 +159,12 @@ pub struct EnvVariables {
     pub scan_new_files: bool,
     pub background_threads_count: usize,
     pub allowed_origins: Vec<String>,
+    pub proxy: ProxyConfig,
+    pub oidc: Option<OidcConfig>,
+    pub map: MapConfig,
+    pub writeback: WritebackConfig,
+    /// The largest file that can be uploaded with the tus protocol, in bytes
+    pub max_resumable_upload_size: i64,
 }
 
 impl EnvVariables {
@@ -62,6 +196,64 @@ impl EnvVariables {
             .map(|s| s.split(',').map(|s| s.trim().to_string()).collect())
             .unwrap_or_default();
 
+        let proxy_auth = match std::env::var("PROXY_AUTH_HEADER") {
+            Ok(user_header) if !user_header.trim().is_empty() => {
+                let trusted_proxies = parse_networks(
+                    "PROXY_AUTH_TRUSTED_PROXIES",
+                    &required_env_var("PROXY_AUTH_TRUSTED_PROXIES")?,
+                )?;
+                if trusted_proxies.is_empty() {
+                    return Err("PROXY_AUTH_TRUSTED_PROXIES must not be empty!".to_string());
+                }
+
+                Some(ProxyAuthConfig {
+                    user_header: user_header.trim().to_string(),
+                    name_header: std::env::var("PROXY_AUTH_NAME_HEADER")
+                        .ok()
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty()),
+                    trusted_proxies,
+                    auto_create_users: optional_env_var("PROXY_AUTH_AUTO_CREATE_USERS", false),
+                })
+            }
+            _ => None,
+        };
+
+        // Only a proxy on the same machine by default, as any client on the network could set the headers
+        let trusted_proxies = parse_networks(
+            "TRUSTED_PROXIES",
+            &std::env::var("TRUSTED_PROXIES").unwrap_or_else(|_| "127.0.0.0/8, ::1".to_string()),
+        )?;
+
+        let oidc = match std::env::var("OIDC_ISSUER_URL") {
+            Ok(issuer_url) if !issuer_url.trim().is_empty() => Some(OidcConfig {
+                issuer_url: issuer_url.trim().to_string(),
+                client_id: required_env_var("OIDC_CLIENT_ID")?,
+                client_secret: std::env::var("OIDC_CLIENT_SECRET")
+                    .ok()
+                    .filter(|s| !s.is_empty()),
+                redirect_url: required_env_var("OIDC_REDIRECT_URL")?,
+                provider_name: env_var_or("OIDC_PROVIDER_NAME", "SSO"),
+                scopes: env_var_or("OIDC_SCOPES", "openid profile email"),
+                user_claim: env_var_or("OIDC_USER_CLAIM", "preferred_username"),
+                name_claim: env_var_or("OIDC_NAME_CLAIM", "name"),
+                auto_create_users: optional_env_var("OIDC_AUTO_CREATE_USERS", false),
+            }),
+            _ => None,
+        };
+
+        let map = MapConfig {
+            tile_url: env_var_or(
+                "MAP_TILE_URL",
+                "https://tile.openstreetmap.org/{z}/{x}/{y}.png",
+            ),
+            attribution: env_var_or(
+                "MAP_TILE_ATTRIBUTION",
+                "&copy; <a href=\"https://www.openstreetmap.org/copyright\">OpenStreetMap</a> contributors",
+            ),
+            max_zoom: optional_env_var("MAP_MAX_ZOOM", 19),
+        };
+
         Ok(Self {
             server_port: required_env_var("SERVER_PORT")?
                 .parse()
@@ -72,6 +264,50 @@ impl EnvVariables {
             scan_new_files: optional_env_var("SCAN_NEW_FILES", true),
             background_threads_count: optional_env_var("BACKGROUND_THREADS_COUNT", 0),
             allowed_origins,
+            proxy: ProxyConfig {
+                trusted_proxies,
+                auth: proxy_auth,
+            },
+            oidc,
+            map,
+            writeback: WritebackConfig {
+                xmp_sidecars: optional_env_var("WRITE_XMP_SIDECARS", false),
+                timestamps: optional_env_var("WRITE_TIMESTAMPS", TimestampWriteback::Off),
+            },
+            max_resumable_upload_size: optional_env_var("MAX_RESUMABLE_UPLOAD_MB", 10 * 1024i64)
+                .saturating_mul(1024 * 1024),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trusted_proxies() {
+        let config = ProxyAuthConfig {
+            user_header: "Remote-User".to_string(),
+            name_header: None,
+            trusted_proxies: parse_networks(
+                "PROXY_AUTH_TRUSTED_PROXIES",
+                "10.0.0.0/8, 127.0.0.1, fd00::/8",
+            )
+            .unwrap(),
+            auto_create_users: false,
+        };
+
+        assert!(config.trusts("10.1.2.3".parse().unwrap()));
+        assert!(config.trusts("127.0.0.1".parse().unwrap()));
+        assert!(config.trusts("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(config.trusts("fd12::1".parse().unwrap()));
+
+        assert!(!config.trusts("127.0.0.2".parse().unwrap()));
+        assert!(!config.trusts("192.168.1.1".parse().unwrap()));
+        assert!(!config.trusts("::1".parse().unwrap()));
+
+        assert!(parse_networks("TRUSTED_PROXIES", "10.0.0.0/33").is_err());
+        assert!(parse_networks("TRUSTED_PROXIES", "proxy").is_err());
+        assert!(parse_networks("TRUSTED_PROXIES", "").unwrap().is_empty());
+    }
+}
@@ -1,6 +1,11 @@
-use exif::{In, Value};
+use crate::model::photo_metadata::PhotoMetadata;
+use exif::{Exif, In, Tag, Value};
 use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use time::OffsetDateTime;
+use time::macros::format_description;
 
 #[derive(Serialize)]
 pub struct ExifField {
@@ -8,12 +13,14 @@ pub struct ExifField {
     value: String,
 }
 
-pub fn read_exif<P: AsRef<Path>>(absolute_path: P) -> Option<Vec<ExifField>> {
+fn read_exif_container(absolute_path: &Path) -> Option<Exif> {
     let file = std::fs::File::open(absolute_path).ok()?;
     let mut bufreader = std::io::BufReader::new(&file);
-    let reader = exif::Reader::new()
-        .read_from_container(&mut bufreader)
-        .ok()?;
+    exif::Reader::new().read_from_container(&mut bufreader).ok()
+}
+
+pub fn read_exif<P: AsRef<Path>>(absolute_path: P) -> Option<Vec<ExifField>> {
+    let reader = read_exif_container(absolute_path.as_ref())?;
 
     let mut exif_data = vec![];
 
@@ -29,6 +36,410 @@ pub fn read_exif<P: AsRef<Path>>(absolute_path: P) -> Option<Vec<ExifField>> {
     Some(exif_data)
 }
 
+/// Reads the structured metadata of a photo, the returned `photo_id` is left to 0.
+/// Returns [`None`] if the file has no EXIF data
+pub fn read_photo_metadata<P: AsRef<Path>>(absolute_path: P) -> Option<PhotoMetadata> {
+    let exif = read_exif_container(absolute_path.as_ref())?;
+
+    let text = |tag| get_field_value(&exif, tag).and_then(ascii_value);
+    let number = |tag| get_field_value(&exif, tag).and_then(|v| number_value(v, 0));
+    let integer = |tag| {
+        get_field_value(&exif, tag)
+            .and_then(|v| v.get_uint(0))
+            .map(i64::from)
+    };
+
+    Some(PhotoMetadata {
+        photo_id: 0,
+        camera_make: text(Tag::Make),
+        camera_model: text(Tag::Model),
+        lens_model: text(Tag::LensModel),
+        focal_length: number(Tag::FocalLength),
+        aperture: number(Tag::FNumber),
+        exposure_time: number(Tag::ExposureTime),
+        iso: integer(Tag::PhotographicSensitivity),
+        width: integer(Tag::PixelXDimension).or_else(|| integer(Tag::ImageWidth)),
+        height: integer(Tag::PixelYDimension).or_else(|| integer(Tag::ImageLength)),
+        orientation: integer(Tag::Orientation),
+        latitude: gps_coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef),
+        longitude: gps_coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef),
+        ..Default::default()
+    })
+}
+
+/// Overwrites the `DateTimeOriginal` and `OffsetTimeOriginal` of a JPEG or HEIC photo in place, the
+/// rest of the file is left as it is. Returns `false` if the photo has no `DateTimeOriginal` to overwrite
+pub fn write_date_taken(absolute_path: &Path, date: OffsetDateTime) -> std::io::Result<bool> {
+    // Other containers, like PNG, have checksums that would have to be updated
+    let supported = absolute_path.extension().is_some_and(|extension| {
+        ["jpg", "jpeg", "heic", "heif"]
+            .iter()
+            .any(|supported| extension.eq_ignore_ascii_case(supported))
+    });
+    if !supported {
+        return Ok(false);
+    }
+
+    let Some(exif) = read_exif_container(absolute_path) else {
+        return Ok(false);
+    };
+    let tiff = exif.buf();
+    let Some(patches) = date_taken_patches(tiff, date) else {
+        return Ok(false);
+    };
+
+    // Only the few bytes of the dates are written, so the permissions and owner of the file are kept
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(absolute_path)?;
+    let Some(tiff_start) = find_tiff_start(&mut BufReader::new(&file))? else {
+        return Ok(false);
+    };
+
+    // The EXIF data found must be the one that was parsed
+    let mut found = vec![0; tiff.len()];
+    file.seek(SeekFrom::Start(tiff_start))?;
+    if file.read_exact(&mut found).is_err() || found != tiff {
+        return Ok(false);
+    }
+
+    for (offset, value) in patches {
+        file.seek(SeekFrom::Start(tiff_start + offset as u64))?;
+        file.write_all(&value)?;
+    }
+    file.sync_all()?;
+
+    Ok(true)
+}
+
+/// The position in the file of the TIFF data holding the EXIF fields,
+/// as kamadak-exif doesn't tell where it found them
+fn find_tiff_start<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<u64>> {
+    let mut magic = [0; 8];
+    if reader.read_exact(&mut magic).is_err() {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(0))?;
+
+    let result = if magic.starts_with(&[0xFF, 0xD8]) {
+        jpeg_tiff_start(reader)
+    } else if &magic[4..] == b"ftyp" {
+        heif_tiff_start(reader)
+    } else {
+        return Ok(None);
+    };
+
+    // A truncated file has nothing to patch
+    match result {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        result => result,
+    }
+}
+
+const JPEG_APP1: u8 = 0xE1;
+const JPEG_SOS: u8 = 0xDA;
+const JPEG_EOI: u8 = 0xD9;
+const EXIF_ID: &[u8; 6] = b"Exif\0\0";
+
+/// The EXIF data is in the APP1 segment starting with the EXIF id, which comes before the image data
+fn jpeg_tiff_start<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<u64>> {
+    reader.seek(SeekFrom::Start(2))?;
+
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let [prefix, marker, ..] = header;
+        if prefix != 0xFF || marker == JPEG_SOS || marker == JPEG_EOI {
+            return Ok(None);
+        }
+
+        // The length includes its own 2 bytes
+        let mut remaining = i64::from(u16::from_be_bytes([header[2], header[3]])) - 2;
+        if marker == JPEG_APP1 && remaining >= EXIF_ID.len() as i64 {
+            let mut id = [0; 6];
+            reader.read_exact(&mut id)?;
+            if &id == EXIF_ID {
+                return reader.stream_position().map(Some);
+            }
+            remaining -= id.len() as i64;
+        }
+
+        if remaining < 0 {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Current(remaining))?;
+    }
+}
+
+/// Largest `meta` box read, it only holds the descriptions of the items
+const MAX_HEIF_META_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The EXIF data is an item described in the `meta` box, whose content starts with the offset of
+/// the TIFF header
+fn heif_tiff_start<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<u64>> {
+    loop {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        let (size, header_size) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            1 => {
+                let mut size = [0; 8];
+                reader.read_exact(&mut size)?;
+                (u64::from_be_bytes(size), 16)
+            }
+            size => (u64::from(size), 8),
+        };
+        // A size of 0 means the box extends to the end of the file, which only happens for the last one
+        let Some(body_size) = size.checked_sub(header_size) else {
+            return Ok(None);
+        };
+
+        if &header[4..] != b"meta" {
+            reader.seek(SeekFrom::Current(body_size as i64))?;
+            continue;
+        }
+
+        if body_size > MAX_HEIF_META_SIZE {
+            return Ok(None);
+        }
+        let mut meta = vec![0; body_size as usize];
+        reader.read_exact(&mut meta)?;
+        let Some(item_start) = heif_exif_item_start(&meta) else {
+            return Ok(None);
+        };
+
+        reader.seek(SeekFrom::Start(item_start))?;
+        let mut header_offset = [0; 4];
+        reader.read_exact(&mut header_offset)?;
+        return Ok(Some(
+            item_start + 4 + u64::from(u32::from_be_bytes(header_offset)),
+        ));
+    }
+}
+
+/// Finds the EXIF item in the `iinf` box and its position in the `iloc` box.
+/// Items split in several extents or stored inside the `meta` box are not supported
+fn heif_exif_item_start(meta: &[u8]) -> Option<u64> {
+    // The meta box starts with its version and flags
+    let children = || child_boxes(meta.get(4..).unwrap_or_default());
+    let (_, iinf) = children().find(|(box_type, _)| box_type == b"iinf")?;
+    let (_, iloc) = children().find(|(box_type, _)| box_type == b"iloc")?;
+
+    let mut iinf = BoxReader::new(iinf);
+    let version = iinf.uint(1)?;
+    iinf.uint(3)?;
+    iinf.uint(if version == 0 { 2 } else { 4 })?;
+    let item_id = child_boxes(iinf.rest())
+        .filter(|(box_type, _)| box_type == b"infe")
+        .find_map(|(_, infe)| {
+            let mut infe = BoxReader::new(infe);
+            let version = infe.uint(1)?;
+            infe.uint(3)?;
+            let item_id = match version {
+                2 => infe.uint(2)?,
+                3 => infe.uint(4)?,
+                _ => return None,
+            };
+            // The protection index
+            infe.uint(2)?;
+            (infe.uint(4)? == u64::from(u32::from_be_bytes(*b"Exif"))).then_some(item_id)
+        })?;
+
+    let mut iloc = BoxReader::new(iloc);
+    let version = iloc.uint(1)?;
+    iloc.uint(3)?;
+    let sizes = iloc.uint(2)?;
+    let (offset_size, length_size, base_offset_size) =
+        (sizes >> 12, sizes >> 8 & 0xF, sizes >> 4 & 0xF);
+    let index_size = if version == 0 { 0 } else { sizes & 0xF };
+    let id_size = if version < 2 { 2 } else { 4 };
+
+    for _ in 0..iloc.uint(id_size)? {
+        let id = iloc.uint(id_size)?;
+        let construction_method = if version == 0 { 0 } else { iloc.uint(2)? & 0xF };
+        // The data reference index
+        iloc.uint(2)?;
+        let base_offset = iloc.uint(base_offset_size)?;
+        let extent_count = iloc.uint(2)?;
+        let mut extent_offset = 0;
+        for _ in 0..extent_count {
+            iloc.uint(index_size)?;
+            extent_offset = iloc.uint(offset_size)?;
+            iloc.uint(length_size)?;
+        }
+
+        if id == item_id {
+            return (construction_method == 0 && extent_count == 1)
+                .then(|| base_offset.checked_add(extent_offset))
+                .flatten();
+        }
+    }
+
+    None
+}
+
+/// The type and the content of the boxes stored one after the other in `data`
+fn child_boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut reader = BoxReader::new(data);
+    std::iter::from_fn(move || {
+        let size = reader.uint(4)?.checked_sub(8)?;
+        let box_type = reader.bytes(4)?;
+        Some((box_type, reader.bytes(size)?))
+    })
+}
+
+/// Reads the big endian values of a box
+struct BoxReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BoxReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn bytes(&mut self, count: u64) -> Option<&'a [u8]> {
+        let count = usize::try_from(count).ok()?;
+        let bytes = self.data.get(..count)?;
+        self.data = &self.data[count..];
+        Some(bytes)
+    }
+
+    fn uint(&mut self, size: u64) -> Option<u64> {
+        let bytes = self.bytes(size)?;
+        (size <= 8).then(|| {
+            bytes
+                .iter()
+                .fold(0, |value, &byte| value << 8 | u64::from(byte))
+        })
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const ASCII_TYPE: u16 = 2;
+
+/// The offsets in the TIFF data of the new date values, which must have the same length as the
+/// existing ones since nothing can be moved
+fn date_taken_patches(tiff: &[u8], date: OffsetDateTime) -> Option<Vec<(usize, Vec<u8>)>> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| {
+        let bytes = tiff.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |offset: usize| {
+        let bytes = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        let value = if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        };
+        Some(value as usize)
+    };
+    // The entries of an IFD are 12 bytes: the tag, the type, the count and the value or its offset
+    let entries = |ifd: usize| {
+        let count = u16_at(ifd).unwrap_or_default() as usize;
+        (0..count).map(move |index| ifd + 2 + index * 12)
+    };
+
+    let exif_ifd = entries(u32_at(4)?)
+        .find(|&entry| u16_at(entry) == Some(EXIF_IFD_POINTER))
+        .and_then(|entry| u32_at(entry + 8))?;
+
+    let date_time = date
+        .format(format_description!(
+            "[year]:[month]:[day] [hour]:[minute]:[second]\0"
+        ))
+        .ok()?;
+    let offset_time = date
+        .format(format_description!(
+            "[offset_hour sign:mandatory]:[offset_minute]\0"
+        ))
+        .ok()?;
+
+    let mut patches = Vec::new();
+    let mut has_date_time = false;
+    for entry in entries(exif_ifd) {
+        let tag = u16_at(entry);
+        let value = match tag {
+            Some(DATE_TIME_ORIGINAL) => &date_time,
+            Some(OFFSET_TIME_ORIGINAL) => &offset_time,
+            _ => continue,
+        };
+
+        // Values longer than 4 bytes are stored at an offset
+        if u16_at(entry + 2) == Some(ASCII_TYPE)
+            && u32_at(entry + 4) == Some(value.len())
+            && let Some(offset) = u32_at(entry + 8)
+            && offset + value.len() <= tiff.len()
+        {
+            has_date_time |= tag == Some(DATE_TIME_ORIGINAL);
+            patches.push((offset, value.as_bytes().to_vec()));
+        }
+    }
+
+    has_date_time.then_some(patches)
+}
+
+fn get_field_value(exif: &Exif, tag: Tag) -> Option<&Value> {
+    exif.get_field(tag, In::PRIMARY).map(|field| &field.value)
+}
+
+fn ascii_value(value: &Value) -> Option<String> {
+    let Value::Ascii(vec) = value else {
+        return None;
+    };
+
+    let text = String::from_utf8_lossy(vec.first()?);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn number_value(value: &Value, index: usize) -> Option<f64> {
+    let number = match value {
+        Value::Rational(vec) => vec.get(index).filter(|r| r.denom != 0)?.to_f64(),
+        Value::SRational(vec) => vec.get(index).filter(|r| r.denom != 0)?.to_f64(),
+        value => f64::from(value.get_uint(index)?),
+    };
+
+    number.is_finite().then_some(number)
+}
+
+/// Converts the degrees, minutes and seconds of a GPS coordinate to signed decimal degrees
+fn gps_coordinate(exif: &Exif, tag: Tag, ref_tag: Tag) -> Option<f64> {
+    let value = get_field_value(exif, tag)?;
+    let reference = get_field_value(exif, ref_tag).and_then(ascii_value);
+
+    dms_to_degrees(value, reference.as_deref())
+}
+
+fn dms_to_degrees(value: &Value, reference: Option<&str>) -> Option<f64> {
+    let degrees = number_value(value, 0)?;
+    let minutes = number_value(value, 1).unwrap_or_default();
+    let seconds = number_value(value, 2).unwrap_or_default();
+
+    let coordinate = degrees + minutes / 60.0 + seconds / 3600.0;
+
+    match reference {
+        Some("S" | "W") => Some(-coordinate),
+        _ => Some(coordinate),
+    }
+}
+
 fn value_to_string(value: &Value) -> String {
     match value {
         Value::Byte(vec) => format!("{vec:?}"),
@@ -84,3 +495,150 @@ fn value_to_string(value: &Value) -> String {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::Rational;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_dms_to_degrees() {
+        let rational = |num, denom| Rational { num, denom };
+        let value = Value::Rational(vec![rational(48, 1), rational(51, 1), rational(2964, 100)]);
+
+        let north = dms_to_degrees(&value, Some("N")).unwrap();
+        assert!((north - 48.858_233).abs() < 1e-6);
+
+        let west = dms_to_degrees(&value, Some("W")).unwrap();
+        assert!((west + 48.858_233).abs() < 1e-6);
+
+        // A zero denominator is invalid
+        let value = Value::Rational(vec![rational(48, 0)]);
+        assert_eq!(dms_to_degrees(&value, Some("N")), None);
+    }
+
+    #[test]
+    fn test_ascii_value() {
+        let value = Value::Ascii(vec![b"Canon  \0\0".to_vec()]);
+        assert_eq!(ascii_value(&value).as_deref(), Some("Canon"));
+
+        let value = Value::Ascii(vec![b"\0".to_vec()]);
+        assert_eq!(ascii_value(&value), None);
+    }
+
+    fn ascii_field(tag: Tag, value: &str) -> exif::Field {
+        exif::Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        }
+    }
+
+    /// EXIF data with a date taken and the camera that took it
+    fn date_taken_tiff() -> Vec<u8> {
+        let fields = [
+            ascii_field(Tag::Make, "Canon"),
+            ascii_field(Tag::DateTimeOriginal, "2016:09:22 16:04:30"),
+            ascii_field(Tag::OffsetTimeOriginal, "+00:00"),
+        ];
+        let mut writer = exif::experimental::Writer::new();
+        fields.iter().for_each(|field| writer.push_field(field));
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        tiff.into_inner()
+    }
+
+    fn assert_date_taken_written(path: &Path, original: &[u8]) {
+        use time::macros::datetime;
+
+        assert!(write_date_taken(path, datetime!(2017-01-02 03:04:05 +2)).unwrap());
+
+        let written = std::fs::read(path).unwrap();
+        assert_eq!(written.len(), original.len());
+        let exif = read_exif_container(path).unwrap();
+        let text = |tag| get_field_value(&exif, tag).and_then(ascii_value);
+        assert_eq!(
+            text(Tag::DateTimeOriginal).as_deref(),
+            Some("2017:01:02 03:04:05")
+        );
+        assert_eq!(text(Tag::OffsetTimeOriginal).as_deref(), Some("+02:00"));
+        assert_eq!(text(Tag::Make).as_deref(), Some("Canon"));
+    }
+
+    #[test]
+    fn test_write_date_taken() {
+        use time::macros::datetime;
+
+        let tiff = date_taken_tiff();
+
+        // A JPEG with only the start of image, a comment, the EXIF segment and the end of image markers
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xFE, 0x00, 0x06];
+        jpeg.extend_from_slice(b"Exif");
+        jpeg.extend_from_slice(&[0xFF, 0xE1]);
+        jpeg.extend_from_slice(&(tiff.len() as u16 + 8).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xD9]);
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("photo.jpg");
+        std::fs::write(&path, &jpeg).unwrap();
+        let permissions = std::fs::metadata(&path).unwrap().permissions();
+
+        assert_date_taken_written(&path, &jpeg);
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions(), permissions);
+
+        // Formats that can't be written in place are left to the sidecars
+        let png = directory.path().join("photo.png");
+        std::fs::write(&png, &jpeg).unwrap();
+        assert!(!write_date_taken(&png, datetime!(2017-01-02 03:04:05 UTC)).unwrap());
+    }
+
+    #[test]
+    fn test_write_date_taken_heif() {
+        fn push_box(file: &mut Vec<u8>, box_type: &[u8], body: &[u8]) {
+            file.extend_from_slice(&(body.len() as u32 + 8).to_be_bytes());
+            file.extend_from_slice(box_type);
+            file.extend_from_slice(body);
+        }
+
+        let tiff = date_taken_tiff();
+        // The EXIF item starts with the offset of the TIFF header, after some padding here
+        let mut item = vec![0, 0, 0, 2, 0, 0];
+        item.extend_from_slice(&tiff);
+
+        let mut heif = Vec::new();
+        push_box(&mut heif, b"ftyp", b"heic\0\0\0\0mif1heic");
+
+        let mut infe = Vec::new();
+        // Version 2, item 1, no protection, of type Exif
+        push_box(&mut infe, b"infe", b"\x02\0\0\0\0\x01\0\0Exif\0");
+        let mut iinf = vec![0, 0, 0, 0, 0, 1];
+        iinf.extend_from_slice(&infe);
+
+        let meta_size = 4 + (iinf.len() + 8) + (22 + 8) + 8;
+        let item_offset = (heif.len() + meta_size + 8) as u32;
+        // Version 0, 4 byte offsets and lengths, 1 item with 1 extent
+        let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00, 0, 1, 0, 1, 0, 0, 0, 1];
+        iloc.extend_from_slice(&item_offset.to_be_bytes());
+        iloc.extend_from_slice(&(item.len() as u32).to_be_bytes());
+
+        let mut meta = vec![0, 0, 0, 0];
+        push_box(&mut meta, b"iinf", &iinf);
+        push_box(&mut meta, b"iloc", &iloc);
+        push_box(&mut heif, b"meta", &meta);
+        assert_eq!(heif.len() + 8, item_offset as usize);
+        push_box(&mut heif, b"mdat", &item);
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("photo.heic");
+        std::fs::write(&path, &heif).unwrap();
+
+        assert_eq!(
+            find_tiff_start(&mut std::fs::File::open(&path).unwrap()).unwrap(),
+            Some(item_offset as u64 + 6)
+        );
+        assert_date_taken_written(&path, &heif);
+    }
+}
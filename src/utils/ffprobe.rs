@@ -0,0 +1,316 @@
+use crate::model::photo_metadata::PhotoMetadata;
+use crate::utils::read_stderr;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{self, Read as _};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use tracing::warn;
+use wait_timeout::ChildExt;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Details of the first video stream of a file, as reported by ffprobe
+#[derive(Debug, Default, PartialEq)]
+pub struct VideoMetadata {
+    pub creation_time: Option<OffsetDateTime>,
+    /// In seconds
+    pub duration: Option<f64>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    /// Clockwise rotation needed to display the video, in degrees
+    pub rotation: i64,
+    pub codec: Option<String>,
+    pub frame_rate: Option<f64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl From<VideoMetadata> for PhotoMetadata {
+    fn from(video: VideoMetadata) -> Self {
+        // Same values as the EXIF orientation of rotated photos
+        let orientation = match video.rotation {
+            90 => Some(6),
+            180 => Some(3),
+            270 => Some(8),
+            _ => None,
+        };
+
+        PhotoMetadata {
+            width: video.width,
+            height: video.height,
+            orientation,
+            latitude: video.latitude,
+            longitude: video.longitude,
+            duration: video.duration,
+            video_codec: video.codec,
+            frame_rate: video.frame_rate,
+            ..Default::default()
+        }
+    }
+}
+
+pub fn probe_video(path: &Path) -> io::Result<VideoMetadata> {
+    let mut child = Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("v:0")
+        .arg("-show_format")
+        .arg("-show_streams")
+        .arg("-print_format")
+        .arg("json")
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    match child.wait_timeout(PROBE_TIMEOUT) {
+        Ok(Some(status)) => {
+            if !status.success() {
+                let stderr = read_stderr(&mut child);
+                warn!(
+                    "ffprobe failed for {}: exit={}, stderr={}",
+                    path.display(),
+                    status,
+                    stderr
+                );
+                return Err(io::Error::other(format!("ffprobe failed: {}", stderr)));
+            }
+
+            let mut output = Vec::new();
+            if let Some(mut stdout) = child.stdout.take() {
+                stdout.read_to_end(&mut output)?;
+            }
+
+            parse_probe_output(&output).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        Ok(None) => {
+            child.kill()?;
+            Err(io::Error::new(io::ErrorKind::TimedOut, "ffprobe timeout"))
+        }
+        Err(e) => {
+            child.kill()?;
+            Err(e)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_name: Option<String>,
+    width: Option<i64>,
+    height: Option<i64>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    duration: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    side_data_list: Vec<ProbeSideData>,
+}
+
+#[derive(Deserialize)]
+struct ProbeSideData {
+    rotation: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+fn parse_probe_output(output: &[u8]) -> serde_json::Result<VideoMetadata> {
+    let output: ProbeOutput = serde_json::from_slice(output)?;
+    let stream = output.streams.into_iter().next();
+    let format = output.format;
+
+    let format_tags = format.as_ref().map(|format| &format.tags);
+    let stream_tags = stream.as_ref().map(|stream| &stream.tags);
+    let tag = |name: &str| {
+        format_tags
+            .and_then(|tags| tags.get(name))
+            .or_else(|| stream_tags.and_then(|tags| tags.get(name)))
+    };
+
+    // Apple's creation date keeps the local offset, the standard one is always UTC
+    let creation_time = tag("com.apple.quicktime.creationdate")
+        .and_then(|date| parse_creation_time(date))
+        .or_else(|| tag("creation_time").and_then(|date| parse_creation_time(date)));
+
+    let (latitude, longitude) = [
+        "com.apple.quicktime.location.ISO6709",
+        "location",
+        "location-eng",
+    ]
+    .into_iter()
+    .find_map(|name| tag(name).and_then(|location| parse_iso6709(location)))
+    .unzip();
+
+    let duration = format
+        .as_ref()
+        .and_then(|format| format.duration.as_deref())
+        .or_else(|| stream.as_ref().and_then(|s| s.duration.as_deref()))
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .filter(|duration| duration.is_finite() && *duration > 0.0);
+
+    let Some(stream) = stream else {
+        return Ok(VideoMetadata {
+            creation_time,
+            duration,
+            latitude,
+            longitude,
+            ..Default::default()
+        });
+    };
+
+    // The display matrix rotation is counterclockwise, the legacy tag is clockwise
+    let rotation = stream
+        .side_data_list
+        .iter()
+        .find_map(|side_data| side_data.rotation)
+        .map(|rotation| -rotation as i64)
+        .or_else(|| stream.tags.get("rotate").and_then(|r| r.parse().ok()))
+        .unwrap_or_default()
+        .rem_euclid(360);
+
+    let frame_rate = stream
+        .avg_frame_rate
+        .as_deref()
+        .and_then(parse_frame_rate)
+        .or_else(|| stream.r_frame_rate.as_deref().and_then(parse_frame_rate));
+
+    Ok(VideoMetadata {
+        creation_time,
+        duration,
+        width: stream.width,
+        height: stream.height,
+        rotation,
+        codec: stream.codec_name,
+        frame_rate,
+        latitude,
+        longitude,
+    })
+}
+
+fn parse_creation_time(date: &str) -> Option<OffsetDateTime> {
+    let format_apple = format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second][offset_hour sign:mandatory][offset_minute]"
+    );
+
+    OffsetDateTime::parse(date, &Rfc3339)
+        .or_else(|_| OffsetDateTime::parse(date, &format_apple))
+        .ok()
+        // Cameras without a clock write zero, which is either 1904 or 1970
+        .filter(|date| date.unix_timestamp() > 0)
+}
+
+/// Parses "30000/1001" into frames per second
+fn parse_frame_rate(rate: &str) -> Option<f64> {
+    let (num, denom) = rate.split_once('/')?;
+    let (num, denom) = (num.parse::<f64>().ok()?, denom.parse::<f64>().ok()?);
+
+    (num > 0.0 && denom > 0.0).then(|| (num / denom * 100.0).round() / 100.0)
+}
+
+/// Parses an ISO 6709 location like "+48.8582+002.2945+035.000/"
+fn parse_iso6709(location: &str) -> Option<(f64, f64)> {
+    static LOCATION_PATTERN: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^([+-]\d+(?:\.\d+)?)([+-]\d+(?:\.\d+)?)").unwrap());
+
+    let capture = LOCATION_PATTERN.captures(location.trim())?;
+    let latitude: f64 = capture[1].parse().ok()?;
+    let longitude: f64 = capture[2].parse().ok()?;
+
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_parse_iphone_output() {
+        let output = br#"{
+            "streams": [{
+                "codec_name": "hevc",
+                "codec_type": "video",
+                "width": 1920,
+                "height": 1080,
+                "r_frame_rate": "30/1",
+                "avg_frame_rate": "30000/1001",
+                "tags": { "creation_time": "2024-07-14T10:00:05.000000Z" },
+                "side_data_list": [{ "side_data_type": "Display Matrix", "rotation": -90 }]
+            }],
+            "format": {
+                "duration": "12.345000",
+                "tags": {
+                    "creation_time": "2024-07-14T10:00:05.000000Z",
+                    "com.apple.quicktime.location.ISO6709": "+48.8582+002.2945+035.000/",
+                    "com.apple.quicktime.creationdate": "2024-07-14T12:00:00+0200"
+                }
+            }
+        }"#;
+
+        let video = parse_probe_output(output).unwrap();
+        assert_eq!(
+            video,
+            VideoMetadata {
+                creation_time: Some(datetime!(2024-07-14 12:00:00 +2)),
+                duration: Some(12.345),
+                width: Some(1920),
+                height: Some(1080),
+                rotation: 90,
+                codec: Some("hevc".to_string()),
+                frame_rate: Some(29.97),
+                latitude: Some(48.8582),
+                longitude: Some(2.2945),
+            }
+        );
+
+        let metadata = PhotoMetadata::from(video);
+        assert_eq!(metadata.orientation, Some(6));
+        assert_eq!(metadata.video_codec.as_deref(), Some("hevc"));
+    }
+
+    #[test]
+    fn test_parse_unset_values() {
+        let output = br#"{
+            "streams": [{ "codec_name": "h264", "avg_frame_rate": "0/0", "tags": { "rotate": "180" } }],
+            "format": { "duration": "N/A", "tags": { "creation_time": "1904-01-01T00:00:00.000000Z" } }
+        }"#;
+
+        let video = parse_probe_output(output).unwrap();
+        assert_eq!(video.creation_time, None);
+        assert_eq!(video.duration, None);
+        assert_eq!(video.frame_rate, None);
+        assert_eq!(video.rotation, 180);
+
+        assert_eq!(
+            parse_creation_time("2019-03-01T08:30:00.000000Z"),
+            Some(datetime!(2019-03-01 08:30:00 UTC))
+        );
+        assert_eq!(parse_iso6709("+95.0000-002.0000/"), None);
+        assert_eq!(
+            parse_iso6709("-33.8688+151.2093/"),
+            Some((-33.8688, 151.2093))
+        );
+    }
+}
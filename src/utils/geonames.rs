@@ -0,0 +1,118 @@
+use crate::model::place::Place;
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+
+/// Reads a GeoNames cities dump, like `cities15000.txt`, skipping the malformed lines.
+/// The country names are filled in from `countries` when known
+pub fn parse_cities(
+    reader: impl BufRead,
+    countries: &HashMap<String, String>,
+) -> io::Result<Vec<Place>> {
+    let mut places = Vec::new();
+
+    for line in reader.lines() {
+        if let Some(place) = parse_city(&line?) {
+            places.push(Place {
+                country: countries.get(&place.country_code).cloned(),
+                ..place
+            });
+        }
+    }
+
+    Ok(places)
+}
+
+/// The columns are documented in the readme of https://download.geonames.org/export/dump/
+fn parse_city(line: &str) -> Option<Place> {
+    let columns: Vec<&str> = line.split('\t').collect();
+    if columns.len() < 15 {
+        return None;
+    }
+
+    let latitude: f64 = columns[4].parse().ok()?;
+    let longitude: f64 = columns[5].parse().ok()?;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return None;
+    }
+
+    let name = columns[1].trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(Place {
+        id: columns[0].parse().ok()?,
+        name: name.to_string(),
+        country_code: columns[8].to_string(),
+        country: None,
+        latitude,
+        longitude,
+        population: columns[14].parse().unwrap_or_default(),
+    })
+}
+
+/// Reads the `countryInfo.txt` of GeoNames into the names of the countries by their code
+pub fn parse_country_names(reader: impl BufRead) -> io::Result<HashMap<String, String>> {
+    let mut countries = HashMap::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let columns: Vec<&str> = line.split('\t').collect();
+        if let [code, _, _, _, name, ..] = columns[..]
+            && !code.is_empty()
+            && !name.is_empty()
+        {
+            countries.insert(code.to_string(), name.to_string());
+        }
+    }
+
+    Ok(countries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cities() {
+        let countries = "#ISO\tISO3\tISO-Numeric\tfips\tCountry\tCapital\n\
+            FR\tFRA\t250\tFR\tFrance\tParis\n\
+            RO\tROU\t642\tRO\tRomania\tBucharest\n";
+        let countries = parse_country_names(countries.as_bytes()).unwrap();
+        assert_eq!(countries.len(), 2);
+
+        let cities = "2988507\tParis\tParis\tLutetia,Paris\t48.85341\t2.3488\tP\tPPLC\tFR\t\t11\t75\t751\t75056\t2138551\t\t42\tEurope/Paris\t2024-01-01\n\
+            683506\tBucharest\tBucharest\t\t44.43225\t26.10626\tP\tPPLC\tRO\t\t10\t\t\t\t1877155\t\t83\tEurope/Bucharest\t2024-01-01\n\
+            1\tNowhere\tNowhere\t\t91.0\t0.0\tP\tPPL\tXX\t\t\t\t\t\t0\t\t\t\t\n\
+            not a city\n";
+        let places = parse_cities(cities.as_bytes(), &countries).unwrap();
+
+        assert_eq!(
+            places,
+            vec![
+                Place {
+                    id: 2988507,
+                    name: "Paris".to_string(),
+                    country_code: "FR".to_string(),
+                    country: Some("France".to_string()),
+                    latitude: 48.85341,
+                    longitude: 2.3488,
+                    population: 2138551,
+                },
+                Place {
+                    id: 683506,
+                    name: "Bucharest".to_string(),
+                    country_code: "RO".to_string(),
+                    country: Some("Romania".to_string()),
+                    latitude: 44.43225,
+                    longitude: 26.10626,
+                    population: 1877155,
+                },
+            ]
+        );
+    }
+}
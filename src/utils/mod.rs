@@ -1,10 +1,31 @@
 pub mod env_reader;
 pub mod exif;
+pub mod ffprobe;
+pub mod geonames;
+pub mod oidc;
 pub mod password_hash;
 pub mod storage_resolver;
+pub mod totp;
+pub mod xmp;
 
-const BLAKE_3_LEN: usize = 32;
+use std::io::Read as _;
+use std::process::Child;
+
+pub const BLAKE_3_LEN: usize = 32;
 
 pub fn crop_blake_3_hash(hash: &[u8; BLAKE_3_LEN]) -> Vec<u8> {
     hash[..BLAKE_3_LEN / 2].to_vec()
 }
+
+/// Reads whatever a child process wrote to its piped stderr
+pub fn read_stderr(child: &mut Child) -> String {
+    child
+        .stderr
+        .take()
+        .map(|mut s| {
+            let mut buf = String::new();
+            s.read_to_string(&mut buf).ok();
+            buf.trim().to_owned()
+        })
+        .unwrap_or_default()
+}
@@ -1,5 +1,7 @@
+use crate::utils::xmp;
 use std::fs;
 use std::path::{Path, PathBuf};
+use tracing::warn;
 
 #[derive(Clone)]
 pub struct StorageResolver {
@@ -41,6 +43,11 @@ impl StorageResolver {
         self.preview_folder.join(relative.as_ref())
     }
 
+    /// The data of the resumable uploads in progress, which aren't photos yet
+    pub fn resolve_upload(&self, upload_id: &str) -> PathBuf {
+        self.storage_folder.join(".uploads").join(upload_id)
+    }
+
     pub fn move_photo<P1: AsRef<Path>, P2: AsRef<Path>>(
         &self,
         src_relative: P1,
@@ -61,6 +68,17 @@ impl StorageResolver {
             fs::create_dir_all(parent)?;
         }
 
-        fs::rename(self.resolve_photo(src_relative), destination_path)
+        let source_path = self.resolve_photo(src_relative);
+        fs::rename(&source_path, &destination_path)?;
+
+        // The sidecar follows the photo, but the photo is moved even if it can't
+        if let Err(e) = xmp::move_sidecar(&source_path, &destination_path) {
+            warn!(
+                "Failed to move the sidecar of {}: {e}",
+                source_path.display()
+            );
+        }
+
+        Ok(())
     }
 }
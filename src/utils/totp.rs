@@ -0,0 +1,157 @@
+use qrcode::QrCode;
+use qrcode::render::svg;
+use time::OffsetDateTime;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const ISSUER: &str = "Family Photos";
+const RECOVERY_CODES_COUNT: usize = 10;
+
+/// A new random secret, base32 encoded
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+/// The codes of the previous and next period are accepted too, for clocks that drift
+const ALLOWED_SKEW: i64 = 1;
+
+/// RFC 6238 defaults, which is what most authenticator apps support: SHA1, 6 digits, 30 seconds.
+/// The skew is handled by [check_code], which needs to know the period of the code
+fn build_totp(secret: &str, user_id: &str) -> Option<TOTP> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+
+    Some(TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        0,
+        30,
+        secret,
+        Some(ISSUER.to_string()),
+        user_id.to_string(),
+    ))
+}
+
+/// The otpauth:// URI that authenticator apps import
+pub fn otpauth_url(secret: &str, user_id: &str) -> Option<String> {
+    build_totp(secret, user_id).map(|totp| totp.get_url())
+}
+
+pub fn qr_code_svg(data: &str) -> Option<String> {
+    let code = QrCode::new(data).ok()?;
+
+    Some(
+        code.render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .quiet_zone(true)
+            .build(),
+    )
+}
+
+/// Returns the time step of the code if it is valid and newer than the last used one,
+/// so that a code can't be replayed once it was accepted
+pub fn check_code(secret: &str, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+    check_code_at(
+        secret,
+        code,
+        last_used_step,
+        OffsetDateTime::now_utc().unix_timestamp(),
+    )
+}
+
+fn check_code_at(secret: &str, code: &str, last_used_step: Option<i64>, now: i64) -> Option<i64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let totp = build_totp(secret, "")?;
+    let period = totp.step as i64;
+    let current_step = now / period;
+
+    (current_step - ALLOWED_SKEW..=current_step + ALLOWED_SKEW)
+        .filter(|step| *step >= 0 && last_used_step.is_none_or(|last| *step > last))
+        .find(|step| totp.check(&code, (step * period) as u64))
+}
+
+/// One-time codes that can replace a TOTP code when the authenticator is lost.
+/// They are shown to the user once, only their hashes are stored
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let random = uuid::Uuid::new_v4().simple().to_string();
+            format!("{}-{}", &random[..5], &random[5..10])
+        })
+        .collect()
+}
+
+/// Recovery codes are random enough that a fast hash is sufficient
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    blake3::hash(normalized.as_bytes()).to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_code() {
+        let secret = generate_secret();
+        let code = build_totp(&secret, "user1")
+            .unwrap()
+            .generate_current()
+            .unwrap();
+
+        assert!(check_code(&secret, &code, None).is_some());
+        assert!(check_code(&secret, &format!(" {} {} ", &code[..3], &code[3..]), None).is_some());
+        assert!(check_code(&secret, "abcdef", None).is_none());
+        assert!(check_code("not base32!", &code, None).is_none());
+    }
+
+    #[test]
+    fn test_check_code_replay() {
+        let secret = generate_secret();
+        let totp = build_totp(&secret, "user1").unwrap();
+        let now = 1_700_000_000;
+        let step = now / 30;
+        let code = totp.generate(now as u64);
+        let previous_code = totp.generate((now - 30) as u64);
+
+        assert_eq!(check_code_at(&secret, &code, None, now), Some(step));
+        // A code can only be used once
+        assert_eq!(check_code_at(&secret, &code, Some(step), now), None);
+        // And the older codes are no longer valid after a newer one was used
+        assert_eq!(
+            check_code_at(&secret, &previous_code, Some(step), now),
+            None
+        );
+        assert_eq!(
+            check_code_at(&secret, &previous_code, Some(step - 2), now),
+            Some(step - 1)
+        );
+        // Out of the allowed skew
+        assert_eq!(check_code_at(&secret, &previous_code, None, now + 60), None);
+    }
+
+    #[test]
+    fn test_otpauth_url() {
+        let url = otpauth_url("JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP", "user1").unwrap();
+
+        assert!(url.starts_with("otpauth://totp/Family%20Photos:user1?"));
+        assert!(url.contains("secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODES_COUNT);
+        assert!(codes.iter().all(|code| code.len() == 11));
+
+        let code = &codes[0];
+        assert_eq!(
+            hash_recovery_code(code),
+            hash_recovery_code(&format!(" {} ", code.to_uppercase()))
+        );
+        assert_ne!(hash_recovery_code(code), hash_recovery_code(&codes[1]));
+    }
+}
@@ -0,0 +1,545 @@
+use quick_xml::Writer;
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, BytesText, Event};
+use quick_xml::reader::Reader;
+use std::fs;
+use std::path::{Path, PathBuf};
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use tracing::warn;
+
+const DESCRIPTION: &str = "rdf:Description";
+const RATING: &str = "xmp:Rating";
+const CAPTION: &str = "dc:description";
+const KEYWORDS: &str = "dc:subject";
+const DATE_TIME_ORIGINAL: &str = "exif:DateTimeOriginal";
+const DATE_CREATED: &str = "photoshop:DateCreated";
+/// Only read, it's the date of the digital file rather than of the photo
+const CREATE_DATE: &str = "xmp:CreateDate";
+
+/// The properties replaced when writing a sidecar, everything else is kept as it is
+const WRITTEN_PROPERTIES: [&str; 5] = [RATING, CAPTION, KEYWORDS, DATE_TIME_ORIGINAL, DATE_CREATED];
+
+/// The namespaces of [`WRITTEN_PROPERTIES`]
+const NAMESPACES: [(&str, &str); 4] = [
+    ("xmlns:xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmlns:dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmlns:exif", "http://ns.adobe.com/exif/1.0/"),
+    ("xmlns:photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+];
+
+const EMPTY_XMP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""/>
+ </rdf:RDF>
+</x:xmpmeta>
+"#;
+
+/// The parts of an XMP sidecar that are kept in sync with the photos
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct XmpData {
+    /// From 1 to 5 stars, 0 when unrated or rejected
+    pub rating: i64,
+    pub caption: Option<String>,
+    pub keywords: Vec<String>,
+    pub date_taken: Option<OffsetDateTime>,
+}
+
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("xmp"))
+}
+
+/// Finds the sidecar of a photo, named like `IMG_1.jpg.xmp` (darktable) or `IMG_1.xmp` (Lightroom)
+pub fn find_sidecar(photo_path: &Path) -> Option<PathBuf> {
+    let full = photo_path.as_os_str();
+    let stem = photo_path.with_extension("");
+
+    ["xmp", "XMP"]
+        .into_iter()
+        .flat_map(|extension| {
+            let mut with_full_name = full.to_owned();
+            with_full_name.push(format!(".{extension}"));
+            [
+                PathBuf::from(with_full_name),
+                stem.with_extension(extension),
+            ]
+        })
+        .find(|path| path.is_file())
+}
+
+/// The sidecar named after the full file name of the photo, which only belongs to that photo
+/// unlike the ones named after the stem that a JPEG and its RAW file can share
+fn own_sidecar(photo_path: &Path) -> Option<PathBuf> {
+    ["xmp", "XMP"]
+        .into_iter()
+        .map(|extension| {
+            let mut path = photo_path.as_os_str().to_owned();
+            path.push(format!(".{extension}"));
+            PathBuf::from(path)
+        })
+        .find(|path| path.is_file())
+}
+
+/// Moves the sidecar along with the photo, if it has its own
+pub fn move_sidecar(source_photo: &Path, destination_photo: &Path) -> std::io::Result<()> {
+    let Some(source) = own_sidecar(source_photo) else {
+        return Ok(());
+    };
+
+    let mut destination = destination_photo.as_os_str().to_owned();
+    if let Some(extension) = source.extension() {
+        destination.push(".");
+        destination.push(extension);
+    }
+
+    fs::rename(source, destination)
+}
+
+/// Removes the sidecar of a deleted photo, if it has its own
+pub fn remove_sidecar(photo_path: &Path) -> std::io::Result<()> {
+    match own_sidecar(photo_path) {
+        Some(path) => fs::remove_file(path),
+        None => Ok(()),
+    }
+}
+
+/// Returns [`None`] if the photo has no sidecar or it can't be read
+pub fn read_sidecar(photo_path: &Path) -> Option<XmpData> {
+    let path = find_sidecar(photo_path)?;
+    let xml = fs::read_to_string(&path)
+        .inspect_err(|e| warn!("Failed to read sidecar {}: {e}", path.display()))
+        .ok()?;
+
+    parse_xmp(&xml)
+        .inspect_err(|e| warn!("Failed to parse sidecar {}: {e}", path.display()))
+        .ok()
+}
+
+/// Updates the sidecar of a photo, creating it next to the photo if there is none
+pub fn write_sidecar(photo_path: &Path, data: &XmpData) -> std::io::Result<()> {
+    let path = find_sidecar(photo_path).unwrap_or_else(|| {
+        let mut path = photo_path.as_os_str().to_owned();
+        path.push(".xmp");
+        PathBuf::from(path)
+    });
+
+    let existing = fs::read_to_string(&path).ok();
+    let xml = update_xmp(existing.as_deref(), data).map_err(std::io::Error::other)?;
+
+    fs::write(path, xml)
+}
+
+#[derive(Default)]
+struct Dates {
+    date_time_original: Option<OffsetDateTime>,
+    date_created: Option<OffsetDateTime>,
+    create_date: Option<OffsetDateTime>,
+}
+
+pub fn parse_xmp(xml: &str) -> quick_xml::Result<XmpData> {
+    let mut reader = Reader::from_str(xml);
+    let mut data = XmpData::default();
+    let mut dates = Dates::default();
+    let mut elements: Vec<String> = Vec::new();
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                if name == DESCRIPTION {
+                    read_attributes(&e, &mut data, &mut dates)?;
+                }
+                elements.push(name);
+                text.clear();
+            }
+            Event::Empty(e) if e.name().as_ref() == DESCRIPTION.as_bytes() => {
+                read_attributes(&e, &mut data, &mut dates)?;
+            }
+            Event::Text(e) => text.push_str(&e.decode()?),
+            Event::CData(e) => text.push_str(&e.decode()?),
+            Event::GeneralRef(e) => text.push_str(&unescape(&format!("&{};", e.decode()?))?),
+            Event::End(_) => {
+                let name = elements.pop().unwrap_or_default();
+                let value = std::mem::take(&mut text);
+                let value = value.trim();
+
+                // The values of lists are in their `rdf:li` items
+                let property = if name == "rdf:li" {
+                    elements
+                        .iter()
+                        .rev()
+                        .find(|e| e.contains(':') && !e.starts_with("rdf:"))
+                } else {
+                    Some(&name)
+                };
+
+                if let Some(property) = property
+                    && !value.is_empty()
+                {
+                    read_property(property, value, &mut data, &mut dates);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    data.date_taken = dates
+        .date_time_original
+        .or(dates.date_created)
+        .or(dates.create_date);
+
+    Ok(data)
+}
+
+/// Simple properties can also be written as attributes of `rdf:Description`
+fn read_attributes(
+    element: &BytesStart,
+    data: &mut XmpData,
+    dates: &mut Dates,
+) -> quick_xml::Result<()> {
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        let name = String::from_utf8_lossy(attribute.key.as_ref());
+        read_property(&name, &attribute.unescape_value()?, data, dates);
+    }
+
+    Ok(())
+}
+
+fn read_property(name: &str, value: &str, data: &mut XmpData, dates: &mut Dates) {
+    match name {
+        RATING => {
+            data.rating = value
+                .parse::<f64>()
+                .map_or(0, |rating| rating.round().clamp(0.0, 5.0) as i64)
+        }
+        CAPTION if data.caption.is_none() => data.caption = Some(value.to_string()),
+        KEYWORDS => data.keywords.push(value.to_string()),
+        DATE_TIME_ORIGINAL => dates.date_time_original = parse_date(value),
+        DATE_CREATED => dates.date_created = parse_date(value),
+        CREATE_DATE => dates.create_date = parse_date(value),
+        _ => {}
+    }
+}
+
+/// Parses an XMP date like "2023-06-15T10:30:00+02:00", the ones without an offset are in UTC
+/// like the EXIF dates
+fn parse_date(value: &str) -> Option<OffsetDateTime> {
+    let with_seconds = format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]");
+    let with_minutes = format_description!("[year]-[month]-[day]T[hour]:[minute]");
+    // The fraction of a second is dropped
+    let without_fraction = value.split('.').next().unwrap_or(value);
+
+    OffsetDateTime::parse(value, &Rfc3339)
+        .ok()
+        .or_else(|| {
+            PrimitiveDateTime::parse(without_fraction, &with_seconds)
+                .or_else(|_| PrimitiveDateTime::parse(value, &with_minutes))
+                .map(PrimitiveDateTime::assume_utc)
+                .ok()
+        })
+        .or_else(|| {
+            Date::parse(value, format_description!("[year]-[month]-[day]"))
+                .map(|date| date.midnight().assume_utc())
+                .ok()
+        })
+}
+
+fn format_date(date: OffsetDateTime) -> String {
+    let local = format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]");
+    let with_offset = format_description!(
+        "[year]-[month]-[day]T[hour]:[minute]:[second][offset_hour sign:mandatory]:[offset_minute]"
+    );
+
+    let format = if date.offset() == UtcOffset::UTC {
+        local
+    } else {
+        with_offset
+    };
+
+    date.format(&format).unwrap_or_default()
+}
+
+/// Replaces the synced properties of an XMP packet, or creates a new one when there is none.
+/// The new properties are added to the first `rdf:Description`
+pub fn update_xmp(xml: Option<&str>, data: &XmpData) -> quick_xml::Result<String> {
+    let Some(xml) = xml.filter(|xml| xml.contains(DESCRIPTION)) else {
+        return update_xmp(Some(EMPTY_XMP), data);
+    };
+
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::new());
+    let mut depth = 0usize;
+    // The depth of the `rdf:Description` receiving the properties, until they are written
+    let mut target_depth = None;
+    let mut written = false;
+    // The depth of the replaced property being skipped
+    let mut skipped_depth = None;
+
+    loop {
+        let event = reader.read_event()?;
+
+        if let Some(skipped) = skipped_depth {
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => {
+                    depth -= 1;
+                    if depth == skipped {
+                        skipped_depth = None;
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            Event::Start(e) if is_written_property(&e) => {
+                skipped_depth = Some(depth);
+                depth += 1;
+            }
+            Event::Empty(e) if is_written_property(&e) => {}
+            Event::Start(e) if e.name().as_ref() == DESCRIPTION.as_bytes() => {
+                let is_target = !written && target_depth.is_none();
+                writer.write_event(Event::Start(without_written_properties(&e, is_target)))?;
+                if is_target {
+                    target_depth = Some(depth);
+                }
+                depth += 1;
+            }
+            Event::Empty(e) if e.name().as_ref() == DESCRIPTION.as_bytes() => {
+                if !written && target_depth.is_none() {
+                    let start = without_written_properties(&e, true);
+                    writer.write_event(Event::Start(start.borrow()))?;
+                    write_properties(&mut writer, data)?;
+                    writer.write_event(Event::End(start.to_end()))?;
+                    written = true;
+                } else {
+                    writer.write_event(Event::Empty(without_written_properties(&e, false)))?;
+                }
+            }
+            Event::Start(e) => {
+                writer.write_event(Event::Start(e))?;
+                depth += 1;
+            }
+            Event::End(e) => {
+                depth = depth.saturating_sub(1);
+                if target_depth == Some(depth) {
+                    write_properties(&mut writer, data)?;
+                    target_depth = None;
+                    written = true;
+                }
+                writer.write_event(Event::End(e))?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+
+    if !written {
+        return update_xmp(None, data);
+    }
+
+    Ok(String::from_utf8_lossy(&writer.into_inner()).into_owned())
+}
+
+fn is_written_property(element: &BytesStart) -> bool {
+    WRITTEN_PROPERTIES
+        .iter()
+        .any(|name| element.name().as_ref() == name.as_bytes())
+}
+
+/// Copies an `rdf:Description` without the attributes of the written properties,
+/// declaring their namespaces if it will receive them
+fn without_written_properties(element: &BytesStart, add_namespaces: bool) -> BytesStart<'static> {
+    let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+    let mut copy = BytesStart::new(name);
+
+    let mut declared = Vec::new();
+    for attribute in element.attributes().flatten() {
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        if WRITTEN_PROPERTIES.contains(&key.as_str()) {
+            continue;
+        }
+        copy.push_attribute(attribute);
+        declared.push(key);
+    }
+
+    if add_namespaces {
+        for (key, uri) in NAMESPACES {
+            if !declared.iter().any(|declared| declared == key) {
+                copy.push_attribute((key, uri));
+            }
+        }
+    }
+
+    copy
+}
+
+fn write_properties(writer: &mut Writer<Vec<u8>>, data: &XmpData) -> std::io::Result<()> {
+    if data.rating > 0 {
+        writer
+            .create_element(RATING)
+            .write_text_content(BytesText::new(&data.rating.to_string()))?;
+    }
+
+    if let Some(caption) = &data.caption {
+        writer
+            .create_element(CAPTION)
+            .write_inner_content(|writer| {
+                writer
+                    .create_element("rdf:Alt")
+                    .write_inner_content(|writer| {
+                        writer
+                            .create_element("rdf:li")
+                            .with_attribute(("xml:lang", "x-default"))
+                            .write_text_content(BytesText::new(caption))?;
+                        Ok(())
+                    })?;
+                Ok(())
+            })?;
+    }
+
+    if !data.keywords.is_empty() {
+        writer
+            .create_element(KEYWORDS)
+            .write_inner_content(|writer| {
+                writer
+                    .create_element("rdf:Bag")
+                    .write_inner_content(|writer| {
+                        for keyword in &data.keywords {
+                            writer
+                                .create_element("rdf:li")
+                                .write_text_content(BytesText::new(keyword))?;
+                        }
+                        Ok(())
+                    })?;
+                Ok(())
+            })?;
+    }
+
+    if let Some(date_taken) = data.date_taken {
+        let date = format_date(date_taken);
+        for name in [DATE_TIME_ORIGINAL, DATE_CREATED] {
+            writer
+                .create_element(name)
+                .write_text_content(BytesText::new(&date))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    const DARKTABLE_XMP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="XMP Core 4.4.0-Exiv2">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    xmlns:darktable="http://darktable.sf.net/"
+    xmp:Rating="4"
+    exif:DateTimeOriginal="2023-06-15T10:30:00.25"
+    darktable:xmp_version="5">
+   <dc:description>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">Grandma &amp; the cake</rdf:li>
+    </rdf:Alt>
+   </dc:description>
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>Birthday</rdf:li>
+     <rdf:li>Family</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+   <darktable:history>
+    <rdf:Seq>
+     <rdf:li darktable:operation="exposure"/>
+    </rdf:Seq>
+   </darktable:history>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+"#;
+
+    #[test]
+    fn test_parse_xmp() {
+        assert_eq!(
+            parse_xmp(DARKTABLE_XMP).unwrap(),
+            XmpData {
+                rating: 4,
+                caption: Some("Grandma & the cake".to_string()),
+                keywords: vec!["Birthday".to_string(), "Family".to_string()],
+                date_taken: Some(datetime!(2023-06-15 10:30:00 UTC)),
+            }
+        );
+
+        // Lightroom writes the properties as elements, rejected photos have a rating of -1
+        let lightroom = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+            <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/">
+                <xmp:Rating>-1</xmp:Rating>
+                <xmp:CreateDate>2020-01-01T00:00:00</xmp:CreateDate>
+                <photoshop:DateCreated>2019-12-31T23:59:59+02:00</photoshop:DateCreated>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        assert_eq!(
+            parse_xmp(lightroom).unwrap(),
+            XmpData {
+                date_taken: Some(datetime!(2019-12-31 23:59:59 +2)),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_update_xmp() {
+        let data = XmpData {
+            rating: 2,
+            caption: Some("Cake <3".to_string()),
+            keywords: vec!["Family".to_string()],
+            date_taken: Some(datetime!(2023-06-16 08:00:00 UTC)),
+        };
+
+        let updated = update_xmp(Some(DARKTABLE_XMP), &data).unwrap();
+        assert_eq!(parse_xmp(&updated).unwrap(), data);
+        // The properties of other programs are kept
+        assert!(updated.contains(r#"darktable:xmp_version="5""#));
+        assert!(updated.contains(r#"<rdf:li darktable:operation="exposure"/>"#));
+        assert!(!updated.contains("Birthday"));
+
+        // Removing everything
+        let updated = update_xmp(Some(&updated), &XmpData::default()).unwrap();
+        assert_eq!(parse_xmp(&updated).unwrap(), XmpData::default());
+        assert!(updated.contains("darktable:history"));
+
+        let created = update_xmp(None, &data).unwrap();
+        assert_eq!(parse_xmp(&created).unwrap(), data);
+        assert!(created.contains(r#"xmlns:dc="http://purl.org/dc/elements/1.1/""#));
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(
+            format_date(datetime!(2023-06-15 10:30:00 UTC)),
+            "2023-06-15T10:30:00"
+        );
+        assert_eq!(
+            format_date(datetime!(2023-06-15 10:30:00 -5)),
+            "2023-06-15T10:30:00-05:00"
+        );
+        assert_eq!(
+            parse_date("2023-06-15"),
+            Some(datetime!(2023-06-15 00:00:00 UTC))
+        );
+    }
+}